@@ -0,0 +1,628 @@
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::process::{exit, Command};
+
+use crate::{
+    database::{Commit, Database, ParsedContent},
+    index::Index,
+    migration::Migration,
+    refs::{parse_revision, Refs},
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+};
+
+/// The files keeping the state of a bisection between invocations
+static BISECT_START: &str = "BISECT_START";
+static BISECT_BAD: &str = "BISECT_BAD";
+static BISECT_GOOD: &str = "BISECT_GOOD";
+static BISECT_SKIP: &str = "BISECT_SKIP";
+static BISECT_LOG: &str = "BISECT_LOG";
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct BisectCMD {
+    #[command(subcommand)]
+    command: BisectCommand,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+enum BisectCommand {
+    /// Start bisecting, optionally giving the bad commit followed by good ones
+    Start(RevisionsArgs),
+
+    /// Mark a commit, HEAD by default, as containing the regression
+    Bad(RevisionArgs),
+
+    /// Mark commits, HEAD by default, as coming before the regression
+    Good(RevisionsArgs),
+
+    /// Mark commits, HEAD by default, as impossible to test
+    Skip(RevisionsArgs),
+
+    /// Stop bisecting and go back to the branch bisecting started from, or to a commit
+    Reset(RevisionArgs),
+
+    /// Show the commits marked so far, in a form `replay` can read
+    Log,
+
+    /// Bisect again from the marks recorded in a log file
+    Replay(ReplayArgs),
+
+    /// Run a command at each step: exiting with 0 marks the commit good, with 125 skips
+    /// it and with anything else below 128 marks it bad
+    Run(RunArgs),
+}
+
+#[derive(Args, Debug, PartialEq)]
+struct RevisionArgs {
+    revision: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+struct RevisionsArgs {
+    revisions: Vec<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+struct ReplayArgs {
+    /// A file written by `bisect log`
+    logfile: PathBuf,
+}
+
+#[derive(Args, Debug, PartialEq)]
+struct RunArgs {
+    /// The command and its arguments
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Term {
+    Bad,
+    Good,
+    Skip,
+}
+
+impl Term {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Term::Bad => "bad",
+            Term::Good => "good",
+            Term::Skip => "skip",
+        }
+    }
+}
+
+/// Where the bisection stands after a step
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// Either the bad commit or the good ones are still unknown
+    Waiting,
+    /// A commit in the middle of the remaining candidates is checked out
+    Testing,
+    /// The first bad commit has been found
+    Found,
+}
+
+impl BisectCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let bisect = Bisect {
+            refs: Refs::new(git_path.clone()),
+            database: Database::new(git_path.join("objects")),
+            root_path,
+            git_path,
+        };
+
+        match &self.command {
+            BisectCommand::Start(args) => {
+                bisect.start(&args.revisions)?;
+                bisect.next()?;
+            }
+            BisectCommand::Bad(args) => {
+                bisect.mark_revisions(Term::Bad, args.revision.as_slice())?;
+            }
+            BisectCommand::Good(args) => {
+                bisect.mark_revisions(Term::Good, &args.revisions)?;
+            }
+            BisectCommand::Skip(args) => {
+                bisect.mark_revisions(Term::Skip, &args.revisions)?;
+            }
+            BisectCommand::Reset(args) => bisect.reset(args.revision.as_deref())?,
+            BisectCommand::Log => {
+                if !bisect.in_progress() {
+                    write_to_stderr("error: We are not bisecting.")?;
+                    exit(1);
+                }
+                let log = fs::read_to_string(bisect.git_path.join(BISECT_LOG))?;
+                write_to_stdout(log.trim_end())?;
+            }
+            BisectCommand::Replay(args) => bisect.replay(&args.logfile)?,
+            BisectCommand::Run(args) => bisect.run(&args.command)?,
+        }
+        Ok(())
+    }
+}
+
+struct Bisect {
+    root_path: PathBuf,
+    git_path: PathBuf,
+    refs: Refs,
+    database: Database,
+}
+
+impl Bisect {
+    fn in_progress(&self) -> bool {
+        self.git_path.join(BISECT_START).is_file()
+    }
+
+    /// Remember where bisecting started from and forget the marks of an earlier bisection,
+    /// then mark the first revision bad and the others good
+    fn start(&self, revisions: &[String]) -> Result<()> {
+        let oids = revisions
+            .iter()
+            .map(|revision| self.resolve(revision))
+            .collect::<Result<Vec<_>>>()?;
+
+        let start = match fs::read_to_string(self.git_path.join(BISECT_START)) {
+            Ok(start) => start.trim().to_owned(),
+            Err(_) => match (self.refs.current_branch(), self.refs.read_head()) {
+                (Some(branch), _) => branch,
+                (None, Some(oid)) => oid,
+                (None, None) => anyhow::bail!("fatal: bad HEAD - I need a HEAD"),
+            },
+        };
+        self.clear_state()?;
+        fs::write(self.git_path.join(BISECT_START), format!("{}\n", start))?;
+        fs::write(self.git_path.join(BISECT_LOG), "")?;
+
+        for (n, oid) in oids.iter().enumerate() {
+            let term = if n == 0 { Term::Bad } else { Term::Good };
+            self.mark(term, oid, false)?;
+        }
+        let quoted = revisions
+            .iter()
+            .map(|revision| format!(" '{}'", revision))
+            .collect::<String>();
+        self.log(&format!("git bisect start{}", quoted))
+    }
+
+    fn mark_revisions(&self, term: Term, revisions: &[String]) -> Result<Outcome> {
+        if !self.in_progress() {
+            write_to_stderr("You need to start by \"git bisect start\"")?;
+            exit(1);
+        }
+        let revisions = match revisions.is_empty() {
+            true => vec!["HEAD".to_owned()],
+            false => revisions.to_vec(),
+        };
+        for revision in revisions {
+            let oid = self.resolve(&revision)?;
+            self.mark(term, &oid, true)?;
+        }
+        self.next()
+    }
+
+    /// Record a mark in the state files and in the log. The bad commit replaces any
+    /// earlier one, good and skipped commits add up.
+    fn mark(&self, term: Term, oid: &str, log_command: bool) -> Result<()> {
+        match term {
+            Term::Bad => fs::write(self.git_path.join(BISECT_BAD), format!("{}\n", oid))?,
+            Term::Good => self.append(BISECT_GOOD, oid)?,
+            Term::Skip => self.append(BISECT_SKIP, oid)?,
+        }
+        self.log(&format!(
+            "# {}: [{}] {}",
+            term.as_str(),
+            oid,
+            self.subject(oid)?
+        ))?;
+        if log_command {
+            self.log(&format!("git bisect {} {}", term.as_str(), oid))?;
+        }
+        Ok(())
+    }
+
+    /// Work out what is left to test from the marks, and either check out the commit
+    /// halving the candidates or report the first bad commit
+    fn next(&self) -> Result<Outcome> {
+        let bad = self.read_oids(BISECT_BAD)?.pop();
+        let goods = self.read_oids(BISECT_GOOD)?;
+        let bad = match (bad, goods.len()) {
+            (Some(bad), 1..) => bad,
+            (bad, good_count) => {
+                let status = match bad {
+                    Some(_) => "status: waiting for good commit(s), bad commit known".to_owned(),
+                    None if good_count == 0 => {
+                        "status: waiting for both good and bad commits".to_owned()
+                    }
+                    None => format!(
+                        "status: waiting for bad commit, {} good commit{} known",
+                        good_count,
+                        if good_count == 1 { "" } else { "s" }
+                    ),
+                };
+                write_to_stdout(&status)?;
+                self.log(&format!("# {}", status))?;
+                return Ok(Outcome::Waiting);
+            }
+        };
+
+        let candidates = self.candidates(&bad, &goods)?;
+        if !candidates.contains_key(&bad) {
+            write_to_stderr("Some good revs are not ancestors of the bad rev.\ngit bisect cannot work properly in this case.\nMaybe you mistook good and bad revs?")?;
+            exit(1);
+        }
+
+        let skipped = self.read_oids(BISECT_SKIP)?;
+        let all = candidates.len();
+        let best = candidates
+            .keys()
+            .filter(|oid| **oid != bad && !skipped.contains(oid))
+            .map(|oid| (oid, Bisect::weight(oid, &candidates)))
+            .max_by(|(a_oid, a), (b_oid, b)| {
+                let distance = |weight: usize| weight.min(all - weight);
+                distance(*a)
+                    .cmp(&distance(*b))
+                    .then(b.cmp(a))
+                    .then(b_oid.cmp(a_oid))
+            });
+
+        let (best, reaches) = match best {
+            Some(best) => best,
+            None if all == 1 => return self.found(&bad),
+            None => return self.only_skipped_left(&bad, &candidates),
+        };
+
+        let left = all - reaches - 1;
+        let steps = Bisect::estimate_steps(all);
+        write_to_stdout(&format!(
+            "Bisecting: {} revision{} left to test after this (roughly {} step{})",
+            left,
+            if left == 1 { "" } else { "s" },
+            steps,
+            if steps == 1 { "" } else { "s" }
+        ))?;
+        self.checkout(best)?;
+        write_to_stdout(&format!("[{}] {}", best, self.subject(best)?))?;
+        Ok(Outcome::Testing)
+    }
+
+    /// The commits reachable from the bad commit but from none of the good ones, with their
+    /// parents
+    fn candidates(&self, bad: &str, goods: &[String]) -> Result<HashMap<String, Vec<String>>> {
+        let mut good = HashSet::new();
+        let mut queue = goods.to_vec();
+        while let Some(oid) = queue.pop() {
+            if good.insert(oid.clone()) {
+                queue.extend(self.parents(&oid)?);
+            }
+        }
+
+        let mut candidates = HashMap::new();
+        let mut queue = vec![bad.to_owned()];
+        while let Some(oid) = queue.pop() {
+            if good.contains(&oid) || candidates.contains_key(&oid) {
+                continue;
+            }
+            let parents = self.parents(&oid)?;
+            queue.extend(parents.iter().cloned());
+            candidates.insert(oid, parents);
+        }
+        Ok(candidates)
+    }
+
+    fn parents(&self, oid: &str) -> Result<Vec<String>> {
+        match self.database.read_object(oid)? {
+            ParsedContent::CommitContent(commit) => Ok(commit.parents),
+            _ => anyhow::bail!("fatal: {} is not a commit", oid),
+        }
+    }
+
+    /// How many candidates are reachable from `oid`, itself included. Testing it leaves
+    /// that many if it turns out bad and the rest if it is good.
+    fn weight(oid: &str, candidates: &HashMap<String, Vec<String>>) -> usize {
+        let mut seen = HashSet::new();
+        let mut queue = vec![oid];
+        while let Some(oid) = queue.pop() {
+            if let Some(parents) = candidates.get(oid) {
+                if seen.insert(oid) {
+                    queue.extend(parents.iter().map(String::as_str));
+                }
+            }
+        }
+        seen.len()
+    }
+
+    /// The number of steps still needed for `all` candidates, worked out the way git does
+    fn estimate_steps(all: usize) -> usize {
+        if all < 3 {
+            return 0;
+        }
+        let n = all.ilog2() as usize;
+        let e = 1 << n;
+        let x = all - e;
+        match e < 3 * x {
+            true => n,
+            false => n - 1,
+        }
+    }
+
+    fn found(&self, bad: &str) -> Result<Outcome> {
+        let commit = self.database.read_commit(bad)?;
+        write_to_stdout(&format!("{} is the first bad commit", bad))?;
+        write_to_stdout(&format!("commit {}", bad))?;
+        write_to_stdout(&format!(
+            "Author: {} <{}>",
+            commit.author.name, commit.author.email
+        ))?;
+        write_to_stdout(&format!("Date:   {}", commit.author.readable_time()))?;
+        write_to_stdout("")?;
+        for line in commit.message.lines() {
+            write_to_stdout(&format!("    {}", line))?;
+        }
+        self.log(&format!(
+            "# first bad commit: [{}] {}",
+            bad,
+            Bisect::first_line(&commit)
+        ))?;
+        Ok(Outcome::Found)
+    }
+
+    fn only_skipped_left(
+        &self,
+        bad: &str,
+        candidates: &HashMap<String, Vec<String>>,
+    ) -> Result<Outcome> {
+        let mut possible = candidates.keys().cloned().collect::<Vec<_>>();
+        possible.sort_by_key(|oid| (oid != bad, oid.clone()));
+        write_to_stdout("There are only 'skip'ped commits left to test.")?;
+        write_to_stdout("The first bad commit could be any of:")?;
+        for oid in &possible {
+            write_to_stdout(oid)?;
+            self.log(&format!(
+                "# possible first bad commit: [{}] {}",
+                oid,
+                self.subject(oid)?
+            ))?;
+        }
+        write_to_stdout("We cannot bisect more!")?;
+        exit(2);
+    }
+
+    /// Go back to where bisecting started, or to `revision`, and remove the state files
+    fn reset(&self, revision: Option<&str>) -> Result<()> {
+        if !self.in_progress() {
+            write_to_stdout("We are not bisecting.")?;
+            return Ok(());
+        }
+        let start = fs::read_to_string(self.git_path.join(BISECT_START))?;
+        let target = revision.unwrap_or(start.trim()).to_owned();
+        let oid = self.resolve(&target)?;
+
+        let previous = self.refs.read_head();
+        if let Some(previous) = previous.as_ref().filter(|previous| **previous != oid) {
+            self.migrate(&oid)?;
+            write_to_stderr(&format!(
+                "Previous HEAD position was {} {}",
+                &previous[..7],
+                self.subject(previous)?
+            ))?;
+        }
+        let message = format!("checkout: moving from {} to {}", self.head_name(), target);
+        self.refs.set_head(&target, &oid, &message)?;
+        match self.refs.current_branch() {
+            Some(branch) if previous.as_ref() == Some(&oid) => {
+                write_to_stderr(&format!("Already on '{}'", branch))?
+            }
+            Some(branch) => write_to_stderr(&format!("Switched to branch '{}'", branch))?,
+            None => write_to_stderr(&format!(
+                "HEAD is now at {} {}",
+                &oid[..7],
+                self.subject(&oid)?
+            ))?,
+        }
+        self.clear_state()?;
+        fs::remove_file(self.git_path.join(BISECT_START))?;
+        Ok(())
+    }
+
+    /// Start over and apply the `git bisect` commands of a log file, then check out the
+    /// next commit to test
+    fn replay(&self, logfile: &PathBuf) -> Result<()> {
+        let content = match fs::read_to_string(logfile) {
+            Ok(content) => content,
+            Err(_) => {
+                write_to_stderr(&format!(
+                    "fatal: cannot read file '{}' for replaying",
+                    logfile.display()
+                ))?;
+                exit(128);
+            }
+        };
+        if self.in_progress() {
+            self.reset(None)?;
+        }
+
+        for line in content.lines() {
+            let command = match line
+                .strip_prefix("git bisect ")
+                .or_else(|| line.strip_prefix("rgit bisect "))
+            {
+                Some(command) => command,
+                None => continue,
+            };
+            let mut words = command.split_whitespace();
+            let name = words.next().unwrap_or_default();
+            let revisions = words
+                .map(|word| word.trim_matches('\'').to_owned())
+                .collect::<Vec<_>>();
+            let term = match name {
+                "start" => {
+                    self.start(&revisions)?;
+                    continue;
+                }
+                "bad" => Term::Bad,
+                "good" => Term::Good,
+                "skip" => Term::Skip,
+                _ => {
+                    write_to_stderr(&format!("error: ?? what are you talking about? {}", line))?;
+                    exit(1);
+                }
+            };
+            for revision in revisions {
+                let oid = self.resolve(&revision)?;
+                self.mark(term, &oid, true)?;
+            }
+        }
+        self.next()?;
+        Ok(())
+    }
+
+    /// Run the command at each step, turning its exit code into a mark, until the first
+    /// bad commit is found
+    fn run(&self, command: &[String]) -> Result<()> {
+        if !self.in_progress() {
+            write_to_stderr("You need to start by \"git bisect start\"")?;
+            exit(1);
+        }
+        if self.read_oids(BISECT_BAD)?.is_empty() || self.read_oids(BISECT_GOOD)?.is_empty() {
+            write_to_stderr("You need to give me at least one good and one bad revision.\n(You can use \"git bisect bad\" and \"git bisect good\" for that.)")?;
+            exit(1);
+        }
+
+        let quoted = command
+            .iter()
+            .map(|word| format!("'{}'", word))
+            .collect::<Vec<_>>()
+            .join(" ");
+        loop {
+            write_to_stdout(&format!("running {}", quoted))?;
+            let status = Command::new(&command[0])
+                .args(&command[1..])
+                .current_dir(&self.root_path)
+                .status();
+            let term = match status.ok().and_then(|status| status.code()) {
+                Some(0) => Term::Good,
+                Some(125) => Term::Skip,
+                Some(1..=127) => Term::Bad,
+                code => {
+                    write_to_stderr(&format!(
+                        "error: bisect run failed: exit code {} from '{}' is < 0 or >= 128",
+                        code.unwrap_or(-1),
+                        quoted
+                    ))?;
+                    exit(1);
+                }
+            };
+
+            let head = self.refs.read_head().expect("no HEAD to test");
+            self.mark(term, &head, true)?;
+            if self.next()? == Outcome::Found {
+                write_to_stdout("bisect found first bad commit")?;
+                return Ok(());
+            }
+        }
+    }
+
+    /// Detach HEAD at `oid`, updating the index and the working tree to match it
+    fn checkout(&self, oid: &str) -> Result<()> {
+        self.migrate(oid)?;
+        let message = format!("checkout: moving from {} to {}", self.head_name(), oid);
+        self.refs.update_ref("HEAD", oid, &message)
+    }
+
+    /// Update the index and the working tree from HEAD's tree to the tree of `oid`
+    fn migrate(&self, oid: &str) -> Result<()> {
+        let mut index = Index::new(self.git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+        let head_tree = self.database.read_head()?;
+        let tree = self.database.read_commit_tree(oid)?;
+        let mut migration = Migration::new(
+            self.root_path.clone(),
+            &self.database,
+            &mut index,
+            head_tree,
+            tree,
+        );
+
+        let errors = migration.conflicts("checkout");
+        if !errors.is_empty() {
+            index.lockfile.rollback()?;
+            for error in errors {
+                write_to_stderr(&error)?;
+            }
+            write_to_stderr("Aborting")?;
+            exit(1);
+        }
+        migration.apply_changes()?;
+        index.changed = true;
+        index.write_updates()
+    }
+
+    /// The branch HEAD is on, or the commit it is detached at
+    fn head_name(&self) -> String {
+        self.refs
+            .current_branch()
+            .or_else(|| self.refs.read_head())
+            .unwrap_or_default()
+    }
+
+    fn resolve(&self, revision: &str) -> Result<String> {
+        match parse_revision(revision, revision).resolve_commit(&self.refs) {
+            Ok(oid) => Ok(oid),
+            Err(_) => {
+                write_to_stderr(&format!("fatal: Bad rev input: {}", revision))?;
+                exit(128);
+            }
+        }
+    }
+
+    fn read_oids(&self, name: &str) -> Result<Vec<String>> {
+        match fs::read_to_string(self.git_path.join(name)) {
+            Ok(content) => Ok(content.lines().map(str::to_owned).collect()),
+            Err(_) => Ok(vec![]),
+        }
+    }
+
+    fn append(&self, name: &str, oid: &str) -> Result<()> {
+        let mut oids = self.read_oids(name)?;
+        if !oids.iter().any(|known| known == oid) {
+            oids.push(oid.to_owned());
+        }
+        fs::write(self.git_path.join(name), format!("{}\n", oids.join("\n")))?;
+        Ok(())
+    }
+
+    fn log(&self, line: &str) -> Result<()> {
+        let path = self.git_path.join(BISECT_LOG);
+        let mut log = fs::read_to_string(&path).unwrap_or_default();
+        log.push_str(line);
+        log.push('\n');
+        fs::write(path, log)?;
+        Ok(())
+    }
+
+    /// Remove every state file but `BISECT_START`
+    fn clear_state(&self) -> Result<()> {
+        for name in [BISECT_BAD, BISECT_GOOD, BISECT_SKIP, BISECT_LOG] {
+            let path = self.git_path.join(name);
+            if path.is_file() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn subject(&self, oid: &str) -> Result<String> {
+        Ok(Bisect::first_line(&self.database.read_commit(oid)?))
+    }
+
+    fn first_line(commit: &Commit) -> String {
+        commit.message.lines().next().unwrap_or("").to_owned()
+    }
+}
@@ -0,0 +1,448 @@
+use anyhow::Result;
+use clap::Parser;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::process::exit;
+
+use crate::{
+    command::show::ShowCMD,
+    database::{Commit, Content, Database, FlatTree},
+    diff::{EditType, Myres},
+    refs::{parse_revision, Refs},
+    utils::{get_root_path, is_binary_file, write_to_stderr, write_to_stdout},
+};
+
+/// Like git, a line has to have this many alphanumeric characters before it is taken as
+/// moved or copied; shorter lines such as `}` would match almost anywhere
+const MOVE_SCORE: usize = 20;
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct BlameCMD {
+    /// Only annotate the lines from start to end, given as `<start>,<end>` or
+    /// `<start>,+<count>`
+    #[arg(short = 'L', value_name = "start,end")]
+    range: Option<String>,
+
+    /// Show the output in a format meant for scripts and editors
+    #[arg(long)]
+    porcelain: bool,
+
+    /// Follow lines that were moved within the file
+    #[arg(short = 'M')]
+    moves: bool,
+
+    /// Follow lines that were moved or copied from any other file of the parent commit,
+    /// as well as within the file
+    #[arg(short = 'C')]
+    copies: bool,
+
+    /// The commit to start from, HEAD by default, followed by the file to annotate
+    #[arg(num_args = 1..=2, required = true, value_name = "[rev] file")]
+    args: Vec<String>,
+}
+
+/// The commit a line of the blamed file comes from, with the path and line number (from 0)
+/// it has there
+#[derive(Debug, Clone, PartialEq)]
+struct Origin {
+    oid: String,
+    path: String,
+    line: usize,
+}
+
+/// A version of a file still to be looked at, with the lines of the blamed file it has not
+/// been cleared of yet: pairs of the line in the blamed file and the line in this version
+struct Suspect {
+    oid: String,
+    path: String,
+    lines: Vec<(usize, usize)>,
+}
+
+impl BlameCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+
+        let (revision, path) = match self.args.as_slice() {
+            [path] => ("HEAD", path.trim_start_matches("./")),
+            [revision, path, ..] => (revision.as_str(), path.trim_start_matches("./")),
+            [] => unreachable!("clap requires the file"),
+        };
+        let oid = parse_revision(revision, revision).resolve_commit_or_exit(&refs)?;
+
+        let mut blame = Blame::new(&database, self.moves || self.copies, self.copies);
+        let lines = match blame.lines(&oid, path)? {
+            Some(lines) => lines,
+            None => {
+                write_to_stderr(&format!("fatal: no such path '{}' in {}", path, revision))?;
+                exit(128);
+            }
+        };
+        let (start, end) = match &self.range {
+            Some(range) => self.parse_range(range, lines.len(), path)?,
+            None => (0, lines.len()),
+        };
+
+        let origins = blame.run(&oid, path, start..end)?;
+        if self.porcelain {
+            self.print_porcelain(&mut blame, &lines, &origins, start)
+        } else {
+            self.print(&mut blame, path, &lines, &origins, start)
+        }
+    }
+
+    /// Turn `-L` into a range of line indexes, checking it against the length of the file
+    fn parse_range(&self, range: &str, length: usize, path: &str) -> Result<(usize, usize)> {
+        let (start, end) = range.split_once(',').unwrap_or((range, ""));
+        let start = match start {
+            "" => 1,
+            start => start.parse::<usize>().unwrap_or(0),
+        };
+        let end = match end {
+            "" => length,
+            end if end.starts_with('+') => end[1..]
+                .parse::<usize>()
+                .map_or(0, |count| start + count.max(1) - 1),
+            end => end.parse::<usize>().unwrap_or(0),
+        };
+        if start == 0 || end == 0 {
+            write_to_stderr(&format!("fatal: invalid -L argument '{}'", range))?;
+            exit(128);
+        }
+        if start > length {
+            write_to_stderr(&format!("fatal: file {} has only {} lines", path, length))?;
+            exit(128);
+        }
+        let (start, end) = (start.min(end), start.max(end));
+        Ok((start - 1, end.min(length)))
+    }
+
+    /// One line per line of the file: the commit, the author and date, the line number and
+    /// the line. Lines of a root commit get a `^`, and the path they had is shown when some
+    /// of them came from another file.
+    fn print(
+        &self,
+        blame: &mut Blame,
+        path: &str,
+        lines: &[String],
+        origins: &[Origin],
+        start: usize,
+    ) -> Result<()> {
+        let mut commits = vec![];
+        for origin in origins {
+            commits.push(blame.commit(&origin.oid)?.clone());
+        }
+        let show_path = origins.iter().any(|origin| origin.path != path);
+        let path_width = origins.iter().map(|origin| origin.path.len()).max();
+        let name_width = commits
+            .iter()
+            .map(|commit| commit.author.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let number_width = (start + origins.len()).to_string().len();
+
+        for (i, (origin, commit)) in origins.iter().zip(&commits).enumerate() {
+            let mut short_oid = blame.database.short_oid(&origin.oid, 8);
+            if commit.parents.is_empty() {
+                short_oid = format!("^{}", &short_oid[..short_oid.len() - 1]);
+            }
+            let path_column = match show_path {
+                true => format!(" {:<1$}", origin.path, path_width.unwrap_or(0)),
+                false => String::new(),
+            };
+            write_to_stdout(&format!(
+                "{}{} ({:<name_width$} {} {:>number_width$}) {}",
+                short_oid,
+                path_column,
+                commit.author.name,
+                commit.author.time.format("%Y-%m-%d %H:%M:%S %z"),
+                start + i + 1,
+                lines[start + i]
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// The format of `git blame --porcelain`: for each run of lines from the same commit a
+    /// header with the commit, the line numbers and the length of the run, the details of
+    /// a commit the first time it shows up, and every line prefixed with a tab
+    fn print_porcelain(
+        &self,
+        blame: &mut Blame,
+        lines: &[String],
+        origins: &[Origin],
+        start: usize,
+    ) -> Result<()> {
+        let mut shown = HashSet::new();
+        let mut i = 0;
+        while i < origins.len() {
+            let origin = &origins[i];
+            let mut count = 1;
+            while i + count < origins.len()
+                && origins[i + count].oid == origin.oid
+                && origins[i + count].path == origin.path
+                && origins[i + count].line == origin.line + count
+            {
+                count += 1;
+            }
+
+            for offset in 0..count {
+                let line_origin = &origins[i + offset];
+                let header = format!(
+                    "{} {} {}",
+                    line_origin.oid,
+                    line_origin.line + 1,
+                    start + i + offset + 1
+                );
+                match offset {
+                    0 => write_to_stdout(&format!("{} {}", header, count))?,
+                    _ => write_to_stdout(&header)?,
+                }
+                if shown.insert(line_origin.oid.clone()) {
+                    let commit = blame.commit(&line_origin.oid)?;
+                    let author = &commit.author;
+                    let time = author.time.format("%s").to_string();
+                    let tz = author.time.format("%z").to_string();
+                    let mut details = vec![
+                        format!("author {}", author.name),
+                        format!("author-mail <{}>", author.email),
+                        format!("author-time {}", time),
+                        format!("author-tz {}", tz),
+                        format!("committer {}", author.name),
+                        format!("committer-mail <{}>", author.email),
+                        format!("committer-time {}", time),
+                        format!("committer-tz {}", tz),
+                        format!("summary {}", commit.message.lines().next().unwrap_or("")),
+                    ];
+                    if commit.parents.is_empty() {
+                        details.push("boundary".to_owned());
+                    }
+                    details.push(format!("filename {}", line_origin.path));
+                    write_to_stdout(&details.join("\n"))?;
+                }
+                write_to_stdout(&format!("\t{}", lines[start + i + offset]))?;
+            }
+            i += count;
+        }
+        Ok(())
+    }
+}
+
+/// Walks the first-parent history from a commit, passing each line on to the parent as
+/// long as the parent has it too. A line stays with the first commit whose parent does not
+/// have it, which is the commit that added or last changed it.
+struct Blame<'a> {
+    database: &'a Database,
+    moves: bool,
+    copies: bool,
+    commits: HashMap<String, Commit>,
+    trees: HashMap<String, FlatTree>,
+    /// Where each line long enough to be followed first appears in the blobs looked at
+    /// for copies, empty for a binary one
+    copy_sources: HashMap<String, HashMap<String, usize>>,
+}
+
+impl<'a> Blame<'a> {
+    fn new(database: &'a Database, moves: bool, copies: bool) -> Self {
+        Self {
+            database,
+            moves,
+            copies,
+            commits: HashMap::new(),
+            trees: HashMap::new(),
+            copy_sources: HashMap::new(),
+        }
+    }
+
+    /// Find the origin of each line of `range` of the file at `path` in commit `oid`
+    fn run(&mut self, oid: &str, path: &str, range: std::ops::Range<usize>) -> Result<Vec<Origin>> {
+        let start = range.start;
+        let mut origins: Vec<Option<Origin>> = vec![None; range.len()];
+        let mut queue = VecDeque::from([Suspect {
+            oid: oid.to_owned(),
+            path: path.to_owned(),
+            lines: range.map(|line| (line - start, line)).collect(),
+        }]);
+
+        while let Some(suspect) = queue.pop_front() {
+            for (path, lines) in self.pass_blame(&suspect, &mut origins)? {
+                let parent = self
+                    .commit(&suspect.oid)?
+                    .parent()
+                    .expect("lines only pass to a parent");
+                match queue
+                    .iter_mut()
+                    .find(|queued| queued.oid == parent && queued.path == path)
+                {
+                    Some(queued) => queued.lines.extend(lines),
+                    None => queue.push_back(Suspect {
+                        oid: parent,
+                        path,
+                        lines,
+                    }),
+                }
+            }
+        }
+
+        Ok(origins
+            .into_iter()
+            .map(|origin| origin.expect("every line gets an origin"))
+            .collect())
+    }
+
+    /// Hand the lines of the suspect that its parent has on to the parent, grouped by the
+    /// path they have there, and blame the suspect for the others
+    fn pass_blame(
+        &mut self,
+        suspect: &Suspect,
+        origins: &mut [Option<Origin>],
+    ) -> Result<BTreeMap<String, Vec<(usize, usize)>>> {
+        let mut passed: BTreeMap<String, Vec<(usize, usize)>> = BTreeMap::new();
+        let mut remaining = suspect.lines.clone();
+
+        if let Some(parent) = self.commit(&suspect.oid)?.parent() {
+            let lines = self.lines(&suspect.oid, &suspect.path)?.unwrap_or_default();
+
+            if let Some(parent_lines) = self.lines(&parent, &suspect.path)? {
+                let kept = Myres::new(parent_lines.join("\n"), lines.join("\n"))
+                    .edits()
+                    .into_iter()
+                    .filter(|edit| edit.edit_type == EditType::Equal)
+                    .filter_map(|edit| Some((edit.b_line?.line_number, edit.a_line?.line_number)))
+                    .map(|(b, a)| (b as usize - 1, a as usize - 1))
+                    .collect::<HashMap<_, _>>();
+                remaining.retain(|(line, suspect_line)| match kept.get(suspect_line) {
+                    Some(parent_line) => {
+                        passed
+                            .entry(suspect.path.clone())
+                            .or_default()
+                            .push((*line, *parent_line));
+                        false
+                    }
+                    None => true,
+                });
+
+                if self.moves {
+                    Blame::find_lines(
+                        &lines,
+                        |text| parent_lines.iter().position(|other| other == text),
+                        &suspect.path,
+                        &mut remaining,
+                        &mut passed,
+                    );
+                }
+            }
+
+            // most files are the same from one parent to the next, so each blob is only
+            // read once for the whole walk
+            if self.copies && !remaining.is_empty() {
+                let files = self
+                    .tree(&parent)?
+                    .entries
+                    .iter()
+                    .filter(|(path, _)| **path != suspect.path)
+                    .filter_map(|(path, file)| Some((path.clone(), file.oid.clone()?)))
+                    .collect::<Vec<_>>();
+                for (path, oid) in files {
+                    if remaining.is_empty() {
+                        break;
+                    }
+                    let sources = self.copy_sources(&oid)?;
+                    Blame::find_lines(
+                        &lines,
+                        |text| sources.get(text).copied(),
+                        &path,
+                        &mut remaining,
+                        &mut passed,
+                    );
+                }
+            }
+        }
+
+        for (line, suspect_line) in remaining {
+            origins[line] = Some(Origin {
+                oid: suspect.oid.clone(),
+                path: suspect.path.clone(),
+                line: suspect_line,
+            });
+        }
+        Ok(passed)
+    }
+
+    /// Pass on the remaining lines that appear as they are in the version of `path` in the
+    /// parent, which `position` finds them in
+    fn find_lines(
+        lines: &[String],
+        position: impl Fn(&str) -> Option<usize>,
+        path: &str,
+        remaining: &mut Vec<(usize, usize)>,
+        passed: &mut BTreeMap<String, Vec<(usize, usize)>>,
+    ) {
+        remaining.retain(|(line, suspect_line)| {
+            let text = &lines[*suspect_line];
+            if text.chars().filter(|c| c.is_alphanumeric()).count() < MOVE_SCORE {
+                return true;
+            }
+            match position(text) {
+                Some(other_line) => {
+                    passed
+                        .entry(path.to_owned())
+                        .or_default()
+                        .push((*line, other_line));
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+
+    /// The lines of the file at `path` in commit `oid`, `None` if it has no such file
+    fn lines(&mut self, oid: &str, path: &str) -> Result<Option<Vec<String>>> {
+        let file = match self.tree(oid)?.entries.get(path) {
+            Some(file) => file.clone(),
+            None => return Ok(None),
+        };
+        let content = ShowCMD::read_blob(self.database, Some(&file))?;
+        Ok(Some(
+            String::from_utf8_lossy(&content)
+                .lines()
+                .map(|line| line.to_owned())
+                .collect(),
+        ))
+    }
+
+    /// The lines of the blob `oid` a copied line can be found in, with where each one first
+    /// appears
+    fn copy_sources(&mut self, oid: &str) -> Result<&HashMap<String, usize>> {
+        if !self.copy_sources.contains_key(oid) {
+            let content = Content::parse(oid, self.database.object_store.clone())?.body;
+            let mut sources = HashMap::new();
+            if !is_binary_file(&content)? {
+                for (position, line) in String::from_utf8_lossy(&content).lines().enumerate() {
+                    if line.chars().filter(|c| c.is_alphanumeric()).count() >= MOVE_SCORE {
+                        sources.entry(line.to_owned()).or_insert(position);
+                    }
+                }
+            }
+            self.copy_sources.insert(oid.to_owned(), sources);
+        }
+        Ok(&self.copy_sources[oid])
+    }
+
+    fn commit(&mut self, oid: &str) -> Result<&Commit> {
+        if !self.commits.contains_key(oid) {
+            let commit = self.database.read_commit(oid)?;
+            self.commits.insert(oid.to_owned(), commit);
+        }
+        Ok(&self.commits[oid])
+    }
+
+    fn tree(&mut self, oid: &str) -> Result<&FlatTree> {
+        if !self.trees.contains_key(oid) {
+            let tree = self.database.read_commit_tree(oid)?;
+            self.trees.insert(oid.to_owned(), tree);
+        }
+        Ok(&self.trees[oid])
+    }
+}
@@ -33,13 +33,13 @@ impl BranchCMD {
                 let output = format!("Create branch: {}", name);
                 write_to_stdout(&output)?;
                 let oid = refs.get_ref_content();
-                refs.create_branch(name, &oid)?;
+                refs.create_branch(name, &oid, "branch: Created from HEAD")?;
             } else if name.len() == 2 {
                 let branch_name = &name[0];
                 let rev = &name[1];
                 let revision_object = parse_revision(rev, rev);
-                let oid = revision_object.resolve(&refs).expect("OID");
-                refs.create_branch(branch_name, &oid)?;
+                let oid = revision_object.resolve_commit_or_exit(&refs)?;
+                refs.create_branch(branch_name, &oid, &format!("branch: Created from {}", rev))?;
             } else {
                 let output = "Invalid branch format".to_string();
                 write_to_stdout(&output)?;
@@ -55,9 +55,13 @@ impl BranchCMD {
         let git_path = root_path.join(".rgit");
         let refs = Refs::new(git_path);
         let branches = refs.list_branches()?;
-        let current_branch = refs.get_branch_name();
+        let current_branch = refs.current_branch();
+        if current_branch.is_none() {
+            let oid = refs.get_ref_content();
+            write_to_stdout(&format!("* (HEAD detached at {})", &oid[..7]))?;
+        }
         for branch in branches {
-            if branch == current_branch {
+            if Some(&branch) == current_branch.as_ref() {
                 write_to_stdout(&format!("* {}", branch))?;
             } else {
                 write_to_stdout(&branch)?;
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use clap::{ArgGroup, Parser};
+use std::io::{self, BufRead, Write};
+use std::process::exit;
+
+use crate::{
+    database::{Content, Database, Header, ObjectType, Tree},
+    refs::{parse_revision, Refs},
+    utils::{get_root_path, write_to_stdout},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+#[command(group(ArgGroup::new("mode").required(true)))]
+pub struct CatFileCMD {
+    /// Show the object type
+    #[arg(short = 't', group = "mode")]
+    show_type: bool,
+
+    /// Show the object size
+    #[arg(short = 's', group = "mode")]
+    size: bool,
+
+    /// Pretty-print the object content
+    #[arg(short = 'p', group = "mode")]
+    pretty: bool,
+
+    /// Exit with zero status if the object exists
+    #[arg(short = 'e', group = "mode")]
+    exists: bool,
+
+    /// Print the type, size and content of every object named on stdin
+    #[arg(long, group = "mode")]
+    batch: bool,
+
+    /// Print the type and size of every object named on stdin
+    #[arg(long, group = "mode")]
+    batch_check: bool,
+
+    /// The object to show
+    #[arg(required_unless_present_any = ["batch", "batch_check"])]
+    object: Option<String>,
+}
+
+impl CatFileCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+
+        if self.batch || self.batch_check {
+            return self.run_batch(&refs, &database);
+        }
+
+        let name = self.object.as_ref().expect("object is required");
+        let oid = match CatFileCMD::lookup(&refs, &database, name) {
+            Some(oid) => oid,
+            None => parse_revision(name, name).resolve_or_exit(&refs)?,
+        };
+        if !database.exists(&oid) {
+            if self.exists {
+                exit(1);
+            }
+            anyhow::bail!("fatal: Not a valid object name {}", name);
+        }
+
+        let object_store = database.object_store.clone();
+        let header = Header::parse(&oid, object_store.clone())?;
+        if self.exists {
+            return Ok(());
+        } else if self.show_type {
+            write_to_stdout(header.object_type.as_str())?;
+        } else if self.size {
+            write_to_stdout(&header.object_size.to_string())?;
+        } else if let ObjectType::Tree = header.object_type {
+            for entry in Tree::read_entries(&oid, object_store)? {
+                write_to_stdout(&format!(
+                    "{:06o} {} {}\t{}",
+                    entry.mode,
+                    entry.object_type(),
+                    entry.oid,
+                    entry.name
+                ))?;
+            }
+        } else {
+            let content = Content::parse(&oid, object_store)?;
+            io::stdout().write_all(&content.body)?;
+        }
+        Ok(())
+    }
+
+    /// Answer one object name per line of stdin until it is closed. Every answer is flushed
+    /// straight away so a script can keep the process open and talk to it.
+    fn run_batch(&self, refs: &Refs, database: &Database) -> Result<()> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+
+        for line in stdin.lock().lines() {
+            let line = line?;
+            let name = line.trim();
+            let oid = CatFileCMD::lookup(refs, database, name).filter(|oid| database.exists(oid));
+            match oid {
+                Some(oid) => {
+                    let content = Content::parse(&oid, database.object_store.clone())?;
+                    writeln!(
+                        handle,
+                        "{} {} {}",
+                        oid,
+                        content.header.object_type.as_str(),
+                        content.header.object_size
+                    )?;
+                    if self.batch {
+                        handle.write_all(&content.body)?;
+                        writeln!(handle)?;
+                    }
+                }
+                None => writeln!(handle, "{} missing", name)?,
+            }
+            handle.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Find the object a plain name refers to: a full or unambiguous abbreviated oid, or a
+    /// ref. A full oid is returned even if there is no such object.
+    fn lookup(refs: &Refs, database: &Database, name: &str) -> Option<String> {
+        if name.len() == 40 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Some(name.to_owned());
+        }
+        if let Some(oid) = refs.read_ref(name) {
+            return Some(oid);
+        }
+
+        let oids = database.prefix_match_oids(name);
+        match oids.as_slice() {
+            [oid] if name.len() >= 4 => Some(oid.clone()),
+            _ => None,
+        }
+    }
+}
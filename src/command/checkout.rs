@@ -0,0 +1,136 @@
+use anyhow::Result;
+use clap::Parser;
+use std::process::exit;
+
+use crate::{
+    database::{Commit, Database, ParsedContent},
+    index::Index,
+    migration::Migration,
+    refs::{parse_revision, Refs},
+    utils::{get_root_path, write_to_stderr},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct CheckoutCMD {
+    /// Create a new branch and switch to it
+    #[arg(short = 'b', short_alias = 'c', value_name = "new-branch")]
+    new_branch: Option<String>,
+
+    /// The branch or commit to switch to
+    target: Option<String>,
+}
+
+impl CheckoutCMD {
+    pub fn run(self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+
+        let target = match (&self.target, &self.new_branch) {
+            (Some(target), _) => target.clone(),
+            (None, Some(_)) => "HEAD".to_owned(),
+            (None, None) => anyhow::bail!("fatal: you must specify a branch to switch to"),
+        };
+
+        let current_oid = refs.read_head().filter(|oid| !oid.is_empty());
+        let target_oid = parse_revision(&target, &target).resolve_commit_or_exit(&refs)?;
+        let target_commit = match database.read_object(&target_oid)? {
+            ParsedContent::CommitContent(commit) => commit,
+            _ => anyhow::bail!("fatal: reference is not a tree: {}", target),
+        };
+
+        if let Some(new_branch) = &self.new_branch {
+            refs.check_new_branch(new_branch)?;
+        }
+
+        let mut index = Index::new(git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+
+        let old_tree = database.read_head()?;
+        let new_tree = database.read_commit_tree(&target_oid)?;
+        let mut migration =
+            Migration::new(root_path.clone(), &database, &mut index, old_tree, new_tree);
+
+        let errors = migration.conflicts("checkout");
+        if !errors.is_empty() {
+            index.lockfile.rollback()?;
+            for error in errors {
+                write_to_stderr(&error)?;
+            }
+            write_to_stderr("Aborting")?;
+            exit(1);
+        }
+
+        migration.apply_changes()?;
+        index.changed = true;
+        index.write_updates()?;
+
+        // only now that the workspace has been switched, so a checkout aborting on local
+        // changes leaves no branch behind
+        if let Some(new_branch) = &self.new_branch {
+            refs.create_branch(
+                new_branch,
+                &target_oid,
+                &format!("branch: Created from {}", target),
+            )?;
+        }
+
+        let previous_branch = refs.current_branch();
+        let branch = self.new_branch.clone().unwrap_or(target.clone());
+        let from = previous_branch
+            .clone()
+            .or(current_oid.clone())
+            .unwrap_or_default();
+        let message = format!("checkout: moving from {} to {}", from, branch);
+        refs.set_head(&branch, &target_oid, &message)?;
+
+        self.print_message(&refs, previous_branch, current_oid, &target_commit)?;
+        Ok(())
+    }
+
+    fn print_message(
+        &self,
+        refs: &Refs,
+        previous_branch: Option<String>,
+        previous_oid: Option<String>,
+        target_commit: &Commit,
+    ) -> Result<()> {
+        let oid = target_commit.oid.clone().expect("no OID found");
+        let short_oid = &oid[..7];
+        let title = target_commit.message.lines().next().unwrap_or("");
+
+        if previous_branch.is_none() {
+            if let Some(previous_oid) = previous_oid.filter(|previous| previous != &oid) {
+                write_to_stderr(&format!(
+                    "Previous HEAD position was {}",
+                    &previous_oid[..7]
+                ))?;
+            }
+        }
+
+        match refs.current_branch() {
+            Some(branch) => {
+                if self.new_branch.is_some() {
+                    write_to_stderr(&format!("Switched to a new branch '{}'", branch))?;
+                } else if previous_branch.as_ref() == Some(&branch) {
+                    write_to_stderr(&format!("Already on '{}'", branch))?;
+                } else {
+                    write_to_stderr(&format!("Switched to branch '{}'", branch))?;
+                }
+            }
+            None => {
+                if previous_branch.is_some() {
+                    write_to_stderr(&format!(
+                        "Note: switching to '{}'.\n\nYou are in 'detached HEAD' state. You can look around, make experimental\nchanges and commit them, and you can discard any commits you make in this\nstate without impacting any branches by switching back to a branch.\n",
+                        self.target.clone().unwrap_or_default()
+                    ))?;
+                }
+                write_to_stderr(&format!("HEAD is now at {} {}", short_oid, title))?;
+            }
+        }
+        Ok(())
+    }
+}
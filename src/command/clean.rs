@@ -0,0 +1,199 @@
+use anyhow::Result;
+use clap::{ArgGroup, Parser};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use crate::{
+    index::{FlatIndex, Index},
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+    workspace::WorkspaceTree,
+};
+
+#[derive(Parser, Debug, PartialEq)]
+#[command(group(ArgGroup::new("ignored").args(["all", "only_ignored"])))]
+pub struct CleanCMD {
+    /// Only show what would be removed
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Really remove the files; without it or -n nothing is done
+    #[arg(short, long)]
+    force: bool,
+
+    /// Remove untracked directories as well as untracked files
+    #[arg(short)]
+    directories: bool,
+
+    /// Remove ignored files as well
+    #[arg(short = 'x')]
+    all: bool,
+
+    /// Only remove ignored files
+    #[arg(short = 'X')]
+    only_ignored: bool,
+
+    /// Leave the files matching this pattern alone, on top of the ignore rules
+    #[arg(short, long = "exclude", value_name = "pattern")]
+    exclude: Vec<String>,
+
+    /// Only clean inside these paths
+    paths: Vec<String>,
+}
+
+impl CleanCMD {
+    pub fn run(&self) -> Result<()> {
+        if !self.force && !self.dry_run {
+            write_to_stderr("fatal: clean.requireForce defaults to true and neither -n nor -f given; refusing to clean")?;
+            exit(128);
+        }
+
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let mut index = Index::new(git_path.join("index"));
+        index.load()?;
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+        let tracked = flat_index
+            .entries
+            .keys()
+            .chain(index.conflicts.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let ignored_files = WorkspaceTree::ignored_files()?;
+
+        let mut candidates = vec![];
+        self.collect(
+            &root_path,
+            Path::new(""),
+            &tracked,
+            &ignored_files,
+            &mut candidates,
+        )?;
+
+        for candidate in candidates {
+            if self.dry_run {
+                write_to_stdout(&format!("Would remove {}", candidate))?;
+                continue;
+            }
+            write_to_stdout(&format!("Removing {}", candidate))?;
+            let path = root_path.join(&candidate);
+            // a symlink goes on its own, whatever it points to
+            if fs::symlink_metadata(&path)?.is_dir() {
+                fs::remove_dir_all(path)?;
+            } else {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Collect what to remove inside the directory `dir`, relative to the root. A directory
+    /// with nothing to keep inside is removed as a whole, so it goes in as `dir/` instead
+    /// of its files. Returns whether everything inside `dir` is to be removed.
+    fn collect(
+        &self,
+        root_path: &PathBuf,
+        dir: &Path,
+        tracked: &BTreeSet<String>,
+        ignored_files: &[String],
+        candidates: &mut Vec<String>,
+    ) -> Result<bool> {
+        let mut entries = fs::read_dir(root_path.join(dir))?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort();
+
+        let mut everything = true;
+        for name in entries {
+            let path = dir.join(&name);
+            let key = path.to_str().expect("invalid path").to_owned();
+            // the repository itself is never touched
+            if key == ".rgit" {
+                everything = false;
+                continue;
+            }
+
+            let ignored = WorkspaceTree::is_ignored(&path, ignored_files);
+            let excluded = self.is_excluded(&path);
+            // with -X only ignored files go, the excluded ones included
+            let remove = match (self.all, self.only_ignored) {
+                (_, true) => ignored || excluded,
+                (true, _) => !excluded,
+                _ => !ignored && !excluded,
+            };
+
+            // symlinks are left as they are, so they count as files here; anything they
+            // point to, such as the repository or a directory outside of the working tree,
+            // is never walked into
+            let is_dir = fs::symlink_metadata(root_path.join(&path))?.is_dir();
+            // nested repositories are not ours to clean
+            if is_dir && root_path.join(&path).join(".rgit").exists() {
+                everything = false;
+                continue;
+            }
+
+            if is_dir {
+                let prefix = format!("{}/", key);
+                let has_tracked = tracked.iter().any(|path| path.starts_with(&prefix));
+                let in_scope = self.in_scope(&key);
+                let leads_to_scope = self.paths.iter().any(|spec| spec.starts_with(&prefix));
+                if (!has_tracked && !self.directories) || (!in_scope && !leads_to_scope) {
+                    everything = false;
+                    continue;
+                }
+                if !has_tracked && (ignored || excluded) {
+                    match remove {
+                        true => candidates.push(prefix),
+                        false => everything = false,
+                    }
+                    continue;
+                }
+
+                let mut inside = vec![];
+                let all_inside =
+                    self.collect(root_path, &path, tracked, ignored_files, &mut inside)?;
+                // with -X a directory that is not ignored itself stays
+                if all_inside && !has_tracked && in_scope && !self.only_ignored {
+                    candidates.push(prefix);
+                } else {
+                    candidates.extend(inside);
+                    everything = false;
+                }
+            } else if !tracked.contains(&key) && remove && self.in_scope(&key) {
+                candidates.push(key);
+            } else {
+                everything = false;
+            }
+        }
+        Ok(everything)
+    }
+
+    /// Whether a component of the path, or the whole of it, matches one of the -e patterns
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.exclude.iter().any(|pattern| {
+            let pattern = pattern.trim_matches('/');
+            glob::Pattern::new(pattern).is_ok_and(|glob| {
+                glob.matches_path(path)
+                    || path
+                        .components()
+                        .any(|component| glob.matches(component.as_os_str().to_str().unwrap()))
+            })
+        })
+    }
+
+    /// Whether the path is one of the given paths or inside one
+    fn in_scope(&self, path: &str) -> bool {
+        self.paths.is_empty()
+            || self.paths.iter().any(|spec| {
+                let spec = spec.trim_start_matches("./").trim_end_matches('/');
+                spec.is_empty()
+                    || spec == "."
+                    || path == spec
+                    || path.starts_with(&format!("{}/", spec))
+            })
+    }
+}
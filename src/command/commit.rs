@@ -1,18 +1,40 @@
 use anyhow::Result;
 use clap::{arg, Parser};
 use std::env;
+use std::path::Path;
+use std::process::exit;
 
 use crate::{
-    database::{Author, Commit, Database, Tree},
+    command::write_tree::write_tree,
+    database::{Author, Commit, Database},
     index::Index,
+    merge::Bases,
+    pending_commit::{MergeType, PendingCommit},
     refs::Refs,
-    utils::{get_root_path, write_to_stdout},
+    utils::{edit_message, get_root_path, write_to_stderr, write_to_stdout},
 };
 
+/// What committing with conflicts still in the index fails with, before the line saying
+/// which command gave up
+pub static UNMERGED_FILES: &str = "error: Committing is not possible because you have unmerged files.\nhint: Fix them up in the work tree, and then use 'rgit add <file>'\nhint: as appropriate to mark resolution and make a commit.";
+
 #[derive(Parser, Debug, PartialEq)]
 pub struct CommitCMD {
     #[arg(short)]
-    message: String,
+    message: Option<String>,
+
+    /// Replace the tip of the current branch with a new commit of the index, keeping the
+    /// parents, message and author of the commit it replaces
+    #[arg(long)]
+    amend: bool,
+
+    /// Keep the message of the amended commit without opening the editor
+    #[arg(long, requires = "amend")]
+    no_edit: bool,
+
+    /// Make the current user the author of the amended commit
+    #[arg(long, requires = "amend")]
+    reset_author: bool,
 }
 
 impl CommitCMD {
@@ -20,48 +42,181 @@ impl CommitCMD {
         let root_path = get_root_path()?;
         let git_path = root_path.join(".rgit");
         let refs = Refs::new(git_path.clone());
-        let object_store = git_path.join("objects");
-        let mut db = Database::new(object_store);
+        let pending_commit = PendingCommit::new(git_path.clone());
         let mut index = Index::new(git_path.join("index"));
-
         index.load()?;
-        let mut root = Tree::new();
-        root.build_from_index(&index);
-        root.traverse(&mut db)?;
-        db.store(&mut root)?;
 
-        let (name, email) = self
-            .get_config()
-            .map_err(|_| anyhow::anyhow!("failed to get author details"))?;
-        let author = Author::new(&name, &email);
+        if index.is_conflicted() {
+            write_to_stderr(&format!(
+                "{}\nfatal: Exiting because of an unresolved conflict.",
+                UNMERGED_FILES
+            ))?;
+            exit(128);
+        }
 
-        let parent = refs.read_head();
-        let message = self.message.clone();
-        let mut commit = Commit::new(
-            parent.to_owned(),
-            root.oid.expect("OID not found"),
-            author,
-            message.clone(),
-        );
-        db.store(&mut commit)?;
+        if self.amend {
+            return self.amend(&git_path, &refs, &pending_commit, &index);
+        }
 
-        // update the index to store the tree cache
+        let mut parents = refs
+            .read_head()
+            .into_iter()
+            .filter(|oid| !oid.is_empty())
+            .collect::<Vec<_>>();
+        let message = match &self.message {
+            Some(message) => message.clone(),
+            None if pending_commit.in_progress() => {
+                pending_commit.merge_message()?.trim_end().to_owned()
+            }
+            None => anyhow::bail!("Aborting commit due to empty commit message."),
+        };
+        // a cherry-picked commit keeps its author, a merge records what was merged in
+        let author = match pending_commit.merge_type() {
+            Some(MergeType::CherryPick) => {
+                let database = Database::new(git_path.join("objects"));
+                database.read_commit(&pending_commit.merge_oid()?)?.author
+            }
+            _ => self.get_author()?,
+        };
+        let action = match pending_commit.merge_type() {
+            _ if parents.is_empty() => "commit (initial)",
+            Some(MergeType::Merge) => "commit (merge)",
+            _ => "commit",
+        };
+        if pending_commit.merge_type() == Some(MergeType::Merge) {
+            parents.push(pending_commit.merge_oid()?);
+        }
+
+        let commit = write_commit(&git_path, &index, parents, author, message, action)?;
+        pending_commit.clear()?;
 
         let commit_oid = commit.oid.expect("Failed to get commit oid").clone();
-        refs.update_head(&commit_oid)?;
+        write_to_stdout(&format!("{} {}", commit_oid, commit.message))?;
+        Ok(())
+    }
+
+    /// Commit the index in place of HEAD, with HEAD's parents. The branch ref moves to the
+    /// new commit; other branches that contain the old one keep pointing at it, which is
+    /// worth a warning since they no longer share the rewritten history.
+    fn amend(
+        &self,
+        git_path: &Path,
+        refs: &Refs,
+        pending_commit: &PendingCommit,
+        index: &Index,
+    ) -> Result<()> {
+        if let Some(merge_type) = pending_commit.merge_type() {
+            write_to_stderr(&format!(
+                "fatal: You are in the middle of a {} -- cannot amend.",
+                merge_type.name()
+            ))?;
+            exit(128);
+        }
+        let head_oid = match refs.read_head().filter(|oid| !oid.is_empty()) {
+            Some(oid) => oid,
+            None => {
+                write_to_stderr("fatal: You have nothing to amend.")?;
+                exit(128);
+            }
+        };
+
+        let database = Database::new(git_path.join("objects"));
+        let head = database.read_commit(&head_oid)?;
+        let message = match &self.message {
+            Some(message) => message.clone(),
+            None if self.no_edit => head.message.clone(),
+            None => edit_message(git_path, &head.message)?,
+        };
+        let author = match self.reset_author {
+            true => self.get_author()?,
+            false => head.author.clone(),
+        };
 
+        let commit = write_commit(
+            git_path,
+            index,
+            head.parents.clone(),
+            author,
+            message,
+            "commit (amend)",
+        )?;
+        let commit_oid = commit.oid.expect("Failed to get commit oid").clone();
         write_to_stdout(&format!("{} {}", commit_oid, commit.message))?;
+
+        let current_branch = refs.current_branch();
+        for branch in refs.list_branches()? {
+            if Some(&branch) == current_branch.as_ref() {
+                continue;
+            }
+            let branch_oid = match refs.read_ref(&format!("refs/heads/{}", branch)) {
+                Some(oid) if !oid.is_empty() => oid,
+                _ => continue,
+            };
+            let bases = Bases::new(&database, &branch_oid, &head_oid).find()?;
+            if bases.first() == Some(&head_oid) {
+                write_to_stderr(&format!(
+                    "warning: the amended commit {} is also on branch '{}', which still points at the old history",
+                    database.short_oid(&head_oid, 7),
+                    branch
+                ))?;
+            }
+        }
         Ok(())
     }
+
+    /// Build the author from the configured name and email
+    fn get_author(&self) -> Result<Author> {
+        let (name, email) = self
+            .get_config()
+            .map_err(|_| anyhow::anyhow!("failed to get author details"))?;
+        Ok(Author::new(&name, &email))
+    }
+
     /// Get the author name and email from the environment variables
     fn get_config(&self) -> Result<(String, String)> {
-        Ok((
-            env::var("RGIT_AUTHOR_NAME")?,
-            env::var("RGIT_AUTHOR_EMAIL")?,
-        ))
+        author_config()
     }
 }
 
+/// Get the author name and email from the environment variables
+pub fn author_config() -> Result<(String, String)> {
+    Ok((
+        env::var("RGIT_AUTHOR_NAME")?,
+        env::var("RGIT_AUTHOR_EMAIL")?,
+    ))
+}
+
+/// Build the author for a new commit from the environment variables
+pub fn current_author() -> Result<Author> {
+    let (name, email) =
+        author_config().map_err(|_| anyhow::anyhow!("failed to get author details"))?;
+    Ok(Author::new(&name, &email))
+}
+
+/// Write the index out as a tree, commit it with the given parents and move HEAD to the new
+/// commit. `action` starts the reflog message, which goes on with the commit's subject, e.g.
+/// `commit (amend)`.
+pub fn write_commit(
+    git_path: &Path,
+    index: &Index,
+    parents: Vec<String>,
+    author: Author,
+    message: String,
+    action: &str,
+) -> Result<Commit> {
+    let refs = Refs::new(git_path.to_path_buf());
+    let mut db = Database::new(git_path.join("objects"));
+
+    let tree = write_tree(&mut db, index)?;
+    let mut commit = Commit::new(parents, tree, author, message);
+    db.store(&mut commit)?;
+
+    let commit_oid = commit.oid.clone().expect("Failed to get commit oid");
+    let subject = commit.message.lines().next().unwrap_or("");
+    refs.update_head(&commit_oid, &format!("{}: {}", action, subject))?;
+    Ok(commit)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,7 +227,10 @@ mod tests {
         std::env::set_var("RGIT_AUTHOR_EMAIL", "test@example.com");
 
         let commit_cmd = CommitCMD {
-            message: "".to_string(),
+            message: Some("".to_string()),
+            amend: false,
+            no_edit: false,
+            reset_author: false,
         };
         let (name, email) = commit_cmd.get_config()?;
 
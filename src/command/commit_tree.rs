@@ -0,0 +1,74 @@
+use anyhow::Result;
+use clap::Parser;
+use std::io::{self, Read};
+use std::process::exit;
+
+use crate::{
+    command::commit::current_author,
+    database::{Commit, Database, Header, ObjectType},
+    refs::{parse_revision, Refs},
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct CommitTreeCMD {
+    /// The tree of the new commit
+    tree: String,
+
+    /// A parent of the new commit, may be given more than once
+    #[arg(short = 'p', value_name = "parent")]
+    parents: Vec<String>,
+
+    /// A paragraph of the commit message, read from stdin when not given
+    #[arg(short = 'm', value_name = "message")]
+    messages: Vec<String>,
+}
+
+impl CommitTreeCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+
+        let tree = parse_revision(&self.tree, &self.tree).resolve_or_exit(&refs)?;
+        if !database.exists(&tree)
+            || !matches!(
+                Header::parse(&tree, database.object_store.clone())?.object_type,
+                ObjectType::Tree
+            )
+        {
+            write_to_stderr(&format!(
+                "fatal: {} is not a valid 'tree' object",
+                self.tree
+            ))?;
+            exit(128);
+        }
+
+        let mut parents = vec![];
+        for parent in &self.parents {
+            let oid = parse_revision(parent, parent).resolve_commit_or_exit(&refs)?;
+            if !parents.contains(&oid) {
+                parents.push(oid);
+            }
+        }
+
+        let message = if self.messages.is_empty() {
+            let mut message = String::new();
+            io::stdin().read_to_string(&mut message)?;
+            message
+        } else {
+            self.messages.join("\n\n")
+        };
+
+        let mut commit = Commit::new(
+            parents,
+            tree,
+            current_author()?,
+            message.trim_end().to_owned(),
+        );
+        database.store(&mut commit)?;
+        write_to_stdout(&commit.oid.expect("failed to get commit oid"))?;
+        Ok(())
+    }
+}
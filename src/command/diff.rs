@@ -237,45 +237,50 @@ impl DiffCMD {
         )
         .expect("failed to parse content to utf8");
 
-        let diff = Myres::new(index_entry_content, workspace_entry_content);
-        let hunks = diff.diff();
-
-        for hunk in hunks {
-            let (a_offset, b_offset) = hunk.header();
-
-            let hunks_offsets = format!(
-                "@@ -{} +{} @@",
-                a_offset
-                    .iter()
-                    .map(|n| n.to_string())
-                    .collect::<Vec<_>>()
-                    .join(","),
-                b_offset
-                    .iter()
-                    .map(|n| n.to_string())
-                    .collect::<Vec<_>>()
-                    .join(",")
-            );
-
-            println!("{}", hunks_offsets.cyan());
-
-            for edit in hunk.edits {
-                match edit.edit_type {
-                    EditType::Add => {
-                        println!("{}", format!("+{}", edit.b_line.unwrap().line).green());
-                    }
-                    EditType::Remove => {
-                        println!("{}", format!("-{}", edit.a_line.unwrap().line).red());
-                    }
-                    EditType::Equal => {
-                        println!("{}", format!(" {}", edit.a_line.unwrap().line));
-                    }
-                }
-            }
-        }
+        print_hunks(&index_entry_content, &workspace_entry_content);
     }
 
     fn short_oid(&self, oid: &str) -> String {
         oid.chars().take(7).collect()
     }
 }
+
+/// Print the hunks of the line diff from `a` to `b`, each under its `@@` header
+pub fn print_hunks(a: &str, b: &str) {
+    let diff = Myres::new(a.to_owned(), b.to_owned());
+    let hunks = diff.diff();
+
+    for hunk in hunks {
+        let (a_offset, b_offset) = hunk.header();
+
+        let hunks_offsets = format!(
+            "@@ -{} +{} @@",
+            a_offset
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            b_offset
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+
+        println!("{}", hunks_offsets.cyan());
+
+        for edit in hunk.edits {
+            match edit.edit_type {
+                EditType::Add => {
+                    println!("{}", format!("+{}", edit.b_line.unwrap().line).green());
+                }
+                EditType::Remove => {
+                    println!("{}", format!("-{}", edit.a_line.unwrap().line).red());
+                }
+                EditType::Equal => {
+                    println!("{}", format!(" {}", edit.a_line.unwrap().line));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::Result;
+use clap::Parser;
+
+use super::repack::default_threads;
+use crate::{database::Prune, pack::Repack, reflog::parse_expiry, utils::get_root_path};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct GcCMD {
+    /// Search harder for deltas, trying 250 bases for each object instead of 10
+    #[arg(long)]
+    aggressive: bool,
+
+    /// Prune the unreachable loose objects written before this, `never` keeping them all
+    #[arg(long, value_name = "time", default_value = "2.weeks.ago")]
+    prune: String,
+
+    /// Don't report progress
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+impl GcCMD {
+    /// Consolidate everything reachable into a single pack, removing the old packs and the
+    /// loose objects that made it in, then prune the old unreachable loose objects. The
+    /// unreachable objects of the old packs are written out loose first, so the grace
+    /// period of `--prune` applies to them too.
+    pub fn run(&self) -> Result<()> {
+        let git_path = get_root_path()?.join(".rgit");
+        let expire = parse_expiry("--prune", &self.prune)?;
+        let repack = Repack {
+            all: true,
+            delete: true,
+            unpack_unreachable: true,
+            window: if self.aggressive { 250 } else { 10 },
+            depth: 50,
+            threads: default_threads(),
+            quiet: self.quiet,
+        };
+        repack.run(&git_path)?;
+        let prune = Prune {
+            expire,
+            dry_run: false,
+        };
+        prune.run(&git_path)
+    }
+}
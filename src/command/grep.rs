@@ -0,0 +1,276 @@
+use anyhow::Result;
+use clap::{ArgGroup, Parser};
+use regex::{Regex, RegexBuilder};
+use std::fs;
+use std::path::Path;
+use std::process::exit;
+
+use crate::{
+    command::show::ShowCMD,
+    database::Database,
+    index::{FlatIndex, Index},
+    refs::{parse_revision, Refs, USE_SEPARATOR},
+    utils::{get_root_path, is_binary_file, write_to_stderr, write_to_stdout},
+    workspace::WorkspaceTree,
+};
+
+#[derive(Parser, Debug, PartialEq)]
+#[command(group(ArgGroup::new("syntax").args(["extended", "fixed"])))]
+pub struct GrepCMD {
+    /// Search the files staged in the index instead of the working tree
+    #[arg(long)]
+    cached: bool,
+
+    /// Prefix every matching line with its line number
+    #[arg(short = 'n', long)]
+    line_number: bool,
+
+    /// Ignore case differences between the pattern and the files
+    #[arg(short, long)]
+    ignore_case: bool,
+
+    /// Only show the names of the files that match
+    #[arg(short = 'l', long)]
+    files_with_matches: bool,
+
+    /// Show the number of matching lines of each file instead of the lines
+    #[arg(short, long)]
+    count: bool,
+
+    /// Only match the pattern at word boundaries
+    #[arg(short, long)]
+    word_regexp: bool,
+
+    /// Take the pattern as an extended regular expression instead of a basic one
+    #[arg(short = 'E', long)]
+    extended: bool,
+
+    /// Take the pattern as a fixed string
+    #[arg(short = 'F', long = "fixed-strings")]
+    fixed: bool,
+
+    /// The pattern to look for
+    pattern: String,
+
+    /// The revisions whose trees to search, followed by the paths to limit the search to
+    args: Vec<String>,
+
+    /// The paths to limit the search to, when given after `--`, which makes all the
+    /// arguments before it revisions
+    #[arg(last = true)]
+    paths: Option<Vec<String>>,
+}
+
+/// The revisions to search as they were given, each with the commit it names
+type Revisions = Vec<(String, String)>;
+
+/// A file to search: its name in the output and its content
+struct Source {
+    name: String,
+    content: Vec<u8>,
+}
+
+impl GrepCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+
+        let regex = match self.regex() {
+            Ok(regex) => regex,
+            Err(error) => {
+                write_to_stderr(&format!("fatal: invalid pattern: {}", error))?;
+                exit(128);
+            }
+        };
+
+        let (revisions, paths) = self.split_args(&root_path, &refs)?;
+        if self.cached && !revisions.is_empty() {
+            write_to_stderr("fatal: --cached cannot be used with revisions")?;
+            exit(128);
+        }
+
+        let mut index = Index::new(git_path.join("index"));
+        index.load()?;
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+
+        let mut sources = vec![];
+        if !revisions.is_empty() {
+            for (revision, oid) in &revisions {
+                let tree = database.read_commit_tree(oid)?;
+                for (path, file) in &tree.entries {
+                    if GrepCMD::matches(&paths, path) {
+                        sources.push(Source {
+                            name: format!("{}:{}", revision, path),
+                            content: ShowCMD::read_blob(&database, Some(file))?,
+                        });
+                    }
+                }
+            }
+        } else {
+            // only tracked files are searched, so untracked files never show up
+            let ignored_files = WorkspaceTree::ignored_files()?;
+            for (path, file) in &flat_index.entries {
+                if !GrepCMD::matches(&paths, path)
+                    || WorkspaceTree::is_ignored(Path::new(path), &ignored_files)
+                {
+                    continue;
+                }
+                let content = match self.cached {
+                    true => ShowCMD::read_blob(&database, Some(file))?,
+                    false => match fs::read(root_path.join(path)) {
+                        Ok(content) => content,
+                        Err(_) => continue,
+                    },
+                };
+                sources.push(Source {
+                    name: path.clone(),
+                    content,
+                });
+            }
+        }
+
+        let mut found = false;
+        for source in &sources {
+            found |= self.search(&regex, source)?;
+        }
+        if !found {
+            exit(1);
+        }
+        Ok(())
+    }
+
+    /// Tell the revisions from the paths. Without `--` the leading arguments that name
+    /// commits are revisions and the rest are paths, and like git an argument that could be
+    /// either, or neither, is refused.
+    fn split_args(&self, root_path: &Path, refs: &Refs) -> Result<(Revisions, Vec<String>)> {
+        let mut revisions = vec![];
+        if let Some(paths) = &self.paths {
+            for arg in &self.args {
+                let oid = parse_revision(arg, arg).resolve_commit_or_exit(refs)?;
+                revisions.push((arg.clone(), oid));
+            }
+            return Ok((revisions, paths.clone()));
+        }
+
+        let mut paths = vec![];
+        for arg in &self.args {
+            let oid = match paths.is_empty() {
+                true => parse_revision(arg, arg).resolve_commit(refs).ok(),
+                false => None,
+            };
+            // a glob can't be checked against the working tree
+            let is_path = root_path.join(arg).exists() || arg.contains(['*', '?', '[']);
+            match (oid, is_path) {
+                (Some(_), true) => {
+                    write_to_stderr(&format!(
+                        "fatal: ambiguous argument '{}': both revision and filename\n{}",
+                        arg, USE_SEPARATOR
+                    ))?;
+                    exit(128);
+                }
+                (Some(oid), false) => revisions.push((arg.clone(), oid)),
+                (None, true) => paths.push(arg.clone()),
+                (None, false) => {
+                    write_to_stderr(&format!(
+                        "fatal: ambiguous argument '{}': unknown revision or path not in the working tree.\n{}",
+                        arg, USE_SEPARATOR
+                    ))?;
+                    exit(128);
+                }
+            }
+        }
+        Ok((revisions, paths))
+    }
+
+    /// Build the regex for the pattern. Like git, a pattern is a basic regular expression
+    /// unless -E or -F is given.
+    fn regex(&self) -> Result<Regex, regex::Error> {
+        let mut pattern = match (self.fixed, self.extended) {
+            (true, _) => regex::escape(&self.pattern),
+            (_, true) => self.pattern.clone(),
+            _ => GrepCMD::basic_to_extended(&self.pattern),
+        };
+        // a word match is one that no word character comes right before or after
+        if self.word_regexp {
+            pattern = format!(r"(?:^|\W)(?:{})(?:\W|$)", pattern);
+        }
+        RegexBuilder::new(&pattern)
+            .case_insensitive(self.ignore_case)
+            .build()
+    }
+
+    /// In a basic regular expression `?`, `+`, `|`, braces and parentheses are plain
+    /// characters, and only special when escaped. `\<` and `\>` match the start and the
+    /// end of a word.
+    fn basic_to_extended(pattern: &str) -> String {
+        let mut extended = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(next @ ('?' | '+' | '|' | '(' | ')' | '{' | '}')) => extended.push(next),
+                    Some('<') => extended.push_str(r"\b{start}"),
+                    Some('>') => extended.push_str(r"\b{end}"),
+                    Some(next) => {
+                        extended.push('\\');
+                        extended.push(next);
+                    }
+                    None => extended.push_str(r"\\"),
+                },
+                '?' | '+' | '|' | '(' | ')' | '{' | '}' => {
+                    extended.push('\\');
+                    extended.push(c);
+                }
+                _ => extended.push(c),
+            }
+        }
+        extended
+    }
+
+    /// Print the matches of one file, returning whether there were any
+    fn search(&self, regex: &Regex, source: &Source) -> Result<bool> {
+        let text = String::from_utf8_lossy(&source.content);
+        let matches = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| regex.is_match(line))
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            return Ok(false);
+        }
+
+        if self.files_with_matches {
+            write_to_stdout(&source.name)?;
+        } else if self.count {
+            write_to_stdout(&format!("{}:{}", source.name, matches.len()))?;
+        } else if is_binary_file(&source.content)? {
+            write_to_stdout(&format!("Binary file {} matches", source.name))?;
+        } else {
+            for (number, line) in matches {
+                match self.line_number {
+                    true => write_to_stdout(&format!("{}:{}:{}", source.name, number + 1, line))?,
+                    false => write_to_stdout(&format!("{}:{}", source.name, line))?,
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Whether `path` is one of the pathspecs, sits inside one or matches one as a glob
+    fn matches(paths: &[String], path: &str) -> bool {
+        paths.is_empty()
+            || paths.iter().any(|spec| {
+                let spec = spec.trim_start_matches("./").trim_end_matches('/');
+                spec.is_empty()
+                    || spec == "."
+                    || path == spec
+                    || path.starts_with(&format!("{}/", spec))
+                    || glob::Pattern::new(spec).is_ok_and(|pattern| pattern.matches(path))
+            })
+    }
+}
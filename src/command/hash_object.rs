@@ -0,0 +1,103 @@
+use anyhow::Result;
+use clap::Parser;
+use std::fs;
+use std::io::{self, Read};
+use std::process::exit;
+
+use crate::{
+    database::{Database, Storable},
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct HashObjectCMD {
+    /// Write the object into the database
+    #[arg(short)]
+    write: bool,
+
+    /// Read the object from stdin
+    #[arg(long)]
+    stdin: bool,
+
+    /// The type of the object
+    #[arg(short = 't', default_value = "blob")]
+    object_type: String,
+
+    /// The files to hash
+    files: Vec<String>,
+}
+
+/// An object of any type, stored exactly as it was given
+struct LooseObject {
+    oid: Option<String>,
+    object_type: String,
+    data: String,
+}
+
+impl Storable for LooseObject {
+    fn set_oid(&mut self, oid: String) {
+        self.oid = Some(oid);
+    }
+
+    fn blob_type(&self) -> String {
+        self.object_type.clone()
+    }
+
+    fn data(&self) -> String {
+        self.data.clone()
+    }
+}
+
+impl HashObjectCMD {
+    pub fn run(&self) -> Result<()> {
+        if !["blob", "tree", "commit", "tag"].contains(&self.object_type.as_str()) {
+            write_to_stderr(&format!(
+                "fatal: invalid object type \"{}\"",
+                self.object_type
+            ))?;
+            exit(128);
+        }
+
+        // hashing works anywhere, only writing needs a repository
+        let object_store = match self.write {
+            true => get_root_path()?.join(".rgit").join("objects"),
+            false => Default::default(),
+        };
+        let database = Database::new(object_store);
+
+        let mut contents = vec![];
+        if self.stdin {
+            let mut data = vec![];
+            io::stdin().read_to_end(&mut data)?;
+            contents.push(data);
+        }
+        for file in &self.files {
+            match fs::read(file) {
+                Ok(data) => contents.push(data),
+                Err(_) => {
+                    write_to_stderr(&format!(
+                        "fatal: could not open '{}' for reading: No such file or directory",
+                        file
+                    ))?;
+                    exit(128);
+                }
+            }
+        }
+
+        for data in contents {
+            let mut object = LooseObject {
+                oid: None,
+                object_type: self.object_type.clone(),
+                data: unsafe { String::from_utf8_unchecked(data) },
+            };
+            let oid = if self.write {
+                database.store(&mut object)?;
+                object.oid.expect("failed to get object oid")
+            } else {
+                database.hash_object(&object)
+            };
+            write_to_stdout(&oid)?;
+        }
+        Ok(())
+    }
+}
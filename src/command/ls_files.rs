@@ -0,0 +1,153 @@
+use anyhow::Result;
+use clap::Parser;
+use std::path::Path;
+use std::process::exit;
+
+use crate::{
+    index::{FlatIndex, Index},
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+    workspace::{File, WorkspaceTree},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct LsFilesCMD {
+    /// Show the files in the index (the default)
+    #[arg(short, long)]
+    cached: bool,
+
+    /// Show the mode, oid and stage of every index entry
+    #[arg(short, long)]
+    stage: bool,
+
+    /// Show the files in the index that are missing from the working tree
+    #[arg(short, long)]
+    deleted: bool,
+
+    /// Show the files in the index that differ from the working tree
+    #[arg(short, long)]
+    modified: bool,
+
+    /// Show the files in the working tree that are not in the index
+    #[arg(short, long)]
+    others: bool,
+
+    /// Only show ignored files, together with --cached or --others
+    #[arg(short, long)]
+    ignored: bool,
+}
+
+impl LsFilesCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let mut index = Index::new(git_path.join("index"));
+        index.load()?;
+
+        if self.ignored && !self.cached && !self.others {
+            write_to_stderr("fatal: ls-files -i must be used with either -o or -c")?;
+            exit(128);
+        }
+        let show_cached =
+            self.cached || self.stage || !(self.deleted || self.modified || self.others);
+
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+
+        // every entry in path order, the stages of a conflicted path in stage order
+        let mut entries = flat_index.entries.values().collect::<Vec<_>>();
+        entries.extend(index.conflicts.values().flatten());
+        entries.sort_by(|a, b| {
+            (a.path.to_str(), Index::stage(a)).cmp(&(b.path.to_str(), Index::stage(b)))
+        });
+        let ignored_files = WorkspaceTree::ignored_files()?;
+
+        if self.others {
+            self.show_others(&root_path, &flat_index, &index)?;
+        }
+
+        if show_cached {
+            for entry in &entries {
+                if self.ignored && !WorkspaceTree::is_ignored(&entry.path, &ignored_files) {
+                    continue;
+                }
+                self.show_entry(entry)?;
+            }
+        }
+
+        if self.deleted || self.modified {
+            let workspace = WorkspaceTree::new(Some(&root_path));
+            let mut flat_workspace = FlatIndex {
+                entries: Default::default(),
+            };
+            Index::flatten_entries(&workspace.workspace, &mut flat_workspace);
+
+            for entry in &entries {
+                let path = entry.path.to_str().unwrap();
+                match flat_workspace.entries.get(path) {
+                    None if !root_path.join(path).is_file() => {
+                        // a deleted file counts as modified too
+                        if self.deleted {
+                            self.show_entry(entry)?;
+                        }
+                        if self.modified {
+                            self.show_entry(entry)?;
+                        }
+                    }
+                    Some(file) if self.modified && LsFilesCMD::is_modified(entry, file) => {
+                        self.show_entry(entry)?;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Show the workspace files that are not in the index, or only the ignored ones with
+    /// --ignored
+    fn show_others(&self, root_path: &Path, flat_index: &FlatIndex, index: &Index) -> Result<()> {
+        let tracked = |path: &str| {
+            flat_index.entries.contains_key(path) || index.conflicts.contains_key(path)
+        };
+
+        if self.ignored {
+            for path in WorkspaceTree::list_ignored_files(&root_path.to_path_buf()) {
+                let path = path.to_str().unwrap();
+                if !tracked(path) {
+                    write_to_stdout(path)?;
+                }
+            }
+            return Ok(());
+        }
+
+        for file in WorkspaceTree::list_files(&root_path.to_path_buf()) {
+            let path = file.path.to_str().unwrap();
+            if !tracked(path) {
+                write_to_stdout(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn show_entry(&self, entry: &File) -> Result<()> {
+        let path = entry.path.to_str().unwrap();
+        if self.stage {
+            write_to_stdout(&format!(
+                "{:06o} {} {}\t{}",
+                entry.stat.mode,
+                entry.oid.as_ref().expect("failed to get oid"),
+                Index::stage(entry),
+                path
+            ))
+        } else {
+            write_to_stdout(path)
+        }
+    }
+
+    /// Whether the workspace file differs from its index entry in content or mode
+    fn is_modified(entry: &File, file: &File) -> bool {
+        entry.oid != file.oid || entry.stat.mode != file.stat.mode
+    }
+}
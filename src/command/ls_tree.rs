@@ -0,0 +1,96 @@
+use anyhow::Result;
+use clap::Parser;
+use std::process::exit;
+
+use crate::{
+    database::{Database, ParsedContent, Tree},
+    refs::{parse_revision, Refs},
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct LsTreeCMD {
+    /// Recurse into subtrees
+    #[arg(short)]
+    recursive: bool,
+
+    /// Show the tree entries themselves when recursing into them
+    #[arg(short = 't')]
+    show_trees: bool,
+
+    /// Only show the paths
+    #[arg(long)]
+    name_only: bool,
+
+    /// The tree, or the commit or tag pointing to it
+    tree_ish: String,
+
+    /// Only show the entries matching these paths
+    paths: Vec<String>,
+}
+
+impl LsTreeCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+
+        let oid = parse_revision(&self.tree_ish, &self.tree_ish).resolve_commit_or_exit(&refs)?;
+        let tree = match database.read_object(&oid)? {
+            ParsedContent::CommitContent(commit) => commit.tree,
+            ParsedContent::TreeContent(_) => oid,
+            _ => {
+                write_to_stderr("fatal: not a tree object")?;
+                exit(128);
+            }
+        };
+
+        self.show_tree(&database, &tree, "")
+    }
+
+    /// Show the entries of the tree `oid`, which sits at `prefix` inside the root tree
+    fn show_tree(&self, database: &Database, oid: &str, prefix: &str) -> Result<()> {
+        for entry in Tree::read_entries(oid, database.object_store.clone())? {
+            let path = format!("{}{}", prefix, entry.name);
+            let is_tree = entry.object_type() == "tree";
+            let descend = is_tree && (self.recursive || self.is_above_path(&path));
+
+            if self.matches(&path) && (!descend || self.show_trees) {
+                if self.name_only {
+                    write_to_stdout(&path)?;
+                } else {
+                    write_to_stdout(&format!(
+                        "{:06o} {} {}\t{}",
+                        entry.mode,
+                        entry.object_type(),
+                        entry.oid,
+                        path
+                    ))?;
+                }
+            }
+            if descend {
+                self.show_tree(database, &entry.oid, &format!("{}/", path))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `path` is one of the given paths or sits inside one. A path ending in `/`
+    /// only matches what is inside it.
+    fn matches(&self, path: &str) -> bool {
+        self.paths.is_empty()
+            || self.paths.iter().any(|spec| {
+                path == spec
+                    || (spec.ends_with('/') && path.starts_with(spec.as_str()))
+                    || path.starts_with(&format!("{}/", spec))
+            })
+    }
+
+    /// Whether one of the given paths is inside the tree at `path`, so it has to be read
+    /// even without -r
+    fn is_above_path(&self, path: &str) -> bool {
+        let prefix = format!("{}/", path);
+        self.paths.iter().any(|spec| spec.starts_with(&prefix))
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::Result;
+use clap::Parser;
+use std::process::exit;
+
+use crate::{
+    command::{
+        commit::{current_author, write_commit},
+        status::tracked_files,
+    },
+    database::Database,
+    index::{FlatIndex, Index},
+    merge::{Bases, Inputs, Resolve},
+    migration::Migration,
+    pending_commit::{MergeType, PendingCommit},
+    refs::{parse_revision, Refs},
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct MergeCMD {
+    /// The message for the merge commit
+    #[arg(short, long)]
+    message: Option<String>,
+
+    /// Merge histories that share no commit, starting from an empty tree
+    #[arg(long)]
+    allow_unrelated_histories: bool,
+
+    /// The branch or commit to merge into the current branch
+    revision: String,
+}
+
+impl MergeCMD {
+    pub fn run(self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+        let pending_commit = PendingCommit::new(git_path.clone());
+
+        if let Some(merge_type) = pending_commit.merge_type() {
+            write_to_stderr(&format!(
+                "fatal: You have not concluded your {} ({} exists).\nPlease, commit your changes before you merge.",
+                merge_type.name(),
+                merge_type.head_file()
+            ))?;
+            exit(128);
+        }
+
+        let head_oid = refs.get_ref_content();
+        let merge_oid =
+            parse_revision(&self.revision, &self.revision).resolve_commit_or_exit(&refs)?;
+
+        // when there is more than one best common ancestor we simply use the newest one
+        let base_oid = Bases::new(&database, &head_oid, &merge_oid)
+            .find()?
+            .first()
+            .cloned();
+
+        if base_oid.is_none() && !self.allow_unrelated_histories {
+            write_to_stderr("fatal: refusing to merge unrelated histories")?;
+            exit(128);
+        }
+        if base_oid.as_ref() == Some(&merge_oid) {
+            write_to_stdout("Already up to date.")?;
+            return Ok(());
+        }
+
+        let mut index = Index::new(git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+
+        if base_oid.as_ref() == Some(&head_oid) {
+            return self.fast_forward(&refs, &database, &mut index, &head_oid, &merge_oid);
+        }
+
+        // whatever is staged would end up in the merge commit
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+        let staged = tracked_files(&flat_index, &database.read_commit_tree(&head_oid)?);
+        if !staged.is_empty() {
+            index.lockfile.rollback()?;
+            write_to_stderr(&format!(
+                "error: Your local changes to the following files would be overwritten by merge:\n\t{}\nPlease commit your changes or stash them before you merge.\nAborting",
+                staged.into_keys().collect::<Vec<_>>().join("\n\t")
+            ))?;
+            exit(1);
+        }
+
+        let inputs = Inputs {
+            left_name: "HEAD".to_owned(),
+            right_name: self.revision.clone(),
+            left_oid: head_oid.clone(),
+            right_oid: merge_oid.clone(),
+            base_oid,
+        };
+        let mut resolve = Resolve::new(root_path.clone(), &database, &mut index, &inputs);
+        let errors = resolve.execute()?;
+        if !errors.is_empty() {
+            index.lockfile.rollback()?;
+            for error in errors {
+                write_to_stderr(&error)?;
+            }
+            write_to_stderr("Aborting")?;
+            exit(1);
+        }
+        let messages = resolve.messages.clone();
+        let conflicted = !resolve.conflicts.is_empty();
+
+        index.changed = true;
+        index.write_updates()?;
+        for message in messages {
+            write_to_stdout(&message)?;
+        }
+
+        let message = self.merge_message(&refs);
+        if conflicted {
+            pending_commit.start(&merge_oid, &message, MergeType::Merge)?;
+            write_to_stdout("Automatic merge failed; fix conflicts and then commit the result.")?;
+            exit(1);
+        }
+
+        let author = current_author()?;
+        write_commit(
+            &git_path,
+            &index,
+            vec![head_oid, merge_oid],
+            author,
+            message,
+            &format!("merge {}", self.revision),
+        )?;
+        write_to_stdout("Merge made by the 'recursive' strategy.")?;
+        Ok(())
+    }
+
+    fn fast_forward(
+        &self,
+        refs: &Refs,
+        database: &Database,
+        index: &mut Index,
+        head_oid: &str,
+        merge_oid: &str,
+    ) -> Result<()> {
+        let root_path = get_root_path()?;
+        let old_tree = database.read_commit_tree(head_oid)?;
+        let new_tree = database.read_commit_tree(merge_oid)?;
+        let mut migration = Migration::new(root_path, database, index, old_tree, new_tree);
+
+        let errors = migration.conflicts("merge");
+        if !errors.is_empty() {
+            index.lockfile.rollback()?;
+            for error in errors {
+                write_to_stderr(&error)?;
+            }
+            write_to_stderr("Aborting")?;
+            exit(1);
+        }
+        migration.apply_changes()?;
+        index.changed = true;
+        index.write_updates()?;
+        refs.update_head(merge_oid, &format!("merge {}: Fast-forward", self.revision))?;
+
+        write_to_stdout(&format!(
+            "Updating {}..{}\nFast-forward",
+            &head_oid[..7],
+            &merge_oid[..7]
+        ))?;
+        Ok(())
+    }
+
+    fn merge_message(&self, refs: &Refs) -> String {
+        if let Some(message) = &self.message {
+            return message.clone();
+        }
+        if refs
+            .git_path
+            .join("refs/heads")
+            .join(&self.revision)
+            .exists()
+        {
+            format!("Merge branch '{}'", self.revision)
+        } else {
+            format!("Merge commit '{}'", self.revision)
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::Result;
+use clap::Parser;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::{
+    merge::{Diff3, Labels},
+    utils::write_to_stderr,
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct MergeFileCMD {
+    /// Print the result instead of overwriting <current>
+    #[arg(short = 'p', long = "stdout")]
+    stdout: bool,
+
+    /// Show the base version inside conflicts as well
+    #[arg(long)]
+    diff3: bool,
+
+    /// Label for <current>, <base> and <other> in the conflict markers, in that order
+    #[arg(short = 'L', value_name = "name")]
+    labels: Vec<String>,
+
+    /// The file the changes are merged into
+    current: PathBuf,
+
+    /// The common ancestor of the two other files
+    base: PathBuf,
+
+    /// The file whose changes are merged in
+    other: PathBuf,
+}
+
+impl MergeFileCMD {
+    pub fn run(self) -> Result<()> {
+        if self.labels.len() > 3 {
+            write_to_stderr("error: too many labels on the command line")?;
+            exit(129);
+        }
+
+        let current = MergeFileCMD::read(&self.current)?;
+        let base = MergeFileCMD::read(&self.base)?;
+        let other = MergeFileCMD::read(&self.other)?;
+
+        let current_name = self.current.display().to_string();
+        let base_name = self.base.display().to_string();
+        let other_name = self.other.display().to_string();
+        let labels = Labels {
+            a: Some(self.labels.first().unwrap_or(&current_name)),
+            o: Some(self.labels.get(1).unwrap_or(&base_name)),
+            b: Some(self.labels.get(2).unwrap_or(&other_name)),
+        };
+
+        let result = Diff3::merge(&base, &current, &other);
+        let content = result.render(&labels, self.diff3);
+        if self.stdout {
+            print!("{}", content);
+        } else {
+            fs::write(&self.current, content)?;
+        }
+
+        // like git, the exit status is the number of conflicts
+        let conflicts = result.conflicts();
+        if conflicts > 0 {
+            exit(conflicts.min(127) as i32);
+        }
+        Ok(())
+    }
+
+    fn read(path: &PathBuf) -> Result<String> {
+        fs::read_to_string(path)
+            .map_err(|_| anyhow::anyhow!("error: could not open '{}'", path.display()))
+    }
+}
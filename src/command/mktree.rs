@@ -0,0 +1,94 @@
+use anyhow::Result;
+use clap::Parser;
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::{
+    database::{Database, FileMode, FileOrTree, Tree},
+    index::Stat,
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+    workspace::File,
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct MktreeCMD {}
+
+impl MktreeCMD {
+    /// Build a tree from lines in the format printed by `ls-tree`, one entry per line:
+    /// `<mode> SP <type> SP <oid> TAB <name>`
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let database = Database::new(root_path.join(".rgit").join("objects"));
+
+        let mut tree = Tree::new();
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let (name, entry) = match MktreeCMD::parse_entry(&database, &line)? {
+                Some(entry) => entry,
+                None => {
+                    write_to_stderr(&format!("fatal: input format error: {}", line))?;
+                    exit(128);
+                }
+            };
+            tree.entries.insert(name, entry);
+        }
+
+        database.store(&mut tree)?;
+        write_to_stdout(&tree.oid.expect("failed to get tree oid"))?;
+        Ok(())
+    }
+
+    /// Parse one line of input, `None` if it is not a well formed entry
+    fn parse_entry(database: &Database, line: &str) -> Result<Option<(String, FileOrTree)>> {
+        let (info, name) = match line.split_once('\t') {
+            Some((info, name)) if !name.is_empty() && !name.contains('/') => (info, name),
+            _ => return Ok(None),
+        };
+        let (mode, object_type, oid) = match info.split(' ').collect::<Vec<_>>()[..] {
+            [mode, object_type, oid] if oid.len() == 40 => (mode, object_type, oid),
+            _ => return Ok(None),
+        };
+
+        let mode = FileMode::from_str(mode);
+        let mode_type = match mode {
+            FileMode::Regular | FileMode::Executable => "blob",
+            FileMode::Directory => "tree",
+            FileMode::Unknown => return Ok(None),
+        };
+        if object_type != mode_type {
+            write_to_stderr(&format!(
+                "fatal: entry '{}' object type ({}) doesn't match mode type ({})",
+                name, object_type, mode_type
+            ))?;
+            exit(128);
+        }
+        if !database.exists(oid) {
+            write_to_stderr(&format!(
+                "fatal: entry '{}' object {} is unavailable",
+                name, oid
+            ))?;
+            exit(128);
+        }
+
+        let entry = match mode {
+            FileMode::Directory => FileOrTree::Tree(Tree {
+                oid: Some(oid.to_owned()),
+                entries: Default::default(),
+            }),
+            mode => FileOrTree::File(File {
+                name: name.to_owned(),
+                path: PathBuf::from(name),
+                stat: Stat {
+                    mode: mode.into(),
+                    ..Default::default()
+                },
+                oid: Some(oid.to_owned()),
+            }),
+        };
+        Ok(Some((name.to_owned(), entry)))
+    }
+}
@@ -2,12 +2,42 @@ use anyhow::Result;
 use clap::Subcommand;
 
 mod add;
+mod bisect;
+mod blame;
 mod branch;
-mod commit;
+mod cat_file;
+mod checkout;
+mod cherry_pick;
+mod clean;
+pub mod commit;
+mod commit_tree;
 mod diff;
+mod gc;
+mod grep;
+mod hash_object;
 mod init;
 mod log;
+mod ls_files;
+mod ls_tree;
+mod merge;
+mod merge_file;
+mod mktree;
+mod mv;
+mod prune;
+mod rebase;
+mod reflog;
+mod repack;
+mod reset;
+mod restore;
+mod rev_parse;
+mod revert;
+mod rm;
+mod sequencing;
+mod show;
+mod stash;
 mod status;
+mod tag;
+mod write_tree;
 
 #[derive(Subcommand, Debug)]
 pub enum GitCMD {
@@ -31,6 +61,94 @@ pub enum GitCMD {
 
     /// Log
     Log(log::LogCMD),
+
+    /// Switch branches or check out a commit
+    #[command(alias = "switch")]
+    Checkout(checkout::CheckoutCMD),
+
+    /// Join another branch into the current branch
+    Merge(merge::MergeCMD),
+
+    /// Run a three-way merge on a single file
+    MergeFile(merge_file::MergeFileCMD),
+
+    /// Remove files from the working tree and from the index
+    Rm(rm::RmCMD),
+
+    /// Reset the current branch, the index or the working tree to a commit
+    Reset(reset::ResetCMD),
+
+    /// Restore working tree files or index entries
+    Restore(restore::RestoreCMD),
+
+    /// Create, list or delete tags
+    Tag(tag::TagCMD),
+
+    /// Show the type, size or content of objects in the database
+    CatFile(cat_file::CatFileCMD),
+
+    /// Compute the object id of a file and optionally write it to the database
+    HashObject(hash_object::HashObjectCMD),
+
+    /// Create a tree object from the index
+    WriteTree(write_tree::WriteTreeCMD),
+
+    /// Create a commit object from a tree
+    CommitTree(commit_tree::CommitTreeCMD),
+
+    /// Build a tree object from ls-tree formatted text
+    Mktree(mktree::MktreeCMD),
+
+    /// Show information about the files in the index and the working tree
+    LsFiles(ls_files::LsFilesCMD),
+
+    /// List the contents of a tree object
+    LsTree(ls_tree::LsTreeCMD),
+
+    /// Resolve revisions to object ids and show repository paths
+    RevParse(rev_parse::RevParseCMD),
+
+    /// Show a commit with its patch, or a tag, tree or blob
+    Show(show::ShowCMD),
+
+    /// Stash the changes in a dirty working directory away
+    Stash(stash::StashCMD),
+
+    /// Apply the changes introduced by existing commits
+    CherryPick(cherry_pick::CherryPickCMD),
+
+    /// Revert the changes introduced by existing commits
+    Revert(revert::RevertCMD),
+
+    /// Reapply commits on top of another base
+    Rebase(rebase::RebaseCMD),
+
+    /// Show what revision and author last modified each line of a file
+    Blame(blame::BlameCMD),
+
+    /// Print lines of tracked files matching a pattern
+    Grep(grep::GrepCMD),
+
+    /// Move or rename a file or a directory
+    Mv(mv::MvCMD),
+
+    /// Remove untracked files from the working tree
+    Clean(clean::CleanCMD),
+
+    /// Use binary search to find the commit that introduced a bug
+    Bisect(bisect::BisectCMD),
+
+    /// Show and manage the history of where HEAD and the branches have been
+    Reflog(reflog::ReflogCMD),
+
+    /// Pack the reachable objects, delta compressing them against each other
+    Repack(repack::RepackCMD),
+
+    /// Pack everything reachable into one pack and remove the redundant objects
+    Gc(gc::GcCMD),
+
+    /// Remove the old loose objects that nothing reachable points to
+    Prune(prune::PruneCMD),
 }
 
 impl GitCMD {
@@ -43,6 +161,35 @@ impl GitCMD {
             GitCMD::Diff(diff) => diff.run()?,
             GitCMD::Branch(branch) => branch.run()?,
             GitCMD::Log(log) => log.run()?,
+            GitCMD::Checkout(checkout) => checkout.run()?,
+            GitCMD::Merge(merge) => merge.run()?,
+            GitCMD::MergeFile(merge_file) => merge_file.run()?,
+            GitCMD::Rm(rm) => rm.run()?,
+            GitCMD::Reset(reset) => reset.run()?,
+            GitCMD::Restore(restore) => restore.run()?,
+            GitCMD::Tag(tag) => tag.run()?,
+            GitCMD::CatFile(cat_file) => cat_file.run()?,
+            GitCMD::HashObject(hash_object) => hash_object.run()?,
+            GitCMD::WriteTree(write_tree) => write_tree.run()?,
+            GitCMD::CommitTree(commit_tree) => commit_tree.run()?,
+            GitCMD::Mktree(mktree) => mktree.run()?,
+            GitCMD::LsFiles(ls_files) => ls_files.run()?,
+            GitCMD::LsTree(ls_tree) => ls_tree.run()?,
+            GitCMD::RevParse(rev_parse) => rev_parse.run()?,
+            GitCMD::Show(show) => show.run()?,
+            GitCMD::Stash(stash) => stash.run()?,
+            GitCMD::CherryPick(cherry_pick) => cherry_pick.run()?,
+            GitCMD::Revert(revert) => revert.run()?,
+            GitCMD::Rebase(rebase) => rebase.run()?,
+            GitCMD::Blame(blame) => blame.run()?,
+            GitCMD::Grep(grep) => grep.run()?,
+            GitCMD::Mv(mv) => mv.run()?,
+            GitCMD::Clean(clean) => clean.run()?,
+            GitCMD::Bisect(bisect) => bisect.run()?,
+            GitCMD::Reflog(reflog) => reflog.run()?,
+            GitCMD::Repack(repack) => repack.run()?,
+            GitCMD::Gc(gc) => gc.run()?,
+            GitCMD::Prune(prune) => prune.run()?,
         }
         Ok(())
     }
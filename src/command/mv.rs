@@ -0,0 +1,175 @@
+use anyhow::Result;
+use clap::Parser;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use crate::{
+    index::{FlatIndex, Index},
+    utils::{get_root_path, remove_empty_parents, write_to_stderr},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct MvCMD {
+    /// Move even if the destination exists, overwriting it
+    #[arg(short, long)]
+    force: bool,
+
+    /// Skip the moves that would fail instead of stopping
+    #[arg(short = 'k')]
+    skip_errors: bool,
+
+    /// The files or directories to move, followed by the destination. With more than one
+    /// source the destination has to be a directory.
+    #[arg(required = true, num_args = 2..)]
+    paths: Vec<String>,
+}
+
+impl MvCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let mut index = Index::new(git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+
+        let (sources, destination) = self.paths.split_at(self.paths.len() - 1);
+        // a trailing slash asks for the destination to be a directory, which then has to exist
+        let into_directory = sources.len() > 1
+            || destination[0].ends_with('/')
+            || root_path.join(&destination[0]).is_dir();
+        let destination = destination[0].trim_end_matches('/');
+        if sources.len() > 1 && !root_path.join(destination).is_dir() {
+            index.lockfile.rollback()?;
+            write_to_stderr(&format!(
+                "fatal: destination '{}' is not a directory",
+                destination
+            ))?;
+            exit(128);
+        }
+
+        let mut moves = vec![];
+        let mut targets = BTreeSet::new();
+        for source in sources {
+            let source = source.trim_end_matches('/');
+            let target = match into_directory {
+                true => {
+                    let name = Path::new(source).file_name().and_then(|name| name.to_str());
+                    format!("{}/{}", destination, name.unwrap_or(source))
+                }
+                false => destination.to_owned(),
+            };
+
+            match self.check(&root_path, &index, &flat_index, source, &target, &targets) {
+                Ok(()) => {
+                    targets.insert(target.clone());
+                    moves.push((source.to_owned(), target));
+                }
+                Err(_) if self.skip_errors => continue,
+                Err(reason) => {
+                    index.lockfile.rollback()?;
+                    write_to_stderr(&format!(
+                        "fatal: {}, source={}, destination={}",
+                        reason, source, target
+                    ))?;
+                    exit(128);
+                }
+            }
+        }
+
+        for (source, target) in &moves {
+            let source_path = root_path.join(source);
+            let target_path = root_path.join(target);
+            // -f has already been checked: the file in the way is replaced
+            if target_path.is_file() {
+                fs::remove_file(&target_path)?;
+            }
+            if flat_index.entries.contains_key(target) {
+                index.remove(&flat_index.entries[target]);
+            }
+
+            fs::rename(&source_path, &target_path)?;
+            remove_empty_parents(&root_path, &source_path)?;
+            index.rename(source, target)?;
+        }
+        index.changed = true;
+        index.write_updates()?;
+        Ok(())
+    }
+
+    /// Why moving `source` to `target` is not possible, if it is not
+    fn check(
+        &self,
+        root_path: &PathBuf,
+        index: &Index,
+        flat_index: &FlatIndex,
+        source: &str,
+        target: &str,
+        targets: &BTreeSet<String>,
+    ) -> std::result::Result<(), &'static str> {
+        let source_path = root_path.join(source);
+        let target_path = root_path.join(target);
+        if !source_path.exists() {
+            return Err("bad source");
+        }
+
+        let prefix = format!("{}/", source);
+        if source_path.is_dir() {
+            // the conflict stages would be left behind at the old paths
+            if index.conflicts.keys().any(|path| path.starts_with(&prefix)) {
+                return Err("conflicted");
+            }
+            if !flat_index
+                .entries
+                .keys()
+                .any(|path| path.starts_with(&prefix))
+            {
+                return Err("source directory is empty");
+            }
+            if target == source || target.starts_with(&prefix) {
+                return Err("can not move directory into itself");
+            }
+            if target_path.exists() {
+                return Err("destination already exists");
+            }
+        } else {
+            if index.conflicts.contains_key(source) {
+                return Err("conflicted");
+            }
+            if !flat_index.entries.contains_key(source) {
+                return Err("not under version control");
+            }
+            if target_path.is_dir() {
+                return Err("cannot move a file onto a directory");
+            }
+            if target_path.exists() && !self.force {
+                return Err("destination exists");
+            }
+        }
+
+        if targets.contains(target) {
+            return Err("multiple sources for the same target");
+        }
+        match target_path.parent() {
+            Some(parent) if parent.is_dir() => {}
+            _ => return Err("destination directory does not exist"),
+        }
+        // a file deleted from the workspace still has its entry where the directory is now
+        let file_in_the_way = Path::new(target)
+            .ancestors()
+            .skip(1)
+            .filter_map(|parent| parent.to_str())
+            .any(|parent| flat_index.entries.contains_key(parent));
+        match file_in_the_way {
+            true => Err("destination directory is a file in the index"),
+            false => Ok(()),
+        }
+    }
+}
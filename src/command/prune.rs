@@ -0,0 +1,27 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::{database::Prune, reflog::parse_expiry, utils::get_root_path};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct PruneCMD {
+    /// Only remove the unreachable objects written before this, e.g. `2.weeks.ago`, `now`
+    /// or `never`
+    #[arg(long, value_name = "time", default_value = "2.weeks.ago")]
+    expire: String,
+
+    /// Only list the objects that would be removed, with their types
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+}
+
+impl PruneCMD {
+    pub fn run(&self) -> Result<()> {
+        let git_path = get_root_path()?.join(".rgit");
+        let prune = Prune {
+            expire: parse_expiry("--expire", &self.expire)?,
+            dry_run: self.dry_run,
+        };
+        prune.run(&git_path)
+    }
+}
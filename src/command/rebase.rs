@@ -0,0 +1,655 @@
+use anyhow::Result;
+use clap::{ArgGroup, Parser};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::process::{exit, Command};
+
+use crate::{
+    command::{
+        commit::{write_commit, UNMERGED_FILES},
+        reset::ResetCMD,
+        status::{modified_files, tracked_files},
+    },
+    database::{Commit, Database},
+    index::{FlatIndex, Index},
+    merge::{Bases, Inputs, Resolve},
+    migration::Migration,
+    pending_commit::{MergeType, PendingCommit},
+    refs::{parse_revision, Refs},
+    sequencer::{Action, Sequencer, Step},
+    utils::{edit_file, edit_message, editor, get_root_path, write_to_stderr, write_to_stdout},
+    workspace::WorkspaceTree,
+};
+
+static TODO_HELP: &str = "
+# Commands:
+# p, pick <commit> = use commit
+# r, reword <commit> = use commit, but edit the commit message
+# e, edit <commit> = use commit, but stop for amending
+# s, squash <commit> = use commit, but meld into previous commit
+# f, fixup <commit> = like \"squash\", but discard this commit's log message
+# x, exec <command> = run command (the rest of the line) using shell
+# d, drop <commit> = remove commit
+#
+# These lines can be re-ordered; they are executed from top to bottom.
+#
+# If you remove a line here THAT COMMIT WILL BE LOST.
+#
+# However, if you remove everything, the rebase will be aborted.
+";
+
+#[derive(Parser, Debug, PartialEq)]
+#[command(group(ArgGroup::new("sequence").args(["resume", "skip", "abort"])))]
+pub struct RebaseCMD {
+    /// Edit the list of commits to replay before starting
+    #[arg(short, long, conflicts_with = "sequence")]
+    interactive: bool,
+
+    /// Replay the commits on top of this commit instead of the upstream
+    #[arg(long, value_name = "newbase", conflicts_with = "sequence")]
+    onto: Option<String>,
+
+    /// Commit the resolved conflict and replay the remaining commits
+    #[arg(long = "continue")]
+    resume: bool,
+
+    /// Leave out the commit the rebase stopped at and go on with the next one
+    #[arg(long)]
+    skip: bool,
+
+    /// Give up and go back to the branch as it was before the rebase
+    #[arg(long)]
+    abort: bool,
+
+    /// The branch to replay the commits on; the commits it already has are left out
+    #[arg(required_unless_present_any = ["resume", "skip", "abort"], conflicts_with = "sequence")]
+    upstream: Option<String>,
+}
+
+impl RebaseCMD {
+    pub fn run(&self) -> Result<()> {
+        let rebase = Rebase::new()?;
+        if self.resume {
+            rebase.resume()
+        } else if self.skip {
+            rebase.skip()
+        } else if self.abort {
+            rebase.abort()
+        } else {
+            let upstream = self.upstream.as_deref().expect("upstream is required");
+            rebase.start(upstream, self.onto.as_deref(), self.interactive)
+        }
+    }
+}
+
+/// A rebase in progress. HEAD is detached while the commits are replayed one by one from
+/// the todo list in `.rgit/rebase-merge`, which also records the branch being rebased
+/// (`head-name`) and where it goes (`onto`). When a step stops the process exits and a
+/// later `--continue`, `--skip` or `--abort` picks the state up again. The branch itself
+/// is only moved once every step is done.
+struct Rebase {
+    root_path: PathBuf,
+    git_path: PathBuf,
+    refs: Refs,
+    database: Database,
+    sequencer: Sequencer,
+    pending_commit: PendingCommit,
+}
+
+impl Rebase {
+    fn new() -> Result<Self> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        Ok(Self {
+            refs: Refs::new(git_path.clone()),
+            database: Database::new(git_path.join("objects")),
+            sequencer: Sequencer::rebase(git_path.clone()),
+            pending_commit: PendingCommit::new(git_path.clone()),
+            root_path,
+            git_path,
+        })
+    }
+
+    fn start(&self, upstream: &str, onto: Option<&str>, interactive: bool) -> Result<()> {
+        if self.sequencer.in_progress() {
+            write_to_stderr("fatal: It seems that there is already a rebase-merge directory, and\nI wonder if you are in the middle of another rebase.  If that is the\ncase, please try\n\trgit rebase (--continue | --abort | --skip)")?;
+            exit(128);
+        }
+
+        let upstream_oid = self.resolve_upstream(upstream)?;
+        let onto_oid = match onto {
+            Some(onto) => self.resolve_upstream(onto)?,
+            None => upstream_oid.clone(),
+        };
+        let head_oid = match self.refs.read_head().filter(|oid| !oid.is_empty()) {
+            Some(oid) => oid,
+            None => anyhow::bail!("fatal: no commits yet"),
+        };
+        self.refuse_local_changes()?;
+
+        let branch = self.refs.current_branch();
+        let base_oid = Bases::new(&self.database, &head_oid, &upstream_oid)
+            .find()?
+            .first()
+            .cloned();
+        if !interactive && base_oid.as_ref() == Some(&onto_oid) {
+            write_to_stdout(&format!(
+                "Current branch {} is up to date.",
+                branch.as_deref().unwrap_or("HEAD")
+            ))?;
+            return Ok(());
+        }
+
+        let mut steps = vec![];
+        for (oid, commit) in self.commits_to_replay(&head_oid, &upstream_oid)? {
+            steps.push(Step {
+                action: Action::Pick,
+                oid: self.database.short_oid(&oid, 7),
+                subject: commit.subject().to_owned(),
+            });
+        }
+
+        let head_name = match &branch {
+            Some(branch) => format!("refs/heads/{}", branch),
+            None => "detached HEAD".to_owned(),
+        };
+        self.sequencer.start(&head_oid, &steps)?;
+        self.sequencer.write_state("head-name", &head_name)?;
+        self.sequencer.write_state("onto", &onto_oid)?;
+
+        if interactive && !self.edit_todo(&steps, &upstream_oid, &head_oid, &onto_oid)? {
+            self.sequencer.quit()?;
+            write_to_stderr("error: nothing to do")?;
+            exit(1);
+        }
+
+        let message = format!("rebase (start): checkout {}", onto.unwrap_or(upstream));
+        self.refs.update_ref("ORIG_HEAD", &head_oid, &message)?;
+        self.checkout(&onto_oid, &message)?;
+        self.run()
+    }
+
+    /// Commit the resolved conflict, if there is one, and go on with the remaining steps
+    fn resume(&self) -> Result<()> {
+        self.require_in_progress()?;
+
+        let mut index = Index::new(self.git_path.join("index"));
+        index.load()?;
+        if index.is_conflicted() {
+            write_to_stderr(UNMERGED_FILES)?;
+            exit(1);
+        }
+
+        if self.pending_commit.merge_type() == Some(MergeType::CherryPick) {
+            let message = self.pending_commit.merge_message()?.trim_end().to_owned();
+            let action = self.sequencer.done()?.pop().map(|step| step.action);
+            if self.sequencer.read_state("amend").is_some() {
+                let edit = action == Some(Action::Squash);
+                self.amend(&index, &message, edit, "rebase (continue)")?;
+                self.sequencer.remove_state("amend")?;
+                self.pending_commit.clear()?;
+            } else {
+                let oid = self.pending_commit.merge_oid()?;
+                let commit = self.database.read_commit(&oid)?;
+                let head_oid = self.refs.get_ref_content();
+                write_commit(
+                    &self.git_path,
+                    &index,
+                    vec![head_oid],
+                    commit.author.clone(),
+                    message,
+                    "rebase (continue)",
+                )?;
+                self.pending_commit.clear()?;
+                match action {
+                    Some(Action::Reword) => self.reword()?,
+                    Some(Action::Edit) => self.stop_for_edit(&oid, &commit)?,
+                    _ => {}
+                }
+            }
+        } else {
+            let mut flat_index = FlatIndex {
+                entries: Default::default(),
+            };
+            Index::flatten_entries(&index.entries, &mut flat_index);
+            if !tracked_files(&flat_index, &self.database.read_head()?).is_empty() {
+                write_to_stderr("error: you have staged changes in your working tree\nIf these changes are meant to be squashed into the previous commit, run:\n\n  rgit commit --amend\n\nIf they are meant to go into a new commit, run:\n\n  rgit commit\n\nIn both cases, once you're done, continue with:\n\n  rgit rebase --continue")?;
+                exit(1);
+            }
+        }
+
+        self.run()
+    }
+
+    /// Throw away the changes of the step that stopped and go on with the next one
+    fn skip(&self) -> Result<()> {
+        self.require_in_progress()?;
+
+        let mut index = Index::new(self.git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+        let head_tree = self.database.read_head()?;
+        ResetCMD::reset_workspace(
+            &self.root_path,
+            &self.database,
+            &mut index,
+            &head_tree,
+            &head_tree,
+        )?;
+        index.changed = true;
+        index.write_updates()?;
+
+        self.pending_commit.clear()?;
+        self.sequencer.remove_state("amend")?;
+        self.run()
+    }
+
+    /// Put the branch, the index and the workspace back the way they were before the rebase
+    fn abort(&self) -> Result<()> {
+        self.require_in_progress()?;
+
+        let original_head = self.sequencer.original_head()?;
+        let mut index = Index::new(self.git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+        let head_tree = self.database.read_head()?;
+        let original_tree = self.database.read_commit_tree(&original_head)?;
+        ResetCMD::reset_workspace(
+            &self.root_path,
+            &self.database,
+            &mut index,
+            &head_tree,
+            &original_tree,
+        )?;
+        index.changed = true;
+        index.write_updates()?;
+
+        let head_name = self
+            .sequencer
+            .read_state("head-name")
+            .filter(|_| self.branch().is_some())
+            .unwrap_or_else(|| original_head.clone());
+        let message = format!("rebase (abort): returning to {}", head_name);
+        match self.branch() {
+            Some(branch) => self.refs.set_head(&branch, &original_head, &message)?,
+            None => self.refs.update_ref("HEAD", &original_head, &message)?,
+        }
+        self.pending_commit.clear()?;
+        self.sequencer.quit()
+    }
+
+    fn run(&self) -> Result<()> {
+        while let Some(step) = self.sequencer.next_step()? {
+            self.apply_step(&step)?;
+        }
+        self.finish()
+    }
+
+    /// Carry out one line of the todo list. Steps that stop the rebase exit the process,
+    /// leaving the state on disk.
+    fn apply_step(&self, step: &Step) -> Result<()> {
+        match step.action {
+            Action::Drop => return Ok(()),
+            Action::Exec => return self.exec(&step.subject),
+            Action::Revert => {
+                write_to_stderr(&format!(
+                    "error: invalid command '{}' in the todo list",
+                    step.action.as_str()
+                ))?;
+                exit(1);
+            }
+            _ => {}
+        }
+
+        let oid = parse_revision(&step.oid, &step.oid).resolve_commit_or_exit(&self.refs)?;
+        let commit = self.database.read_commit(&oid)?;
+
+        match step.action {
+            Action::Squash | Action::Fixup => self.squash(&oid, &commit, step.action),
+            _ => {
+                self.pick(&oid, &commit)?;
+                match step.action {
+                    Action::Reword => self.reword(),
+                    Action::Edit => self.stop_for_edit(&oid, &commit),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Replay the commit on HEAD, keeping its author and message. A commit whose parent
+    /// is HEAD already is reused as it is.
+    fn pick(&self, oid: &str, commit: &Commit) -> Result<()> {
+        let head_oid = self.refs.get_ref_content();
+        if commit.parent().as_ref() == Some(&head_oid) {
+            let message = format!("rebase (pick): {}", commit.subject());
+            return self.checkout(oid, &message);
+        }
+
+        let index = self.merge(oid, commit, &commit.message)?;
+        write_commit(
+            &self.git_path,
+            &index,
+            vec![head_oid],
+            commit.author.clone(),
+            commit.message.clone(),
+            "rebase (pick)",
+        )?;
+        Ok(())
+    }
+
+    /// Meld the commit into HEAD. A squash keeps both messages and lets the user edit
+    /// them, a fixup keeps HEAD's message only.
+    fn squash(&self, oid: &str, commit: &Commit, action: Action) -> Result<()> {
+        let head_oid = self.refs.get_ref_content();
+        let head = self.database.read_commit(&head_oid)?;
+        let message = match action {
+            Action::Fixup => head.message.clone(),
+            _ => format!(
+                "{}\n\n{}",
+                head.message.trim_end(),
+                commit.message.trim_end()
+            ),
+        };
+
+        // on a conflict `--continue` has to amend HEAD rather than add a commit
+        self.sequencer.write_state("amend", &head_oid)?;
+        let index = self.merge(oid, commit, &message)?;
+        let reflog_action = match action {
+            Action::Fixup => "rebase (fixup)",
+            _ => "rebase (squash)",
+        };
+        self.amend(&index, &message, action == Action::Squash, reflog_action)?;
+        self.sequencer.remove_state("amend")
+    }
+
+    /// Apply the change the commit made to its parent onto HEAD, returning the updated
+    /// index. On a conflict `message` is saved for `--continue` and the process exits.
+    fn merge(&self, oid: &str, commit: &Commit, message: &str) -> Result<Index> {
+        let short_oid = self.database.short_oid(oid, 7);
+        let subject = commit.subject();
+        let inputs = Inputs {
+            left_name: "HEAD".to_owned(),
+            right_name: format!("{} ({})", short_oid, subject),
+            left_oid: self.refs.get_ref_content(),
+            right_oid: oid.to_owned(),
+            base_oid: commit.parent(),
+        };
+
+        let mut index = Index::new(self.git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+        let mut resolve = Resolve::new(self.root_path.clone(), &self.database, &mut index, &inputs);
+        let errors = resolve.execute()?;
+        if !errors.is_empty() {
+            index.lockfile.rollback()?;
+            write_to_stderr(&errors.join("\n"))?;
+            write_to_stderr(&format!(
+                "error: could not apply {}... {}",
+                short_oid, subject
+            ))?;
+            exit(1);
+        }
+        let messages = resolve.messages.clone();
+        let conflicted = !resolve.conflicts.is_empty();
+
+        index.changed = true;
+        index.write_updates()?;
+        for line in messages {
+            write_to_stdout(&line)?;
+        }
+
+        if conflicted {
+            self.pending_commit
+                .start(oid, message, MergeType::CherryPick)?;
+            write_to_stderr(&format!(
+                "error: could not apply {}... {}\nhint: Resolve all conflicts manually, mark them as resolved with\nhint: \"rgit add/rm <conflicted_files>\", then run \"rgit rebase --continue\".\nhint: You can instead skip this commit: run \"rgit rebase --skip\".\nhint: To abort and get back to the state before \"rgit rebase\", run \"rgit rebase --abort\".",
+                short_oid, subject
+            ))?;
+            exit(1);
+        }
+        Ok(index)
+    }
+
+    /// Replace HEAD with a commit of the index that has the same parents and author
+    fn amend(&self, index: &Index, message: &str, edit: bool, action: &str) -> Result<()> {
+        let head = self.database.read_commit(&self.refs.get_ref_content())?;
+        let message = match edit {
+            true => edit_message(&self.git_path, message)?,
+            false => message.to_owned(),
+        };
+        write_commit(
+            &self.git_path,
+            index,
+            head.parents.clone(),
+            head.author.clone(),
+            message,
+            action,
+        )?;
+        Ok(())
+    }
+
+    fn reword(&self) -> Result<()> {
+        let mut index = Index::new(self.git_path.join("index"));
+        index.load()?;
+        let head = self.database.read_commit(&self.refs.get_ref_content())?;
+        self.amend(&index, &head.message, true, "rebase (reword)")
+    }
+
+    fn stop_for_edit(&self, oid: &str, commit: &Commit) -> Result<()> {
+        write_to_stdout(&format!(
+            "Stopped at {}...  {}\nYou can amend the commit now, with\n\n  rgit commit --amend\n\nOnce you are satisfied with your changes, run\n\n  rgit rebase --continue",
+            self.database.short_oid(oid, 7),
+            commit.subject()
+        ))?;
+        exit(0);
+    }
+
+    fn exec(&self, command: &str) -> Result<()> {
+        write_to_stdout(&format!("Executing: {}", command))?;
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(&self.root_path)
+            .status()?;
+        if !status.success() {
+            write_to_stderr(&format!(
+                "warning: execution failed: {}\nYou can fix the problem, and then run\n\n  rgit rebase --continue",
+                command
+            ))?;
+            exit(1);
+        }
+        Ok(())
+    }
+
+    /// Move the rebased branch to where HEAD ended up and attach HEAD to it again. The
+    /// branch only moves if it is still where the rebase started from, so commits made on
+    /// it in the meantime are not lost; otherwise HEAD is left detached at the rebased
+    /// commits.
+    fn finish(&self) -> Result<()> {
+        let head_oid = self.refs.get_ref_content();
+        let head_name = self
+            .sequencer
+            .read_state("head-name")
+            .unwrap_or_else(|| "detached HEAD".to_owned());
+        if let Some(branch) = self.branch() {
+            let original_head = self.sequencer.original_head()?;
+            let onto = self.sequencer.read_state("onto").unwrap_or_default();
+            let message = format!("rebase (finish): {} onto {}", head_name, onto);
+            let moved =
+                self.refs
+                    .compare_and_swap(&head_name, &original_head, &head_oid, &message)?;
+            if let Some(oid) = moved {
+                self.sequencer.quit()?;
+                write_to_stderr(&format!(
+                    "error: update_ref failed for ref '{}': is at {} but expected {}\nhint: the branch moved while it was being rebased, the rebased commits are\nhint: left on a detached HEAD",
+                    head_name, oid, original_head
+                ))?;
+                exit(1);
+            }
+            let message = format!("rebase (finish): returning to {}", head_name);
+            self.refs.set_head(&branch, &head_oid, &message)?;
+        }
+        self.sequencer.quit()?;
+        write_to_stdout(&format!("Successfully rebased and updated {}.", head_name))
+    }
+
+    /// Check out `oid` on a detached HEAD. The workspace has no local changes here, so
+    /// the migration cannot run into conflicts.
+    fn checkout(&self, oid: &str, message: &str) -> Result<()> {
+        let mut index = Index::new(self.git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+        let head_tree = self.database.read_head()?;
+        let tree = self.database.read_commit_tree(oid)?;
+        let mut migration = Migration::new(
+            self.root_path.clone(),
+            &self.database,
+            &mut index,
+            head_tree,
+            tree,
+        );
+        migration.apply_changes()?;
+        index.changed = true;
+        index.write_updates()?;
+        self.refs.update_ref("HEAD", oid, message)
+    }
+
+    /// Write the todo list out with the instructions and let the user edit it. Returns
+    /// false if every step was removed.
+    fn edit_todo(
+        &self,
+        steps: &[Step],
+        upstream_oid: &str,
+        head_oid: &str,
+        onto_oid: &str,
+    ) -> Result<bool> {
+        let mut content = steps
+            .iter()
+            .map(|step| format!("pick {} {}\n", step.oid, step.subject))
+            .collect::<String>();
+        content.push_str(&format!(
+            "\n# Rebase {}..{} onto {} ({} commands)\n#",
+            self.database.short_oid(upstream_oid, 7),
+            self.database.short_oid(head_oid, 7),
+            self.database.short_oid(onto_oid, 7),
+            steps.len()
+        ));
+        content.push_str(TODO_HELP);
+        fs::write(&self.sequencer.todo_path, content)?;
+
+        let todo_editor = editor(&["GIT_SEQUENCE_EDITOR", "GIT_EDITOR", "EDITOR"]);
+        let steps = match edit_file(&todo_editor, &self.sequencer.todo_path)
+            .and_then(|_| self.sequencer.todo())
+        {
+            Ok(steps) => steps,
+            Err(error) => {
+                self.sequencer.quit()?;
+                return Err(error);
+            }
+        };
+        self.sequencer.write_todo(&steps)?;
+        Ok(!steps.is_empty())
+    }
+
+    /// The commits reachable from HEAD but not from the upstream, parents before their
+    /// children. Merge commits are left out, their changes come with the commits merged.
+    fn commits_to_replay(
+        &self,
+        head_oid: &str,
+        upstream_oid: &str,
+    ) -> Result<Vec<(String, Commit)>> {
+        let mut upstream = HashSet::new();
+        let mut queue = vec![upstream_oid.to_owned()];
+        while let Some(oid) = queue.pop() {
+            if upstream.insert(oid.clone()) {
+                queue.extend(self.database.read_commit(&oid)?.parents);
+            }
+        }
+
+        let mut commits = HashMap::new();
+        let mut order = vec![];
+        let mut stack = vec![(head_oid.to_owned(), false)];
+        while let Some((oid, visited)) = stack.pop() {
+            if visited {
+                order.push(oid);
+                continue;
+            }
+            if upstream.contains(&oid) || commits.contains_key(&oid) {
+                continue;
+            }
+            let commit = self.database.read_commit(&oid)?;
+            stack.push((oid.clone(), true));
+            for parent in commit.parents.iter().rev() {
+                stack.push((parent.clone(), false));
+            }
+            commits.insert(oid, commit);
+        }
+
+        Ok(order
+            .into_iter()
+            .filter_map(|oid| commits.remove(&oid).map(|commit| (oid, commit)))
+            .filter(|(_, commit)| commit.parents.len() <= 1)
+            .collect())
+    }
+
+    fn refuse_local_changes(&self) -> Result<()> {
+        let mut index = Index::new(self.git_path.join("index"));
+        index.load()?;
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+        let workspace = WorkspaceTree::new(Some(&self.root_path));
+        let mut flat_workspace = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&workspace.workspace, &mut flat_workspace);
+        let head_tree = self.database.read_head()?;
+
+        if index.is_conflicted()
+            || !modified_files(&flat_workspace, &flat_index, &head_tree).is_empty()
+        {
+            write_to_stderr(
+                "error: cannot rebase: You have unstaged changes.\nerror: Please commit or stash them.",
+            )?;
+            exit(1);
+        }
+        if !tracked_files(&flat_index, &head_tree).is_empty() {
+            write_to_stderr("error: cannot rebase: Your index contains uncommitted changes.\nerror: Please commit or stash them.")?;
+            exit(1);
+        }
+        Ok(())
+    }
+
+    fn resolve_upstream(&self, revision: &str) -> Result<String> {
+        match parse_revision(revision, revision).resolve_commit(&self.refs) {
+            Ok(oid) => Ok(oid),
+            Err(_) => {
+                write_to_stderr(&format!("fatal: invalid upstream '{}'", revision))?;
+                exit(128);
+            }
+        }
+    }
+
+    fn require_in_progress(&self) -> Result<()> {
+        if !self.sequencer.in_progress() {
+            write_to_stderr("fatal: No rebase in progress?")?;
+            exit(128);
+        }
+        Ok(())
+    }
+
+    /// The branch being rebased, `None` when the rebase started on a detached HEAD
+    fn branch(&self) -> Option<String> {
+        self.sequencer
+            .read_state("head-name")?
+            .strip_prefix("refs/heads/")
+            .map(|branch| branch.to_owned())
+    }
+}
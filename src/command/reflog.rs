@@ -0,0 +1,251 @@
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use regex::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::process::exit;
+use walkdir::WalkDir;
+
+use crate::{
+    database::Database,
+    reflog::{parse_expiry, Reflog, NULL_OID},
+    refs::{Refs, USE_SEPARATOR},
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct ReflogCMD {
+    #[command(subcommand)]
+    command: Option<ReflogCommand>,
+
+    #[command(flatten)]
+    show: ShowArgs,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+enum ReflogCommand {
+    /// List where a ref has been, newest first (the default)
+    Show(ShowArgs),
+
+    /// Prune the old entries of reflogs
+    Expire(ExpireArgs),
+
+    /// Remove single entries, given as `<ref>@{<n>}`
+    Delete(DeleteArgs),
+}
+
+#[derive(Args, Debug, PartialEq)]
+struct ShowArgs {
+    /// The ref whose log to show, HEAD by default
+    reference: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+struct ExpireArgs {
+    /// Prune the entries older than this, e.g. `2.weeks.ago`, `now` or `never`
+    #[arg(long, value_name = "time", default_value = "90.days.ago")]
+    expire: String,
+
+    /// Prune the entries older than this whose commit the ref no longer contains
+    #[arg(long, value_name = "time", default_value = "30.days.ago")]
+    expire_unreachable: String,
+
+    /// Expire the logs of every ref
+    #[arg(long)]
+    all: bool,
+
+    /// Only show what would be pruned
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Print each entry with whether it is kept or pruned
+    #[arg(long)]
+    verbose: bool,
+
+    /// The refs whose logs to expire
+    refs: Vec<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+struct DeleteArgs {
+    /// The entries to remove
+    #[arg(required = true)]
+    entries: Vec<String>,
+}
+
+impl ReflogCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+
+        match &self.command {
+            None => ReflogCMD::show(&refs, &database, &self.show),
+            Some(ReflogCommand::Show(args)) => ReflogCMD::show(&refs, &database, args),
+            Some(ReflogCommand::Expire(args)) => ReflogCMD::expire(&refs, &database, args),
+            Some(ReflogCommand::Delete(args)) => ReflogCMD::delete(&refs, args),
+        }
+    }
+
+    fn show(refs: &Refs, database: &Database, args: &ShowArgs) -> Result<()> {
+        let name = args.reference.as_deref().unwrap_or("HEAD");
+        let full_name = match refs.full_ref_name(name) {
+            Some(full_name) => full_name,
+            None => {
+                write_to_stderr(&format!(
+                    "fatal: ambiguous argument '{}': unknown revision or path not in the working tree.\n{}",
+                    name, USE_SEPARATOR
+                ))?;
+                exit(128);
+            }
+        };
+
+        let entries = Reflog::new(&refs.git_path, &full_name).read()?;
+        for (n, entry) in entries.iter().rev().enumerate() {
+            write_to_stdout(&format!(
+                "{} {}@{{{}}}: {}",
+                database.short_oid(&entry.new_oid, 7),
+                name,
+                n,
+                entry.message
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Drop the entries older than `--expire`, and the ones older than
+    /// `--expire-unreachable` that moved the ref to a commit it no longer contains
+    fn expire(refs: &Refs, database: &Database, args: &ExpireArgs) -> Result<()> {
+        let expire = parse_expiry("--expire", &args.expire)?;
+        let expire_unreachable = parse_expiry("--expire-unreachable", &args.expire_unreachable)?;
+
+        let names = match args.all {
+            true => ReflogCMD::all_logs(&refs.git_path),
+            false => args
+                .refs
+                .iter()
+                .map(|name| match refs.full_ref_name(name) {
+                    Some(full_name) => Ok(full_name),
+                    None => {
+                        write_to_stderr(&format!("error: reflog could not be found: '{}'", name))?;
+                        exit(1);
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?,
+        };
+
+        for name in names {
+            let tip = match name.as_str() {
+                "HEAD" => refs.read_head(),
+                _ => refs.read_ref(&name),
+            };
+            let reachable = match (expire_unreachable, tip) {
+                (Some(_), Some(tip)) => ReflogCMD::ancestors(database, &tip)?,
+                _ => HashSet::new(),
+            };
+
+            Reflog::new(&refs.git_path, &name).rewrite(|entries| {
+                let mut kept = vec![];
+                for entry in entries.iter() {
+                    let time = entry.author.time;
+                    let pruned = expire.is_some_and(|expire| time < expire)
+                        || expire_unreachable.is_some_and(|expire| {
+                            time < expire && !reachable.contains(&entry.new_oid)
+                        });
+                    if args.verbose {
+                        let verb = match (pruned, args.dry_run) {
+                            (false, _) => "keep",
+                            (true, true) => "would prune",
+                            (true, false) => "prune",
+                        };
+                        write_to_stdout(&format!("{} {}", verb, entry.message))?;
+                    }
+                    if !pruned {
+                        kept.push(entry.clone());
+                    }
+                }
+                match !args.dry_run && kept.len() != entries.len() {
+                    true => Ok(Some(kept)),
+                    false => Ok(None),
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    fn delete(refs: &Refs, args: &DeleteArgs) -> Result<()> {
+        let entry_re = Regex::new(r"^(.*)@\{(\d+)\}$").unwrap();
+        // the positions to remove in each log, counted from the newest entry
+        let mut removals = BTreeMap::<String, Vec<(usize, &str)>>::new();
+        for entry in &args.entries {
+            let caps = entry_re.captures(entry);
+            let full_name = caps
+                .as_ref()
+                .and_then(|caps| refs.full_ref_name(caps.get(1).unwrap().as_str()));
+            match (caps, full_name) {
+                (Some(caps), Some(full_name)) => {
+                    let n = caps.get(2).unwrap().as_str().parse::<usize>()?;
+                    removals.entry(full_name).or_default().push((n, entry));
+                }
+                _ => {
+                    write_to_stderr(&format!("error: not a reflog: {}", entry))?;
+                    exit(1);
+                }
+            }
+        }
+
+        for (name, positions) in removals {
+            let mut missing = None;
+            Reflog::new(&refs.git_path, &name).rewrite(|entries| {
+                if let Some((_, entry)) = positions.iter().find(|(n, _)| *n >= entries.len()) {
+                    missing = Some(entry.to_owned());
+                    return Ok(None);
+                }
+                let kept = entries
+                    .into_iter()
+                    .rev()
+                    .enumerate()
+                    .filter(|(n, _)| !positions.iter().any(|(position, _)| position == n))
+                    .map(|(_, entry)| entry)
+                    .collect::<Vec<_>>();
+                Ok(Some(kept.into_iter().rev().collect()))
+            })?;
+            if let Some(entry) = missing {
+                write_to_stderr(&format!("error: reflog entry '{}' not found", entry))?;
+                exit(1);
+            }
+        }
+        Ok(())
+    }
+
+    /// The names of all the refs that have a log, relative to the git directory
+    fn all_logs(git_path: &Path) -> Vec<String> {
+        let logs_path = git_path.join("logs");
+        let mut names = WalkDir::new(&logs_path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| {
+                let name = entry.path().strip_prefix(&logs_path).ok()?;
+                Some(name.to_string_lossy().into_owned())
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Every commit reachable from `oid`, itself included
+    fn ancestors(database: &Database, oid: &str) -> Result<HashSet<String>> {
+        let mut seen = HashSet::new();
+        let mut queue = vec![oid.to_owned()];
+        while let Some(oid) = queue.pop() {
+            if oid == NULL_OID || !seen.insert(oid.clone()) {
+                continue;
+            }
+            queue.extend(database.read_commit(&oid)?.parents);
+        }
+        Ok(seen)
+    }
+}
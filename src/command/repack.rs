@@ -0,0 +1,57 @@
+use anyhow::Result;
+use clap::Parser;
+use std::thread;
+
+use crate::{pack::Repack, utils::get_root_path};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct RepackCMD {
+    /// Pack every reachable object into a single pack instead of only the loose ones
+    #[arg(short = 'a')]
+    all: bool,
+
+    /// Like -a, but with -d the unreachable objects of the old packs are kept as loose
+    /// objects, for prune to remove once they are old enough
+    #[arg(short = 'A')]
+    unpack_unreachable: bool,
+
+    /// Then remove the packs and the loose objects made redundant by the new pack
+    #[arg(short = 'd')]
+    delete: bool,
+
+    /// How many of the objects sorted before each one to try as its delta base
+    #[arg(long, default_value_t = 10)]
+    window: usize,
+
+    /// The longest chain of deltas to build
+    #[arg(long, default_value_t = 50)]
+    depth: usize,
+
+    /// How many threads search for deltas, one per CPU by default
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Don't report progress
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+impl RepackCMD {
+    pub fn run(&self) -> Result<()> {
+        let git_path = get_root_path()?.join(".rgit");
+        let repack = Repack {
+            all: self.all || self.unpack_unreachable,
+            delete: self.delete,
+            unpack_unreachable: self.unpack_unreachable,
+            window: self.window,
+            depth: self.depth,
+            threads: self.threads.unwrap_or_else(default_threads),
+            quiet: self.quiet,
+        };
+        repack.run(&git_path)
+    }
+}
+
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
@@ -0,0 +1,202 @@
+use anyhow::Result;
+use clap::Parser;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::{
+    database::{Blob, Database, FlatTree},
+    index::{FlatIndex, Index, Stat},
+    migration::Migration,
+    pending_commit::PendingCommit,
+    refs::{parse_revision, Refs},
+    utils::{get_root_path, remove_empty_parents, write_to_stderr, write_to_stdout},
+    workspace::File,
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct ResetCMD {
+    /// Only move the current branch, keeping the index and the working tree
+    #[arg(long, group = "mode")]
+    soft: bool,
+
+    /// Move the current branch and reset the index, keeping the working tree (the default)
+    #[arg(long, group = "mode")]
+    mixed: bool,
+
+    /// Move the current branch and reset both the index and the working tree
+    #[arg(long, group = "mode")]
+    hard: bool,
+
+    /// The commit to reset to, HEAD by default
+    revision: Option<String>,
+
+    /// Only reset the index entries for these paths, leaving the branch where it is
+    #[arg(last = true)]
+    paths: Vec<String>,
+}
+
+impl ResetCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+
+        let revision = self.revision.clone().unwrap_or("HEAD".to_owned());
+        let oid = parse_revision(&revision, &revision).resolve_commit_or_exit(&refs)?;
+        let commit = database.read_commit(&oid)?;
+        let tree = database.read_commit_tree(&oid)?;
+
+        if !self.paths.is_empty() {
+            if self.soft || self.hard {
+                let mode = if self.soft { "soft" } else { "hard" };
+                write_to_stderr(&format!("fatal: Cannot do {} reset with paths.", mode))?;
+                exit(128);
+            }
+            return self.reset_paths(&root_path, &git_path, &database, &tree);
+        }
+
+        let pending_commit = PendingCommit::new(git_path.clone());
+        if self.soft && pending_commit.in_progress() {
+            write_to_stderr("fatal: Cannot do a soft reset in the middle of a merge.")?;
+            exit(128);
+        }
+
+        if !self.soft {
+            let mut index = Index::new(git_path.join("index"));
+            if !index.load_for_update()? {
+                anyhow::bail!("fatal: Unable to create lock on index");
+            }
+            if self.hard {
+                let head_tree = database.read_head()?;
+                ResetCMD::reset_workspace(&root_path, &database, &mut index, &head_tree, &tree)?;
+            } else {
+                index.clear();
+                for file in tree.entries.values() {
+                    index.add(&index_entry(&root_path, &database, file));
+                }
+            }
+            index.changed = true;
+            index.write_updates()?;
+            pending_commit.clear()?;
+        }
+
+        let message = format!("reset: moving to {}", revision);
+        if let Some(head_oid) = refs.read_head() {
+            refs.update_ref("ORIG_HEAD", &head_oid, &message)?;
+        }
+        refs.update_head(&oid, &message)?;
+
+        if self.hard {
+            let title = commit.message.lines().next().unwrap_or("");
+            write_to_stdout(&format!("HEAD is now at {} {}", &oid[..7], title))?;
+        }
+        Ok(())
+    }
+
+    /// Replace the index entries under each path with the ones from `tree`
+    fn reset_paths(
+        &self,
+        root_path: &PathBuf,
+        git_path: &PathBuf,
+        database: &Database,
+        tree: &FlatTree,
+    ) -> Result<()> {
+        let mut index = Index::new(git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+
+        for path in &self.paths {
+            let path = path.trim_end_matches('/');
+            let prefix = format!("{}/", path);
+            let in_path = |key: &String| key == path || key.starts_with(&prefix);
+
+            for (key, file) in &flat_index.entries {
+                if in_path(key) {
+                    index.remove(file);
+                }
+            }
+            for (key, file) in &tree.entries {
+                if in_path(key) {
+                    index.add(&index_entry(root_path, database, file));
+                }
+            }
+        }
+        index.changed = true;
+        index.write_updates()?;
+        Ok(())
+    }
+
+    /// Make the workspace match `tree`: files tracked by the index or HEAD that are not in
+    /// `tree` are deleted and everything in `tree` is written out, discarding local changes.
+    /// Untracked files are left alone.
+    pub fn reset_workspace(
+        root_path: &PathBuf,
+        database: &Database,
+        index: &mut Index,
+        head_tree: &FlatTree,
+        tree: &FlatTree,
+    ) -> Result<()> {
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+        let tracked = flat_index
+            .entries
+            .keys()
+            .chain(index.conflicts.keys())
+            .chain(head_tree.entries.keys())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for path in tracked {
+            if tree.entries.contains_key(&path) {
+                continue;
+            }
+            let workspace_path = root_path.join(&path);
+            if workspace_path.is_file() {
+                fs::remove_file(&workspace_path)?;
+            }
+            remove_empty_parents(root_path, &workspace_path)?;
+        }
+
+        index.clear();
+        let empty_tree = FlatTree {
+            entries: Default::default(),
+        };
+        let mut migration =
+            Migration::new(root_path.clone(), database, index, empty_tree, tree.clone());
+        migration.apply_changes()
+    }
+}
+
+/// The index entry for a file from a tree. The workspace file's stat is only kept when the
+/// file holds that very blob, otherwise the entry gets an empty stat so that the file keeps
+/// showing up as changed.
+pub fn index_entry(root_path: &PathBuf, database: &Database, file: &File) -> File {
+    let workspace_path = root_path.join(&file.path);
+    let unchanged = workspace_path.is_file()
+        && fs::read(&workspace_path).is_ok_and(|data| {
+            let blob = Blob::new(unsafe { String::from_utf8_unchecked(data) });
+            Some(database.hash_object(&blob)) == file.oid
+        });
+    let mut stat = match unchanged {
+        true => Stat::new(&workspace_path),
+        false => Stat::default(),
+    };
+    stat.mode = file.stat.mode;
+    stat.flags = file.path.to_str().unwrap().len().min(0xfff) as u16;
+
+    File {
+        name: file.name.clone(),
+        path: file.path.clone(),
+        stat,
+        oid: file.oid.clone(),
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::Result;
+use clap::Parser;
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::{
+    command::reset::index_entry,
+    database::{Database, FileMode, FlatTree, ParsedContent},
+    index::{FlatIndex, Index, Stat},
+    refs::{parse_revision, Refs},
+    utils::{get_root_path, remove_empty_parents, write_to_stderr},
+    workspace::File,
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct RestoreCMD {
+    /// Restore the index entries, from HEAD unless a source is given
+    #[arg(short = 'S', long)]
+    staged: bool,
+
+    /// Restore the working tree files (the default unless --staged is given)
+    #[arg(short = 'W', long)]
+    worktree: bool,
+
+    /// The commit to take the files from
+    #[arg(short, long, value_name = "tree")]
+    source: Option<String>,
+
+    #[clap(required = true)]
+    paths: Vec<String>,
+}
+
+impl RestoreCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+        let staged = self.staged;
+        let worktree = self.worktree || !self.staged;
+
+        let mut index = Index::new(git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+
+        // the working tree is restored from the index unless told otherwise
+        let from_index = self.source.is_none() && !staged;
+        let source = match &self.source {
+            Some(revision) => {
+                let oid = parse_revision(revision, revision).resolve_commit_or_exit(&refs)?;
+                database.read_commit_tree(&oid)?
+            }
+            None if staged => database.read_head()?,
+            None => FlatTree {
+                entries: flat_index.entries.clone(),
+            },
+        };
+
+        let mut errors = vec![];
+        let mut paths = BTreeMap::new();
+        for path in &self.paths {
+            let path = path.trim_end_matches('/');
+            let prefix = format!("{}/", path);
+            let matched = source
+                .entries
+                .keys()
+                .chain(flat_index.entries.keys())
+                .chain(index.conflicts.keys())
+                .filter(|key| path == "." || *key == path || key.starts_with(&prefix))
+                .map(|key| (key.clone(), source.entries.get(key).cloned()))
+                .collect::<BTreeMap<_, _>>();
+
+            if matched.is_empty() {
+                errors.push(format!(
+                    "error: pathspec '{}' did not match any file(s) known to git",
+                    path
+                ));
+            }
+            paths.extend(matched);
+        }
+        if from_index {
+            for path in paths
+                .keys()
+                .filter(|path| index.conflicts.contains_key(*path))
+            {
+                errors.push(format!("error: path '{}' is unmerged", path));
+            }
+        }
+        if !errors.is_empty() {
+            index.lockfile.rollback()?;
+            for error in errors {
+                write_to_stderr(&error)?;
+            }
+            exit(1);
+        }
+
+        for (path, file) in &paths {
+            let workspace_path = root_path.join(path);
+            if worktree {
+                match file {
+                    Some(file) => RestoreCMD::write_file(&database, &workspace_path, file)?,
+                    None => {
+                        if workspace_path.is_file() {
+                            fs::remove_file(&workspace_path)?;
+                        }
+                        remove_empty_parents(&root_path, &workspace_path)?;
+                    }
+                }
+            }
+
+            if staged {
+                match file {
+                    Some(file) => index.add(&index_entry(&root_path, &database, file)),
+                    None => index.remove(&File {
+                        name: path.clone(),
+                        path: PathBuf::from(path),
+                        stat: Stat::default(),
+                        oid: None,
+                    }),
+                }
+            } else if from_index {
+                // the file now matches its index entry again, so refresh the cached stat
+                if let Some(file) = file {
+                    index.add(&index_entry(&root_path, &database, file));
+                }
+            }
+        }
+
+        index.write_updates()?;
+        Ok(())
+    }
+
+    pub fn write_file(database: &Database, workspace_path: &PathBuf, file: &File) -> Result<()> {
+        let oid = file.oid.as_ref().expect("failed to get oid");
+        let blob = match database.read_object(oid)? {
+            ParsedContent::BlobContent(blob) => blob,
+            _ => anyhow::bail!("fatal: {} is not a blob", oid),
+        };
+
+        if workspace_path.is_dir() {
+            fs::remove_dir_all(workspace_path)?;
+        }
+        if let Some(parent) = workspace_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(workspace_path, blob.data)?;
+
+        let mode = match file.stat.mode {
+            mode if mode == u32::from(FileMode::Executable) => 0o755,
+            _ => 0o644,
+        };
+        fs::set_permissions(workspace_path, fs::Permissions::from_mode(mode))?;
+        Ok(())
+    }
+}
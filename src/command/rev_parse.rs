@@ -0,0 +1,126 @@
+use anyhow::Result;
+use clap::Parser;
+use std::process::exit;
+
+use crate::{
+    database::Database,
+    refs::{parse_revision, Refs, USE_SEPARATOR},
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct RevParseCMD {
+    /// Check that exactly one revision is given and that it names an object
+    #[arg(long)]
+    verify: bool,
+
+    /// Abbreviate the oid to at least this many characters (7 by default); implies --verify
+    #[arg(
+        long,
+        value_name = "length",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "7"
+    )]
+    short: Option<usize>,
+
+    /// Show the short name of a ref instead of its oid
+    #[arg(long)]
+    abbrev_ref: bool,
+
+    /// Show the path of the repository directory
+    #[arg(long)]
+    git_dir: bool,
+
+    /// Show the absolute path of the top of the working tree
+    #[arg(long)]
+    show_toplevel: bool,
+
+    /// Show whether the current directory is inside a working tree
+    #[arg(long)]
+    is_inside_work_tree: bool,
+
+    /// The revisions to resolve
+    revisions: Vec<String>,
+}
+
+impl RevParseCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = match get_root_path() {
+            Ok(root_path) => root_path,
+            Err(error) => {
+                write_to_stderr(&error.to_string())?;
+                exit(128);
+            }
+        };
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+
+        if self.git_dir {
+            write_to_stdout(".rgit")?;
+        }
+        if self.show_toplevel {
+            write_to_stdout(&root_path.display().to_string())?;
+        }
+        if self.is_inside_work_tree {
+            write_to_stdout("true")?;
+        }
+
+        let verify = self.verify || self.short.is_some();
+        if verify && self.revisions.len() != 1 {
+            write_to_stderr("fatal: Needed a single revision")?;
+            exit(128);
+        }
+
+        for revision in &self.revisions {
+            let oid = match parse_revision(revision, revision).resolve(&refs) {
+                Ok(oid) => oid,
+                Err(_) if verify => {
+                    write_to_stderr("fatal: Needed a single revision")?;
+                    exit(128);
+                }
+                // anything that is not a revision but names a file is echoed back
+                Err(_) if root_path.join(revision).exists() => {
+                    write_to_stdout(revision)?;
+                    continue;
+                }
+                Err(_) => {
+                    write_to_stderr(&format!(
+                        "fatal: ambiguous argument '{}': unknown revision or path not in the working tree.\n{}",
+                        revision, USE_SEPARATOR
+                    ))?;
+                    exit(128);
+                }
+            };
+
+            if self.abbrev_ref {
+                if let Some(name) = RevParseCMD::short_ref_name(&refs, revision) {
+                    write_to_stdout(&name)?;
+                }
+            } else if let Some(length) = self.short {
+                write_to_stdout(&database.short_oid(&oid, length))?;
+            } else {
+                write_to_stdout(&oid)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The name of the branch or tag `revision` refers to, without its `refs/...` prefix.
+    /// HEAD stays HEAD when it is detached. Revisions that are not a ref have no name.
+    fn short_ref_name(refs: &Refs, revision: &str) -> Option<String> {
+        if revision == "HEAD" || revision == "@" {
+            return Some(refs.current_branch().unwrap_or("HEAD".to_owned()));
+        }
+        for prefix in ["refs/heads/", "refs/tags/"] {
+            if let Some(name) = revision.strip_prefix(prefix) {
+                return Some(name.to_owned());
+            }
+            if refs.git_path.join(prefix).join(revision).is_file() {
+                return Some(revision.to_owned());
+            }
+        }
+        None
+    }
+}
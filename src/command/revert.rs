@@ -0,0 +1,33 @@
+use anyhow::Result;
+use clap::{ArgGroup, Parser};
+
+use crate::{command::sequencing::Sequencing, sequencer::Action};
+
+#[derive(Parser, Debug, PartialEq)]
+#[command(group(ArgGroup::new("sequence").args(["resume", "abort"])))]
+pub struct RevertCMD {
+    /// Commit the resolved conflict and revert the remaining commits
+    #[arg(long = "continue")]
+    resume: bool,
+
+    /// Give up and go back to where HEAD was before the revert
+    #[arg(long)]
+    abort: bool,
+
+    /// The commits whose changes to undo, in order
+    #[arg(required_unless_present_any = ["resume", "abort"], conflicts_with = "sequence")]
+    commits: Vec<String>,
+}
+
+impl RevertCMD {
+    pub fn run(&self) -> Result<()> {
+        let sequencing = Sequencing::new(Action::Revert)?;
+        if self.resume {
+            sequencing.resume()
+        } else if self.abort {
+            sequencing.abort()
+        } else {
+            sequencing.start(&self.commits)
+        }
+    }
+}
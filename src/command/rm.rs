@@ -0,0 +1,212 @@
+use anyhow::Result;
+use clap::Parser;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::{
+    database::{Blob, Database, FlatTree},
+    index::{FlatIndex, Index},
+    utils::{get_root_path, remove_empty_parents, write_to_stderr, write_to_stdout},
+    workspace::File,
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct RmCMD {
+    /// Only remove the paths from the index, keeping them in the working tree
+    #[arg(long)]
+    cached: bool,
+
+    /// Allow removing directories recursively
+    #[arg(short)]
+    recursive: bool,
+
+    /// Remove the paths even if they have changes that are not committed
+    #[arg(short, long)]
+    force: bool,
+
+    #[clap(required = true)]
+    paths: Vec<String>,
+}
+
+impl RmCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let database = Database::new(git_path.join("objects"));
+        let mut index = Index::new(git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("Failed to hold index for update");
+        }
+
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+        for (path, stages) in &index.conflicts {
+            flat_index.entries.insert(path.clone(), stages[0].clone());
+        }
+
+        let mut files = BTreeMap::new();
+        for path in &self.paths {
+            match self.expand_path(path, &flat_index) {
+                Ok(matched) => files.extend(matched),
+                Err(error) => {
+                    index.lockfile.rollback()?;
+                    write_to_stderr(&error)?;
+                    exit(128);
+                }
+            }
+        }
+
+        if !self.force {
+            let head_tree = database.read_head()?;
+            let errors = self.check_changes(&root_path, &database, &index, &head_tree, &files)?;
+            if !errors.is_empty() {
+                index.lockfile.rollback()?;
+                for error in errors {
+                    write_to_stderr(&error)?;
+                }
+                exit(1);
+            }
+        }
+
+        for (path, file) in &files {
+            index.remove(file);
+            if !self.cached {
+                let workspace_path = root_path.join(path);
+                if workspace_path.is_file() {
+                    fs::remove_file(&workspace_path)?;
+                }
+                remove_empty_parents(&root_path, &workspace_path)?;
+            }
+            write_to_stdout(&format!("rm '{}'", path))?;
+        }
+        index.write_updates()?;
+
+        Ok(())
+    }
+
+    /// Find the index entries a path names: the file itself, or everything under it when it
+    /// is a directory and `-r` was given
+    fn expand_path(
+        &self,
+        path: &str,
+        flat_index: &FlatIndex,
+    ) -> std::result::Result<BTreeMap<String, File>, String> {
+        let path = path.trim_end_matches('/');
+        if let Some(file) = flat_index.entries.get(path) {
+            return Ok(BTreeMap::from([(path.to_owned(), file.clone())]));
+        }
+
+        let prefix = format!("{}/", path);
+        let matched = flat_index
+            .entries
+            .iter()
+            .filter(|(key, _)| path == "." || key.starts_with(&prefix))
+            .map(|(key, file)| (key.clone(), file.clone()))
+            .collect::<BTreeMap<_, _>>();
+
+        if matched.is_empty() {
+            Err(format!(
+                "fatal: pathspec '{}' did not match any files",
+                path
+            ))
+        } else if !self.recursive {
+            Err(format!(
+                "fatal: not removing '{}' recursively without -r",
+                path
+            ))
+        } else {
+            Ok(matched)
+        }
+    }
+
+    /// Collect the files that would lose changes by being removed. Changes staged in the
+    /// index and changes in the working tree protect a file, except that `--cached` keeps
+    /// the working tree copy so only a file differing from both is refused.
+    fn check_changes(
+        &self,
+        root_path: &PathBuf,
+        database: &Database,
+        index: &Index,
+        head_tree: &FlatTree,
+        files: &BTreeMap<String, File>,
+    ) -> Result<Vec<String>> {
+        let mut both_changed = vec![];
+        let mut staged = vec![];
+        let mut uncommitted = vec![];
+
+        for (path, file) in files {
+            // unmerged paths can always be removed
+            if index.conflicts.contains_key(path) {
+                continue;
+            }
+
+            let head_oid = head_tree
+                .entries
+                .get(path)
+                .and_then(|file| file.oid.clone());
+            let staged_change = head_oid != file.oid;
+
+            let workspace_path = root_path.join(path);
+            let workspace_change = if workspace_path.is_file() {
+                let data = fs::read(&workspace_path)?;
+                let blob = Blob::new(unsafe { String::from_utf8_unchecked(data) });
+                Some(database.hash_object(&blob)) != file.oid
+            } else {
+                false
+            };
+
+            if staged_change && workspace_change {
+                both_changed.push(path.clone());
+            } else if staged_change && !self.cached {
+                staged.push(path.clone());
+            } else if workspace_change && !self.cached {
+                uncommitted.push(path.clone());
+            }
+        }
+
+        let mut errors = vec![];
+        if !both_changed.is_empty() {
+            errors.push(RmCMD::error_message(
+                &both_changed,
+                "staged content different from both the\nfile and the HEAD",
+                "(use -f to force removal)",
+            ));
+        }
+        if !staged.is_empty() {
+            errors.push(RmCMD::error_message(
+                &staged,
+                "changes staged in the index",
+                "(use --cached to keep the file, or -f to force removal)",
+            ));
+        }
+        if !uncommitted.is_empty() {
+            errors.push(RmCMD::error_message(
+                &uncommitted,
+                "local modifications",
+                "(use --cached to keep the file, or -f to force removal)",
+            ));
+        }
+        Ok(errors)
+    }
+
+    fn error_message(paths: &[String], problem: &str, hint: &str) -> String {
+        let subject = if paths.len() == 1 {
+            "file has"
+        } else {
+            "files have"
+        };
+        let paths = paths
+            .iter()
+            .map(|path| format!("    {}", path))
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            "error: the following {} {}:\n{}\n{}",
+            subject, problem, paths, hint
+        )
+    }
+}
@@ -0,0 +1,364 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::{
+    command::{
+        commit::{current_author, write_commit, UNMERGED_FILES},
+        reset::ResetCMD,
+        status::tracked_files,
+        write_tree::write_tree,
+    },
+    database::{Author, Database},
+    index::{FlatIndex, Index},
+    merge::{Inputs, Resolve},
+    pending_commit::{MergeType, PendingCommit},
+    refs::{parse_revision, Refs},
+    sequencer::{Action, Sequencer, Step},
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+};
+
+/// The parts cherry-pick and revert share: replaying or inverting the change of one commit
+/// onto HEAD with a three-way merge, and walking the sequencer's todo list so that a
+/// conflict can be resolved and the rest picked up with `--continue`, or everything undone
+/// with `--abort`.
+pub struct Sequencing {
+    root_path: PathBuf,
+    git_path: PathBuf,
+    refs: Refs,
+    database: Database,
+    sequencer: Sequencer,
+    pending_commit: PendingCommit,
+    action: Action,
+}
+
+impl Sequencing {
+    pub fn new(action: Action) -> Result<Self> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        Ok(Self {
+            refs: Refs::new(git_path.clone()),
+            database: Database::new(git_path.join("objects")),
+            sequencer: Sequencer::new(git_path.clone()),
+            pending_commit: PendingCommit::new(git_path.clone()),
+            root_path,
+            git_path,
+            action,
+        })
+    }
+
+    /// Apply each of the revisions in turn
+    pub fn start(&self, revisions: &[String]) -> Result<()> {
+        let name = self.name();
+        if self.sequencer.in_progress() {
+            write_to_stderr(&format!(
+                "error: a cherry-pick or revert is already in progress\nhint: try \"rgit {} (--continue | --abort)\"\nfatal: {} failed",
+                name, name
+            ))?;
+            exit(128);
+        }
+        if let Some(merge_type) = self.pending_commit.merge_type() {
+            write_to_stderr(&format!(
+                "fatal: You have not concluded your {} ({} exists).",
+                merge_type.name(),
+                merge_type.head_file()
+            ))?;
+            exit(128);
+        }
+
+        self.refuse_staged_changes()?;
+
+        let head_oid = self.refs.get_ref_content();
+        let mut steps = vec![];
+        for revision in revisions {
+            let oid = parse_revision(revision, revision).resolve_commit_or_exit(&self.refs)?;
+            let commit = self.database.read_commit(&oid)?;
+            steps.push(Step {
+                action: self.action,
+                oid,
+                subject: commit.subject().to_owned(),
+            });
+        }
+
+        self.sequencer.start(&head_oid, &steps)?;
+        self.run()
+    }
+
+    /// Commit the resolved conflict, if there is one, and go on with the remaining steps
+    pub fn resume(&self) -> Result<()> {
+        if !self.sequencer.in_progress() {
+            self.not_in_progress()?;
+        }
+
+        let merge_type = self.pending_commit.merge_type();
+        if matches!(merge_type, Some(MergeType::CherryPick | MergeType::Revert)) {
+            let mut index = Index::new(self.git_path.join("index"));
+            index.load()?;
+            if index.is_conflicted() {
+                write_to_stderr(&format!(
+                    "{}\nfatal: {} failed",
+                    UNMERGED_FILES,
+                    self.name()
+                ))?;
+                exit(128);
+            }
+
+            let author = match merge_type {
+                Some(MergeType::CherryPick) => {
+                    let oid = self.pending_commit.merge_oid()?;
+                    self.database.read_commit(&oid)?.author
+                }
+                _ => current_author()?,
+            };
+            // a step left with nothing to commit is skipped
+            if !self.is_empty(&index)? {
+                let message = self.pending_commit.merge_message()?;
+                self.commit(&index, author, message.trim_end().to_owned())?;
+            }
+            self.pending_commit.clear()?;
+        }
+
+        self.run()
+    }
+
+    /// Go back to where HEAD was before the first step, discarding everything done since
+    pub fn abort(&self) -> Result<()> {
+        if !self.sequencer.in_progress() {
+            self.not_in_progress()?;
+        }
+
+        let original_head = self.sequencer.original_head()?;
+        let mut index = Index::new(self.git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+        let head_tree = self.database.read_head()?;
+        let original_tree = self.database.read_commit_tree(&original_head)?;
+        ResetCMD::reset_workspace(
+            &self.root_path,
+            &self.database,
+            &mut index,
+            &head_tree,
+            &original_tree,
+        )?;
+        index.changed = true;
+        index.write_updates()?;
+
+        self.refs.update_head(
+            &original_head,
+            &format!("reset: moving to {}", original_head),
+        )?;
+        self.pending_commit.clear()?;
+        self.sequencer.quit()
+    }
+
+    fn run(&self) -> Result<()> {
+        while let Some(step) = self.sequencer.next_step()? {
+            self.apply_step(&step)?;
+        }
+        self.sequencer.quit()
+    }
+
+    /// Merge the change of the step's commit into HEAD and commit it. On a conflict the
+    /// message is saved for `--continue` and the process exits.
+    fn apply_step(&self, step: &Step) -> Result<()> {
+        let oid = match parse_revision(&step.oid, &step.oid).resolve_commit(&self.refs) {
+            Ok(oid) => oid,
+            Err(error) => return self.fail(&error.to_string()),
+        };
+        let commit = self.database.read_commit(&oid)?;
+        if commit.parents.len() > 1 {
+            return self.fail(&format!(
+                "error: commit {} is a merge but no -m option was given.",
+                oid
+            ));
+        }
+
+        let head_oid = self.refs.get_ref_content();
+        let short_oid = self.database.short_oid(&oid, 7);
+        let subject = commit.subject();
+        let (inputs, message, author, merge_type, verb) = match step.action {
+            Action::Pick => (
+                Inputs {
+                    left_name: "HEAD".to_owned(),
+                    right_name: format!("{} ({})", short_oid, subject),
+                    left_oid: head_oid.clone(),
+                    right_oid: oid.clone(),
+                    base_oid: commit.parent(),
+                },
+                format!(
+                    "{}\n\n(cherry picked from commit {})",
+                    commit.message.trim_end(),
+                    oid
+                ),
+                commit.author.clone(),
+                MergeType::CherryPick,
+                "apply",
+            ),
+            Action::Revert => {
+                let parent = match commit.parent() {
+                    Some(parent) => parent,
+                    None => return self.fail(&format!("error: cannot revert root commit {}", oid)),
+                };
+                (
+                    Inputs {
+                        left_name: "HEAD".to_owned(),
+                        right_name: format!("parent of {} ({})", short_oid, subject),
+                        left_oid: head_oid.clone(),
+                        right_oid: parent,
+                        base_oid: Some(oid.clone()),
+                    },
+                    format!("Revert \"{}\"\n\nThis reverts commit {}.", subject, oid),
+                    current_author()?,
+                    MergeType::Revert,
+                    "revert",
+                )
+            }
+            // only rebase knows how to edit, squash and so on
+            Action::Reword
+            | Action::Edit
+            | Action::Squash
+            | Action::Fixup
+            | Action::Exec
+            | Action::Drop => {
+                return self.fail(&format!(
+                    "error: unsupported command '{}' in the todo list",
+                    step.action.as_str()
+                ))
+            }
+        };
+
+        let mut index = Index::new(self.git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+        let mut resolve = Resolve::new(self.root_path.clone(), &self.database, &mut index, &inputs);
+        let errors = resolve.execute()?;
+        if !errors.is_empty() {
+            index.lockfile.rollback()?;
+            return self.fail(&errors.join("\n"));
+        }
+        let messages = resolve.messages.clone();
+        let conflicted = !resolve.conflicts.is_empty();
+
+        index.changed = true;
+        index.write_updates()?;
+        for message in messages {
+            write_to_stdout(&message)?;
+        }
+
+        if conflicted {
+            self.pending_commit.start(&oid, &message, merge_type)?;
+            write_to_stderr(&format!(
+                "error: could not {} {}... {}\nhint: after resolving the conflicts, mark the corrected paths\nhint: with 'rgit add <paths>' or 'rgit rm <paths>'\nhint: and commit the result with 'rgit {} --continue'",
+                verb, short_oid, subject, self.name()
+            ))?;
+            exit(1);
+        }
+
+        // the change is already there, which git doesn't make an empty commit for either
+        if self.is_empty(&index)? {
+            self.pending_commit.start(&oid, &message, merge_type)?;
+            let branch = match self.refs.current_branch() {
+                Some(branch) => format!("On branch {}", branch),
+                None => "Not currently on any branch.".to_owned(),
+            };
+            let doing = match merge_type {
+                MergeType::Revert => "reverting",
+                _ => "cherry-picking",
+            };
+            write_to_stdout(&format!(
+                "{}\nYou are currently {} commit {}.\n\nnothing to commit, working tree clean",
+                branch, doing, short_oid
+            ))?;
+            write_to_stderr(&format!(
+                "The previous {} is now empty, possibly due to conflict resolution.\nhint: use 'rgit {} --continue' to skip it, or 'rgit {} --abort' to give up",
+                self.name(), self.name(), self.name()
+            ))?;
+            exit(1);
+        }
+
+        self.commit(&index, author, message)
+    }
+
+    /// Stop before the first step when the index differs from HEAD, as whatever is staged
+    /// would be folded into the new commits
+    fn refuse_staged_changes(&self) -> Result<()> {
+        let mut index = Index::new(self.git_path.join("index"));
+        index.load()?;
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+        if !tracked_files(&flat_index, &self.database.read_head()?).is_empty() {
+            write_to_stderr(&format!(
+                "error: your local changes would be overwritten by {}.\nhint: commit your changes or stash them to proceed.\nfatal: {} failed",
+                self.name(),
+                self.name()
+            ))?;
+            exit(128);
+        }
+        Ok(())
+    }
+
+    /// Whether committing the index would change nothing from HEAD
+    fn is_empty(&self, index: &Index) -> Result<bool> {
+        let mut database = Database::new(self.git_path.join("objects"));
+        let tree = write_tree(&mut database, index)?;
+        let head_oid = self.refs.get_ref_content();
+        Ok(self.database.read_commit(&head_oid)?.tree == tree)
+    }
+
+    fn commit(&self, index: &Index, author: Author, message: String) -> Result<()> {
+        let head_oid = self.refs.get_ref_content();
+        let commit = write_commit(
+            &self.git_path,
+            index,
+            vec![head_oid],
+            author,
+            message,
+            self.name(),
+        )?;
+        let oid = commit.oid.as_ref().expect("failed to get commit oid");
+        let branch = self
+            .refs
+            .current_branch()
+            .unwrap_or_else(|| "detached HEAD".to_owned());
+        write_to_stdout(&format!(
+            "[{} {}] {}",
+            branch,
+            self.database.short_oid(oid, 7),
+            commit.subject()
+        ))
+    }
+
+    /// Report an error that stops the sequence before the step could be applied. The
+    /// sequencer state is dropped, leaving HEAD at the last commit that was made.
+    fn fail(&self, error: &str) -> Result<()> {
+        self.sequencer.quit()?;
+        write_to_stderr(error)?;
+        write_to_stderr(&format!("fatal: {} failed", self.name()))?;
+        exit(128);
+    }
+
+    fn not_in_progress(&self) -> Result<()> {
+        write_to_stderr(&format!(
+            "error: no cherry-pick or revert in progress\nfatal: {} failed",
+            self.name()
+        ))?;
+        exit(128);
+    }
+
+    fn name(&self) -> &'static str {
+        match self.action {
+            Action::Pick => "cherry-pick",
+            Action::Revert => "revert",
+            Action::Reword
+            | Action::Edit
+            | Action::Squash
+            | Action::Fixup
+            | Action::Exec
+            | Action::Drop => unreachable!("only cherry-pick and revert run a sequence"),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::Result;
+use clap::Parser;
+use colored::Colorize;
+use std::io::{self, Write};
+use std::process::exit;
+
+use crate::{
+    command::diff::print_hunks,
+    database::{Commit, Content, Database, FlatTree, ParsedContent, Tag, Tree},
+    migration::{Change, Migration},
+    refs::{parse_revision, Refs},
+    utils::{
+        get_root_path, is_binary_file, write_to_stderr, write_to_stdout, write_to_stdout_color,
+    },
+    workspace::File,
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct ShowCMD {
+    /// The objects to show, as revisions or `<revision>:<path>`; HEAD by default
+    objects: Vec<String>,
+}
+
+impl ShowCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let database = Database::new(git_path.join("objects"));
+
+        let objects = match self.objects.is_empty() {
+            true => vec!["HEAD".to_owned()],
+            false => self.objects.clone(),
+        };
+        for (i, name) in objects.iter().enumerate() {
+            if i > 0 {
+                write_to_stdout("")?;
+            }
+            let oid = match name.split_once(':') {
+                Some((revision, path)) => ShowCMD::resolve_path(&refs, &database, revision, path)?,
+                None => ShowCMD::resolve(&refs, name, false)?,
+            };
+            ShowCMD::show_object(&database, name, &oid)?;
+        }
+        Ok(())
+    }
+
+    fn resolve(refs: &Refs, revision: &str, peel: bool) -> Result<String> {
+        let revision_object = parse_revision(revision, revision);
+        match peel {
+            true => revision_object.resolve_commit_or_exit(refs),
+            false => revision_object.resolve_or_exit(refs),
+        }
+    }
+
+    /// Find the blob or tree at `path` in the tree of `revision`
+    fn resolve_path(
+        refs: &Refs,
+        database: &Database,
+        revision: &str,
+        path: &str,
+    ) -> Result<String> {
+        let oid = ShowCMD::resolve(refs, revision, true)?;
+        let mut oid = match database.read_object(&oid)? {
+            ParsedContent::CommitContent(commit) => commit.tree,
+            ParsedContent::TreeContent(_) => oid,
+            _ => anyhow::bail!("fatal: {} is not a tree", revision),
+        };
+
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let entry = match database.read_object(&oid)? {
+                ParsedContent::TreeContent(_) => {
+                    Tree::read_entries(&oid, database.object_store.clone())?
+                        .into_iter()
+                        .find(|entry| entry.name == name)
+                }
+                _ => None,
+            };
+            match entry {
+                Some(entry) => oid = entry.oid,
+                None => {
+                    write_to_stderr(&format!(
+                        "fatal: path '{}' does not exist in '{}'",
+                        path, revision
+                    ))?;
+                    exit(128);
+                }
+            }
+        }
+        Ok(oid)
+    }
+
+    fn show_object(database: &Database, name: &str, oid: &str) -> Result<()> {
+        match database.read_object(oid)? {
+            ParsedContent::CommitContent(commit) => ShowCMD::show_commit(database, &commit),
+            ParsedContent::TagContent(tag) => ShowCMD::show_tag(database, &tag),
+            ParsedContent::TreeContent(_) => {
+                write_to_stdout_color(&format!("tree {}", name).yellow())?;
+                write_to_stdout("")?;
+                for entry in Tree::read_entries(oid, database.object_store.clone())? {
+                    match entry.object_type() {
+                        "tree" => write_to_stdout(&format!("{}/", entry.name))?,
+                        _ => write_to_stdout(&entry.name)?,
+                    }
+                }
+                Ok(())
+            }
+            ParsedContent::BlobContent(_) => {
+                let content = Content::parse(oid, database.object_store.clone())?;
+                io::stdout().write_all(&content.body)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn show_tag(database: &Database, tag: &Tag) -> Result<()> {
+        write_to_stdout_color(&format!("tag {}", tag.name).yellow())?;
+        write_to_stdout(&format!(
+            "Tagger: {} <{}>",
+            tag.tagger.name, tag.tagger.email
+        ))?;
+        write_to_stdout(&format!("Date:   {}", tag.tagger.readable_time()))?;
+        write_to_stdout("")?;
+        write_to_stdout(&tag.message)?;
+        write_to_stdout("")?;
+        ShowCMD::show_object(database, &tag.object, &tag.object)
+    }
+
+    fn show_commit(database: &Database, commit: &Commit) -> Result<()> {
+        let oid = commit.oid.as_ref().expect("failed to get commit oid");
+        write_to_stdout_color(&format!("commit {}", oid).yellow())?;
+        if commit.parents.len() > 1 {
+            let parents = commit
+                .parents
+                .iter()
+                .map(|parent| database.short_oid(parent, 7))
+                .collect::<Vec<_>>();
+            write_to_stdout(&format!("Merge: {}", parents.join(" ")))?;
+        }
+        write_to_stdout(&format!(
+            "Author: {} <{}>",
+            commit.author.name, commit.author.email
+        ))?;
+        write_to_stdout(&format!("Date:   {}", commit.author.readable_time()))?;
+        write_to_stdout("")?;
+        for line in commit.message.lines() {
+            write_to_stdout(&format!("    {}", line))?;
+        }
+
+        // like git, merges are shown without a patch
+        if commit.parents.len() > 1 {
+            return Ok(());
+        }
+        let old_tree = match commit.parent() {
+            Some(parent) => database.read_commit_tree(&parent)?,
+            None => FlatTree {
+                entries: Default::default(),
+            },
+        };
+        let new_tree = database.read_commit_tree(oid)?;
+        if !Migration::tree_diff(&old_tree, &new_tree).is_empty() {
+            write_to_stdout("")?;
+        }
+        print_tree_diff(database, &old_tree, &new_tree)
+    }
+
+    /// Print the patch for one path, `None` standing for the side where the file is missing
+    fn show_patch(
+        database: &Database,
+        path: &str,
+        old_file: Option<&File>,
+        new_file: Option<&File>,
+    ) -> Result<()> {
+        let null_oid = "0".repeat(40);
+        let (old_oid, old_mode) = old_file.map_or((null_oid.as_str(), 0), |file| {
+            (
+                file.oid.as_deref().expect("failed to get oid"),
+                file.stat.mode,
+            )
+        });
+        let (new_oid, new_mode) = new_file.map_or((null_oid.as_str(), 0), |file| {
+            (
+                file.oid.as_deref().expect("failed to get oid"),
+                file.stat.mode,
+            )
+        });
+
+        let mut header = vec![format!("diff --git a/{} b/{}", path, path)];
+        let index_line = format!(
+            "index {}..{}",
+            database.short_oid(old_oid, 7),
+            database.short_oid(new_oid, 7)
+        );
+        match (old_file, new_file) {
+            (None, _) => {
+                header.push(format!("new file mode {:o}", new_mode));
+                header.push(index_line);
+            }
+            (_, None) => {
+                header.push(format!("deleted file mode {:o}", old_mode));
+                header.push(index_line);
+            }
+            _ if old_mode != new_mode => {
+                header.push(format!("old mode {:o}", old_mode));
+                header.push(format!("new mode {:o}", new_mode));
+                if old_oid != new_oid {
+                    header.push(index_line);
+                }
+            }
+            _ => header.push(format!("{} {:o}", index_line, new_mode)),
+        }
+        for line in header {
+            write_to_stdout_color(&line.bold())?;
+        }
+        if old_oid == new_oid {
+            return Ok(());
+        }
+
+        let old_content = ShowCMD::read_blob(database, old_file)?;
+        let new_content = ShowCMD::read_blob(database, new_file)?;
+        if is_binary_file(&old_content)? || is_binary_file(&new_content)? {
+            write_to_stdout("Binary files differ")?;
+            return Ok(());
+        }
+
+        let old_path = old_file.map_or("/dev/null".to_owned(), |_| format!("a/{}", path));
+        let new_path = new_file.map_or("/dev/null".to_owned(), |_| format!("b/{}", path));
+        write_to_stdout_color(&format!("--- {}", old_path).bold())?;
+        write_to_stdout_color(&format!("+++ {}", new_path).bold())?;
+        print_hunks(
+            &String::from_utf8_lossy(&old_content),
+            &String::from_utf8_lossy(&new_content),
+        );
+        Ok(())
+    }
+
+    pub fn read_blob(database: &Database, file: Option<&File>) -> Result<Vec<u8>> {
+        match file.and_then(|file| file.oid.as_ref()) {
+            Some(oid) => Ok(Content::parse(oid, database.object_store.clone())?.body),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+/// Print the patch of every path that differs between two trees
+pub fn print_tree_diff(
+    database: &Database,
+    old_tree: &FlatTree,
+    new_tree: &FlatTree,
+) -> Result<()> {
+    for (path, change) in Migration::tree_diff(old_tree, new_tree) {
+        let old_file = old_tree.entries.get(&path);
+        match change {
+            Change::Create(file) => ShowCMD::show_patch(database, &path, None, Some(&file))?,
+            Change::Update(file) => ShowCMD::show_patch(database, &path, old_file, Some(&file))?,
+            Change::Delete(file) => ShowCMD::show_patch(database, &path, Some(&file), None)?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,590 @@
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::{
+    command::{
+        commit::current_author,
+        reset::{index_entry, ResetCMD},
+        restore::RestoreCMD,
+        show::{print_tree_diff, ShowCMD},
+        write_tree::write_tree,
+    },
+    database::{Blob, Commit, Database, FlatTree},
+    diff::{EditType, Myres},
+    index::{FlatIndex, Index, Stat},
+    merge::{Inputs, Resolve},
+    migration::{Change, Migration},
+    reflog::Reflog,
+    refs::{Refs, STASH_REF},
+    utils::{
+        get_root_path, is_binary_file, remove_empty_parents, write_to_stderr, write_to_stdout,
+    },
+    workspace::{File, WorkspaceTree},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct StashCMD {
+    #[command(subcommand)]
+    command: Option<StashCommand>,
+
+    #[command(flatten)]
+    push: PushArgs,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+enum StashCommand {
+    /// Save the local changes as a new stash entry and reset to HEAD (the default)
+    Push(PushArgs),
+
+    /// Apply a stash entry and drop it if it applied cleanly
+    Pop(EntryArgs),
+
+    /// Apply a stash entry, keeping it on the stack
+    Apply(EntryArgs),
+
+    /// List the stash entries, newest first
+    List,
+
+    /// Show the changes recorded in a stash entry
+    Show(ShowArgs),
+
+    /// Remove a stash entry from the stack
+    Drop(EntryArgs),
+}
+
+#[derive(Args, Debug, PartialEq)]
+struct PushArgs {
+    /// Stash the untracked files as well, removing them from the working tree
+    #[arg(short = 'u', long)]
+    include_untracked: bool,
+
+    /// The description of the stash entry
+    #[arg(short, long)]
+    message: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+struct EntryArgs {
+    /// The entry to use, as `stash@{<n>}` or `<n>`; the latest by default
+    stash: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+struct ShowArgs {
+    /// Show the changes as a patch instead of a diffstat
+    #[arg(short = 'p', long)]
+    patch: bool,
+
+    /// The entry to show, as `stash@{<n>}` or `<n>`; the latest by default
+    stash: Option<String>,
+}
+
+impl StashCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path.clone());
+        let mut database = Database::new(git_path.join("objects"));
+        let reflog = Reflog::new(&git_path, STASH_REF);
+
+        match &self.command {
+            None => StashCMD::push(&root_path, &refs, &mut database, &self.push),
+            Some(StashCommand::Push(args)) => {
+                StashCMD::push(&root_path, &refs, &mut database, args)
+            }
+            Some(StashCommand::Pop(args)) => {
+                let position = StashCMD::entry_position(&reflog, args.stash.as_deref())?;
+                StashCMD::apply(&root_path, &refs, &database, &reflog, position, true)?;
+                StashCMD::drop(&reflog, position)
+            }
+            Some(StashCommand::Apply(args)) => {
+                let position = StashCMD::entry_position(&reflog, args.stash.as_deref())?;
+                StashCMD::apply(&root_path, &refs, &database, &reflog, position, false)
+            }
+            Some(StashCommand::List) => {
+                for (n, entry) in reflog.read()?.iter().rev().enumerate() {
+                    write_to_stdout(&format!("stash@{{{}}}: {}", n, entry.message))?;
+                }
+                Ok(())
+            }
+            Some(StashCommand::Show(args)) => {
+                let position = StashCMD::entry_position(&reflog, args.stash.as_deref())?;
+                StashCMD::show(&database, &reflog, position, args.patch)
+            }
+            Some(StashCommand::Drop(args)) => {
+                let position = StashCMD::entry_position(&reflog, args.stash.as_deref())?;
+                StashCMD::drop(&reflog, position)
+            }
+        }
+    }
+
+    /// Record the index and the working tree as commits on `refs/stash` and reset both to
+    /// HEAD. The stash commit has HEAD, the index commit and, with `-u`, the untracked
+    /// files commit as its parents, the way git lays them out.
+    fn push(
+        root_path: &PathBuf,
+        refs: &Refs,
+        database: &mut Database,
+        args: &PushArgs,
+    ) -> Result<()> {
+        let head_oid = match refs.read_head().filter(|oid| !oid.is_empty()) {
+            Some(oid) => oid,
+            None => {
+                write_to_stderr("You do not have the initial commit yet")?;
+                exit(1);
+            }
+        };
+
+        let mut index = Index::new(refs.git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+        StashCMD::refuse_unmerged(&mut index)?;
+
+        let head_commit = database.read_commit(&head_oid)?;
+        let head_tree = database.read_commit_tree(&head_oid)?;
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+
+        // the working tree state covers the tracked files only, as they are on disk
+        let mut work_entries = BTreeMap::new();
+        for path in flat_index.entries.keys() {
+            if let Some(file) = StashCMD::store_workspace_file(root_path, database, path)? {
+                work_entries.insert(path.clone(), file);
+            }
+        }
+        let mut untracked_entries = BTreeMap::new();
+        if args.include_untracked {
+            for file in WorkspaceTree::list_files(root_path) {
+                let path = file.path.to_str().unwrap().to_owned();
+                if flat_index.entries.contains_key(&path) {
+                    continue;
+                }
+                if let Some(file) = StashCMD::store_workspace_file(root_path, database, &path)? {
+                    untracked_entries.insert(path, file);
+                }
+            }
+        }
+
+        let index_tree = write_tree(database, &index)?;
+        let work_tree = StashCMD::store_tree(database, &work_entries)?;
+        if index_tree == head_commit.tree && work_tree == index_tree && untracked_entries.is_empty()
+        {
+            index.lockfile.rollback()?;
+            write_to_stdout("No local changes to save")?;
+            return Ok(());
+        }
+
+        let branch = refs
+            .current_branch()
+            .unwrap_or_else(|| "(no branch)".to_owned());
+        let subject = head_commit.message.lines().next().unwrap_or("");
+        let summary = format!(
+            "{}: {} {}",
+            branch,
+            database.short_oid(&head_oid, 7),
+            subject
+        );
+        let author = current_author()?;
+
+        let mut index_commit = Commit::new(
+            vec![head_oid.clone()],
+            index_tree,
+            author.clone(),
+            format!("index on {}", summary),
+        );
+        database.store(&mut index_commit)?;
+        let mut parents = vec![
+            head_oid.clone(),
+            index_commit.oid.expect("failed to get commit oid"),
+        ];
+        if !untracked_entries.is_empty() {
+            let untracked_tree = StashCMD::store_tree(database, &untracked_entries)?;
+            let mut untracked_commit = Commit::new(
+                vec![],
+                untracked_tree,
+                author.clone(),
+                format!("untracked files on {}", summary),
+            );
+            database.store(&mut untracked_commit)?;
+            parents.push(untracked_commit.oid.expect("failed to get commit oid"));
+        }
+
+        let message = match &args.message {
+            Some(message) => format!("On {}: {}", branch, message),
+            None => format!("WIP on {}", summary),
+        };
+        let mut stash_commit = Commit::new(parents, work_tree, author, message.clone());
+        database.store(&mut stash_commit)?;
+        let stash_oid = stash_commit.oid.expect("failed to get commit oid");
+
+        refs.update_ref(STASH_REF, &stash_oid, &message)?;
+
+        ResetCMD::reset_workspace(root_path, database, &mut index, &head_tree, &head_tree)?;
+        index.changed = true;
+        index.write_updates()?;
+        for path in untracked_entries.keys() {
+            let workspace_path = root_path.join(path);
+            fs::remove_file(&workspace_path)?;
+            remove_empty_parents(root_path, &workspace_path)?;
+        }
+
+        write_to_stdout(&format!(
+            "Saved working directory and index state {}",
+            message
+        ))?;
+        Ok(())
+    }
+
+    /// Merge the stashed working tree into the current one, relative to the commit the stash
+    /// was made on. When that goes cleanly, the index is put back the way it was stashed for
+    /// the paths HEAD has not changed since, and the untracked files are restored.
+    fn apply(
+        root_path: &PathBuf,
+        refs: &Refs,
+        database: &Database,
+        reflog: &Reflog,
+        position: usize,
+        pop: bool,
+    ) -> Result<()> {
+        let stash_oid = reflog.read()?[position].new_oid.clone();
+        let stash = database.read_commit(&stash_oid)?;
+        let head_oid = match refs.read_head().filter(|oid| !oid.is_empty()) {
+            Some(oid) => oid,
+            None => anyhow::bail!("fatal: You do not have the initial commit yet"),
+        };
+
+        let mut index = Index::new(refs.git_path.join("index"));
+        if !index.load_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on index");
+        }
+        StashCMD::refuse_unmerged(&mut index)?;
+
+        let untracked_tree = match stash.parents.get(2) {
+            Some(oid) => database.read_commit_tree(oid)?,
+            None => FlatTree {
+                entries: Default::default(),
+            },
+        };
+        let existing = untracked_tree
+            .entries
+            .keys()
+            .filter(|path| root_path.join(path).exists())
+            .collect::<Vec<_>>();
+        if !existing.is_empty() {
+            index.lockfile.rollback()?;
+            for path in existing {
+                write_to_stderr(&format!("{} already exists, no checkout", path))?;
+            }
+            write_to_stderr("error: could not restore untracked files from stash")?;
+            exit(1);
+        }
+
+        let base_oid = stash.parents[0].clone();
+        let inputs = Inputs {
+            left_name: "Updated upstream".to_owned(),
+            right_name: "Stashed changes".to_owned(),
+            left_oid: head_oid.clone(),
+            right_oid: stash_oid.clone(),
+            base_oid: Some(base_oid.clone()),
+        };
+        let mut resolve = Resolve::new(root_path.clone(), database, &mut index, &inputs);
+        let errors = resolve.execute()?;
+        if !errors.is_empty() {
+            index.lockfile.rollback()?;
+            for error in errors {
+                write_to_stderr(&error)?;
+            }
+            write_to_stderr("Aborting")?;
+            if pop {
+                write_to_stderr("The stash entry is kept in case you need it again.")?;
+            }
+            exit(1);
+        }
+        let messages = resolve.messages.clone();
+        let conflicted = !resolve.conflicts.is_empty();
+
+        if !conflicted {
+            let base_tree = database.read_commit_tree(&base_oid)?;
+            let index_tree = database.read_commit_tree(&stash.parents[1])?;
+            let work_tree = database.read_commit_tree(&stash_oid)?;
+            let head_tree = database.read_commit_tree(&head_oid)?;
+            StashCMD::restore_index(
+                root_path,
+                database,
+                &mut index,
+                &base_tree,
+                &index_tree,
+                &work_tree,
+                &head_tree,
+            );
+        }
+        for (path, file) in &untracked_tree.entries {
+            RestoreCMD::write_file(database, &root_path.join(path), file)?;
+        }
+        index.changed = true;
+        index.write_updates()?;
+
+        for message in messages {
+            write_to_stdout(&message)?;
+        }
+        if conflicted {
+            if pop {
+                write_to_stdout("The stash entry is kept in case you need it again.")?;
+            }
+            exit(1);
+        }
+        Ok(())
+    }
+
+    /// After the merge the index holds the stashed working tree for every path the stash
+    /// touched. Paths that were staged when stashing get their staged version back, as long
+    /// as HEAD still has what the stash was made on; the rest are unstaged again, except
+    /// for new files, which stay added.
+    fn restore_index(
+        root_path: &PathBuf,
+        database: &Database,
+        index: &mut Index,
+        base_tree: &FlatTree,
+        index_tree: &FlatTree,
+        work_tree: &FlatTree,
+        head_tree: &FlatTree,
+    ) {
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+
+        let paths = Migration::tree_diff(base_tree, work_tree)
+            .into_keys()
+            .chain(Migration::tree_diff(base_tree, index_tree).into_keys())
+            .collect::<BTreeSet<_>>();
+        for path in paths {
+            let base_entry = base_tree.entries.get(&path);
+            let staged_entry = index_tree.entries.get(&path);
+            let head_entry = head_tree.entries.get(&path);
+
+            let entry = if !StashCMD::same_entry(staged_entry, base_entry)
+                && StashCMD::same_entry(head_entry, base_entry)
+            {
+                staged_entry
+            } else if head_entry.is_none() {
+                flat_index.entries.get(&path)
+            } else {
+                head_entry
+            };
+            match entry {
+                Some(file) => index.add(&index_entry(root_path, database, file)),
+                None if flat_index.entries.contains_key(&path) => index.remove(&File {
+                    name: path.clone(),
+                    path: PathBuf::from(&path),
+                    stat: Stat::default(),
+                    oid: None,
+                }),
+                None => (),
+            }
+        }
+    }
+
+    fn drop(reflog: &Reflog, position: usize) -> Result<()> {
+        let mut n = 0;
+        let mut removed = None;
+        reflog.rewrite_and_update_ref(|mut entries| {
+            n = entries.len() - 1 - position;
+            let entry = entries.remove(position);
+            // the entry above the dropped one now follows the one below it
+            if let Some(next) = entries.get_mut(position) {
+                next.old_oid = entry.old_oid.clone();
+            }
+            removed = Some(entry);
+            Ok(Some(entries))
+        })?;
+        let removed = removed.unwrap();
+
+        write_to_stdout(&format!(
+            "Dropped refs/stash@{{{}}} ({})",
+            n, removed.new_oid
+        ))?;
+        Ok(())
+    }
+
+    fn show(database: &Database, reflog: &Reflog, position: usize, patch: bool) -> Result<()> {
+        let stash_oid = reflog.read()?[position].new_oid.clone();
+        let stash = database.read_commit(&stash_oid)?;
+        let base_tree = database.read_commit_tree(&stash.parents[0])?;
+        let work_tree = database.read_commit_tree(&stash_oid)?;
+
+        if patch {
+            return print_tree_diff(database, &base_tree, &work_tree);
+        }
+        StashCMD::print_diffstat(database, &base_tree, &work_tree)
+    }
+
+    /// Print a line per changed path with its number of changed lines, followed by the
+    /// totals, like `git diff --stat`
+    fn print_diffstat(database: &Database, old_tree: &FlatTree, new_tree: &FlatTree) -> Result<()> {
+        let mut stats = vec![];
+        for (path, change) in Migration::tree_diff(old_tree, new_tree) {
+            let new_file = match &change {
+                Change::Create(file) | Change::Update(file) => Some(file),
+                Change::Delete(_) => None,
+            };
+            let old_content = ShowCMD::read_blob(database, old_tree.entries.get(&path))?;
+            let new_content = ShowCMD::read_blob(database, new_file)?;
+            if is_binary_file(&old_content)? || is_binary_file(&new_content)? {
+                stats.push((path, None));
+                continue;
+            }
+
+            let edits = Myres::new(
+                String::from_utf8_lossy(&old_content).into_owned(),
+                String::from_utf8_lossy(&new_content).into_owned(),
+            )
+            .edits();
+            let insertions = edits
+                .iter()
+                .filter(|edit| edit.edit_type == EditType::Add)
+                .count();
+            let deletions = edits
+                .iter()
+                .filter(|edit| edit.edit_type == EditType::Remove)
+                .count();
+            stats.push((path, Some((insertions, deletions))));
+        }
+
+        let name_width = stats.iter().map(|(path, _)| path.len()).max().unwrap_or(0);
+        let largest = stats
+            .iter()
+            .filter_map(|(_, counts)| counts.map(|(ins, del)| ins + del))
+            .max()
+            .unwrap_or(0);
+        let count_width = largest.to_string().len();
+        // long bars are scaled down so that the largest one fits in 40 columns
+        let scale = |count: usize| match largest > 40 && count > 0 {
+            true => (count * 40 / largest).max(1),
+            false => count,
+        };
+
+        let (mut total_insertions, mut total_deletions) = (0, 0);
+        for (path, counts) in &stats {
+            match counts {
+                Some((insertions, deletions)) => {
+                    total_insertions += insertions;
+                    total_deletions += deletions;
+                    write_to_stdout(&format!(
+                        " {:name_width$} | {:>count_width$} {}{}",
+                        path,
+                        insertions + deletions,
+                        "+".repeat(scale(*insertions)),
+                        "-".repeat(scale(*deletions)),
+                    ))?;
+                }
+                None => write_to_stdout(&format!(" {:name_width$} | Bin", path))?,
+            }
+        }
+
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        let mut summary = format!(" {} file{} changed", stats.len(), plural(stats.len()));
+        if total_insertions > 0 || total_deletions == 0 {
+            summary.push_str(&format!(
+                ", {} insertion{}(+)",
+                total_insertions,
+                plural(total_insertions)
+            ));
+        }
+        if total_deletions > 0 || total_insertions == 0 {
+            summary.push_str(&format!(
+                ", {} deletion{}(-)",
+                total_deletions,
+                plural(total_deletions)
+            ));
+        }
+        write_to_stdout(&summary)?;
+        Ok(())
+    }
+
+    /// Turn `stash@{<n>}` or `<n>` into the position of the entry in the reflog, which
+    /// lists the oldest entry first
+    fn entry_position(reflog: &Reflog, name: Option<&str>) -> Result<usize> {
+        let entries = reflog.read()?;
+        if entries.is_empty() {
+            write_to_stderr("No stash entries found.")?;
+            exit(1);
+        }
+
+        let name = name.unwrap_or("stash@{0}");
+        let n = name
+            .strip_prefix("stash@{")
+            .and_then(|rest| rest.strip_suffix('}'))
+            .unwrap_or(name)
+            .parse::<usize>();
+        match n {
+            Ok(n) if n < entries.len() => Ok(entries.len() - 1 - n),
+            _ => {
+                write_to_stderr(&format!("error: {} is not a valid reference", name))?;
+                exit(1);
+            }
+        }
+    }
+
+    fn refuse_unmerged(index: &mut Index) -> Result<()> {
+        if !index.is_conflicted() {
+            return Ok(());
+        }
+        index.lockfile.rollback()?;
+        for path in index.conflicts.keys() {
+            write_to_stderr(&format!("{}: needs merge", path))?;
+        }
+        exit(1);
+    }
+
+    /// Store the workspace version of `path` as a blob, returning its entry, or `None` if
+    /// the file is gone
+    fn store_workspace_file(
+        root_path: &PathBuf,
+        database: &Database,
+        path: &str,
+    ) -> Result<Option<File>> {
+        let workspace_path = root_path.join(path);
+        if !workspace_path.is_file() {
+            return Ok(None);
+        }
+        let data = fs::read(&workspace_path)?;
+        let mut blob = Blob::new(unsafe { String::from_utf8_unchecked(data) });
+        database.store(&mut blob)?;
+
+        let path = PathBuf::from(path);
+        Ok(Some(File {
+            name: path.file_name().unwrap().to_str().unwrap().to_owned(),
+            stat: Stat::new(&workspace_path),
+            oid: blob.oid,
+            path,
+        }))
+    }
+
+    /// Store a tree made of `entries`, going through a scratch index that is never written
+    fn store_tree(database: &mut Database, entries: &BTreeMap<String, File>) -> Result<String> {
+        let mut index = Index::new(PathBuf::new());
+        for file in entries.values() {
+            index.add(file);
+        }
+        write_tree(database, &index)
+    }
+
+    fn same_entry(entry: Option<&File>, other: Option<&File>) -> bool {
+        match (entry, other) {
+            (Some(entry), Some(other)) => {
+                entry.oid == other.oid && entry.stat.mode == other.stat.mode
+            }
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
@@ -8,7 +8,7 @@ use crate::{
     database::{Content, Database, FlatTree},
     index::{FlatIndex, Index},
     utils::{get_root_path, write_to_stdout, write_to_stdout_color},
-    workspace::WorkspaceTree,
+    workspace::{File, WorkspaceTree},
 };
 
 #[derive(Parser, Debug, PartialEq)]
@@ -35,22 +35,36 @@ impl StatusCMD {
         };
         Index::flatten_entries(&index.entries, &mut flat_index);
 
-        let untracked_files = untracked_files(&flat_workspace, &flat_index);
+        let mut untracked_files = untracked_files(&flat_workspace, &flat_index);
+        let mut staged_files = tracked_files(&flat_index, &flat_commit_tree);
+        let mut modified_files = modified_files(&flat_workspace, &flat_index, &flat_commit_tree);
+
+        // unmerged paths are only reported in their own section
+        for path in index.conflicts.keys() {
+            untracked_files.remove(path);
+            staged_files.remove(path);
+            modified_files.remove(path);
+        }
+
+        if index.is_conflicted() {
+            write_to_stdout("Unmerged paths:")?;
+            for (file, stages) in &index.conflicts {
+                let message = format!("{}: {}", conflict_status(stages), file);
+                write_to_stdout_color(&message.red())?;
+            }
+        }
+
         write_to_stdout("Untracked files:")?;
         for (file, _) in untracked_files {
             write_to_stdout_color(&file.red())?;
         }
 
-        let staged_files = tracked_files(&flat_index, &flat_commit_tree);
-
         write_to_stdout("Changes to be committed:")?;
         for (file, status) in staged_files.clone() {
             let message = format!("{}: {}", status, file);
             write_to_stdout_color(&message.green())?;
         }
 
-        let modified_files = modified_files(&flat_workspace, &flat_index, &flat_commit_tree);
-
         write_to_stdout("Changed not staged for commit:")?;
         for (file, status) in modified_files.clone() {
             let message = format!("{}: {}", status, file);
@@ -61,6 +75,20 @@ impl StatusCMD {
     }
 }
 
+/// Describes an unmerged path by which of the base, ours and theirs versions are present
+pub fn conflict_status(stages: &[File]) -> &'static str {
+    let stages = stages.iter().map(Index::stage).collect::<Vec<_>>();
+    match stages.as_slice() {
+        [1, 2, 3] => "both modified",
+        [2, 3] => "both added",
+        [1, 2] => "deleted by them",
+        [1, 3] => "deleted by us",
+        [2] => "added by us",
+        [3] => "added by them",
+        _ => "both deleted",
+    }
+}
+
 /// Returns a map of tracked files and their status
 /// If a file is in the index but not in the commit tree, it's a new file
 /// If a file is in the index and the commit tree but the content is different, it's a modified file
@@ -0,0 +1,133 @@
+use anyhow::Result;
+use clap::Parser;
+use std::path::Path;
+use std::process::exit;
+
+use crate::{
+    command::commit::current_author,
+    database::{Database, ParsedContent, Tag},
+    refs::{parse_revision, valid_ref_name, Refs},
+    utils::{edit_tag_message, get_root_path, write_to_stderr, write_to_stdout},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct TagCMD {
+    /// Make an annotated tag object, writing its message in the editor unless -m gives it
+    #[arg(short)]
+    annotate: bool,
+
+    /// The message of an annotated tag; implies -a
+    #[arg(short, long)]
+    message: Option<String>,
+
+    /// Delete the named tags
+    #[arg(short, long)]
+    delete: bool,
+
+    /// List the tags
+    #[arg(short, long)]
+    list: bool,
+
+    /// The tag name, or the tags to delete with -d
+    names: Vec<String>,
+}
+
+impl TagCMD {
+    pub fn run(self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let refs = Refs::new(git_path);
+
+        if self.delete {
+            return self.delete_tags(&refs);
+        }
+        if self.list || self.names.is_empty() {
+            for tag in refs.list_tags()? {
+                write_to_stdout(&tag)?;
+            }
+            return Ok(());
+        }
+        if self.names.len() > 2 {
+            write_to_stderr("fatal: too many arguments")?;
+            exit(129);
+        }
+        self.create_tag(&refs)
+    }
+
+    fn create_tag(&self, refs: &Refs) -> Result<()> {
+        let name = &self.names[0];
+        if !valid_ref_name(name) {
+            write_to_stderr(&format!("fatal: '{}' is not a valid tag name.", name))?;
+            exit(128);
+        }
+        let tags_path = refs.git_path.join("refs/tags");
+        if tags_path.join(name).is_file() {
+            write_to_stderr(&format!("fatal: tag '{}' already exists", name))?;
+            exit(128);
+        }
+        // a tag name can't sit under another tag, nor have other tags under it
+        let prefix = format!("{}/", name);
+        let tags = refs.list_tags()?;
+        let taken = Path::new(name)
+            .ancestors()
+            .skip(1)
+            .filter_map(|parent| parent.to_str())
+            .find(|parent| !parent.is_empty() && tags_path.join(parent).is_file())
+            .map(|parent| parent.to_owned())
+            .or_else(|| tags.into_iter().find(|tag| tag.starts_with(&prefix)));
+        if let Some(taken) = taken {
+            write_to_stderr(&format!(
+                "fatal: cannot lock ref 'refs/tags/{}': 'refs/tags/{}' exists; cannot create 'refs/tags/{}'",
+                name, taken, name
+            ))?;
+            exit(128);
+        }
+
+        let revision = self.names.get(1).map_or("HEAD", |rev| rev.as_str());
+        let target = parse_revision(revision, revision).resolve_or_exit(refs)?;
+
+        if !self.annotate && self.message.is_none() {
+            return refs.create_tag(name, &target);
+        }
+
+        let message = match &self.message {
+            Some(message) => message.clone(),
+            None => edit_tag_message(&refs.git_path, name)?,
+        };
+        let database = Database::new(refs.git_path.join("objects"));
+        let object_type = match database.read_object(&target)? {
+            ParsedContent::BlobContent(_) => "blob",
+            ParsedContent::CommitContent(_) => "commit",
+            ParsedContent::TreeContent(_) => "tree",
+            ParsedContent::TagContent(_) => "tag",
+        };
+        let mut tag = Tag::new(
+            target,
+            object_type.to_owned(),
+            name.clone(),
+            current_author()?,
+            message,
+        );
+        database.store(&mut tag)?;
+        refs.create_tag(name, &tag.oid.expect("failed to get tag oid"))
+    }
+
+    fn delete_tags(&self, refs: &Refs) -> Result<()> {
+        let mut failed = false;
+        for name in &self.names {
+            match refs.delete_tag(name)? {
+                Some(oid) => {
+                    write_to_stdout(&format!("Deleted tag '{}' (was {})", name, &oid[..7]))?
+                }
+                None => {
+                    write_to_stderr(&format!("error: tag '{}' not found.", name))?;
+                    failed = true;
+                }
+            }
+        }
+        if failed {
+            exit(1);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use anyhow::Result;
+use clap::Parser;
+use std::process::exit;
+
+use crate::{
+    database::{Database, Tree},
+    index::Index,
+    utils::{get_root_path, write_to_stderr, write_to_stdout},
+};
+
+#[derive(Parser, Debug, PartialEq)]
+pub struct WriteTreeCMD {}
+
+impl WriteTreeCMD {
+    pub fn run(&self) -> Result<()> {
+        let root_path = get_root_path()?;
+        let git_path = root_path.join(".rgit");
+        let mut database = Database::new(git_path.join("objects"));
+        let mut index = Index::new(git_path.join("index"));
+        index.load()?;
+
+        if index.is_conflicted() {
+            for path in index.conflicts.keys() {
+                write_to_stderr(&format!("{}: unmerged", path))?;
+            }
+            write_to_stderr("fatal: git-write-tree: error building trees")?;
+            exit(128);
+        }
+
+        let oid = write_tree(&mut database, &index)?;
+        write_to_stdout(&oid)?;
+        Ok(())
+    }
+}
+
+/// Store the index as a tree object, along with every subtree, and return the oid of the
+/// root tree
+pub fn write_tree(database: &mut Database, index: &Index) -> Result<String> {
+    let mut root = Tree::new();
+    root.build_from_index(index);
+    root.traverse(database)?;
+    database.store(&mut root)?;
+    Ok(root.oid.expect("OID not found"))
+}
@@ -2,13 +2,19 @@ mod author;
 mod blob;
 mod commit;
 mod database;
+mod prune;
+mod reachable;
 mod storable;
+mod tag;
 mod tree;
 
 pub use self::author::Author;
 pub use self::blob::Blob;
 pub use self::commit::Commit;
-pub use self::database::{Content, Database, FileMode, ParsedContent};
+pub use self::database::{Content, Database, FileMode, Header, ObjectType, ParsedContent};
+pub use self::prune::Prune;
+pub use self::reachable::Reachable;
 pub use self::storable::Storable;
+pub use self::tag::Tag;
 
-pub use self::tree::{FlatTree, Tree};
+pub use self::tree::{FileOrTree, FlatTree, Tree};
@@ -5,7 +5,7 @@ use std::fmt;
 pub struct Author {
     pub name: String,
     pub email: String,
-    pub time: chrono::DateTime<chrono::Local>,
+    pub time: chrono::DateTime<chrono::FixedOffset>,
 }
 
 impl Author {
@@ -13,22 +13,31 @@ impl Author {
         Self {
             name: name.to_owned(),
             email: email.to_owned(),
-            time: chrono::Local::now(),
+            time: chrono::Local::now().fixed_offset(),
         }
     }
 
+    /// Parse an author line of the form `Name <email> timestamp timezone`
     pub fn parse(line: &str) -> Self {
-        let mut parts = line.splitn(2, ' ');
-        let name = parts.next().unwrap();
-        let email = parts.next().unwrap();
-        let email = &email[1..email.len() - 1];
-        let time = chrono::Local::now();
+        let email_start = line.find('<').unwrap_or(line.len());
+        let email_end = line.rfind('>').unwrap_or(line.len());
+        let name = line[..email_start].trim();
+        let email = line.get(email_start + 1..email_end).unwrap_or("");
+        let time = line
+            .get(email_end + 1..)
+            .and_then(|timestamp| chrono::DateTime::parse_from_str(timestamp.trim(), "%s %z").ok())
+            .unwrap_or_else(|| chrono::Local::now().fixed_offset());
         Self {
             name: name.to_owned(),
             email: email.to_owned(),
             time,
         }
     }
+
+    /// The time the way `git log` shows it, e.g. `Thu Oct 17 14:02:11 2024 +0200`
+    pub fn readable_time(&self) -> String {
+        self.time.format("%a %b %-d %H:%M:%S %Y %z").to_string()
+    }
 }
 
 impl fmt::Display for Author {
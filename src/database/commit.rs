@@ -4,7 +4,7 @@ use crate::{database::storable::Storable, database::Author, database::Content};
 
 #[derive(Debug, Clone)]
 pub struct Commit {
-    pub parent: Option<String>,
+    pub parents: Vec<String>,
     pub oid: Option<String>,
     pub tree: String,
     pub author: Author,
@@ -12,9 +12,9 @@ pub struct Commit {
 }
 
 impl Commit {
-    pub fn new(parent: Option<String>, tree: String, author: Author, message: String) -> Self {
+    pub fn new(parents: Vec<String>, tree: String, author: Author, message: String) -> Self {
         Self {
-            parent,
+            parents,
             oid: None,
             tree,
             author,
@@ -22,6 +22,16 @@ impl Commit {
         }
     }
 
+    /// The first parent of the commit, `None` for a root commit
+    pub fn parent(&self) -> Option<String> {
+        self.parents.first().cloned()
+    }
+
+    /// The first line of the message
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+
     pub fn parse(oid: String, object_store: PathBuf) -> Self {
         let content = Content::parse(&oid, object_store)
             .expect("Failed to parse content")
@@ -29,49 +39,33 @@ impl Commit {
 
         let content = String::from_utf8(content).unwrap();
         let mut lines = content.lines();
-        let tree = lines.next().unwrap().split(' ').last().unwrap();
 
-        if content.contains("parent") {
-            let parent = lines
-                .next()
-                .map(|line| line.split(' ').last().unwrap())
-                .map(|parent| parent.to_owned());
-            let author = Author::parse(lines.next().clone().unwrap());
-            let message = lines.skip(2).collect::<Vec<_>>().join("\n");
+        let mut tree = String::new();
+        let mut parents = vec![];
+        let mut author = None;
 
-            Self {
-                parent,
-                oid: Some(oid),
-                tree: tree.to_owned(),
-                author,
-                message,
+        // the headers run until the first empty line, the message follows it
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
             }
-        } else {
-            let author = Author::parse(lines.next().clone().unwrap());
-            let message = lines.skip(2).collect::<Vec<_>>().join("\n");
-
-            Self {
-                parent: None,
-                oid: Some(oid),
-                tree: tree.to_owned(),
-                author,
-                message,
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "tree" => tree = value.to_owned(),
+                "parent" => parents.push(value.to_owned()),
+                "author" => author = Some(Author::parse(value)),
+                _ => (),
             }
         }
-        // let parent = lines
-        //     .next()
-        //     .map(|line| line.split(' ').last().unwrap())
-        //     .map(|parent| parent.to_owned());
-        // let author = Author::parse(lines.next().clone().unwrap());
-        // let message = lines.skip(2).collect::<Vec<_>>().join("\n");
+        let message = lines.collect::<Vec<_>>().join("\n");
 
-        // Self {
-        //     parent,
-        //     oid: Some(oid),
-        //     tree: tree.to_owned(),
-        //     author,
-        //     message,
-        // }
+        Self {
+            parents,
+            oid: Some(oid),
+            tree,
+            author: author.expect("commit has no author"),
+            message,
+        }
     }
 }
 
@@ -85,16 +79,12 @@ impl Storable for Commit {
     }
 
     fn data(&self) -> String {
-        if let Some(parent) = &self.parent {
-            format!(
-                "tree {}\nparent {}\nauthor {}\ncomitter {}\n\n{}\n",
-                self.tree, parent, self.author, self.author, self.message
-            )
-        } else {
-            format!(
-                "tree {}\nauthor {}\ncomitter {}\n\n{}\n",
-                self.tree, self.author, self.author, self.message
-            )
+        let mut lines = vec![format!("tree {}", self.tree)];
+        for parent in &self.parents {
+            lines.push(format!("parent {}", parent));
         }
+        lines.push(format!("author {}", self.author));
+        lines.push(format!("comitter {}", self.author));
+        format!("{}\n\n{}\n", lines.join("\n"), self.message)
     }
 }
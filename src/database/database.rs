@@ -2,11 +2,14 @@ use anyhow::Result;
 use flate2::read::ZlibDecoder;
 use predicates::name;
 use std::fs::{read, rename, File};
-use std::io::{prelude::*, Cursor};
+use std::io::{prelude::*, BufReader, Cursor};
 
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
 
-use crate::database::{Blob, Commit, Tree};
+use crate::database::{Blob, Commit, Tag, Tree};
+use crate::pack::Packs;
 use crate::refs::Refs;
 use crate::utils::get_root_path;
 use crate::{database::Storable, utils::compress_content, utils::hash_content};
@@ -37,17 +40,18 @@ impl FileMode {
         match mode {
             "100644" => FileMode::Regular,
             "100755" => FileMode::Executable,
-            "040000" => FileMode::Directory,
+            "040000" | "40000" => FileMode::Directory,
             _ => FileMode::Unknown,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ObjectType {
     Blob,
     Tree,
     Commit,
+    Tag,
     Unknown,
 }
 
@@ -57,9 +61,20 @@ impl ObjectType {
             "blob" => ObjectType::Blob,
             "tree" => ObjectType::Tree,
             "commit" => ObjectType::Commit,
+            "tag" => ObjectType::Tag,
             _ => ObjectType::Unknown,
         }
     }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectType::Blob => "blob",
+            ObjectType::Tree => "tree",
+            ObjectType::Commit => "commit",
+            ObjectType::Tag => "tag",
+            ObjectType::Unknown => "unknown",
+        }
+    }
 }
 
 pub struct Header {
@@ -68,34 +83,35 @@ pub struct Header {
 }
 
 impl Header {
-    pub fn parse(oid: &str, object_store: PathBuf) -> Self {
+    /// Read the type and size of `oid` without reading its content: only as much of a loose
+    /// object as it takes to get to the end of its header, or the header of the packed entry
+    pub fn parse(oid: &str, object_store: PathBuf) -> Result<Self> {
         let object_path = object_store.join(&oid[0..2]).join(&oid[2..]);
-        let data = read(object_path).unwrap();
-
-        let mut decompressed = ZlibDecoder::new(&data[..]);
-        let mut buffer = Vec::new();
-        decompressed.read_to_end(&mut buffer).unwrap();
+        if !object_path.is_file() {
+            if let Some((object_type, object_size)) = Packs::of(&object_store).header(oid)? {
+                return Ok(Header {
+                    object_type,
+                    object_size,
+                });
+            }
+        }
 
-        let mut cursor = Cursor::new(buffer);
+        let mut reader = BufReader::new(ZlibDecoder::new(File::open(object_path)?));
         let mut header = Vec::new();
-        cursor.read_until(b'\0', &mut header).unwrap();
-
-        let header = String::from_utf8(header).unwrap();
-
-        let mut parts = header.split(' ');
-        let object_type = parts.next().unwrap();
-        let object_type = ObjectType::from_str(object_type);
-        let object_size = parts
-            .next()
-            .unwrap()
-            .trim_end_matches('\0')
-            .parse::<usize>()
-            .unwrap();
+        reader.read_until(b'\0', &mut header)?;
+        Header::from_bytes(header)
+    }
 
-        Header {
+    /// Parse the `<type> <size>\0` a loose object starts with
+    fn from_bytes(header: Vec<u8>) -> Result<Self> {
+        let header = String::from_utf8(header)?;
+        let mut parts = header.trim_end_matches('\0').split(' ');
+        let object_type = ObjectType::from_str(parts.next().unwrap_or_default());
+        let object_size = parts.next().unwrap_or_default().parse::<usize>()?;
+        Ok(Header {
             object_type,
             object_size,
-        }
+        })
     }
 }
 
@@ -105,8 +121,19 @@ pub struct Content {
 }
 
 impl Content {
+    /// Read the object `oid`, from its loose file or else from the pack holding it
     pub fn parse(oid: &str, object_store: PathBuf) -> Result<Self> {
         let object_path = object_store.join(&oid[0..2]).join(&oid[2..]);
+        if !object_path.is_file() {
+            if let Some((object_type, body)) = Packs::of(&object_store).read(oid)? {
+                let header = Header {
+                    object_type,
+                    object_size: body.len(),
+                };
+                let body = Arc::unwrap_or_clone(body);
+                return Ok(Content { header, body });
+            }
+        }
 
         let data = read(object_path)?;
 
@@ -121,8 +148,7 @@ impl Content {
         let mut body = Vec::new();
         cursor.read_to_end(&mut body)?;
 
-        let header = Header::parse(&oid, object_store);
-
+        let header = Header::from_bytes(header)?;
         Ok(Content { header, body })
     }
 }
@@ -137,6 +163,7 @@ pub enum ParsedContent {
     BlobContent(Blob),
     CommitContent(Commit),
     TreeContent(FlatTree),
+    TagContent(Tag),
 }
 
 impl<'a> Database {
@@ -148,7 +175,7 @@ impl<'a> Database {
     }
 
     pub fn read_object(&self, oid: &str) -> Result<ParsedContent> {
-        let header = Header::parse(oid, self.object_store.clone());
+        let header = Header::parse(oid, self.object_store.clone())?;
 
         let parsed_content = match header.object_type {
             ObjectType::Blob => ParsedContent::BlobContent(Blob::parse(oid.to_owned())),
@@ -156,6 +183,9 @@ impl<'a> Database {
                 oid.to_owned(),
                 self.object_store.clone(),
             )),
+            ObjectType::Tag => {
+                ParsedContent::TagContent(Tag::parse(oid.to_owned(), self.object_store.clone()))
+            }
             ObjectType::Tree => {
                 let file_or_dir = Tree::parse(oid.to_owned());
                 ParsedContent::TreeContent(FlatTree {
@@ -184,7 +214,7 @@ impl<'a> Database {
             match commit {
                 ParsedContent::CommitContent(commit) => {
                     commits.push(commit.clone());
-                    match commit.parent {
+                    match commit.parent() {
                         Some(oid) => current_oid = oid,
                         None => break,
                     }
@@ -203,20 +233,7 @@ impl<'a> Database {
         let parent = refs.read_head();
 
         let tree = match parent {
-            Some(oid) => {
-                let commit = self.read_object(&oid).unwrap();
-                match commit {
-                    ParsedContent::CommitContent(commit) => {
-                        let tree_oid = commit.tree;
-                        let tree = self.read_object(&tree_oid).unwrap();
-                        match tree {
-                            ParsedContent::TreeContent(tree) => tree,
-                            _ => panic!("should not happen"),
-                        }
-                    }
-                    _ => panic!("should not happen"),
-                }
-            }
+            Some(oid) => self.read_commit_tree(&oid)?,
             None => FlatTree {
                 entries: Default::default(),
             },
@@ -225,6 +242,39 @@ impl<'a> Database {
         Ok(tree)
     }
 
+    /// Read the commit `oid`, failing if the object is not a commit
+    pub fn read_commit(&self, oid: &str) -> Result<Commit> {
+        match self.read_object(oid)? {
+            ParsedContent::CommitContent(commit) => Ok(commit),
+            _ => anyhow::bail!("fatal: {} is not a commit", oid),
+        }
+    }
+
+    /// Read the flattened tree of the commit `oid`
+    pub fn read_commit_tree(&self, oid: &str) -> Result<FlatTree> {
+        let commit = self.read_object(oid)?;
+        match commit {
+            ParsedContent::CommitContent(commit) => {
+                let tree = self.read_object(&commit.tree)?;
+                match tree {
+                    ParsedContent::TreeContent(tree) => Ok(tree),
+                    _ => panic!("should not happen"),
+                }
+            }
+            _ => anyhow::bail!("fatal: {} is not a commit", oid),
+        }
+    }
+
+    /// Compute the oid of a storable without writing it to the database
+    pub fn hash_object<T>(&self, storable: &T) -> String
+    where
+        T: Storable,
+    {
+        let content = storable.data();
+        let content = format!("{} {}\0{}", storable.blob_type(), content.len(), content);
+        hash_content(&content)
+    }
+
     pub fn store<T>(&self, storable: &mut T) -> Result<()>
     where
         T: Storable,
@@ -238,13 +288,82 @@ impl<'a> Database {
         Ok(())
     }
 
-    pub fn prefix_match(&self, prefix: &str) -> Vec<Commit> {
-        let commits = self.read_commits().unwrap();
+    /// Whether the object `oid` is in the database, loose or packed
+    pub fn exists(&self, oid: &str) -> bool {
+        oid.len() == 40
+            && (self.object_store.join(&oid[0..2]).join(&oid[2..]).is_file()
+                || Packs::of(&self.object_store).contains(oid))
+    }
+
+    /// The oids of all objects starting with `prefix`, sorted
+    pub fn prefix_match_oids(&self, prefix: &str) -> Vec<String> {
+        if prefix.len() < 2 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return vec![];
+        }
+
+        let mut oids = Packs::of(&self.object_store).prefix_match_oids(prefix);
+        let object_dir = self.object_store.join(&prefix[0..2]);
+        if let Ok(entries) = std::fs::read_dir(object_dir) {
+            oids.extend(
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| entry.file_name().into_string().ok())
+                    .map(|name| format!("{}{}", &prefix[0..2], name))
+                    .filter(|oid| oid.len() == 40 && oid.starts_with(prefix)),
+            );
+        }
+        oids.sort();
+        oids.dedup();
+        oids
+    }
+
+    /// The oids of the objects stored loose, under a directory named after their first
+    /// two characters
+    pub fn loose_oids(&self) -> Vec<String> {
+        let is_hex = |name: &str, length: usize| {
+            name.len() == length && name.chars().all(|c| c.is_ascii_hexdigit())
+        };
+        let mut oids = vec![];
+        let dirs = match std::fs::read_dir(&self.object_store) {
+            Ok(dirs) => dirs,
+            Err(_) => return oids,
+        };
+        for dir in dirs.filter_map(|entry| entry.ok()) {
+            let prefix = dir.file_name().to_string_lossy().into_owned();
+            if !is_hex(&prefix, 2) {
+                continue;
+            }
+            let files = match std::fs::read_dir(dir.path()) {
+                Ok(files) => files,
+                Err(_) => continue,
+            };
+            for file in files.filter_map(|entry| entry.ok()) {
+                let name = file.file_name().to_string_lossy().into_owned();
+                if is_hex(&name, 38) {
+                    oids.push(format!("{}{}", prefix, name));
+                }
+            }
+        }
+        oids.sort();
+        oids
+    }
 
+    /// Abbreviate `oid` to at least `min_length` characters, and more where a shorter prefix
+    /// would be ambiguous
+    pub fn short_oid(&self, oid: &str, min_length: usize) -> String {
+        let mut length = min_length.clamp(4, oid.len());
+        while length < oid.len() && self.prefix_match_oids(&oid[..length]).len() > 1 {
+            length += 1;
+        }
+        oid[..length].to_owned()
+    }
+
+    /// Find the commits in the object store whose oid starts with `prefix`
+    pub fn prefix_match(&self, prefix: &str) -> Vec<Commit> {
         let mut matched = Vec::new();
-        for commit in commits {
-            if commit.oid.clone().expect("no OID found").starts_with(prefix) {
-                matched.push(commit.clone());
+        for oid in self.prefix_match_oids(prefix) {
+            if let Ok(ParsedContent::CommitContent(commit)) = self.read_object(&oid) {
+                matched.push(commit);
             }
         }
 
@@ -253,13 +372,18 @@ impl<'a> Database {
 
     pub fn write_object(&self, name: &str, content: &str) -> Result<()> {
         let object_path = PathBuf::from(&self.object_store).join(&name[0..2]);
-        std::fs::create_dir_all(&object_path)?;
         let object_name = object_path.join(&name[2..]);
 
-        // if the object already exists, we don't need to write it again
+        // if the object already exists, we don't need to write it again, but a loose one is
+        // freshened so prune takes it for new and spares it until it has been recorded
         if object_name.exists() {
+            File::open(&object_name)?.set_modified(SystemTime::now())?;
             return Ok(());
         }
+        if Packs::of(&self.object_store).contains(name) {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&object_path)?;
 
         // generate a temporary file and write the content to it, then rename it to the final name
         let temp_file_path =
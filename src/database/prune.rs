@@ -0,0 +1,129 @@
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Utc};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{Content, Database, Reachable};
+use crate::{
+    lockfile::Lockfile,
+    utils::{remove_empty_parents, write_to_stdout},
+};
+
+/// Removes the loose objects nothing reachable points to any more. Only the ones last
+/// written before `expire` go, which spares what a command running alongside has just
+/// written but not recorded in a ref or the index yet; with no `expire` nothing goes.
+pub struct Prune {
+    pub expire: Option<DateTime<FixedOffset>>,
+    /// List the objects that would be removed instead
+    pub dry_run: bool,
+}
+
+impl Prune {
+    pub fn run(&self, git_path: &Path) -> Result<()> {
+        // commands staging files write their blobs while holding the index lock, so while
+        // prune holds it every blob written so far is already in the index
+        let mut lockfile = Lockfile::new(git_path.join("index"));
+        if !lockfile.hold_for_update()? {
+            anyhow::bail!(
+                "fatal: Unable to create '{}': File exists.\nAnother rgit process seems to be running in this repository.",
+                git_path.join("index.lock").display()
+            );
+        }
+        let pruned = self.prune(git_path);
+        lockfile.rollback()?;
+        pruned
+    }
+
+    fn prune(&self, git_path: &Path) -> Result<()> {
+        let object_store = git_path.join("objects");
+        let database = Database::new(object_store.clone());
+        let mut reachable = Reachable::new(&database);
+        for root in Reachable::roots(git_path, &database)? {
+            reachable.walk(&root)?;
+        }
+        let reachable = reachable
+            .objects
+            .into_iter()
+            .map(|object| object.oid)
+            .collect::<HashSet<_>>();
+
+        for oid in database.loose_oids() {
+            let object_path = object_store.join(&oid[0..2]).join(&oid[2..]);
+            if reachable.contains(&oid) || !self.expired(&object_path)? {
+                continue;
+            }
+            match self.dry_run {
+                true => {
+                    let object_type = Content::parse(&oid, object_store.clone())?
+                        .header
+                        .object_type;
+                    write_to_stdout(&format!("{} {}", oid, object_type.as_str()))?;
+                }
+                false => {
+                    fs::remove_file(&object_path)?;
+                    remove_empty_parents(&object_store, &object_path)?;
+                }
+            }
+        }
+
+        // what an interrupted write of an object or a pack left behind
+        for path in Prune::temporary_files(&object_store) {
+            if !self.expired(&path)? {
+                continue;
+            }
+            match self.dry_run {
+                true => {
+                    write_to_stdout(&format!("Removing stale temporary file {}", path.display()))?
+                }
+                false => {
+                    fs::remove_file(&path)?;
+                    remove_empty_parents(&object_store, &path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the file was last written at or before the expiry time
+    fn expired(&self, path: &Path) -> Result<bool> {
+        let expire = match self.expire {
+            Some(expire) => expire,
+            None => return Ok(false),
+        };
+        let modified = DateTime::<Utc>::from(fs::metadata(path)?.modified()?);
+        Ok(modified <= expire)
+    }
+
+    /// The `.tmp` files objects are written to before being renamed in place, and the
+    /// `tmp_` files packs are
+    fn temporary_files(object_store: &Path) -> Vec<PathBuf> {
+        let entries = |dir: &Path| match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .collect::<Vec<_>>(),
+            Err(_) => vec![],
+        };
+        let name = |path: &Path| path.file_name().unwrap().to_string_lossy().into_owned();
+
+        let mut paths = vec![];
+        for dir in entries(object_store) {
+            let dir_name = name(&dir);
+            if dir_name.len() == 2 && dir_name.chars().all(|c| c.is_ascii_hexdigit()) {
+                paths.extend(
+                    entries(&dir)
+                        .into_iter()
+                        .filter(|path| path.is_file() && name(path).ends_with(".tmp")),
+                );
+            }
+        }
+        paths.extend(
+            entries(&object_store.join("pack"))
+                .into_iter()
+                .filter(|path| path.is_file() && name(path).starts_with("tmp_")),
+        );
+        paths.sort();
+        paths
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::{
+    database::{Commit, Database, Header, ObjectType, Tag, Tree},
+    index::{FlatIndex, Index},
+    pending_commit::MergeType,
+    reflog::{Reflog, NULL_OID},
+    refs::Refs,
+    sequencer::Sequencer,
+};
+
+/// An object found by walking from the roots, along with the path it was first found at
+/// for trees and blobs
+#[derive(Debug, Clone)]
+pub struct ReachableObject {
+    pub oid: String,
+    pub object_type: ObjectType,
+    pub path: String,
+}
+
+/// Collects every object that can be reached from a set of starting points, commits and
+/// tags first, in the order they are found, then trees and blobs
+pub struct Reachable<'a> {
+    database: &'a Database,
+    pub objects: Vec<ReachableObject>,
+    seen: HashSet<String>,
+}
+
+impl<'a> Reachable<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        Self {
+            database,
+            objects: vec![],
+            seen: HashSet::new(),
+        }
+    }
+
+    /// The objects a repository still needs: what HEAD, the refs, the entries of the
+    /// reflogs and the index point to, along with the commits an operation in progress
+    /// will come back to. The stash is covered by `refs/stash` and its log, which holds
+    /// the older entries. The ones that are already missing are left out.
+    pub fn roots(git_path: &Path, database: &Database) -> Result<Vec<String>> {
+        let mut roots = vec![];
+        if let Some(head) = Refs::new(git_path.to_owned()).read_head() {
+            roots.push(head);
+        }
+
+        let merge_types = [MergeType::Merge, MergeType::CherryPick, MergeType::Revert];
+        let pseudo_refs = merge_types.map(|merge_type| merge_type.head_file());
+        for name in ["ORIG_HEAD"].into_iter().chain(pseudo_refs) {
+            if let Ok(content) = fs::read_to_string(git_path.join(name)) {
+                roots.push(content.trim().to_owned());
+            }
+        }
+
+        // where cherry-pick, revert or rebase started from and the commits left to apply,
+        // which the todo list may name with abbreviated oids
+        for sequencer in [
+            Sequencer::new(git_path.to_owned()),
+            Sequencer::rebase(git_path.to_owned()),
+        ] {
+            if !sequencer.in_progress() {
+                continue;
+            }
+            roots.extend(sequencer.read_state("head"));
+            roots.extend(sequencer.read_state("onto"));
+            roots.extend(sequencer.read_state("amend"));
+            for step in sequencer.todo()?.into_iter().chain(sequencer.done()?) {
+                match database.prefix_match_oids(&step.oid).as_slice() {
+                    [oid] if step.oid.len() >= 4 => roots.push(oid.clone()),
+                    _ => (),
+                }
+            }
+        }
+
+        let files = |dir: &Path| {
+            WalkDir::new(dir)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.into_path())
+                .collect::<Vec<_>>()
+        };
+        for path in files(&git_path.join("refs")) {
+            // symbolic refs are covered by the ref they point to
+            let content = fs::read_to_string(path)?;
+            roots.push(content.trim().to_owned());
+        }
+
+        let logs_path = git_path.join("logs");
+        for path in files(&logs_path) {
+            let name = path
+                .strip_prefix(&logs_path)?
+                .to_string_lossy()
+                .into_owned();
+            for entry in Reflog::new(git_path, &name).read()? {
+                roots.extend([entry.old_oid, entry.new_oid]);
+            }
+        }
+
+        let mut index = Index::new(git_path.join("index"));
+        index.load()?;
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&index.entries, &mut flat_index);
+        let staged = flat_index.entries.into_values();
+        let conflicted = index.conflicts.into_values().flatten();
+        roots.extend(staged.chain(conflicted).filter_map(|file| file.oid));
+
+        let mut seen = HashSet::new();
+        roots.retain(|oid| oid != NULL_OID && database.exists(oid) && seen.insert(oid.clone()));
+        Ok(roots)
+    }
+
+    /// Add `oid` and everything it points to, stopping at the objects already found
+    pub fn walk(&mut self, oid: &str) -> Result<()> {
+        let mut pending = vec![oid.to_owned()];
+        let mut trees = vec![];
+        while let Some(oid) = pending.pop() {
+            if self.seen.contains(&oid) {
+                continue;
+            }
+            let object_store = self.database.object_store.clone();
+            let object_type = match Header::parse(&oid, object_store.clone()) {
+                Ok(header) => header.object_type,
+                Err(_) => anyhow::bail!("fatal: missing object {}", oid),
+            };
+            match object_type {
+                ObjectType::Commit => {
+                    let commit = Commit::parse(oid.clone(), object_store);
+                    trees.push((commit.tree, String::new()));
+                    pending.extend(commit.parents.into_iter().rev());
+                }
+                ObjectType::Tag => pending.push(Tag::parse(oid.clone(), object_store).object),
+                ObjectType::Tree => {
+                    trees.push((oid, String::new()));
+                    continue;
+                }
+                _ => (),
+            }
+            self.add(&oid, object_type, "");
+        }
+
+        // the entries of each tree are found in order, which keeps the files of a
+        // directory together
+        trees.reverse();
+        while let Some((oid, path)) = trees.pop() {
+            if !self.add(&oid, ObjectType::Tree, &path) {
+                continue;
+            }
+            let entries = match Tree::read_entries(&oid, self.database.object_store.clone()) {
+                Ok(entries) => entries,
+                Err(_) => anyhow::bail!("fatal: missing tree {}", oid),
+            };
+            let mut subtrees = vec![];
+            for entry in entries {
+                let entry_path = match path.is_empty() {
+                    true => entry.name.clone(),
+                    false => format!("{}/{}", path, entry.name),
+                };
+                match entry.object_type() {
+                    "tree" => subtrees.push((entry.oid, entry_path)),
+                    "blob" => {
+                        // blobs are not read on the way, so make sure they are there before packing
+                        let added = self.add(&entry.oid, ObjectType::Blob, &entry_path);
+                        if added && !self.database.exists(&entry.oid) {
+                            anyhow::bail!("fatal: missing blob {}", entry.oid);
+                        }
+                    }
+                    // submodule commits live in another repository
+                    _ => (),
+                }
+            }
+            trees.extend(subtrees.into_iter().rev());
+        }
+        Ok(())
+    }
+
+    /// Record an object, returning whether it was new
+    fn add(&mut self, oid: &str, object_type: ObjectType, path: &str) -> bool {
+        if !self.seen.insert(oid.to_owned()) {
+            return false;
+        }
+        self.objects.push(ReachableObject {
+            oid: oid.to_owned(),
+            object_type,
+            path: path.to_owned(),
+        });
+        true
+    }
+}
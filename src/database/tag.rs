@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use crate::{database::storable::Storable, database::Author, database::Content};
+
+/// An annotated tag: a named pointer to another object, usually a commit, that carries its
+/// own tagger and message
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub oid: Option<String>,
+    pub object: String,
+    pub object_type: String,
+    pub name: String,
+    pub tagger: Author,
+    pub message: String,
+}
+
+impl Tag {
+    pub fn new(
+        object: String,
+        object_type: String,
+        name: String,
+        tagger: Author,
+        message: String,
+    ) -> Self {
+        Self {
+            oid: None,
+            object,
+            object_type,
+            name,
+            tagger,
+            message,
+        }
+    }
+
+    pub fn parse(oid: String, object_store: PathBuf) -> Self {
+        let content = Content::parse(&oid, object_store)
+            .expect("Failed to parse content")
+            .body;
+
+        let content = String::from_utf8(content).unwrap();
+        let mut lines = content.lines();
+
+        let mut object = String::new();
+        let mut object_type = String::new();
+        let mut name = String::new();
+        let mut tagger = None;
+
+        // the headers run until the first empty line, the message follows it
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "object" => object = value.to_owned(),
+                "type" => object_type = value.to_owned(),
+                "tag" => name = value.to_owned(),
+                "tagger" => tagger = Some(Author::parse(value)),
+                _ => (),
+            }
+        }
+        let message = lines.collect::<Vec<_>>().join("\n");
+
+        Self {
+            oid: Some(oid),
+            object,
+            object_type,
+            name,
+            tagger: tagger.expect("tag has no tagger"),
+            message,
+        }
+    }
+}
+
+impl Storable for Tag {
+    fn set_oid(&mut self, oid: String) {
+        self.oid = Some(oid);
+    }
+
+    fn blob_type(&self) -> String {
+        "tag".to_owned()
+    }
+
+    fn data(&self) -> String {
+        let lines = [
+            format!("object {}", self.object),
+            format!("type {}", self.object_type),
+            format!("tag {}", self.name),
+            format!("tagger {}", self.tagger),
+        ];
+        format!("{}\n\n{}\n", lines.join("\n"), self.message)
+    }
+}
@@ -9,7 +9,7 @@ use std::{
 
 use crate::{
     database::{storable::Storable, Content, Database, FileMode},
-    index::{FlatIndex, Index},
+    index::{FlatIndex, Index, Stat},
     utils::get_root_path,
     workspace::{Dir, File, FileOrDir},
 };
@@ -26,6 +26,24 @@ pub struct Tree {
     pub entries: BTreeMap<String, FileOrTree>,
 }
 
+/// One entry of a tree object as it is stored, without descending into subtrees
+#[derive(Debug, PartialEq, Clone)]
+pub struct TreeEntry {
+    pub mode: u32,
+    pub name: String,
+    pub oid: String,
+}
+
+impl TreeEntry {
+    pub fn object_type(&self) -> &'static str {
+        match self.mode {
+            0o040000 => "tree",
+            0o160000 => "commit",
+            _ => "blob",
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct FlatTree {
     pub entries: BTreeMap<String, File>,
@@ -47,6 +65,32 @@ impl Tree {
         Tree::_parse(content, None)
     }
 
+    /// Read the direct entries of the tree `oid`, in the order they are stored
+    pub fn read_entries(oid: &str, object_store: PathBuf) -> Result<Vec<TreeEntry>> {
+        let content = Content::parse(oid, object_store)?.body;
+        let mut cursor = Cursor::new(content);
+        let mut entries = vec![];
+
+        while !cursor.is_empty() {
+            let mut mode_and_name = Vec::new();
+            cursor.read_until(b'\0', &mut mode_and_name)?;
+            let mode_and_name = String::from_utf8(mode_and_name)?;
+            let (mode, name) = mode_and_name
+                .trim_end_matches('\0')
+                .split_once(' ')
+                .ok_or_else(|| anyhow::anyhow!("fatal: corrupt tree {}", oid))?;
+
+            let mut entry_oid = vec![0; 20];
+            cursor.read_exact(&mut entry_oid)?;
+            entries.push(TreeEntry {
+                mode: u32::from_str_radix(mode, 8)?,
+                name: name.to_owned(),
+                oid: hex::encode(entry_oid),
+            });
+        }
+        Ok(entries)
+    }
+
     fn _parse_name_and_mode(cursor: &mut Cursor<Vec<u8>>) -> (String, FileMode) {
         let mut mode_and_name: Vec<u8> = Vec::new();
         cursor.read_until(b'\0', &mut mode_and_name).unwrap();
@@ -96,14 +140,18 @@ impl Tree {
                     };
                     Index::flatten_entries(&index.entries, &mut flat_index);
 
+                    let mut stat: Stat = if flat_index.entries.contains_key(&path) {
+                        flat_index.entries.get(&path).unwrap().stat.clone()
+                    } else {
+                        Default::default()
+                    };
+                    // the mode recorded in the tree wins over whatever the index has
+                    stat.mode = mode.into();
+
                     let file = File {
                         name: name.to_owned(),
                         path: PathBuf::from(path.clone()),
-                        stat: if flat_index.entries.contains_key(&path) {
-                            flat_index.entries.get(&path).unwrap().stat.clone()
-                        } else {
-                            Default::default()
-                        },
+                        stat,
                         oid: Some(oid),
                     };
                     entries.insert(path, file);
@@ -171,9 +219,14 @@ impl Storable for Tree {
 
     fn data(&self) -> String {
         let mut hex_oids: Vec<Vec<u8>> = Vec::new();
+        // git sorts a subtree as if its name ended with a slash, so `a.txt` comes before `a`
         let mut entries = self
             .entries
             .iter()
+            .sorted_by_key(|(name, entry)| match entry {
+                FileOrTree::File(_) => name.to_string(),
+                FileOrTree::Tree(_) => format!("{}/", name),
+            })
             .map(|(name, entry)| match entry {
                 FileOrTree::File(entry) => {
                     let mut output: Vec<&[u8]> = Vec::new();
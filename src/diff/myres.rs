@@ -23,6 +23,12 @@ impl Myres {
     }
 
     pub fn diff(&self) -> Vec<Hunk> {
+        let edits = self.edits();
+        Hunk::filter(&edits)
+    }
+
+    /// Every line of both sides, in order, as kept, removed or added
+    pub fn edits(&self) -> Vec<Edit> {
         let a_lines: Vec<&str> = self.a.lines().collect();
         let b_lines: Vec<&str> = self.b.lines().collect();
 
@@ -38,15 +44,14 @@ impl Myres {
             .map(|(i, line)| Line::new((*line).to_owned(), i as i32 + 1))
             .collect::<Vec<_>>();
 
+        if a_lines.is_empty() && b_lines.is_empty() {
+            return vec![];
+        }
+
         let trace = diff(&a_lines, &b_lines);
         let ans = backtrack(trace.0, &a_lines, &b_lines);
 
-        let edits = render(&a_lines, &b_lines, ans);
-
-        let edits = edits.clone();
-
-        let hunks = Hunk::filter(&edits);
-        hunks
+        render(&a_lines, &b_lines, ans)
     }
 }
 
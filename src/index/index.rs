@@ -1,7 +1,7 @@
 use anyhow::Result;
 use std::collections::BTreeMap;
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{
     index::{Checksum, Stat},
@@ -12,6 +12,8 @@ use crate::{
 static HEADER_SIZE: usize = 12;
 static ENTRY_MIN_SIZE: usize = 64;
 static ENTRY_BLOCK_SIZE: usize = 8;
+static STAGE_MASK: u16 = 0x3000;
+static STAGE_SHIFT: u16 = 12;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct FlatIndex {
@@ -21,6 +23,8 @@ pub struct FlatIndex {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Index {
     pub entries: BTreeMap<String, FileOrDir>,
+    /// Unmerged paths, holding the base, ours and theirs entries (stages 1 to 3) that exist
+    pub conflicts: BTreeMap<String, Vec<MyFile>>,
     pub lockfile: Lockfile,
     pub changed: bool,
 }
@@ -31,6 +35,7 @@ impl Index {
         let entries = BTreeMap::new();
         Self {
             entries,
+            conflicts: BTreeMap::new(),
             lockfile,
             changed: false,
         }
@@ -78,12 +83,129 @@ impl Index {
     }
 
     pub fn remove(&mut self, file: &MyFile) {
-        self.entries.remove(file.path.to_str().unwrap());
+        let components =
+            FileOrDir::components(&file.path).expect("failed to get parent components");
+        Index::remove_entry(&mut self.entries, &components);
+        self.conflicts.remove(file.path.to_str().unwrap());
+        self.changed = true;
+    }
+
+    /// Remove the entry at `components` from the nested entries, pruning any directories
+    /// that become empty on the way back up.
+    fn remove_entry(entries: &mut BTreeMap<String, FileOrDir>, components: &[String]) {
+        if components.len() == 1 {
+            entries.remove(&components[0]);
+            return;
+        }
+
+        if let Some(FileOrDir::Dir(dir)) = entries.get_mut(&components[0]) {
+            Index::remove_entry(&mut dir.children, &components[1..]);
+            if dir.children.is_empty() {
+                entries.remove(&components[0]);
+            }
+        }
+    }
+
+    /// Move the entry at `from`, a file or a whole directory, to `to`. The files keep their
+    /// oid and stat data, so a file that was renamed in the workspace as well does not look
+    /// modified afterwards. Returns false if there is nothing at `from`, and fails if a
+    /// file is in the way of a directory leading to `to`.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<bool> {
+        let from_components =
+            FileOrDir::components(&PathBuf::from(from)).expect("failed to get parent components");
+        let mut entry = match Index::take_entry(&mut self.entries, &from_components) {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+
+        Index::relocate(&mut entry, Path::new(to));
+        let to_components =
+            FileOrDir::components(&PathBuf::from(to)).expect("failed to get parent components");
+        Index::insert_entry(&mut self.entries, &to_components, Path::new(""), entry)?;
         self.changed = true;
+        Ok(true)
+    }
+
+    /// Detach the entry at `components` from the nested entries, pruning any directories
+    /// that become empty
+    fn take_entry(
+        entries: &mut BTreeMap<String, FileOrDir>,
+        components: &[String],
+    ) -> Option<FileOrDir> {
+        if components.len() == 1 {
+            return entries.remove(&components[0]);
+        }
+
+        let FileOrDir::Dir(dir) = entries.get_mut(&components[0])? else {
+            return None;
+        };
+        let entry = Index::take_entry(&mut dir.children, &components[1..]);
+        if dir.children.is_empty() {
+            entries.remove(&components[0]);
+        }
+        entry
+    }
+
+    /// Rewrite the name and path of an entry, and of everything inside it, for its new place
+    fn relocate(entry: &mut FileOrDir, path: &Path) {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_owned();
+        match entry {
+            FileOrDir::File(file) => {
+                file.name = name;
+                file.path = path.to_path_buf();
+                // the low bits hold the length of the path, the ones above it the stage
+                file.stat.flags =
+                    (file.stat.flags & !0xfff) | path.to_str().unwrap().len().min(0xfff) as u16;
+            }
+            FileOrDir::Dir(dir) => {
+                dir.name = name;
+                dir.path = path.to_path_buf();
+                for (child_name, child) in dir.children.iter_mut() {
+                    Index::relocate(child, &path.join(child_name));
+                }
+            }
+        }
+    }
+
+    /// Attach an entry at `components`, creating the directories leading to it. `prefix`
+    /// is the path of the directory `entries` belongs to. A file where one of those
+    /// directories should be is an error rather than something to replace.
+    fn insert_entry(
+        entries: &mut BTreeMap<String, FileOrDir>,
+        components: &[String],
+        prefix: &Path,
+        entry: FileOrDir,
+    ) -> Result<()> {
+        if components.len() == 1 {
+            entries.insert(components[0].clone(), entry);
+            return Ok(());
+        }
+
+        let path = prefix.join(&components[0]);
+        let parent = entries.entry(components[0].clone()).or_insert_with(|| {
+            FileOrDir::Dir(Dir {
+                name: components[0].clone(),
+                path: path.clone(),
+                children: BTreeMap::new(),
+            })
+        });
+        match parent {
+            FileOrDir::Dir(dir) => {
+                Index::insert_entry(&mut dir.children, &components[1..], &path, entry)
+            }
+            FileOrDir::File(_) => {
+                anyhow::bail!("fatal: '{}' is a file in the index", path.display())
+            }
+        }
     }
 
     pub fn add(&mut self, file: &MyFile) {
         self.discard_conflicts(file);
+        self.conflicts.remove(file.path.to_str().unwrap());
         let parents =
             FileOrDir::parent_directories(&file.path).expect("failed to get parent directories");
         let path_components =
@@ -112,6 +234,36 @@ impl Index {
         self.changed = true;
     }
 
+    /// Record a conflict for `path`, replacing its regular entry with the base, ours and
+    /// theirs versions of the file. Missing versions are simply left out.
+    pub fn add_conflict_set(&mut self, path: &str, versions: [Option<MyFile>; 3]) {
+        let components =
+            FileOrDir::components(&PathBuf::from(path)).expect("failed to get parent components");
+        Index::remove_entry(&mut self.entries, &components);
+
+        let mut stages = vec![];
+        for (stage, version) in versions.into_iter().enumerate() {
+            if let Some(mut file) = version {
+                let stage = (stage + 1) as u16;
+                file.path = PathBuf::from(path);
+                file.stat.flags =
+                    (stage << STAGE_SHIFT) | (path.len().min(0xfff) as u16 & !STAGE_MASK);
+                stages.push(file);
+            }
+        }
+        self.conflicts.insert(path.to_owned(), stages);
+        self.changed = true;
+    }
+
+    pub fn is_conflicted(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    /// The stage of an entry: 0 for a merged entry, 1 to 3 for the sides of a conflict
+    pub fn stage(file: &MyFile) -> u16 {
+        (file.stat.flags & STAGE_MASK) >> STAGE_SHIFT
+    }
+
     pub fn from_flat_entries(&mut self, flat_index: &FlatIndex) {
         for (_, entry) in &flat_index.entries {
             self.add(entry);
@@ -159,12 +311,19 @@ impl Index {
         };
         Index::flatten_entries(&self.entries, &mut flat_index);
 
+        // conflicted paths are written as one entry per stage, sorted by path and stage
+        let mut all_entries = flat_index.entries.values().collect::<Vec<_>>();
+        all_entries.extend(self.conflicts.values().flatten());
+        all_entries.sort_by(|a, b| {
+            (a.path.to_str(), Index::stage(a)).cmp(&(b.path.to_str(), Index::stage(b)))
+        });
+
         // pad the number of entries to 4 bytes
-        let num_entries = flat_index.entries.len() as u32;
+        let num_entries = all_entries.len() as u32;
         let num_entries = num_entries.to_be_bytes().to_vec();
         writer.write(&num_entries)?;
 
-        for (_name, entry) in &flat_index.entries {
+        for entry in all_entries {
             let ctime = entry.stat.ctime;
             let ctime_nsec = entry.stat.ctime_nsec;
             let mtime = entry.stat.mtime;
@@ -211,8 +370,9 @@ impl Index {
         Ok(())
     }
 
-    fn clear(&mut self) {
+    pub fn clear(&mut self) {
         self.entries.clear();
+        self.conflicts.clear();
         self.changed = false;
     }
 
@@ -270,6 +430,13 @@ impl Index {
                 path: PathBuf::from(path),
                 oid: Some(oid.clone()),
             };
+            if Index::stage(&entry) > 0 {
+                self.conflicts
+                    .entry(entry.path.to_str().unwrap().to_owned())
+                    .or_default()
+                    .push(entry);
+                continue;
+            }
             flat_index
                 .entries
                 .insert(entry.path.to_str().unwrap().to_owned(), entry);
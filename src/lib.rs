@@ -4,6 +4,12 @@ pub mod database;
 pub mod diff;
 pub mod index;
 pub mod lockfile;
+pub mod merge;
+pub mod migration;
+pub mod pack;
+pub mod pending_commit;
+pub mod reflog;
 pub mod refs;
+pub mod sequencer;
 pub mod utils;
 pub mod workspace;
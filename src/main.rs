@@ -9,7 +9,13 @@ mod database;
 mod diff;
 mod index;
 mod lockfile;
+mod merge;
+mod migration;
+mod pack;
+mod pending_commit;
+mod reflog;
 mod refs;
+mod sequencer;
 mod utils;
 mod workspace;
 
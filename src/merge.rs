@@ -0,0 +1,8 @@
+mod bases;
+mod common_ancestors;
+mod diff3;
+mod resolve;
+
+pub use self::bases::Bases;
+pub use self::diff3::{Diff3, Labels};
+pub use self::resolve::{Inputs, Resolve};
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use std::collections::HashSet;
+
+use crate::database::Database;
+use crate::merge::common_ancestors::{CommonAncestors, Flag};
+
+/// Finds the best common ancestors of two commits: the common ancestors that are not
+/// themselves ancestors of another common ancestor.
+pub struct Bases<'a> {
+    database: &'a Database,
+    one: String,
+    two: String,
+    commits: Vec<String>,
+    redundant: HashSet<String>,
+}
+
+impl<'a> Bases<'a> {
+    pub fn new(database: &'a Database, one: &str, two: &str) -> Self {
+        Self {
+            database,
+            one: one.to_owned(),
+            two: two.to_owned(),
+            commits: vec![],
+            redundant: HashSet::new(),
+        }
+    }
+
+    pub fn find(&mut self) -> Result<Vec<String>> {
+        let mut common = CommonAncestors::new(self.database, &self.one, &[self.two.clone()])?;
+        self.commits = common.find()?;
+        if self.commits.len() <= 1 {
+            return Ok(self.commits.clone());
+        }
+
+        for oid in self.commits.clone() {
+            self.filter_commit(&oid)?;
+        }
+
+        Ok(self
+            .commits
+            .iter()
+            .filter(|oid| !self.redundant.contains(*oid))
+            .cloned()
+            .collect())
+    }
+
+    fn filter_commit(&mut self, oid: &str) -> Result<()> {
+        if self.redundant.contains(oid) {
+            return Ok(());
+        }
+
+        let others = self
+            .commits
+            .iter()
+            .filter(|other| *other != oid && !self.redundant.contains(*other))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut common = CommonAncestors::new(self.database, oid, &others)?;
+        common.find()?;
+
+        if common.is_marked(oid, Flag::Parent2) {
+            self.redundant.insert(oid.to_owned());
+        }
+        for other in others {
+            if common.is_marked(&other, Flag::Parent1) {
+                self.redundant.insert(other);
+            }
+        }
+        Ok(())
+    }
+}
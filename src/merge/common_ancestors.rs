@@ -0,0 +1,114 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+use crate::database::{Commit, Database};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Flag {
+    Parent1,
+    Parent2,
+    Result,
+    Stale,
+}
+
+/// Finds the common ancestors of one commit and a set of others by walking both histories
+/// at once, newest commits first. Every commit is flagged with the side(s) it was reached
+/// from; a commit reached from both sides is a result, and everything behind a result is
+/// marked stale so the walk can stop as soon as only stale commits are left.
+pub struct CommonAncestors<'a> {
+    database: &'a Database,
+    flags: HashMap<String, HashSet<Flag>>,
+    queue: Vec<Commit>,
+    results: Vec<Commit>,
+}
+
+impl<'a> CommonAncestors<'a> {
+    pub fn new(database: &'a Database, one: &str, twos: &[String]) -> Result<Self> {
+        let mut common = Self {
+            database,
+            flags: HashMap::new(),
+            queue: vec![],
+            results: vec![],
+        };
+
+        let commit = database.read_commit(one)?;
+        CommonAncestors::insert_by_date(&mut common.queue, commit);
+        common
+            .flags
+            .insert(one.to_owned(), HashSet::from([Flag::Parent1]));
+
+        for two in twos {
+            let commit = database.read_commit(two)?;
+            CommonAncestors::insert_by_date(&mut common.queue, commit);
+            common
+                .flags
+                .entry(two.to_owned())
+                .or_default()
+                .insert(Flag::Parent2);
+        }
+
+        Ok(common)
+    }
+
+    /// Walk the history and return the oids of the common ancestors, newest first
+    pub fn find(&mut self) -> Result<Vec<String>> {
+        while !self.all_stale() {
+            self.process_queue()?;
+        }
+
+        Ok(self
+            .results
+            .iter()
+            .filter_map(|commit| commit.oid.clone())
+            .filter(|oid| !self.is_marked(oid, Flag::Stale))
+            .collect())
+    }
+
+    pub fn is_marked(&self, oid: &str, flag: Flag) -> bool {
+        self.flags
+            .get(oid)
+            .map_or(false, |flags| flags.contains(&flag))
+    }
+
+    fn all_stale(&self) -> bool {
+        self.queue
+            .iter()
+            .all(|commit| self.is_marked(commit.oid.as_ref().expect("no OID found"), Flag::Stale))
+    }
+
+    fn process_queue(&mut self) -> Result<()> {
+        let commit = self.queue.remove(0);
+        let oid = commit.oid.clone().expect("no OID found");
+        let mut flags = self.flags.get(&oid).cloned().unwrap_or_default();
+
+        if flags == HashSet::from([Flag::Parent1, Flag::Parent2]) {
+            flags.insert(Flag::Result);
+            self.flags.entry(oid).or_default().insert(Flag::Result);
+            CommonAncestors::insert_by_date(&mut self.results, commit.clone());
+            flags.insert(Flag::Stale);
+        }
+        self.add_parents(&commit, &flags)
+    }
+
+    fn add_parents(&mut self, commit: &Commit, flags: &HashSet<Flag>) -> Result<()> {
+        for parent in &commit.parents {
+            let parent_flags = self.flags.entry(parent.clone()).or_default();
+            if flags.is_subset(parent_flags) {
+                continue;
+            }
+            parent_flags.extend(flags.iter().copied());
+
+            let parent_commit = self.database.read_commit(parent)?;
+            CommonAncestors::insert_by_date(&mut self.queue, parent_commit);
+        }
+        Ok(())
+    }
+
+    fn insert_by_date(list: &mut Vec<Commit>, commit: Commit) {
+        let index = list
+            .iter()
+            .position(|other| other.author.time < commit.author.time)
+            .unwrap_or(list.len());
+        list.insert(index, commit);
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use crate::diff::{EditType, Myres};
+
+/// A run of lines in the merged output: either taken as is, or a region where both sides
+/// changed the base differently
+#[derive(Debug, Clone, PartialEq)]
+pub enum Chunk {
+    Clean(Vec<String>),
+    Conflict {
+        o: Vec<String>,
+        a: Vec<String>,
+        b: Vec<String>,
+    },
+}
+
+/// Line based three-way merge of `a` and `b` against their common base `o`.
+///
+/// Both sides are diffed against the base to find the base lines each of them kept. The
+/// merge then walks the three files together: stretches where all three agree are copied,
+/// and every stretch in between is resolved by taking the side that changed it, or becomes
+/// a conflict when both did.
+pub struct Diff3 {
+    o: Vec<String>,
+    a: Vec<String>,
+    b: Vec<String>,
+    match_a: HashMap<usize, usize>,
+    match_b: HashMap<usize, usize>,
+    chunks: Vec<Chunk>,
+    line_o: usize,
+    line_a: usize,
+    line_b: usize,
+}
+
+/// The outcome of a [`Diff3`] merge
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeResult {
+    pub chunks: Vec<Chunk>,
+}
+
+/// Names printed after the conflict markers
+#[derive(Debug, Clone, Default)]
+pub struct Labels<'a> {
+    pub a: Option<&'a str>,
+    pub o: Option<&'a str>,
+    pub b: Option<&'a str>,
+}
+
+impl Diff3 {
+    pub fn merge(o: &str, a: &str, b: &str) -> MergeResult {
+        let o = Diff3::split_lines(o);
+        let a = Diff3::split_lines(a);
+        let b = Diff3::split_lines(b);
+        let match_a = Diff3::match_set(&o, &a);
+        let match_b = Diff3::match_set(&o, &b);
+
+        let mut diff3 = Diff3 {
+            o,
+            a,
+            b,
+            match_a,
+            match_b,
+            chunks: vec![],
+            line_o: 0,
+            line_a: 0,
+            line_b: 0,
+        };
+        diff3.generate_chunks();
+
+        MergeResult {
+            chunks: diff3.chunks,
+        }
+    }
+
+    /// Split into lines, keeping the line endings so the output reproduces them exactly
+    fn split_lines(content: &str) -> Vec<String> {
+        content.split_inclusive('\n').map(str::to_owned).collect()
+    }
+
+    /// Map every base line kept by `other` to its position in `other`
+    fn match_set(o: &[String], other: &[String]) -> HashMap<usize, usize> {
+        let myres = Myres::new(o.concat(), other.concat());
+        myres
+            .edits()
+            .into_iter()
+            .filter(|edit| edit.edit_type == EditType::Equal)
+            .filter_map(|edit| {
+                let o_line = edit.a_line?.line_number as usize - 1;
+                let other_line = edit.b_line?.line_number as usize - 1;
+                // the diff ignores line endings, a change to them still counts as an edit
+                (o[o_line] == other[other_line]).then_some((o_line, other_line))
+            })
+            .collect()
+    }
+
+    fn generate_chunks(&mut self) {
+        loop {
+            match self.find_next_mismatch() {
+                Some(0) => match self.find_next_match() {
+                    Some((o, a, b)) => self.emit_chunk(o, a, b),
+                    None => return self.emit_final_chunk(),
+                },
+                Some(i) => self.emit_chunk(self.line_o + i, self.line_a + i, self.line_b + i),
+                None => return self.emit_final_chunk(),
+            }
+        }
+    }
+
+    /// The offset from the current position of the first line where the three files stop
+    /// lining up
+    fn find_next_mismatch(&self) -> Option<usize> {
+        let mut i = 0;
+        while self.line_o + i < self.o.len()
+            || self.line_a + i < self.a.len()
+            || self.line_b + i < self.b.len()
+        {
+            let matched_a = self.match_a.get(&(self.line_o + i)) == Some(&(self.line_a + i));
+            let matched_b = self.match_b.get(&(self.line_o + i)) == Some(&(self.line_b + i));
+            if !matched_a || !matched_b {
+                return Some(i);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// The next base line kept by both sides, and where it ended up in each
+    fn find_next_match(&self) -> Option<(usize, usize, usize)> {
+        (self.line_o..self.o.len()).find_map(|o| {
+            let a = self.match_a.get(&o)?;
+            let b = self.match_b.get(&o)?;
+            Some((o, *a, *b))
+        })
+    }
+
+    fn emit_chunk(&mut self, o: usize, a: usize, b: usize) {
+        self.write_chunk(
+            self.o[self.line_o..o].to_vec(),
+            self.a[self.line_a..a].to_vec(),
+            self.b[self.line_b..b].to_vec(),
+        );
+        self.line_o = o;
+        self.line_a = a;
+        self.line_b = b;
+    }
+
+    fn emit_final_chunk(&mut self) {
+        self.write_chunk(
+            self.o[self.line_o..].to_vec(),
+            self.a[self.line_a..].to_vec(),
+            self.b[self.line_b..].to_vec(),
+        );
+    }
+
+    fn write_chunk(&mut self, o: Vec<String>, a: Vec<String>, b: Vec<String>) {
+        if a == o || a == b {
+            self.chunks.push(Chunk::Clean(b));
+        } else if b == o {
+            self.chunks.push(Chunk::Clean(a));
+        } else {
+            self.chunks.push(Chunk::Conflict { o, a, b });
+        }
+    }
+}
+
+impl MergeResult {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts() == 0
+    }
+
+    pub fn conflicts(&self) -> usize {
+        self.chunks
+            .iter()
+            .filter(|chunk| matches!(chunk, Chunk::Conflict { .. }))
+            .count()
+    }
+
+    /// Render the merged file. Conflicts show both sides between `<<<<<<<`, `=======` and
+    /// `>>>>>>>` markers; with `diff3` set the base is shown too, after `|||||||`.
+    pub fn render(&self, labels: &Labels, diff3: bool) -> String {
+        let mut content = String::new();
+        for chunk in &self.chunks {
+            match chunk {
+                Chunk::Clean(lines) => content.push_str(&lines.concat()),
+                Chunk::Conflict { o, a, b } => {
+                    content.push_str(&MergeResult::marker('<', labels.a));
+                    MergeResult::push_lines(&mut content, a);
+                    if diff3 {
+                        content.push_str(&MergeResult::marker('|', labels.o));
+                        MergeResult::push_lines(&mut content, o);
+                    }
+                    content.push_str(&MergeResult::marker('=', None));
+                    MergeResult::push_lines(&mut content, b);
+                    content.push_str(&MergeResult::marker('>', labels.b));
+                }
+            }
+        }
+        content
+    }
+
+    fn marker(symbol: char, label: Option<&str>) -> String {
+        let marker = symbol.to_string().repeat(7);
+        match label {
+            Some(label) => format!("{} {}\n", marker, label),
+            None => format!("{}\n", marker),
+        }
+    }
+
+    /// Lines inside a conflict always end in a newline so the next marker starts a line
+    fn push_lines(content: &mut String, lines: &[String]) {
+        for line in lines {
+            content.push_str(line);
+            if !line.ends_with('\n') {
+                content.push('\n');
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::{
+    database::{Blob, Content, Database, FlatTree},
+    index::{Index, Stat},
+    merge::{Diff3, Labels},
+    migration::{Change, Migration},
+    workspace::File,
+};
+
+/// The two sides of a merge and the commit they are merged relative to
+#[derive(Debug, Clone)]
+pub struct Inputs {
+    pub left_name: String,
+    pub right_name: String,
+    pub left_oid: String,
+    pub right_oid: String,
+    pub base_oid: Option<String>,
+}
+
+/// Merges the tree of `right` into the tree of `left`: changes made on only one side since
+/// the base are taken as they are, changes made on both sides are merged file by file.
+/// Paths that cannot be merged cleanly are written to the index as conflicts.
+pub struct Resolve<'a> {
+    root_path: PathBuf,
+    database: &'a Database,
+    index: &'a mut Index,
+    inputs: &'a Inputs,
+    /// The base, left and right versions of every conflicted path
+    pub conflicts: BTreeMap<String, [Option<File>; 3]>,
+    /// Progress and conflict messages, in the order they happened
+    pub messages: Vec<String>,
+}
+
+impl<'a> Resolve<'a> {
+    pub fn new(
+        root_path: PathBuf,
+        database: &'a Database,
+        index: &'a mut Index,
+        inputs: &'a Inputs,
+    ) -> Self {
+        Self {
+            root_path,
+            database,
+            index,
+            inputs,
+            conflicts: BTreeMap::new(),
+            messages: vec![],
+        }
+    }
+
+    /// Merge the trees and update the workspace and the index. If local changes would be
+    /// overwritten nothing is touched and the error messages are returned instead.
+    pub fn execute(&mut self) -> Result<Vec<String>> {
+        let empty_tree = FlatTree {
+            entries: Default::default(),
+        };
+        let base_tree = match &self.inputs.base_oid {
+            Some(oid) => self.database.read_commit_tree(oid)?,
+            None => empty_tree,
+        };
+        let left_tree = self.database.read_commit_tree(&self.inputs.left_oid)?;
+        let right_tree = self.database.read_commit_tree(&self.inputs.right_oid)?;
+
+        let merged_tree = self.merge_trees(&base_tree, &left_tree, &right_tree)?;
+
+        let mut migration = Migration::new(
+            self.root_path.clone(),
+            self.database,
+            &mut *self.index,
+            left_tree,
+            merged_tree,
+        );
+        let errors = migration.conflicts("merge");
+        if !errors.is_empty() {
+            return Ok(errors);
+        }
+        migration.apply_changes()?;
+
+        for (path, versions) in &self.conflicts {
+            self.index.add_conflict_set(path, versions.clone());
+        }
+        Ok(vec![])
+    }
+
+    fn merge_trees(
+        &mut self,
+        base_tree: &FlatTree,
+        left_tree: &FlatTree,
+        right_tree: &FlatTree,
+    ) -> Result<FlatTree> {
+        let left_diff = Migration::tree_diff(base_tree, left_tree);
+        let right_diff = Migration::tree_diff(base_tree, right_tree);
+        let mut merged_tree = left_tree.clone();
+
+        for (path, right_change) in &right_diff {
+            let right_entry = Resolve::change_entry(right_change);
+            let left_entry = match left_diff.get(path) {
+                Some(left_change) => Resolve::change_entry(left_change),
+                None => {
+                    // only the right side touched this path
+                    match right_entry {
+                        Some(entry) => merged_tree.entries.insert(path.clone(), entry.clone()),
+                        None => merged_tree.entries.remove(path),
+                    };
+                    continue;
+                }
+            };
+
+            if Resolve::same_entry(left_entry, right_entry) {
+                continue;
+            }
+            let base_entry = base_tree.entries.get(path);
+            self.merge_path(path, base_entry, left_entry, right_entry, &mut merged_tree)?;
+        }
+
+        Ok(merged_tree)
+    }
+
+    fn merge_path(
+        &mut self,
+        path: &str,
+        base: Option<&File>,
+        left: Option<&File>,
+        right: Option<&File>,
+        merged_tree: &mut FlatTree,
+    ) -> Result<()> {
+        let versions = [base.cloned(), left.cloned(), right.cloned()];
+
+        let (left, right) = match (left, right) {
+            (Some(left), Some(right)) => (left, right),
+            (Some(left), None) => {
+                self.messages.push(format!(
+                    "CONFLICT (modify/delete): {} deleted in {} and modified in {}. Version {} of {} left in tree.",
+                    path, self.inputs.right_name, self.inputs.left_name, self.inputs.left_name, path
+                ));
+                merged_tree.entries.insert(path.to_owned(), left.clone());
+                self.conflicts.insert(path.to_owned(), versions);
+                return Ok(());
+            }
+            (None, Some(right)) => {
+                self.messages.push(format!(
+                    "CONFLICT (modify/delete): {} deleted in {} and modified in {}. Version {} of {} left in tree.",
+                    path, self.inputs.left_name, self.inputs.right_name, self.inputs.right_name, path
+                ));
+                merged_tree.entries.insert(path.to_owned(), right.clone());
+                self.conflicts.insert(path.to_owned(), versions);
+                return Ok(());
+            }
+            (None, None) => return Ok(()),
+        };
+
+        self.messages.push(format!("Auto-merging {}", path));
+
+        let (clean_blob, oid) = self.merge_blobs(base, left, right)?;
+        let (clean_mode, mode) = Resolve::merge_modes(base, left, right);
+
+        let mut stat = Stat::default();
+        stat.mode = mode;
+        merged_tree.entries.insert(
+            path.to_owned(),
+            File {
+                name: left.name.clone(),
+                path: left.path.clone(),
+                stat,
+                oid: Some(oid),
+            },
+        );
+
+        if !clean_blob || !clean_mode {
+            let conflict_type = if base.is_some() { "content" } else { "add/add" };
+            self.messages.push(format!(
+                "CONFLICT ({}): Merge conflict in {}",
+                conflict_type, path
+            ));
+            self.conflicts.insert(path.to_owned(), versions);
+        }
+        Ok(())
+    }
+
+    /// Merge the contents of a file, returning whether the merge was clean and the oid of
+    /// the merged blob
+    fn merge_blobs(
+        &self,
+        base: Option<&File>,
+        left: &File,
+        right: &File,
+    ) -> Result<(bool, String)> {
+        let base_oid = base.and_then(|base| base.oid.clone());
+        let left_oid = left.oid.clone().expect("failed to get oid");
+        let right_oid = right.oid.clone().expect("failed to get oid");
+
+        if left_oid == right_oid || Some(&right_oid) == base_oid.as_ref() {
+            return Ok((true, left_oid));
+        }
+        if Some(&left_oid) == base_oid.as_ref() {
+            return Ok((true, right_oid));
+        }
+
+        let base_content = match &base_oid {
+            Some(oid) => self.read_blob(oid)?,
+            None => String::new(),
+        };
+        let left_content = self.read_blob(&left_oid)?;
+        let right_content = self.read_blob(&right_oid)?;
+        let result = Diff3::merge(&base_content, &left_content, &right_content);
+        let labels = Labels {
+            a: Some(&self.inputs.left_name),
+            o: None,
+            b: Some(&self.inputs.right_name),
+        };
+        let content = result.render(&labels, false);
+
+        let mut blob = Blob::new(content);
+        self.database.store(&mut blob)?;
+        Ok((result.is_clean(), blob.oid.expect("failed to get oid")))
+    }
+
+    fn merge_modes(base: Option<&File>, left: &File, right: &File) -> (bool, u32) {
+        let base_mode = base.map(|base| base.stat.mode);
+        if left.stat.mode == right.stat.mode || Some(right.stat.mode) == base_mode {
+            (true, left.stat.mode)
+        } else if Some(left.stat.mode) == base_mode {
+            (true, right.stat.mode)
+        } else {
+            (false, left.stat.mode)
+        }
+    }
+
+    fn read_blob(&self, oid: &str) -> Result<String> {
+        let content = Content::parse(oid, self.database.object_store.clone())?.body;
+        Ok(unsafe { String::from_utf8_unchecked(content) })
+    }
+
+    fn change_entry(change: &Change) -> Option<&File> {
+        match change {
+            Change::Create(file) | Change::Update(file) => Some(file),
+            Change::Delete(_) => None,
+        }
+    }
+
+    fn same_entry(left: Option<&File>, right: Option<&File>) -> bool {
+        match (left, right) {
+            (Some(left), Some(right)) => left.oid == right.oid && left.stat.mode == right.stat.mode,
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
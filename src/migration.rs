@@ -0,0 +1,278 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+use crate::{
+    database::{Blob, Content, Database, FileMode, FlatTree},
+    index::{FlatIndex, Index, Stat},
+    utils::remove_empty_parents,
+    workspace::{File, FileOrDir},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Create(File),
+    Update(File),
+    Delete(File),
+}
+
+/// Moves the workspace and the index from one tree to another, the way `checkout` does.
+/// Only the paths that differ between the two trees are touched, everything else is left
+/// alone so that unrelated local changes survive the migration.
+pub struct Migration<'a> {
+    root_path: PathBuf,
+    database: &'a Database,
+    index: &'a mut Index,
+    old_tree: FlatTree,
+    pub changes: BTreeMap<String, Change>,
+}
+
+impl<'a> Migration<'a> {
+    pub fn new(
+        root_path: PathBuf,
+        database: &'a Database,
+        index: &'a mut Index,
+        old_tree: FlatTree,
+        new_tree: FlatTree,
+    ) -> Self {
+        let changes = Migration::tree_diff(&old_tree, &new_tree);
+        Self {
+            root_path,
+            database,
+            index,
+            old_tree,
+            changes,
+        }
+    }
+
+    /// Compare two flattened trees and return the change needed for every path that differs
+    pub fn tree_diff(old_tree: &FlatTree, new_tree: &FlatTree) -> BTreeMap<String, Change> {
+        let mut changes = BTreeMap::new();
+        for (path, old_entry) in &old_tree.entries {
+            match new_tree.entries.get(path) {
+                Some(new_entry) => {
+                    if old_entry.oid != new_entry.oid || old_entry.stat.mode != new_entry.stat.mode
+                    {
+                        changes.insert(path.clone(), Change::Update(new_entry.clone()));
+                    }
+                }
+                None => {
+                    changes.insert(path.clone(), Change::Delete(old_entry.clone()));
+                }
+            }
+        }
+
+        for (path, new_entry) in &new_tree.entries {
+            if !old_tree.entries.contains_key(path) {
+                changes.insert(path.clone(), Change::Create(new_entry.clone()));
+            }
+        }
+        changes
+    }
+
+    /// Check that applying the changes would not throw away any local work.
+    /// Returns the error messages to show the user, empty if the migration is safe.
+    /// # Arguments
+    /// * `operation` - The command doing the migration, used in the messages
+    pub fn conflicts(&self, operation: &str) -> Vec<String> {
+        let mut flat_index = FlatIndex {
+            entries: Default::default(),
+        };
+        Index::flatten_entries(&self.index.entries, &mut flat_index);
+
+        let mut stale_files = vec![];
+        let mut untracked_files = vec![];
+
+        for (path, change) in &self.changes {
+            let old_entry = self.old_tree.entries.get(path);
+            let new_entry = match change {
+                Change::Create(file) | Change::Update(file) => Some(file),
+                Change::Delete(_) => None,
+            };
+            let index_entry = flat_index.entries.get(path);
+
+            // the index has staged changes that are in neither tree
+            let index_is_stale = match index_entry {
+                Some(entry) => {
+                    !Migration::same_entry(old_entry, entry)
+                        && !Migration::same_entry(new_entry, entry)
+                }
+                None => old_entry.is_some() && new_entry.is_some(),
+            };
+            if index_is_stale {
+                stale_files.push(path.clone());
+                continue;
+            }
+
+            let workspace_path = self.root_path.join(path);
+            if workspace_path.is_file() {
+                let workspace_entry = self.workspace_entry(path);
+                match index_entry {
+                    Some(entry) => {
+                        if !Migration::same_entry(Some(entry), &workspace_entry)
+                            && !Migration::same_entry(new_entry, &workspace_entry)
+                        {
+                            stale_files.push(path.clone());
+                        }
+                    }
+                    None => {
+                        if !Migration::same_entry(new_entry, &workspace_entry) {
+                            untracked_files.push(path.clone());
+                        }
+                    }
+                }
+            } else if workspace_path.is_dir()
+                && new_entry.is_some()
+                && self.holds_untracked_files(path, &flat_index)
+            {
+                untracked_files.push(path.clone());
+            }
+
+            // an untracked file sitting where the new entry needs a directory
+            if new_entry.is_some() {
+                let parents = FileOrDir::parent_directories(&PathBuf::from(path))
+                    .expect("failed to get parent directories");
+                for parent in parents.iter().take(parents.len() - 1) {
+                    if self.root_path.join(parent).is_file()
+                        && !flat_index.entries.contains_key(parent)
+                    {
+                        untracked_files.push(parent.clone());
+                    }
+                }
+            }
+        }
+
+        let hint = match operation {
+            "checkout" => "before you switch branches.".to_owned(),
+            _ => format!("before you {}.", operation),
+        };
+
+        let mut errors = vec![];
+        if !stale_files.is_empty() {
+            errors.push(format!(
+                "error: Your local changes to the following files would be overwritten by {}:\n\t{}\nPlease commit your changes or stash them {}",
+                operation,
+                stale_files.join("\n\t"),
+                hint
+            ));
+        }
+        if !untracked_files.is_empty() {
+            errors.push(format!(
+                "error: The following untracked working tree files would be overwritten by {}:\n\t{}\nPlease move or remove them {}",
+                operation,
+                untracked_files.join("\n\t"),
+                hint
+            ));
+        }
+        errors
+    }
+
+    /// Write the changes to the workspace and the index. Callers are expected to have
+    /// checked `conflicts` first and to write the index out afterwards.
+    pub fn apply_changes(&mut self) -> Result<()> {
+        let changes = self.changes.clone();
+
+        // remove files first, so that directories can take their place
+        for (path, change) in changes.iter().rev() {
+            if let Change::Delete(file) = change {
+                let workspace_path = self.root_path.join(path);
+                if workspace_path.is_file() {
+                    fs::remove_file(&workspace_path)?;
+                }
+                remove_empty_parents(&self.root_path, &workspace_path)?;
+                self.index.remove(file);
+            }
+        }
+
+        for (path, change) in &changes {
+            let file = match change {
+                Change::Create(file) | Change::Update(file) => file,
+                Change::Delete(_) => continue,
+            };
+            let workspace_path = self.root_path.join(path);
+            self.write_file(&workspace_path, file)?;
+
+            let entry = File {
+                name: file.name.clone(),
+                path: file.path.clone(),
+                stat: Stat::new(&workspace_path),
+                oid: file.oid.clone(),
+            };
+            self.index.add(&entry);
+        }
+        Ok(())
+    }
+
+    /// Write the blob of `file` to `workspace_path`, making room for it if a directory or a
+    /// file is in the way
+    pub fn write_file(&self, workspace_path: &PathBuf, file: &File) -> Result<()> {
+        if workspace_path.is_dir() {
+            fs::remove_dir_all(workspace_path)?;
+        }
+        let parents = FileOrDir::parent_directories(&file.path)?;
+        for parent in parents.iter().take(parents.len() - 1) {
+            let parent_path = self.root_path.join(parent);
+            if parent_path.is_file() {
+                fs::remove_file(&parent_path)?;
+            }
+        }
+        if let Some(parent) = workspace_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let oid = file.oid.as_ref().expect("failed to get oid");
+        let content = Content::parse(oid, self.database.object_store.clone())?.body;
+        fs::write(workspace_path, content)?;
+
+        let mode = match file.stat.mode {
+            mode if mode == u32::from(FileMode::Executable) => 0o755,
+            _ => 0o644,
+        };
+        fs::set_permissions(workspace_path, fs::Permissions::from_mode(mode))?;
+        Ok(())
+    }
+
+    /// Whether the directory at `path` holds files that are neither in the index nor in the
+    /// old tree. Tracked ones are removed along with the directory when a file replaces it.
+    fn holds_untracked_files(&self, path: &str, flat_index: &FlatIndex) -> bool {
+        WalkDir::new(self.root_path.join(path))
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !entry.file_type().is_dir())
+            .filter_map(|entry| {
+                let relative = entry.path().strip_prefix(&self.root_path).ok()?;
+                Some(relative.to_string_lossy().into_owned())
+            })
+            .any(|file| {
+                !flat_index.entries.contains_key(&file)
+                    && !self.old_tree.entries.contains_key(&file)
+            })
+    }
+
+    fn workspace_entry(&self, path: &str) -> File {
+        let workspace_path = self.root_path.join(path);
+        let data = fs::read(&workspace_path).expect("failed to read file");
+        let blob = Blob::new(unsafe { String::from_utf8_unchecked(data) });
+        File {
+            name: workspace_path
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned(),
+            path: PathBuf::from(path),
+            stat: Stat::new(&workspace_path),
+            oid: Some(self.database.hash_object(&blob)),
+        }
+    }
+
+    fn same_entry(entry: Option<&File>, other: &File) -> bool {
+        match entry {
+            Some(entry) => entry.oid == other.oid && entry.stat.mode == other.stat.mode,
+            None => false,
+        }
+    }
+}
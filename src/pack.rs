@@ -0,0 +1,45 @@
+mod compressor;
+mod delta;
+mod index;
+mod packs;
+mod reader;
+mod repack;
+mod writer;
+
+pub use self::packs::Packs;
+pub use self::repack::Repack;
+
+use crate::database::ObjectType;
+
+const PACK_SIGNATURE: &[u8] = b"PACK";
+const PACK_VERSION: u32 = 2;
+const IDX_SIGNATURE: &[u8] = b"\xfftOc";
+const IDX_VERSION: u32 = 2;
+
+// the type numbers of pack entries, from the 3 bits after the first one of their header
+const COMMIT: u8 = 1;
+const TREE: u8 = 2;
+const BLOB: u8 = 3;
+const TAG: u8 = 4;
+const OFS_DELTA: u8 = 6;
+const REF_DELTA: u8 = 7;
+
+fn object_type(type_number: u8) -> Option<ObjectType> {
+    match type_number {
+        COMMIT => Some(ObjectType::Commit),
+        TREE => Some(ObjectType::Tree),
+        BLOB => Some(ObjectType::Blob),
+        TAG => Some(ObjectType::Tag),
+        _ => None,
+    }
+}
+
+fn type_number(object_type: ObjectType) -> u8 {
+    match object_type {
+        ObjectType::Commit => COMMIT,
+        ObjectType::Tree => TREE,
+        ObjectType::Blob => BLOB,
+        ObjectType::Tag => TAG,
+        ObjectType::Unknown => 0,
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::Result;
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::thread;
+
+use super::delta::DeltaIndex;
+use super::type_number;
+use super::writer::{Delta, PackEntry};
+use crate::database::{Content, Header};
+
+/// A delta found for the entry at `target` against the one at `base`
+type Found = (usize, usize, Vec<u8>);
+
+/// Finds the entries of a pack that are best stored as deltas against others. The entries
+/// are sorted so that objects of the same type and path come together, largest first,
+/// and each one is compared against the `window` entries before it. The sorted list is
+/// cut into as many parts as there are threads, each searched on its own.
+pub struct Compressor {
+    pub window: usize,
+    pub depth: usize,
+    pub threads: usize,
+}
+
+impl Compressor {
+    /// Fill in the deltas of `entries`, returning how many were found
+    pub fn compress(&self, object_store: &Path, entries: &mut [PackEntry]) -> Result<usize> {
+        let sizes = self.sizes(object_store, entries)?;
+        let mut order = (0..entries.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&position| {
+            let entry = &entries[position];
+            (
+                type_number(entry.object_type),
+                name_hash(&entry.path),
+                Reverse(sizes[position]),
+                position,
+            )
+        });
+
+        let chunk_size = order.len().div_ceil(self.threads.max(1)).max(1);
+        let entries_ref = &*entries;
+        let found = thread::scope(|scope| {
+            let searches = order
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || self.search(object_store, entries_ref, chunk)))
+                .collect::<Vec<_>>();
+            searches
+                .into_iter()
+                .map(|search| search.join().unwrap())
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        let mut count = 0;
+        for (target, base, data) in found.into_iter().flatten() {
+            entries[target].delta = Some(Delta { base, data });
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// The size of each entry, read by all the threads together
+    fn sizes(&self, object_store: &Path, entries: &[PackEntry]) -> Result<Vec<usize>> {
+        let chunk_size = entries.len().div_ceil(self.threads.max(1)).max(1);
+        let sizes = thread::scope(|scope| {
+            let reads = entries
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|entry| {
+                                let header = Header::parse(&entry.oid, object_store.to_owned())?;
+                                Ok(header.object_size)
+                            })
+                            .collect::<Result<Vec<_>>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            reads
+                .into_iter()
+                .map(|read| read.join().unwrap())
+                .collect::<Result<Vec<_>>>()
+        })?;
+        Ok(sizes.into_iter().flatten().collect())
+    }
+
+    /// Slide the window over the entries of `order`, keeping for each one the smallest
+    /// delta against an entry of the same type in the window whose own chain is not too
+    /// long yet. A delta only counts when it takes less than half the object.
+    fn search(
+        &self,
+        object_store: &Path,
+        entries: &[PackEntry],
+        order: &[usize],
+    ) -> Result<Vec<Found>> {
+        let mut window = VecDeque::<(usize, DeltaIndex)>::new();
+        let mut depths = HashMap::<usize, usize>::new();
+        let mut found = vec![];
+
+        for &target in order {
+            let data = Content::parse(&entries[target].oid, object_store.to_owned())?.body;
+            let mut best: Option<(usize, Vec<u8>)> = None;
+            for (base, index) in window.iter().rev() {
+                let base_depth = depths.get(base).copied().unwrap_or(0);
+                if entries[*base].object_type != entries[target].object_type
+                    || base_depth >= self.depth
+                {
+                    continue;
+                }
+                let max_size = match &best {
+                    Some((_, delta)) => delta.len().saturating_sub(1),
+                    None => (data.len() / 2).saturating_sub(20),
+                };
+                // too different in size for a delta to be worth it
+                if max_size == 0 || data.len().saturating_sub(index.base.len()) >= max_size {
+                    continue;
+                }
+                if let Some(delta) = index.compress(&data, max_size) {
+                    best = Some((*base, delta));
+                }
+            }
+
+            if let Some((base, delta)) = best {
+                let depth = depths.get(&base).copied().unwrap_or(0) + 1;
+                depths.insert(target, depth);
+                found.push((target, base, delta));
+            }
+            window.push_back((target, DeltaIndex::new(data)));
+            if window.len() > self.window {
+                window.pop_front();
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// Git's hash of a path, made mostly of its last characters so that files with the same
+/// name or extension sort next to each other
+fn name_hash(path: &str) -> u32 {
+    path.bytes()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .fold(0, |hash, byte| {
+            (hash >> 2).wrapping_add((byte as u32) << 24)
+        })
+}
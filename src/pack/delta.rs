@@ -0,0 +1,246 @@
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// The size of the blocks of a base that a target is matched against
+const BLOCK_SIZE: usize = 16;
+
+/// How many places of a base to remember for each block; the blocks of repetitive data
+/// such as runs of zeros would otherwise make matching quadratic
+const BLOCK_LIMIT: usize = 64;
+
+/// The largest range one copy instruction can hold, longer matches take several
+const MAX_COPY: usize = 0x10000;
+
+/// The most bytes one insert instruction can hold
+const MAX_INSERT: usize = 0x7f;
+
+/// A base object with the offsets of its blocks, taken every `BLOCK_SIZE` bytes, so that
+/// the targets compared against it can find what to copy from it. Building it once lets
+/// the base be tried against every target in its window.
+pub struct DeltaIndex {
+    pub base: Vec<u8>,
+    blocks: HashMap<[u8; BLOCK_SIZE], Vec<usize>>,
+}
+
+impl DeltaIndex {
+    pub fn new(base: Vec<u8>) -> Self {
+        let mut blocks = HashMap::<_, Vec<_>>::new();
+        let last = base.len().saturating_sub(BLOCK_SIZE - 1);
+        for offset in (0..last).step_by(BLOCK_SIZE) {
+            let block = base[offset..offset + BLOCK_SIZE].try_into().unwrap();
+            let offsets = blocks.entry(block).or_default();
+            if offsets.len() < BLOCK_LIMIT {
+                offsets.push(offset);
+            }
+        }
+        Self { base, blocks }
+    }
+
+    /// The delta turning the base into `target`, unless it would take more than
+    /// `max_size` bytes. Wherever a block of the base starts in the target the match is
+    /// extended as far as it goes both ways, and the bytes between matches are inserted.
+    pub fn compress(&self, target: &[u8], max_size: usize) -> Option<Vec<u8>> {
+        let mut delta = vec![];
+        write_size(&mut delta, self.base.len());
+        write_size(&mut delta, target.len());
+
+        let mut insert = vec![];
+        let mut offset = 0;
+        while offset < target.len() {
+            let (mut start, mut size) = self.longest_match(target, offset);
+            if size == 0 {
+                insert.push(target[offset]);
+                offset += 1;
+                if insert.len() == MAX_INSERT {
+                    flush_insert(&mut delta, &mut insert);
+                }
+            } else {
+                while start > 0 && insert.last() == Some(&self.base[start - 1]) {
+                    insert.pop();
+                    start -= 1;
+                    offset -= 1;
+                    size += 1;
+                }
+                flush_insert(&mut delta, &mut insert);
+                write_copy(&mut delta, start, size);
+                offset += size;
+            }
+            if delta.len() + insert.len() > max_size {
+                return None;
+            }
+        }
+        flush_insert(&mut delta, &mut insert);
+
+        match delta.len() <= max_size {
+            true => Some(delta),
+            false => None,
+        }
+    }
+
+    /// Where in the base the longest run of `target` starting at `offset` is found
+    fn longest_match(&self, target: &[u8], offset: usize) -> (usize, usize) {
+        let offsets = match target
+            .get(offset..offset + BLOCK_SIZE)
+            .and_then(|block| self.blocks.get(block))
+        {
+            Some(offsets) => offsets,
+            None => return (0, 0),
+        };
+        offsets
+            .iter()
+            .map(|&start| {
+                let size = self.base[start..]
+                    .iter()
+                    .zip(&target[offset..])
+                    .take_while(|(base, target)| base == target)
+                    .count();
+                (start, size)
+            })
+            .max_by_key(|&(_, size)| size)
+            .unwrap_or((0, 0))
+    }
+}
+
+fn flush_insert(delta: &mut Vec<u8>, insert: &mut Vec<u8>) {
+    if insert.is_empty() {
+        return;
+    }
+    delta.push(insert.len() as u8);
+    delta.append(insert);
+}
+
+/// Copy instructions give the offset and the size of the range in as many bytes as they
+/// need, the size being left out altogether for `MAX_COPY`
+fn write_copy(delta: &mut Vec<u8>, start: usize, size: usize) {
+    let mut start = start;
+    let mut remaining = size;
+    while remaining > 0 {
+        let size = remaining.min(MAX_COPY);
+        let mut instruction = 0x80;
+        let mut bytes = vec![];
+        for n in 0..4 {
+            let byte = (start >> (8 * n)) as u8;
+            if byte != 0 {
+                instruction |= 1 << n;
+                bytes.push(byte);
+            }
+        }
+        for n in 0..3 {
+            let byte = ((size % MAX_COPY) >> (8 * n)) as u8;
+            if byte != 0 {
+                instruction |= 1 << (4 + n);
+                bytes.push(byte);
+            }
+        }
+        delta.push(instruction);
+        delta.extend(bytes);
+        start += size;
+        remaining -= size;
+    }
+}
+
+fn write_size(delta: &mut Vec<u8>, size: usize) {
+    let mut size = size;
+    while size >= 0x80 {
+        delta.push((size as u8 & 0x7f) | 0x80);
+        size >>= 7;
+    }
+    delta.push(size as u8);
+}
+
+/// Rebuild an object from the `base` it was deltified against. A delta starts with the
+/// sizes of the base and of the result, followed by instructions that either copy a range
+/// of the base or insert the bytes that follow them.
+pub fn apply(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut position = 0;
+    let base_size = read_size(delta, &mut position)?;
+    let target_size = read_size(delta, &mut position)?;
+    if base_size != base.len() {
+        anyhow::bail!(
+            "fatal: delta expects a base of {} bytes, got {}",
+            base_size,
+            base.len()
+        );
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+    while let Some(&instruction) = delta.get(position) {
+        position += 1;
+        if instruction & 0x80 != 0 {
+            // the low 4 bits say which bytes of the offset follow, the next 3 those of
+            // the size, least significant first
+            let offset = read_bits(delta, &mut position, instruction, 4)?;
+            let size = match read_bits(delta, &mut position, instruction >> 4, 3)? {
+                0 => 0x10000,
+                size => size,
+            };
+            match base.get(offset..offset + size) {
+                Some(bytes) => target.extend_from_slice(bytes),
+                None => anyhow::bail!("fatal: delta copies outside of its base"),
+            }
+        } else if instruction != 0 {
+            let size = instruction as usize;
+            match delta.get(position..position + size) {
+                Some(bytes) => target.extend_from_slice(bytes),
+                None => anyhow::bail!("fatal: delta is truncated"),
+            }
+            position += size;
+        } else {
+            anyhow::bail!("fatal: unexpected delta opcode 0");
+        }
+    }
+
+    if target.len() != target_size {
+        anyhow::bail!(
+            "fatal: delta produced {} bytes instead of {}",
+            target.len(),
+            target_size
+        );
+    }
+    Ok(target)
+}
+
+/// The size of the object a delta rebuilds, the second of the sizes it starts with
+pub fn target_size(delta: &[u8]) -> Result<usize> {
+    let mut position = 0;
+    read_size(delta, &mut position)?;
+    read_size(delta, &mut position)
+}
+
+/// A little-endian size in base 128, the top bit of each byte saying whether more follow
+fn read_size(delta: &[u8], position: &mut usize) -> Result<usize> {
+    let mut size = 0;
+    let mut shift = 0;
+    loop {
+        let byte = match delta.get(*position) {
+            Some(byte) => *byte,
+            None => anyhow::bail!("fatal: delta is truncated"),
+        };
+        *position += 1;
+        if shift >= usize::BITS {
+            anyhow::bail!("fatal: delta size does not fit in {} bits", usize::BITS);
+        }
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+/// Read the bytes of a number that are present according to the low `count` bits of
+/// `flags`, the missing ones being zero
+fn read_bits(delta: &[u8], position: &mut usize, flags: u8, count: usize) -> Result<usize> {
+    let mut value = 0;
+    for n in 0..count {
+        if flags & (1 << n) != 0 {
+            let byte = match delta.get(*position) {
+                Some(byte) => *byte,
+                None => anyhow::bail!("fatal: delta is truncated"),
+            };
+            *position += 1;
+            value |= (byte as usize) << (8 * n);
+        }
+    }
+    Ok(value)
+}
@@ -0,0 +1,127 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+use super::{IDX_SIGNATURE, IDX_VERSION};
+
+const HEADER_SIZE: usize = 8;
+const FANOUT_SIZE: usize = 256 * 4;
+
+/// A version 2 `.idx` file: the oids of the objects in a pack, sorted, with the offset of
+/// each one's entry in the `.pack` file
+#[derive(Debug)]
+pub struct PackIndex {
+    /// `fanout[b]` is the number of oids whose first byte is at most `b`
+    fanout: Vec<u32>,
+    oids: Vec<[u8; 20]>,
+    offsets: Vec<u64>,
+}
+
+impl PackIndex {
+    /// Read an index laid out as: the signature and version, the fanout table, the oids,
+    /// one CRC32 per object, 4-byte offsets and then 8-byte ones for the offsets that
+    /// don't fit in 31 bits, which the 4-byte ones point to with their top bit set
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path)?;
+        if data.len() < HEADER_SIZE + FANOUT_SIZE || &data[0..4] != IDX_SIGNATURE {
+            anyhow::bail!("fatal: index file {} is not a pack index", path.display());
+        }
+        let version = read_u32(&data, 4);
+        if version != IDX_VERSION {
+            anyhow::bail!(
+                "fatal: index file {} is version {} and is not supported",
+                path.display(),
+                version
+            );
+        }
+
+        let fanout = (0..256)
+            .map(|n| read_u32(&data, HEADER_SIZE + n * 4))
+            .collect::<Vec<_>>();
+        // every range of oids the fanout gives has to lie within the ones that follow it
+        if fanout.windows(2).any(|pair| pair[0] > pair[1]) {
+            anyhow::bail!("fatal: index file {} is corrupt", path.display());
+        }
+        let count = fanout[255] as usize;
+        let oids_start = HEADER_SIZE + FANOUT_SIZE;
+        let offsets_start = oids_start + count * 20 + count * 4;
+        let large_offsets_start = offsets_start + count * 4;
+        if data.len() < large_offsets_start + 40 {
+            anyhow::bail!("fatal: index file {} is too small", path.display());
+        }
+
+        let oids = (0..count)
+            .map(|n| {
+                let start = oids_start + n * 20;
+                data[start..start + 20].try_into().unwrap()
+            })
+            .collect();
+        let offsets = (0..count)
+            .map(|n| {
+                let offset = read_u32(&data, offsets_start + n * 4);
+                if offset & 0x8000_0000 == 0 {
+                    return Ok(offset as u64);
+                }
+                let start = large_offsets_start + (offset & 0x7fff_ffff) as usize * 8;
+                match data.get(start..start + 8) {
+                    Some(bytes) => Ok(u64::from_be_bytes(bytes.try_into().unwrap())),
+                    None => anyhow::bail!("fatal: index file {} is corrupt", path.display()),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            fanout,
+            oids,
+            offsets,
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.oids.len()
+    }
+
+    /// The offset in the pack of the entry for `oid`, binary searching among the oids
+    /// sharing its first byte
+    pub fn offset(&self, oid: &str) -> Option<u64> {
+        let mut wanted = [0; 20];
+        hex::decode_to_slice(oid, &mut wanted).ok()?;
+        let (start, end) = self.range(wanted[0]);
+        self.oids[start..end]
+            .binary_search(&wanted)
+            .ok()
+            .map(|position| self.offsets[start + position])
+    }
+
+    /// The oids of all the objects in the pack, sorted
+    pub fn oids(&self) -> Vec<String> {
+        self.oids.iter().map(hex::encode).collect()
+    }
+
+    /// The oids of the objects in the pack starting with the hex `prefix`, sorted
+    pub fn prefix_match_oids(&self, prefix: &str) -> Vec<String> {
+        let first_byte = match prefix.get(0..2).map(|byte| u8::from_str_radix(byte, 16)) {
+            Some(Ok(first_byte)) => first_byte,
+            _ => return vec![],
+        };
+        let (start, end) = self.range(first_byte);
+        self.oids[start..end]
+            .iter()
+            .map(hex::encode)
+            .filter(|oid| oid.starts_with(prefix))
+            .collect()
+    }
+
+    /// The positions of the oids starting with `first_byte`
+    fn range(&self, first_byte: u8) -> (usize, usize) {
+        let start = match first_byte {
+            0 => 0,
+            _ => self.fanout[first_byte as usize - 1] as usize,
+        };
+        (start, self.fanout[first_byte as usize] as usize)
+    }
+}
+
+fn read_u32(data: &[u8], start: usize) -> u32 {
+    u32::from_be_bytes(data[start..start + 4].try_into().unwrap())
+}
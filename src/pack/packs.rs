@@ -0,0 +1,194 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
+
+use super::reader::Pack;
+use crate::database::{Content, Header, ObjectType};
+
+static OPENED: OnceLock<Mutex<HashMap<PathBuf, Arc<Packs>>>> = OnceLock::new();
+
+/// How close to a scan the pack directory may have been changed for its mtime to be
+/// trusted: a change in the same tick as the scan would not move the mtime again
+const RACY_MTIME: Duration = Duration::from_secs(1);
+
+/// The packs under `objects/pack`. They are opened once per process and looked for again
+/// when an object can't be found and the directory changed, as another process may have
+/// repacked in the meantime.
+pub struct Packs {
+    object_store: PathBuf,
+    pack_dir: PathBuf,
+    packs: RwLock<Vec<Arc<Pack>>>,
+    /// The mtime of the pack directory when it was last scanned, if it can be trusted
+    scanned: Mutex<Option<SystemTime>>,
+}
+
+impl Packs {
+    /// The packs of `object_store`, shared by everything reading from it
+    pub fn of(object_store: &Path) -> Arc<Packs> {
+        let mut opened = OPENED.get_or_init(Default::default).lock().unwrap();
+        opened
+            .entry(object_store.to_owned())
+            .or_insert_with(|| {
+                let packs = Packs {
+                    object_store: object_store.to_owned(),
+                    pack_dir: object_store.join("pack"),
+                    packs: Default::default(),
+                    scanned: Default::default(),
+                };
+                packs.rescan();
+                Arc::new(packs)
+            })
+            .clone()
+    }
+
+    /// The type and content of `oid`, if it is in one of the packs
+    pub fn read(&self, oid: &str) -> Result<Option<(ObjectType, Arc<Vec<u8>>)>> {
+        self.read_object(oid, 0)
+    }
+
+    /// The type and size of `oid`, if it is in one of the packs
+    pub fn header(&self, oid: &str) -> Result<Option<(ObjectType, usize)>> {
+        self.read_header(oid, 0)
+    }
+
+    /// The base of a delta whose pack doesn't hold it, which may be loose or in another
+    /// pack. `depth` deltas have been followed to get here.
+    pub(super) fn read_base(
+        &self,
+        oid: &str,
+        depth: usize,
+    ) -> Result<Option<(ObjectType, Arc<Vec<u8>>)>> {
+        match self.is_loose(oid) {
+            true => {
+                let content = Content::parse(oid, self.object_store.clone())?;
+                Ok(Some((content.header.object_type, Arc::new(content.body))))
+            }
+            false => self.read_object(oid, depth),
+        }
+    }
+
+    /// The type and size of the base of a delta whose pack doesn't hold it
+    pub(super) fn base_header(
+        &self,
+        oid: &str,
+        depth: usize,
+    ) -> Result<Option<(ObjectType, usize)>> {
+        match self.is_loose(oid) {
+            true => {
+                let header = Header::parse(oid, self.object_store.clone())?;
+                Ok(Some((header.object_type, header.object_size)))
+            }
+            false => self.read_header(oid, depth),
+        }
+    }
+
+    fn read_object(&self, oid: &str, depth: usize) -> Result<Option<(ObjectType, Arc<Vec<u8>>)>> {
+        match self.find(oid) {
+            Some(pack) => pack.read(oid, depth, self),
+            None => Ok(None),
+        }
+    }
+
+    fn read_header(&self, oid: &str, depth: usize) -> Result<Option<(ObjectType, usize)>> {
+        match self.find(oid) {
+            Some(pack) => pack.header(oid, depth, self),
+            None => Ok(None),
+        }
+    }
+
+    fn is_loose(&self, oid: &str) -> bool {
+        oid.len() > 2 && self.object_store.join(&oid[0..2]).join(&oid[2..]).is_file()
+    }
+
+    pub fn contains(&self, oid: &str) -> bool {
+        self.find(oid).is_some()
+    }
+
+    /// The oids of the packed objects starting with `prefix`, sorted
+    pub fn prefix_match_oids(&self, prefix: &str) -> Vec<String> {
+        let mut oids = self
+            .packs
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|pack| pack.prefix_match_oids(prefix))
+            .collect::<Vec<_>>();
+        oids.sort();
+        oids.dedup();
+        oids
+    }
+
+    fn find(&self, oid: &str) -> Option<Arc<Pack>> {
+        let find = || {
+            let packs = self.packs.read().unwrap();
+            packs.iter().find(|pack| pack.contains(oid)).cloned()
+        };
+        match find() {
+            Some(pack) => Some(pack),
+            None if self.refresh() => find(),
+            None => None,
+        }
+    }
+
+    /// Rescan the pack directory, unless its mtime says nothing was added or removed since
+    /// the last scan
+    fn refresh(&self) -> bool {
+        let modified = fs::metadata(&self.pack_dir).and_then(|metadata| metadata.modified());
+        let scanned = *self.scanned.lock().unwrap();
+        match (modified, scanned) {
+            (Ok(modified), Some(scanned)) if modified == scanned => false,
+            _ => self.rescan(),
+        }
+    }
+
+    /// Pick up the packs written and forget the ones removed since the last look, returning
+    /// whether there was any change. A pack without its `.idx` yet is still being written,
+    /// and one that can't be opened is left for the next look.
+    pub fn rescan(&self) -> bool {
+        // taken before reading the directory, so that a change while reading it is seen by
+        // the next look
+        let modified = fs::metadata(&self.pack_dir)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .filter(|modified| {
+                SystemTime::now()
+                    .duration_since(*modified)
+                    .is_ok_and(|age| age > RACY_MTIME)
+            });
+        *self.scanned.lock().unwrap() = modified;
+
+        let mut paths = match fs::read_dir(&self.pack_dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == "pack")
+                })
+                .filter(|path| path.with_extension("idx").is_file())
+                .collect::<Vec<_>>(),
+            Err(_) => vec![],
+        };
+        paths.sort();
+
+        let mut packs = self.packs.write().unwrap();
+        let current = packs
+            .iter()
+            .map(|pack| pack.path.clone())
+            .collect::<Vec<_>>();
+        if current == paths {
+            return false;
+        }
+        *packs = paths
+            .iter()
+            .filter_map(|path| match packs.iter().find(|pack| &pack.path == path) {
+                Some(pack) => Some(pack.clone()),
+                None => Pack::open(path).ok().map(Arc::new),
+            })
+            .collect();
+        true
+    }
+}
@@ -0,0 +1,307 @@
+use anyhow::Result;
+use flate2::read::ZlibDecoder;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{prelude::*, BufReader, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::index::PackIndex;
+use super::{delta, object_type, Packs, OFS_DELTA, PACK_SIGNATURE, REF_DELTA};
+use crate::database::ObjectType;
+
+/// How many bytes of resolved delta bases to keep around before starting over
+const CACHE_LIMIT: usize = 64 * 1024 * 1024;
+
+/// How many deltas a chain may go through before it is taken for a corrupt one looping
+/// back on itself. git writes chains of at most 4095.
+const MAX_DELTA_DEPTH: usize = 4096;
+
+/// Enough of the inflated data of a delta to hold the two sizes it starts with
+const DELTA_SIZES_LENGTH: u64 = 20;
+
+/// What an entry in the pack holds: either a whole object or a delta against a base found
+/// at an earlier offset or by its oid
+enum Entry {
+    Object(ObjectType),
+    OfsDelta(u64),
+    RefDelta(String),
+}
+
+/// The objects resolved so far, by their offset in the pack. Delta chains usually share
+/// their bases, so walking history would otherwise inflate the same entries over and over.
+#[derive(Default)]
+struct Cache {
+    objects: HashMap<u64, (ObjectType, Arc<Vec<u8>>)>,
+    size: usize,
+}
+
+/// A `.pack` file along with its `.idx`
+pub struct Pack {
+    pub path: PathBuf,
+    index: PackIndex,
+    file: Mutex<File>,
+    cache: Mutex<Cache>,
+}
+
+impl Pack {
+    /// Open `path`, a `.pack` file, whose index is the `.idx` file next to it
+    pub fn open(path: &Path) -> Result<Self> {
+        let index = PackIndex::load(&path.with_extension("idx"))?;
+        let mut file = File::open(path)?;
+        let mut header = [0; 12];
+        file.read_exact(&mut header)?;
+        let version = u32::from_be_bytes(header[4..8].try_into().unwrap());
+        let count = u32::from_be_bytes(header[8..12].try_into().unwrap()) as usize;
+        if &header[0..4] != PACK_SIGNATURE || !(version == 2 || version == 3) {
+            anyhow::bail!("fatal: {} is not a version 2 pack", path.display());
+        }
+        if count != index.count() {
+            anyhow::bail!(
+                "fatal: packfile {} does not match its index",
+                path.display()
+            );
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+            index,
+            file: Mutex::new(file),
+            cache: Default::default(),
+        })
+    }
+
+    pub fn contains(&self, oid: &str) -> bool {
+        self.index.offset(oid).is_some()
+    }
+
+    pub fn oids(&self) -> Vec<String> {
+        self.index.oids()
+    }
+
+    pub fn prefix_match_oids(&self, prefix: &str) -> Vec<String> {
+        self.index.prefix_match_oids(prefix)
+    }
+
+    /// The type and content of `oid`, if it is in this pack. `depth` deltas have already
+    /// been followed to get here, and a base outside the pack is read through `packs`.
+    pub fn read(
+        &self,
+        oid: &str,
+        depth: usize,
+        packs: &Packs,
+    ) -> Result<Option<(ObjectType, Arc<Vec<u8>>)>> {
+        match self.index.offset(oid) {
+            Some(offset) => Ok(Some(self.read_at(offset, depth, packs)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The type and size of `oid`, if it is in this pack, without inflating more of it
+    /// than the sizes a delta starts with. A delta takes the type of its base, so its
+    /// chain is still followed down to a whole object.
+    pub fn header(
+        &self,
+        oid: &str,
+        depth: usize,
+        packs: &Packs,
+    ) -> Result<Option<(ObjectType, usize)>> {
+        let mut offset = match self.index.offset(oid) {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        let (entry, mut size, data) = self.read_entry(offset, Some(DELTA_SIZES_LENGTH))?;
+        if !matches!(entry, Entry::Object(_)) {
+            size = delta::target_size(&data)?;
+        }
+
+        let mut entry = entry;
+        for depth in depth.. {
+            self.check_depth(depth, offset)?;
+            if let Some((object_type, _)) = self.cache.lock().unwrap().objects.get(&offset) {
+                return Ok(Some((*object_type, size)));
+            }
+            offset = match entry {
+                Entry::Object(object_type) => return Ok(Some((object_type, size))),
+                Entry::OfsDelta(base_offset) => base_offset,
+                Entry::RefDelta(base) => match self.index.offset(&base) {
+                    Some(base_offset) => base_offset,
+                    None => {
+                        let object_type = match packs.base_header(&base, depth + 1)? {
+                            Some((object_type, _)) => object_type,
+                            None => return Err(self.missing_base(&base)),
+                        };
+                        return Ok(Some((object_type, size)));
+                    }
+                },
+            };
+            entry = self.read_entry(offset, Some(0))?.0;
+        }
+        unreachable!("the depth is checked on every step")
+    }
+
+    /// Resolve the entry at `offset`: follow its chain of deltas down to a whole object,
+    /// or to a base already in the cache or outside the pack, then apply the deltas back
+    /// up from there
+    fn read_at(
+        &self,
+        offset: u64,
+        depth: usize,
+        packs: &Packs,
+    ) -> Result<(ObjectType, Arc<Vec<u8>>)> {
+        let mut deltas = vec![];
+        let mut offset = offset;
+        let (base_offset, object_type, mut data) = loop {
+            self.check_depth(depth + deltas.len(), offset)?;
+            if let Some((object_type, data)) = self.cache.lock().unwrap().objects.get(&offset) {
+                break (Some(offset), *object_type, data.clone());
+            }
+            match self.read_entry(offset, None)? {
+                (Entry::Object(object_type), _, data) => {
+                    break (Some(offset), object_type, Arc::new(data))
+                }
+                (Entry::OfsDelta(base_offset), _, delta) => {
+                    deltas.push((offset, delta));
+                    offset = base_offset;
+                }
+                (Entry::RefDelta(base), _, delta) => {
+                    deltas.push((offset, delta));
+                    match self.index.offset(&base) {
+                        Some(base_offset) => offset = base_offset,
+                        None => match packs.read_base(&base, depth + deltas.len())? {
+                            Some((object_type, data)) => break (None, object_type, data),
+                            None => return Err(self.missing_base(&base)),
+                        },
+                    }
+                }
+            }
+        };
+        if let Some(offset) = base_offset {
+            self.remember(offset, object_type, &data);
+        }
+
+        for (offset, delta) in deltas.into_iter().rev() {
+            data = Arc::new(delta::apply(&data, &delta)?);
+            self.remember(offset, object_type, &data);
+        }
+        Ok((object_type, data))
+    }
+
+    fn check_depth(&self, depth: usize, offset: u64) -> Result<()> {
+        if depth > MAX_DELTA_DEPTH {
+            anyhow::bail!(
+                "fatal: delta chain at offset {} in {} is more than {} deltas deep",
+                offset,
+                self.path.display(),
+                MAX_DELTA_DEPTH
+            );
+        }
+        Ok(())
+    }
+
+    fn missing_base(&self, base: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "fatal: base object {} of a delta in {} is missing",
+            base,
+            self.path.display()
+        )
+    }
+
+    fn remember(&self, offset: u64, object_type: ObjectType, data: &Arc<Vec<u8>>) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.size + data.len() > CACHE_LIMIT {
+            *cache = Cache::default();
+        }
+        if data.len() <= CACHE_LIMIT {
+            cache.size += data.len();
+            cache.objects.insert(offset, (object_type, data.clone()));
+        }
+    }
+
+    /// Read the entry at `offset`, with its inflated size and data, or only the first
+    /// `limit` bytes of the data. Its header holds the type in bits 4 to 6 of the first
+    /// byte and the inflated size in base 128, starting with the low 4 bits of that byte.
+    /// Deltas then give their base, and the zlib stream of the data follows.
+    fn read_entry(&self, offset: u64, limit: Option<u64>) -> Result<(Entry, usize, Vec<u8>)> {
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = BufReader::new(&mut *file);
+
+        let mut byte = read_byte(&mut reader)?;
+        let type_number = (byte >> 4) & 0x7;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            byte = read_byte(&mut reader)?;
+            if shift >= usize::BITS {
+                anyhow::bail!(
+                    "fatal: size of packed object at offset {} in {} does not fit in {} bits",
+                    offset,
+                    self.path.display(),
+                    usize::BITS
+                );
+            }
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+
+        let entry = match type_number {
+            OFS_DELTA => {
+                // a big-endian distance back to the base, in base 128 with each
+                // continuation adding one so that there is a single way to write a number
+                let mut byte = read_byte(&mut reader)?;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    byte = read_byte(&mut reader)?;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+                }
+                match offset.checked_sub(distance) {
+                    Some(base_offset) if distance > 0 => Entry::OfsDelta(base_offset),
+                    _ => anyhow::bail!("fatal: delta base offset is out of bounds"),
+                }
+            }
+            REF_DELTA => {
+                let mut base = [0; 20];
+                reader.read_exact(&mut base)?;
+                Entry::RefDelta(hex::encode(base))
+            }
+            _ => match object_type(type_number) {
+                Some(object_type) => Entry::Object(object_type),
+                None => anyhow::bail!(
+                    "fatal: unknown object type {} at offset {} in {}",
+                    type_number,
+                    offset,
+                    self.path.display()
+                ),
+            },
+        };
+
+        let mut data = vec![];
+        match limit {
+            Some(limit) => {
+                ZlibDecoder::new(reader)
+                    .take(limit)
+                    .read_to_end(&mut data)?;
+            }
+            None => {
+                data.reserve(size);
+                ZlibDecoder::new(reader).read_to_end(&mut data)?;
+                if data.len() != size {
+                    anyhow::bail!(
+                        "fatal: packed object at offset {} in {} is corrupt",
+                        offset,
+                        self.path.display()
+                    );
+                }
+            }
+        }
+
+        Ok((entry, size, data))
+    }
+}
+
+fn read_byte(reader: &mut impl Read) -> Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
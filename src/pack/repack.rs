@@ -0,0 +1,181 @@
+use anyhow::Result;
+use flate2::{write::ZlibEncoder, Compression};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::compressor::Compressor;
+use super::reader::Pack;
+use super::writer::{PackEntry, PackWriter};
+use super::Packs;
+use crate::{
+    database::{Database, Reachable},
+    utils::{remove_empty_parents, write_to_stderr, write_to_stdout},
+};
+
+/// Packs the objects reachable from the refs, the reflogs and the index. By default only
+/// the loose ones go into a new pack; with `all` every reachable object does, so that the
+/// new pack replaces all the existing ones.
+pub struct Repack {
+    pub all: bool,
+    /// Remove the packs replaced by the new one, which drops the unreachable objects
+    /// they held, and the loose objects that are now packed
+    pub delete: bool,
+    /// Write the unreachable objects of the removed packs out as loose objects dated like
+    /// their pack instead of dropping them, leaving it to prune to decide when they go
+    pub unpack_unreachable: bool,
+    pub window: usize,
+    pub depth: usize,
+    pub threads: usize,
+    pub quiet: bool,
+}
+
+impl Repack {
+    pub fn run(&self, git_path: &Path) -> Result<()> {
+        let object_store = git_path.join("objects");
+        let database = Database::new(object_store.clone());
+        let old_packs = Repack::packs(&object_store);
+
+        let mut reachable = Reachable::new(&database);
+        for root in Reachable::roots(git_path, &database)? {
+            reachable.walk(&root)?;
+        }
+        let loose = database.loose_oids().into_iter().collect::<HashSet<_>>();
+        let reachable_oids = reachable
+            .objects
+            .iter()
+            .map(|object| object.oid.clone())
+            .collect::<HashSet<_>>();
+        let mut entries = reachable
+            .objects
+            .into_iter()
+            .filter(|object| self.all || loose.contains(&object.oid))
+            .map(|object| PackEntry {
+                oid: object.oid,
+                object_type: object.object_type,
+                path: object.path,
+                delta: None,
+            })
+            .collect::<Vec<_>>();
+        self.progress(&format!("Enumerating objects: {}, done.", entries.len()))?;
+
+        let new_pack = match entries.is_empty() {
+            true => {
+                if !self.quiet {
+                    write_to_stdout("Nothing new to pack.")?;
+                }
+                None
+            }
+            false => {
+                self.progress(&format!(
+                    "Delta compression using up to {} threads",
+                    self.threads
+                ))?;
+                let compressor = Compressor {
+                    window: self.window,
+                    depth: self.depth,
+                    threads: self.threads,
+                };
+                let deltas = compressor.compress(&object_store, &mut entries)?;
+                let pack_path = PackWriter::new(object_store.clone()).write(&entries)?;
+                self.progress(&format!("Total {} (delta {})", entries.len(), deltas))?;
+                Some(pack_path)
+            }
+        };
+
+        if self.delete {
+            if self.all {
+                for pack_path in old_packs {
+                    if Some(&pack_path) == new_pack.as_ref() {
+                        continue;
+                    }
+                    if self.unpack_unreachable {
+                        Repack::loosen_unreachable(&object_store, &pack_path, &reachable_oids)?;
+                    }
+                    Repack::remove_pack(&pack_path)?;
+                }
+            }
+            let packs = Packs::of(&object_store);
+            packs.rescan();
+            for oid in loose.iter().filter(|oid| packs.contains(oid)) {
+                let object_path = object_store.join(&oid[0..2]).join(&oid[2..]);
+                fs::remove_file(&object_path)?;
+                remove_empty_parents(&object_store, &object_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The `.pack` files under `objects/pack`
+    fn packs(object_store: &Path) -> Vec<PathBuf> {
+        match fs::read_dir(object_store.join("pack")) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == "pack")
+                })
+                .collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Write the objects of the pack at `pack_path` that are neither reachable nor loose
+    /// already as loose objects, with the modification time of the pack so that they are
+    /// only as new to prune as the pack was
+    fn loosen_unreachable(
+        object_store: &Path,
+        pack_path: &Path,
+        reachable: &HashSet<String>,
+    ) -> Result<()> {
+        let mtime = fs::metadata(pack_path)?.modified()?;
+        let packs = Packs::of(object_store);
+        for oid in Pack::open(pack_path)?.oids() {
+            let object_path = object_store.join(&oid[0..2]).join(&oid[2..]);
+            if reachable.contains(&oid) || object_path.is_file() {
+                continue;
+            }
+            let (object_type, body) = match packs.read(&oid)? {
+                Some(object) => object,
+                None => anyhow::bail!("fatal: unable to read {}", oid),
+            };
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            write!(encoder, "{} {}\0", object_type.as_str(), body.len())?;
+            encoder.write_all(&body)?;
+            Repack::write_loose(&object_path, &encoder.finish()?, mtime)?;
+        }
+        Ok(())
+    }
+
+    /// Write a loose object through a temporary file, dated `mtime`
+    fn write_loose(object_path: &Path, compressed: &[u8], mtime: SystemTime) -> Result<()> {
+        fs::create_dir_all(object_path.parent().unwrap())?;
+        let temp_path = object_path.with_extension("tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(compressed)?;
+        file.set_modified(mtime)?;
+        fs::rename(&temp_path, object_path)?;
+        Ok(())
+    }
+
+    /// Remove a pack and its index, the index first so that readers stop looking at the
+    /// pack before it goes
+    fn remove_pack(pack_path: &Path) -> Result<()> {
+        let idx_path = pack_path.with_extension("idx");
+        if idx_path.is_file() {
+            fs::remove_file(idx_path)?;
+        }
+        fs::remove_file(pack_path)?;
+        Ok(())
+    }
+
+    fn progress(&self, message: &str) -> Result<()> {
+        if !self.quiet {
+            write_to_stderr(message)?;
+        }
+        Ok(())
+    }
+}
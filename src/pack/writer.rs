@@ -0,0 +1,214 @@
+use anyhow::Result;
+use flate2::{write::ZlibEncoder, Compression};
+use sha1::{Digest, Sha1};
+use std::fs::{self, File};
+use std::io::{prelude::*, BufWriter};
+use std::path::PathBuf;
+use std::process;
+
+use super::{type_number, IDX_SIGNATURE, IDX_VERSION, OFS_DELTA, PACK_SIGNATURE, PACK_VERSION};
+use crate::database::{Content, ObjectType};
+
+/// An object to write into a pack, either whole or as a delta against another entry
+#[derive(Debug)]
+pub struct PackEntry {
+    pub oid: String,
+    pub object_type: ObjectType,
+    /// Where the object was found in a tree, which the delta search sorts by
+    pub path: String,
+    pub delta: Option<Delta>,
+}
+
+/// The data turning the entry at `base` into another entry
+#[derive(Debug)]
+pub struct Delta {
+    pub base: usize,
+    pub data: Vec<u8>,
+}
+
+/// Writes through to a file while hashing everything written and counting the bytes
+struct HashWriter {
+    file: BufWriter<File>,
+    hasher: Sha1,
+    position: u64,
+}
+
+impl Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Writes objects from the database into a `.pack` file and its `.idx` under
+/// `objects/pack`
+pub struct PackWriter {
+    object_store: PathBuf,
+}
+
+impl PackWriter {
+    pub fn new(object_store: PathBuf) -> Self {
+        Self { object_store }
+    }
+
+    /// Write `entries` into a new pack named after its checksum, returning the path of
+    /// the `.pack` file. Both files are written under temporary names first, and the
+    /// `.idx` is moved in place last as readers only look at packs that have one.
+    pub fn write(&self, entries: &[PackEntry]) -> Result<PathBuf> {
+        let pack_dir = self.object_store.join("pack");
+        fs::create_dir_all(&pack_dir)?;
+        let temp_pack = pack_dir.join(format!("tmp_pack_{}", process::id()));
+        let temp_idx = pack_dir.join(format!("tmp_idx_{}", process::id()));
+
+        let mut output = HashWriter {
+            file: BufWriter::new(File::create(&temp_pack)?),
+            hasher: Sha1::new(),
+            position: 0,
+        };
+        output.write_all(PACK_SIGNATURE)?;
+        output.write_all(&PACK_VERSION.to_be_bytes())?;
+        output.write_all(&(entries.len() as u32).to_be_bytes())?;
+
+        let mut written = vec![None; entries.len()];
+        for position in 0..entries.len() {
+            // a delta can only point back, so the bases of a chain that are not in the
+            // pack yet go first
+            let mut chain = vec![position];
+            while let Some(delta) = &entries[*chain.last().unwrap()].delta {
+                if written[delta.base].is_some() || chain.contains(&delta.base) {
+                    break;
+                }
+                chain.push(delta.base);
+            }
+            for position in chain.into_iter().rev() {
+                if written[position].is_none() {
+                    written[position] =
+                        Some(self.write_entry(&mut output, entries, position, &written)?);
+                }
+            }
+        }
+
+        let checksum = output.hasher.finalize_reset();
+        output.file.write_all(&checksum)?;
+        output.file.flush()?;
+        let written = written.into_iter().map(Option::unwrap).collect::<Vec<_>>();
+        fs::write(&temp_idx, Self::index(entries, &written, &checksum)?)?;
+
+        let name = format!("pack-{}", hex::encode(checksum));
+        let pack_path = pack_dir.join(format!("{}.pack", name));
+        fs::rename(&temp_pack, &pack_path)?;
+        fs::rename(&temp_idx, pack_dir.join(format!("{}.idx", name)))?;
+        Ok(pack_path)
+    }
+
+    /// Write one entry: a header holding its type and size, the distance back to the base
+    /// for a delta, then the zlib stream of its data. Returns the offset of the entry and
+    /// the CRC32 of everything written for it.
+    fn write_entry(
+        &self,
+        output: &mut HashWriter,
+        entries: &[PackEntry],
+        position: usize,
+        written: &[Option<(u64, u32)>],
+    ) -> Result<(u64, u32)> {
+        let entry = &entries[position];
+        let offset = output.position;
+        let object;
+        let (type_number, data, base) = match &entry.delta {
+            Some(delta) => (OFS_DELTA, &delta.data, written[delta.base]),
+            None => {
+                object = Content::parse(&entry.oid, self.object_store.clone())?.body;
+                (type_number(entry.object_type), &object, None)
+            }
+        };
+
+        let mut header = vec![];
+        let mut size = data.len();
+        let mut byte = (type_number << 4) | (size & 0x0f) as u8;
+        size >>= 4;
+        while size > 0 {
+            header.push(byte | 0x80);
+            byte = (size & 0x7f) as u8;
+            size >>= 7;
+        }
+        header.push(byte);
+
+        if let Some((base_offset, _)) = base {
+            // big-endian in base 128, each continuation standing for one more
+            let mut distance = offset - base_offset;
+            let mut bytes = vec![(distance & 0x7f) as u8];
+            distance >>= 7;
+            while distance > 0 {
+                distance -= 1;
+                bytes.push(0x80 | (distance & 0x7f) as u8);
+                distance >>= 7;
+            }
+            header.extend(bytes.into_iter().rev());
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(&header);
+        crc.update(&compressed);
+        output.write_all(&header)?;
+        output.write_all(&compressed)?;
+        Ok((offset, crc.finalize()))
+    }
+
+    /// The version 2 index of the pack: the fanout table, then the oids in order with
+    /// their CRC32s and offsets, those past 31 bits going into a table of 8-byte offsets
+    fn index(entries: &[PackEntry], written: &[(u64, u32)], checksum: &[u8]) -> Result<Vec<u8>> {
+        let mut order = (0..entries.len()).collect::<Vec<_>>();
+        order.sort_by(|a, b| entries[*a].oid.cmp(&entries[*b].oid));
+        let oids = order
+            .iter()
+            .map(|position| hex::decode(&entries[*position].oid))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut index = vec![];
+        index.extend(IDX_SIGNATURE);
+        index.extend(IDX_VERSION.to_be_bytes());
+        let mut fanout = [0u32; 256];
+        for oid in &oids {
+            fanout[oid[0] as usize] += 1;
+        }
+        let mut count = 0;
+        for first_byte_count in fanout {
+            count += first_byte_count;
+            index.extend(count.to_be_bytes());
+        }
+        for oid in &oids {
+            index.extend(oid);
+        }
+        for position in &order {
+            index.extend(written[*position].1.to_be_bytes());
+        }
+        let mut large_offsets = vec![];
+        for position in &order {
+            let offset = written[*position].0;
+            match offset < 0x8000_0000 {
+                true => index.extend((offset as u32).to_be_bytes()),
+                false => {
+                    index.extend((0x8000_0000 | large_offsets.len() as u32).to_be_bytes());
+                    large_offsets.push(offset);
+                }
+            }
+        }
+        for offset in large_offsets {
+            index.extend(offset.to_be_bytes());
+        }
+        index.extend(checksum);
+        let index_checksum = Sha1::digest(&index);
+        index.extend(index_checksum);
+        Ok(index)
+    }
+}
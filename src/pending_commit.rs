@@ -0,0 +1,94 @@
+use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+
+/// The operation that left a commit pending
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeType {
+    Merge,
+    CherryPick,
+    Revert,
+}
+
+impl MergeType {
+    /// The file under `.rgit` holding the commit being merged, picked or reverted
+    pub fn head_file(&self) -> &'static str {
+        match self {
+            MergeType::Merge => "MERGE_HEAD",
+            MergeType::CherryPick => "CHERRY_PICK_HEAD",
+            MergeType::Revert => "REVERT_HEAD",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MergeType::Merge => "merge",
+            MergeType::CherryPick => "cherry-pick",
+            MergeType::Revert => "revert",
+        }
+    }
+}
+
+/// Keeps track of a merge, cherry-pick or revert that stopped before it could be committed.
+/// The commit being merged in is kept in `.rgit/MERGE_HEAD` (or `CHERRY_PICK_HEAD`,
+/// `REVERT_HEAD`) and the message in `.rgit/MERGE_MSG`, so that a later `rgit commit` can
+/// finish the job.
+#[derive(Debug, Clone)]
+pub struct PendingCommit {
+    pub git_path: PathBuf,
+    pub message_path: PathBuf,
+}
+
+impl PendingCommit {
+    pub fn new(git_path: PathBuf) -> Self {
+        Self {
+            message_path: git_path.join("MERGE_MSG"),
+            git_path,
+        }
+    }
+
+    pub fn start(&self, oid: &str, message: &str, merge_type: MergeType) -> Result<()> {
+        fs::write(
+            self.git_path.join(merge_type.head_file()),
+            format!("{}\n", oid),
+        )?;
+        fs::write(&self.message_path, message)?;
+        Ok(())
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.merge_type().is_some()
+    }
+
+    /// The kind of commit that is pending, `None` if there is none
+    pub fn merge_type(&self) -> Option<MergeType> {
+        [MergeType::Merge, MergeType::CherryPick, MergeType::Revert]
+            .into_iter()
+            .find(|merge_type| self.git_path.join(merge_type.head_file()).exists())
+    }
+
+    pub fn merge_oid(&self) -> Result<String> {
+        let head_file = self.merge_type().map_or("MERGE_HEAD", |t| t.head_file());
+        let oid = fs::read_to_string(self.git_path.join(head_file)).map_err(|_| {
+            anyhow::anyhow!("There is no merge in progress ({} missing).", head_file)
+        })?;
+        Ok(oid.trim().to_owned())
+    }
+
+    pub fn merge_message(&self) -> Result<String> {
+        Ok(fs::read_to_string(&self.message_path)?)
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        for merge_type in [MergeType::Merge, MergeType::CherryPick, MergeType::Revert] {
+            let head_path = self.git_path.join(merge_type.head_file());
+            if head_path.exists() {
+                fs::remove_file(&head_path)?;
+            }
+        }
+        if self.message_path.exists() {
+            fs::remove_file(&self.message_path)?;
+        }
+        Ok(())
+    }
+}
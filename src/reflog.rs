@@ -0,0 +1,247 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use crate::{
+    command::commit::author_config, database::Author, lockfile::Lockfile, utils::write_to_stderr,
+};
+
+/// The oid a reflog records for the side of a move where the ref did not exist
+pub static NULL_OID: &str = "0000000000000000000000000000000000000000";
+
+/// One line of a reflog: the ref moved from `old_oid` to `new_oid`, by `author`, for the
+/// reason given in `message`
+#[derive(Debug, Clone)]
+pub struct ReflogEntry {
+    pub old_oid: String,
+    pub new_oid: String,
+    pub author: Author,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    /// Parse a line of the form `<old> <new> Name <email> timestamp timezone\t<message>`
+    fn parse(line: &str) -> Option<Self> {
+        let (header, message) = line.split_once('\t').unwrap_or((line, ""));
+        let mut parts = header.splitn(3, ' ');
+        let old_oid = parts.next()?.to_owned();
+        let new_oid = parts.next()?.to_owned();
+        let author = Author::parse(parts.next()?);
+        Some(Self {
+            old_oid,
+            new_oid,
+            author,
+            message: message.to_owned(),
+        })
+    }
+
+    fn line(&self) -> String {
+        format!(
+            "{} {} {}\t{}\n",
+            self.old_oid, self.new_oid, self.author, self.message
+        )
+    }
+}
+
+/// The history of a ref, kept in `.rgit/logs/<ref>` with the oldest entry first
+#[derive(Debug, Clone)]
+pub struct Reflog {
+    pub path: PathBuf,
+    ref_path: PathBuf,
+}
+
+impl Reflog {
+    /// # Arguments
+    /// * `name` - The path of the ref, relative to the git directory, e.g. `refs/stash`
+    pub fn new(git_path: &Path, name: &str) -> Self {
+        Self {
+            path: git_path.join("logs").join(name),
+            ref_path: git_path.join(name),
+        }
+    }
+
+    pub fn read(&self) -> Result<Vec<ReflogEntry>> {
+        if !self.path.is_file() {
+            return Ok(vec![]);
+        }
+        let content = fs::read_to_string(&self.path)?;
+        Ok(content.lines().filter_map(ReflogEntry::parse).collect())
+    }
+
+    /// Add an entry at the end of the log. Moving a ref holds the lock on the ref while
+    /// appending, which is what keeps concurrent updates from interleaving.
+    pub fn append(&self, entry: ReflogEntry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(entry.line().as_bytes())?;
+        Ok(())
+    }
+
+    /// Replace the log with the entries `edit` makes of its current ones, removing the file
+    /// when there are none left, or leave it as it is when `edit` returns `None`. The lock
+    /// on the ref is held from reading the log to writing it back, as it is while a move
+    /// of the ref appends to it, so no entry can be appended in between and lost.
+    pub fn rewrite(
+        &self,
+        edit: impl FnOnce(Vec<ReflogEntry>) -> Result<Option<Vec<ReflogEntry>>>,
+    ) -> Result<()> {
+        self.rewrite_under_lock(edit, false)
+    }
+
+    /// Like `rewrite`, then point the ref at the newest entry left, or remove it when there
+    /// is none, before letting go of the lock, the way an entry is taken off the stash
+    pub fn rewrite_and_update_ref(
+        &self,
+        edit: impl FnOnce(Vec<ReflogEntry>) -> Result<Option<Vec<ReflogEntry>>>,
+    ) -> Result<()> {
+        self.rewrite_under_lock(edit, true)
+    }
+
+    fn rewrite_under_lock(
+        &self,
+        edit: impl FnOnce(Vec<ReflogEntry>) -> Result<Option<Vec<ReflogEntry>>>,
+        update_ref: bool,
+    ) -> Result<()> {
+        if let Some(parent) = self.ref_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut ref_lock = Lockfile::new(self.ref_path.clone());
+        if !ref_lock.hold_for_update()? {
+            anyhow::bail!(
+                "fatal: Unable to create lock on {}",
+                self.ref_path.display()
+            );
+        }
+        let entries = match self.read().and_then(edit) {
+            Ok(Some(entries)) => entries,
+            other => {
+                ref_lock.rollback()?;
+                return other.map(|_| ());
+            }
+        };
+        if let Err(error) = self.write(&entries) {
+            ref_lock.rollback()?;
+            return Err(error);
+        }
+        match entries.last() {
+            Some(entry) if update_ref => {
+                ref_lock.write(format!("{}\n", entry.new_oid).as_bytes())?;
+                ref_lock.commit()
+            }
+            None if update_ref => {
+                if self.ref_path.is_file() {
+                    fs::remove_file(&self.ref_path)?;
+                }
+                ref_lock.rollback()
+            }
+            _ => ref_lock.rollback(),
+        }
+    }
+
+    fn write(&self, entries: &[ReflogEntry]) -> Result<()> {
+        if entries.is_empty() {
+            if self.path.is_file() {
+                fs::remove_file(&self.path)?;
+            }
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut lockfile = Lockfile::new(self.path.clone());
+        if !lockfile.hold_for_update()? {
+            anyhow::bail!("fatal: Unable to create lock on {}", self.path.display());
+        }
+        for entry in entries {
+            lockfile.write(entry.line().as_bytes())?;
+        }
+        lockfile.commit()
+    }
+}
+
+/// Who moved a ref: the configured author, or an unknown one so that a missing identity
+/// does not get in the way of a checkout or a reset
+pub fn reflog_identity() -> Author {
+    let (name, email) =
+        author_config().unwrap_or_else(|_| ("unknown".to_owned(), "unknown".to_owned()));
+    Author::new(&name, &email)
+}
+
+/// Parse the dates reflog selectors and expiry times are given in: `now`, `yesterday`,
+/// relative ones such as `2.weeks.ago` or `3 hours ago`, `@<timestamp>` and ISO dates
+/// with an optional time, read in the local timezone. `all` means now as well.
+pub fn parse_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    let now = Local::now().fixed_offset();
+    let date = date.trim();
+    match date {
+        "now" | "all" => return Some(now),
+        "yesterday" => return Some(now - Duration::days(1)),
+        _ => (),
+    }
+    if let Some(timestamp) = date.strip_prefix('@') {
+        let time = DateTime::from_timestamp(timestamp.parse().ok()?, 0)?;
+        return Some(time.with_timezone(now.offset()));
+    }
+
+    let words = date
+        .split(|c: char| c == '.' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    if let [count, unit, "ago"] = words.as_slice() {
+        let count = count.parse::<i64>().ok()?;
+        let unit = match unit.strip_suffix('s') {
+            Some(singular) => singular,
+            None => unit,
+        };
+        let duration = match unit {
+            "second" => Duration::seconds(count),
+            "minute" => Duration::minutes(count),
+            "hour" => Duration::hours(count),
+            "day" => Duration::days(count),
+            "week" => Duration::weeks(count),
+            "month" => Duration::days(30 * count),
+            "year" => Duration::days(365 * count),
+            _ => return None,
+        };
+        return Some(now - duration);
+    }
+
+    let time = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .and_then(|day| day.and_hms_opt(0, 0, 0))
+        })?;
+    Local
+        .from_local_datetime(&time)
+        .single()
+        .map(|time| time.fixed_offset())
+}
+
+/// Read the expiry time given to `option`, `never` and `false` meaning nothing expires
+pub fn parse_expiry(option: &str, time: &str) -> Result<Option<DateTime<FixedOffset>>> {
+    if time == "never" || time == "false" {
+        return Ok(None);
+    }
+    match parse_date(time) {
+        Some(time) => Ok(Some(time)),
+        None => {
+            write_to_stderr(&format!(
+                "error: invalid timestamp '{}' given to '{}'",
+                time, option
+            ))?;
+            exit(129);
+        }
+    }
+}
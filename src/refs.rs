@@ -5,11 +5,13 @@ use std::{
     path::PathBuf,
     process::exit,
 };
+use walkdir::WalkDir;
 
 use crate::{
     database::{Commit, Database, ParsedContent},
     lockfile::Lockfile,
-    utils::write_to_stderr,
+    reflog::{parse_date, reflog_identity, Reflog, ReflogEntry, NULL_OID},
+    utils::{remove_empty_parents, write_to_stderr},
 };
 
 #[derive(Debug, Clone)]
@@ -19,8 +21,16 @@ pub struct Ref {
 }
 
 impl Ref {
-    pub fn resolve(&self, context: &Refs) -> Option<String> {
-        return Some(context.get_specific_ref_content(self.name.as_str()));
+    pub fn resolve(&self, context: &Refs) -> Result<String> {
+        match context.get_specific_ref_content(&self.name) {
+            Ok(Some(oid)) => Ok(oid),
+            Ok(None) => Err(invalid_object_name(&self.revision_pattern)),
+            Err(error) => Err(anyhow::anyhow!(
+                "{}\n{}",
+                error,
+                invalid_object_name(&self.revision_pattern)
+            )),
+        }
     }
 }
 #[derive(Debug, Clone)]
@@ -30,21 +40,11 @@ pub struct Parent {
 }
 
 impl Parent {
-    pub fn resolve(&self, context: &Refs) -> Option<String> {
-        let ref_content = self.rev.resolve(context);
-        let content = context.commit_parent(Some(&ref_content.as_ref().unwrap()));
-        match content {
-            Some(c) => Some(c),
-            None => {
-                let output = format!(
-                    "fatal: Not a valid object name: '{}'",
-                    self.revision_pattern
-                );
-
-                write_to_stderr(&output).unwrap();
-                exit(1);
-            }
-        }
+    pub fn resolve(&self, context: &Refs) -> Result<String> {
+        let oid = self.rev.resolve(context)?;
+        context
+            .commit_parent(Some(&oid))
+            .ok_or_else(|| invalid_object_name(&self.revision_pattern))
     }
 }
 
@@ -56,25 +56,80 @@ pub struct Ancestor {
 }
 
 impl Ancestor {
-    pub fn resolve(&self, context: &Refs) -> Option<String> {
-        let mut oid = self.rev.resolve(context);
+    pub fn resolve(&self, context: &Refs) -> Result<String> {
+        let mut oid = self.rev.resolve(context)?;
         for _ in 0..self.num {
-            let parent = context.commit_parent(Some(&oid.as_ref().unwrap()));
-            match parent {
-                Some(p) => {
-                    oid = Some(p);
-                }
-                None => {
-                    let output = format!(
-                        "fatal: Not a valid object name: '{}'",
-                        self.revision_pattern
-                    );
-                    write_to_stderr(&output).unwrap();
-                    exit(1);
+            oid = context
+                .commit_parent(Some(&oid))
+                .ok_or_else(|| invalid_object_name(&self.revision_pattern))?;
+        }
+        Ok(oid)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Peel {
+    rev: Box<Revision>,
+}
+
+impl Peel {
+    pub fn resolve(&self, context: &Refs) -> Result<String> {
+        let oid = self.rev.resolve(context)?;
+        Ok(context.peel(&oid))
+    }
+}
+
+/// `<ref>@{<n>}` or `<ref>@{<date>}`: where the ref was n moves ago, or at that date,
+/// according to its reflog. Without a name it is the current branch.
+#[derive(Debug, Clone)]
+pub struct ReflogSelector {
+    name: String,
+    selector: String,
+    revision_pattern: String,
+}
+
+impl ReflogSelector {
+    pub fn resolve(&self, context: &Refs) -> Result<String> {
+        let full_name = context
+            .full_ref_name(&self.name)
+            .ok_or_else(|| invalid_object_name(&self.revision_pattern))?;
+        let name = match self.name.is_empty() {
+            true => full_name.trim_start_matches("refs/heads/"),
+            false => &self.name,
+        };
+        let entries = Reflog::new(&context.git_path, &full_name).read()?;
+
+        if let Ok(n) = self.selector.parse::<usize>() {
+            return match entries.len().checked_sub(n + 1) {
+                Some(position) => Ok(entries[position].new_oid.clone()),
+                None => anyhow::bail!(
+                    "fatal: log for '{}' only has {} entries",
+                    name,
+                    entries.len()
+                ),
+            };
+        }
+
+        let time = parse_date(&self.selector)
+            .ok_or_else(|| invalid_object_name(&self.revision_pattern))?;
+        if let Some(entry) = entries.iter().rev().find(|entry| entry.author.time <= time) {
+            return Ok(entry.new_oid.clone());
+        }
+        // before the log starts, the ref was where the first move took it from
+        match entries.first() {
+            Some(first) => {
+                write_to_stderr(&format!(
+                    "warning: log for '{}' only goes back to {}",
+                    name,
+                    first.author.readable_time()
+                ))?;
+                match first.old_oid == NULL_OID {
+                    true => Ok(first.new_oid.clone()),
+                    false => Ok(first.old_oid.clone()),
                 }
             }
+            None => anyhow::bail!("fatal: log for '{}' is empty", name),
         }
-        return oid;
     }
 }
 
@@ -82,24 +137,75 @@ impl Ancestor {
 pub enum Revision {
     Parent(Parent),
     Ancestor(Ancestor),
+    Peel(Peel),
     Ref(Ref),
+    Reflog(ReflogSelector),
 }
 
 impl Revision {
-    pub fn resolve(&self, context: &Refs) -> Option<String> {
+    /// Resolve the revision to an oid. The error carries git's message for a name that does
+    /// not point to any object, so commands can print it as it is.
+    pub fn resolve(&self, context: &Refs) -> Result<String> {
         match self {
             Revision::Parent(parent) => parent.resolve(context),
             Revision::Ancestor(ancestor) => ancestor.resolve(context),
+            Revision::Peel(peel) => peel.resolve(context),
             Revision::Ref(r) => r.resolve(context),
+            Revision::Reflog(selector) => selector.resolve(context),
+        }
+    }
+
+    /// Resolve the revision and peel any annotated tags, for commands that need a commit
+    pub fn resolve_commit(&self, context: &Refs) -> Result<String> {
+        let oid = self.resolve(context)?;
+        Ok(context.peel(&oid))
+    }
+
+    /// Resolve the revision as `resolve` does, or print why it does not and exit with 128,
+    /// as for any bad revision given on the command line
+    pub fn resolve_or_exit(&self, context: &Refs) -> Result<String> {
+        Revision::or_exit(self.resolve(context))
+    }
+
+    /// Resolve the revision as `resolve_commit` does, or print why it does not and exit
+    /// with 128
+    pub fn resolve_commit_or_exit(&self, context: &Refs) -> Result<String> {
+        Revision::or_exit(self.resolve_commit(context))
+    }
+
+    fn or_exit(oid: Result<String>) -> Result<String> {
+        match oid {
+            Ok(oid) => Ok(oid),
+            Err(error) => {
+                write_to_stderr(&error.to_string())?;
+                exit(128);
+            }
         }
     }
 }
 
+/// The ref the latest stash entry is kept in, the older ones living in its reflog
+pub static STASH_REF: &str = "refs/stash";
+
+/// The hint that goes with an argument that could be a revision or a path
+pub static USE_SEPARATOR: &str = "Use '--' to separate paths from revisions, like this:\n'rgit <command> [<revision>...] -- [<file>...]'";
+
+fn invalid_object_name(revision_pattern: &str) -> anyhow::Error {
+    anyhow::anyhow!("fatal: Not a valid object name: '{}'", revision_pattern)
+}
+
 pub fn parse_revision(pattern: &str, revision_pattern: &str) -> Revision {
     let parent_re = Regex::new(r"^(.+)\^$").unwrap();
     let revision_re = Regex::new(r"^(.+)~(\d+)$").unwrap();
+    let peel_re = Regex::new(r"^(.+)\^\{\}$").unwrap();
+    let reflog_re = Regex::new(r"^(.*)@\{([^{}]+)\}$").unwrap();
 
-    if parent_re.is_match(pattern) {
+    if let Some(caps) = peel_re.captures(pattern) {
+        let rev = caps.get(1).unwrap().as_str();
+        return Revision::Peel(Peel {
+            rev: Box::new(parse_revision(rev, revision_pattern)),
+        });
+    } else if parent_re.is_match(pattern) {
         let caps = parent_re.captures(pattern).unwrap();
         let rev = caps.get(1).unwrap().as_str();
 
@@ -121,10 +227,16 @@ pub fn parse_revision(pattern: &str, revision_pattern: &str) -> Revision {
             num,
             revision_pattern: revision_pattern.to_string(),
         });
+    } else if let Some(caps) = reflog_re.captures(pattern) {
+        return Revision::Reflog(ReflogSelector {
+            name: caps.get(1).unwrap().as_str().to_string(),
+            selector: caps.get(2).unwrap().as_str().to_string(),
+            revision_pattern: revision_pattern.to_string(),
+        });
     } else {
         if pattern == "@" || pattern == "HEAD" {
             return Revision::Ref(Ref {
-                name: "master".to_string(),
+                name: "HEAD".to_string(),
                 revision_pattern: revision_pattern.to_string(),
             });
         }
@@ -135,6 +247,23 @@ pub fn parse_revision(pattern: &str, revision_pattern: &str) -> Revision {
     }
 }
 
+/// Check a branch or tag name against the rules of
+/// https://git-scm.com/docs/git-check-ref-format
+pub fn valid_ref_name(name: &str) -> bool {
+    let invalid_name_pattern = r"(?x)
+    ^\.
+    | \/ \.
+    | \. \.
+    | ^\/
+    | \/$
+    | \.lock$
+    | @\{
+    | [\x00-\x20*:?\[\\^~\x7f]
+    ";
+
+    !Regex::new(invalid_name_pattern).unwrap().is_match(name)
+}
+
 #[derive(Debug, Clone)]
 pub struct Refs {
     pub git_path: PathBuf,
@@ -149,15 +278,12 @@ impl Refs {
         match oid {
             Some(oid) => {
                 let database = Database::new(self.git_path.join("objects").clone());
-                let commit = database.read_object(oid);
+                let commit = database.read_object(&self.peel(oid));
                 let commit = commit.expect("Failed to read commit");
 
                 match commit {
-                    ParsedContent::CommitContent(commit) => match commit.parent {
-                        Some(parent) => Some(parent),
-                        None => None,
-                    },
-                    _ => panic!("should not happen"),
+                    ParsedContent::CommitContent(commit) => commit.parent(),
+                    _ => None,
                 }
             }
             None => None,
@@ -175,7 +301,7 @@ impl Refs {
             match commit {
                 ParsedContent::CommitContent(commit) => {
                     commits.push(commit.clone());
-                    match commit.parent {
+                    match commit.parent() {
                         Some(parent) => {
                             head = parent;
                         }
@@ -188,8 +314,35 @@ impl Refs {
         Ok(commits)
     }
 
-    pub fn update_head(&self, oid: &str) -> Result<()> {
-        self.update_ref_file(&self.get_ref_path(), oid)
+    /// Move the current branch, or HEAD itself when it is detached, to `oid`
+    /// # Arguments
+    /// * `oid` - The commit hash to store
+    /// * `message` - Why the ref moved, for the reflog, e.g. `commit: Fix typo`
+    pub fn update_head(&self, oid: &str, message: &str) -> Result<()> {
+        self.update_ref_file(&self.get_ref_path(), oid, message)
+    }
+
+    /// Point a ref such as `ORIG_HEAD` at `oid`
+    /// # Arguments
+    /// * `name` - The path of the ref, relative to the git directory
+    /// * `oid` - The commit hash to store
+    /// * `message` - Why the ref moved, for the reflog. Only HEAD and the branches keep one.
+    pub fn update_ref(&self, name: &str, oid: &str, message: &str) -> Result<()> {
+        self.update_ref_file(&self.git_path.join(name), oid, message)
+    }
+
+    /// Point a ref at `oid` as `update_ref` does, but only if it still points at
+    /// `old_oid`. The check is made under the lock on the ref, so nothing can move it in
+    /// between. Returns the oid the ref was found at instead, leaving it alone.
+    pub fn compare_and_swap(
+        &self,
+        name: &str,
+        old_oid: &str,
+        oid: &str,
+        message: &str,
+    ) -> Result<Option<String>> {
+        let ref_path = self.git_path.join(name);
+        self.write_ref_file(&ref_path, oid, oid, Some(old_oid), message)
     }
 
     pub fn ref_path(&self) -> PathBuf {
@@ -228,7 +381,19 @@ impl Refs {
     /// Create a new branch
     /// # Arguments
     /// * `branch_name` - The name of the branch to create
-    pub fn create_branch(&self, branch_name: &str, oid: &str) -> Result<()> {
+    /// * `oid` - The commit the branch starts at
+    /// * `message` - The first line of the branch's reflog, e.g. `branch: Created from HEAD`
+    pub fn create_branch(&self, branch_name: &str, oid: &str, message: &str) -> Result<()> {
+        self.check_new_branch(branch_name)?;
+        let branch_ref_path = self.git_path.join("refs/heads").join(branch_name);
+        self.update_ref_file(&branch_ref_path, oid, message)?;
+
+        Ok(())
+    }
+
+    /// Exit with the error `create_branch` would fail with, so a command can check the name
+    /// before doing any work it would have to undo
+    pub fn check_new_branch(&self, branch_name: &str) -> Result<()> {
         let branch_ref_path = self.git_path.join("refs/heads").join(branch_name);
 
         if branch_ref_path.exists() {
@@ -236,23 +401,7 @@ impl Refs {
             exit(1);
         }
 
-        // check if the branch name is valid
-        // https://git-scm.com/docs/git-check-ref-format
-        let invalid_name_pattern = r"(?x)
-        ^\.
-        | \/ \.
-        | \. \.
-        | ^\/
-        | \/$
-        | \.lock$
-        | @\{
-        | [\x00-\x20*:?\[\\^~\x7f]
-        ";
-
-        if Regex::new(invalid_name_pattern)
-            .unwrap()
-            .is_match(branch_name)
-        {
+        if !valid_ref_name(branch_name) {
             write_to_stderr(&format!("fatal: invalid branch name: {}", branch_name))?;
             exit(1);
         }
@@ -264,12 +413,28 @@ impl Refs {
             exit(1);
         }
 
-        self.update_ref_file(&branch_ref_path, oid)?;
+        Ok(())
+    }
 
+    fn update_ref_file(&self, ref_path: &PathBuf, oid: &str, message: &str) -> Result<()> {
+        self.write_ref_file(ref_path, oid, oid, None, message)?;
         Ok(())
     }
 
-    fn update_ref_file(&self, ref_path: &PathBuf, oid: &str) -> Result<()> {
+    /// Write `content` to a ref file, which leaves the ref resolving to `oid`. The move is
+    /// appended to the reflog while the lock on the ref is still held, so two updates
+    /// cannot log out of order. A branch HEAD points at logs to HEAD's reflog too, under
+    /// the lock on HEAD, as every log is only ever written under the lock of its ref.
+    /// With `expected_oid`, nothing is written unless the ref resolves to it, and the oid
+    /// it resolves to instead is returned.
+    fn write_ref_file(
+        &self,
+        ref_path: &PathBuf,
+        content: &str,
+        oid: &str,
+        expected_oid: Option<&str>,
+        message: &str,
+    ) -> Result<Option<String>> {
         let mut lockfile = Lockfile::new(ref_path.clone());
         match lockfile.hold_for_update()? {
             false => {
@@ -279,10 +444,71 @@ impl Refs {
             true => (),
         }
 
-        lockfile.write(oid.as_bytes())?;
+        let old_oid = self.resolve_ref_file(ref_path);
+        if expected_oid.is_some_and(|expected| expected != old_oid) {
+            lockfile.rollback()?;
+            return Ok(Some(old_oid));
+        }
+        lockfile.write(content.as_bytes())?;
         lockfile.write(b"\n")?;
+        for name in self.logged_refs(ref_path) {
+            let log_ref_path = self.git_path.join(&name);
+            let mut log_lock = None;
+            if &log_ref_path != ref_path {
+                let mut other = Lockfile::new(log_ref_path);
+                if !other.hold_for_update()? {
+                    lockfile.rollback()?;
+                    write_to_stderr(&format!("fatal: Unable to create lock on {}", name))?;
+                    exit(1);
+                }
+                log_lock = Some(other);
+            }
+            Reflog::new(&self.git_path, &name).append(ReflogEntry {
+                old_oid: old_oid.clone(),
+                new_oid: oid.to_owned(),
+                author: reflog_identity(),
+                message: message.to_owned(),
+            })?;
+            if let Some(mut other) = log_lock {
+                other.rollback()?;
+            }
+        }
         lockfile.commit()?;
-        Ok(())
+        Ok(None)
+    }
+
+    /// The oid a ref file resolves to, following HEAD to its branch, or the null oid for a
+    /// ref that does not exist yet
+    fn resolve_ref_file(&self, ref_path: &PathBuf) -> String {
+        let content = fs::read_to_string(ref_path).unwrap_or_default();
+        let content = match content.trim().strip_prefix("ref: ") {
+            Some(target) => fs::read_to_string(self.git_path.join(target)).unwrap_or_default(),
+            None => content,
+        };
+        match content.trim() {
+            "" => NULL_OID.to_owned(),
+            oid => oid.to_owned(),
+        }
+    }
+
+    /// The reflogs an update of the ref file goes into: its own for HEAD, the branches and
+    /// the stash, and HEAD's as well when HEAD points at the branch
+    fn logged_refs(&self, ref_path: &PathBuf) -> Vec<String> {
+        let name = match ref_path.strip_prefix(&self.git_path) {
+            Ok(name) => name.to_string_lossy().into_owned(),
+            Err(_) => return vec![],
+        };
+        if name == "HEAD" || name == STASH_REF {
+            return vec![name];
+        }
+        if !name.starts_with("refs/heads/") {
+            return vec![];
+        }
+        let head = fs::read_to_string(self.git_path.join("HEAD")).unwrap_or_default();
+        match head.trim().strip_prefix("ref: ") == Some(name.as_str()) {
+            true => vec![name, "HEAD".to_owned()],
+            false => vec![name],
+        }
     }
 
     /// Get the content of the ref pointed to by HEAD
@@ -297,28 +523,59 @@ impl Refs {
     }
 
     /// Get the content of a specific ref. This content is the commit hash of the ref.
+    /// A full oid or an abbreviated commit oid is accepted as well, failing when the
+    /// abbreviation fits more than one commit.
     /// # Arguments
     /// * `ref_name` - The name of the ref
     /// # Returns
-    /// * The content of the ref
-    pub fn get_specific_ref_content(&self, ref_name: &str) -> String {
-        let ref_path = self.git_path.join("refs/heads").join(ref_name);
-        if ref_path.exists() {
-            let ref_content = fs::read_to_string(ref_path).expect("Failed to read ref");
-            ref_content.trim().to_string()
-        } else {
-            let database = Database::new(self.git_path.join("objects").clone());
-            let objects = database.prefix_match(ref_name);
+    /// * The content of the ref, or `None` if the name points to nothing
+    pub fn get_specific_ref_content(&self, ref_name: &str) -> Result<Option<String>> {
+        if let Some(oid) = self.read_ref(ref_name) {
+            return Ok(Some(oid));
+        }
+        if ref_name == "HEAD" {
+            return Ok(None);
+        }
 
-            if objects.len() >= 1 {
-                return objects[0].oid.clone().unwrap();
-            } else {
-            write_to_stderr(&format!("fatal: Not a valid object name: '{}'", ref_name)).unwrap();
-            exit(1);
-            }
+        let database = Database::new(self.git_path.join("objects").clone());
+        // a full oid names any object, an abbreviated one only commits
+        if database.exists(ref_name) {
+            return Ok(Some(ref_name.to_owned()));
+        }
+        match database.prefix_match(ref_name).as_slice() {
+            [] => Ok(None),
+            [commit] => Ok(commit.oid.clone()),
+            _ => anyhow::bail!("error: short object ID {} is ambiguous", ref_name),
         }
     }
 
+    /// Read the oid a ref points to, looking at HEAD, then the tags and then the branches,
+    /// as git and `full_ref_name` do. Full ref paths such as `refs/heads/master` and files
+    /// like `ORIG_HEAD` are read as they are. Returns `None` if there is no such ref.
+    pub fn read_ref(&self, ref_name: &str) -> Option<String> {
+        if ref_name == "HEAD" || ref_name == "@" {
+            return self.read_head().filter(|oid| !oid.is_empty());
+        }
+
+        let is_full_path = ref_name.starts_with("refs/")
+            || (ref_name.ends_with("_HEAD")
+                && ref_name.chars().all(|c| c.is_ascii_uppercase() || c == '_'));
+        let full_path = is_full_path.then(|| self.git_path.join(ref_name));
+
+        full_path
+            .into_iter()
+            .chain(
+                ["refs/tags", "refs/heads"]
+                    .iter()
+                    .map(|prefix| self.git_path.join(prefix).join(ref_name)),
+            )
+            .find(|path| path.is_file())
+            .map(|path| {
+                let ref_content = fs::read_to_string(path).expect("Failed to read ref");
+                ref_content.trim().to_string()
+            })
+    }
+
     fn read_ref_content(&self) -> String {
         let ref_path = self.get_ref_path();
         if !ref_path.exists() {
@@ -336,7 +593,7 @@ impl Refs {
         ref_content.trim().to_string()
     }
 
-    /// Get the path of the ref pointed to by HEAD
+    /// Get the path of the ref pointed to by HEAD. When HEAD is detached, this is HEAD itself.
     fn get_ref_path(&self) -> PathBuf {
         let head_content =
             fs::read_to_string(self.git_path.join("HEAD")).expect("Failed to read HEAD");
@@ -344,7 +601,113 @@ impl Refs {
             let ref_path = head_content.trim().split(" ").collect::<Vec<&str>>()[1];
             return self.git_path.join(ref_path);
         } else {
-            panic!("HEAD is not a ref");
+            return self.git_path.join("HEAD");
+        }
+    }
+
+    /// The full name of a ref, e.g. `refs/heads/master` for `master`, looking for it the way
+    /// git does. An empty name stands for the current branch, or HEAD when it is detached.
+    pub fn full_ref_name(&self, name: &str) -> Option<String> {
+        if name.is_empty() {
+            return Some(match self.current_branch() {
+                Some(branch) => format!("refs/heads/{}", branch),
+                None => "HEAD".to_owned(),
+            });
+        }
+        if name == "HEAD" || name == "@" {
+            return Some("HEAD".to_owned());
+        }
+        [
+            name.to_owned(),
+            format!("refs/{}", name),
+            format!("refs/tags/{}", name),
+            format!("refs/heads/{}", name),
+        ]
+        .into_iter()
+        .find(|full_name| full_name.starts_with("refs/") && self.git_path.join(full_name).is_file())
+    }
+
+    /// Get the name of the branch HEAD points to, or `None` if HEAD is detached
+    pub fn current_branch(&self) -> Option<String> {
+        let head_content =
+            fs::read_to_string(self.git_path.join("HEAD")).expect("Failed to read HEAD");
+        head_content
+            .trim()
+            .strip_prefix("ref: refs/heads/")
+            .map(|name| name.to_string())
+    }
+
+    /// Follow annotated tags until reaching an object that is not a tag
+    pub fn peel(&self, oid: &str) -> String {
+        let database = Database::new(self.git_path.join("objects"));
+        let mut oid = oid.to_owned();
+        while let Ok(ParsedContent::TagContent(tag)) = database.read_object(&oid) {
+            oid = tag.object;
+        }
+        oid
+    }
+
+    /// The names of the tags, sorted, with the ones like `v1/rc1` under their directories
+    pub fn list_tags(&self) -> Result<Vec<String>> {
+        let tag_ref_path = self.git_path.join("refs/tags");
+        if !tag_ref_path.exists() {
+            return Ok(vec![]);
+        }
+        let mut tags = vec![];
+        for tag_ref in WalkDir::new(&tag_ref_path) {
+            let tag_ref = tag_ref?;
+            if !tag_ref.file_type().is_file() {
+                continue;
+            }
+            let tag_name = tag_ref
+                .path()
+                .strip_prefix(&tag_ref_path)?
+                .to_str()
+                .expect("Failed to convert tag name to string")
+                .to_owned();
+            tags.push(tag_name);
+        }
+        tags.sort();
+        Ok(tags)
+    }
+
+    /// Create a tag pointing at `oid`, which is a tag object for annotated tags
+    /// # Arguments
+    /// * `tag_name` - The name of the tag to create
+    /// * `oid` - The object the tag points to
+    pub fn create_tag(&self, tag_name: &str, oid: &str) -> Result<()> {
+        let tag_ref_path = self.git_path.join("refs/tags").join(tag_name);
+        if let Some(parent) = tag_ref_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.update_ref_file(&tag_ref_path, oid, "")
+    }
+
+    /// Delete a tag, returning the oid it pointed to or `None` if there is no such tag
+    pub fn delete_tag(&self, tag_name: &str) -> Result<Option<String>> {
+        let tag_ref_path = self.git_path.join("refs/tags").join(tag_name);
+        if !tag_ref_path.is_file() {
+            return Ok(None);
+        }
+        let oid = fs::read_to_string(&tag_ref_path)?.trim().to_string();
+        fs::remove_file(&tag_ref_path)?;
+        remove_empty_parents(&self.git_path.join("refs/tags"), &tag_ref_path)?;
+        Ok(Some(oid))
+    }
+
+    /// Point HEAD at a branch, or detach it at `oid` if `target` is not a branch name
+    /// # Arguments
+    /// * `target` - The branch name or revision that was checked out
+    /// * `oid` - The commit hash `target` resolves to
+    /// * `message` - Why HEAD moved, for its reflog, e.g. `checkout: moving from a to b`
+    pub fn set_head(&self, target: &str, oid: &str, message: &str) -> Result<()> {
+        let head_path = self.git_path.join("HEAD");
+        if self.git_path.join("refs/heads").join(target).exists() {
+            let content = format!("ref: refs/heads/{}", target);
+            self.write_ref_file(&head_path, &content, oid, None, message)?;
+            Ok(())
+        } else {
+            self.update_ref_file(&head_path, oid, message)
         }
     }
 
@@ -0,0 +1,209 @@
+use anyhow::Result;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// What to do with a commit of the todo list
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Pick,
+    Revert,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    Exec,
+    Drop,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Pick => "pick",
+            Action::Revert => "revert",
+            Action::Reword => "reword",
+            Action::Edit => "edit",
+            Action::Squash => "squash",
+            Action::Fixup => "fixup",
+            Action::Exec => "exec",
+            Action::Drop => "drop",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "pick" | "p" => Some(Action::Pick),
+            "revert" => Some(Action::Revert),
+            "reword" | "r" => Some(Action::Reword),
+            "edit" | "e" => Some(Action::Edit),
+            "squash" | "s" => Some(Action::Squash),
+            "fixup" | "f" => Some(Action::Fixup),
+            "exec" | "x" => Some(Action::Exec),
+            "drop" | "d" => Some(Action::Drop),
+            _ => None,
+        }
+    }
+}
+
+/// One line of the todo list: `<action> <oid> <subject>`, or `exec <command>` for which
+/// the oid is empty and the command is kept as the subject
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub action: Action,
+    pub oid: String,
+    pub subject: String,
+}
+
+impl Step {
+    fn parse(line: &str) -> Option<Self> {
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let action = Action::parse(name)?;
+        let rest = rest.trim();
+        if action == Action::Exec {
+            return (!rest.is_empty()).then(|| Self {
+                action,
+                oid: String::new(),
+                subject: rest.to_owned(),
+            });
+        }
+
+        let (oid, subject) = rest.split_once(' ').unwrap_or((rest, ""));
+        (!oid.is_empty()).then(|| Self {
+            action,
+            oid: oid.to_owned(),
+            subject: subject.to_owned(),
+        })
+    }
+
+    fn line(&self) -> String {
+        match self.action {
+            Action::Exec => format!("{} {}\n", self.action.as_str(), self.subject),
+            _ => format!("{} {} {}\n", self.action.as_str(), self.oid, self.subject),
+        }
+    }
+}
+
+/// The state of a command that works through a list of commits, so that it can stop on a
+/// conflict and be resumed by a later process. `head` is the commit HEAD was on when it
+/// started, the todo file lists the steps that have not been started yet and `done` the
+/// ones that have, the last of them being the one in progress.
+#[derive(Debug, Clone)]
+pub struct Sequencer {
+    pub path: PathBuf,
+    pub todo_path: PathBuf,
+}
+
+impl Sequencer {
+    /// The sequencer of cherry-pick and revert, in `.rgit/sequencer`
+    pub fn new(git_path: PathBuf) -> Self {
+        let path = git_path.join("sequencer");
+        Self {
+            todo_path: path.join("todo"),
+            path,
+        }
+    }
+
+    /// The sequencer of rebase, in `.rgit/rebase-merge`
+    pub fn rebase(git_path: PathBuf) -> Self {
+        let path = git_path.join("rebase-merge");
+        Self {
+            todo_path: path.join("git-rebase-todo"),
+            path,
+        }
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.path.is_dir()
+    }
+
+    pub fn start(&self, head_oid: &str, steps: &[Step]) -> Result<()> {
+        fs::create_dir_all(&self.path)?;
+        fs::write(self.path.join("head"), format!("{}\n", head_oid))?;
+        fs::write(self.path.join("done"), "")?;
+        self.write_todo(steps)
+    }
+
+    /// The commit HEAD was on before the first step
+    pub fn original_head(&self) -> Result<String> {
+        Ok(fs::read_to_string(self.path.join("head"))?
+            .trim()
+            .to_owned())
+    }
+
+    /// The steps left to do. Blank lines and `#` comments are skipped.
+    pub fn todo(&self) -> Result<Vec<Step>> {
+        Sequencer::read_steps(&self.todo_path)
+    }
+
+    /// The steps that have been started, the last of them being the current one
+    pub fn done(&self) -> Result<Vec<Step>> {
+        Sequencer::read_steps(&self.path.join("done"))
+    }
+
+    fn read_steps(path: &Path) -> Result<Vec<Step>> {
+        let content = fs::read_to_string(path)?;
+        let mut steps = vec![];
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match Step::parse(line) {
+                Some(step) => steps.push(step),
+                None => anyhow::bail!("error: invalid line in the todo list: {}", line),
+            }
+        }
+        Ok(steps)
+    }
+
+    /// Take the first step off the todo list and record it as done, before it is run
+    pub fn next_step(&self) -> Result<Option<Step>> {
+        let mut steps = self.todo()?;
+        if steps.is_empty() {
+            return Ok(None);
+        }
+        let step = steps.remove(0);
+        self.write_todo(&steps)?;
+
+        let mut done = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path.join("done"))?;
+        done.write_all(step.line().as_bytes())?;
+        Ok(Some(step))
+    }
+
+    /// Read one of the files kept with the state, `None` if it is not there
+    pub fn read_state(&self, name: &str) -> Option<String> {
+        fs::read_to_string(self.path.join(name))
+            .ok()
+            .map(|content| content.trim_end().to_owned())
+    }
+
+    pub fn write_state(&self, name: &str, content: &str) -> Result<()> {
+        fs::write(self.path.join(name), format!("{}\n", content))?;
+        Ok(())
+    }
+
+    pub fn remove_state(&self, name: &str) -> Result<()> {
+        let path = self.path.join(name);
+        if path.is_file() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Forget about the sequence, leaving HEAD where it is
+    pub fn quit(&self) -> Result<()> {
+        if self.path.is_dir() {
+            fs::remove_dir_all(&self.path)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_todo(&self, steps: &[Step]) -> Result<()> {
+        let content = steps.iter().map(Step::line).collect::<String>();
+        fs::write(&self.todo_path, content)?;
+        Ok(())
+    }
+}
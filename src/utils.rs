@@ -2,8 +2,15 @@ use anyhow::Result;
 use colored::ColoredString;
 use flate2::{write::ZlibEncoder, Compression};
 use sha1::{Digest, Sha1};
+use std::fs;
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+static MESSAGE_HELP: &str = "
+# Please enter the commit message for your changes. Lines starting
+# with '#' will be ignored, and an empty message aborts the commit.
+";
 
 pub fn write_to_stdout(content: &str) -> Result<()> {
     let stdout = std::io::stdout();
@@ -67,3 +74,86 @@ fn is_printable(content: &[u8]) -> bool {
         false
     }
 }
+
+/// Remove the directories above `path` that are left empty, stopping at `root_path`
+pub fn remove_empty_parents(root_path: &Path, path: &Path) -> Result<()> {
+    let mut current = path.parent();
+    while let Some(dir) = current {
+        if dir == root_path || !dir.starts_with(root_path) {
+            break;
+        }
+        if !dir.is_dir() || fs::read_dir(dir)?.next().is_some() {
+            break;
+        }
+        fs::remove_dir(dir)?;
+        current = dir.parent();
+    }
+    Ok(())
+}
+
+/// The editor to run: the first of the environment variables that is set, or vi
+pub fn editor(variables: &[&str]) -> String {
+    variables
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_else(|| "vi".to_owned())
+}
+
+/// Open `path` in `editor` and wait for it to exit. The editor is run through the shell
+/// the way git does it, so it can carry arguments or be a small script.
+pub fn edit_file(editor: &str, path: &Path) -> Result<()> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(editor)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        anyhow::bail!("error: there was a problem with the editor '{}'", editor);
+    }
+    Ok(())
+}
+
+/// Drop the `#` comment lines of an edited message along with the blank lines around it
+pub fn strip_comments(message: &str) -> String {
+    let lines = message
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.trim_end())
+        .collect::<Vec<_>>();
+    lines.join("\n").trim().to_owned()
+}
+
+/// Let the user edit a commit message in `COMMIT_EDITMSG` with `$GIT_EDITOR` or `$EDITOR`,
+/// dropping the comment lines afterwards. An empty message aborts.
+pub fn edit_message(git_path: &Path, message: &str) -> Result<String> {
+    let path = git_path.join("COMMIT_EDITMSG");
+    let message = edit_template(&path, &format!("{}\n{}", message.trim_end(), MESSAGE_HELP))?;
+    if message.is_empty() {
+        anyhow::bail!("Aborting commit due to empty commit message.");
+    }
+    Ok(message)
+}
+
+/// Let the user write the message of the annotated tag `name` in `TAG_EDITMSG`, the way
+/// `edit_message` does for commits
+pub fn edit_tag_message(git_path: &Path, name: &str) -> Result<String> {
+    let path = git_path.join("TAG_EDITMSG");
+    let template = format!(
+        "\n#\n# Write a message for tag:\n#   {}\n# Lines starting with '#' will be ignored.\n",
+        name
+    );
+    let message = edit_template(&path, &template)?;
+    if message.is_empty() {
+        anyhow::bail!("fatal: no tag message?");
+    }
+    Ok(message)
+}
+
+/// Write `template` to `path`, open it in the editor and return what was left of it
+/// without the comments
+fn edit_template(path: &Path, template: &str) -> Result<String> {
+    fs::write(path, template)?;
+    edit_file(&editor(&["GIT_EDITOR", "EDITOR"]), path)?;
+    Ok(strip_comments(&fs::read_to_string(path)?))
+}
@@ -3,7 +3,7 @@ use walkdir::WalkDir;
 
 use std::collections::BTreeMap;
 use std::fs::{self};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::index::Stat;
 use crate::utils::{get_root_path, hash_content};
@@ -141,7 +141,8 @@ impl WorkspaceTree {
         Ok(content)
     }
 
-    fn ignored_files() -> Result<Vec<String>> {
+    /// The names on the ignore list; `.rgit` is always ignored
+    pub fn ignored_files() -> Result<Vec<String>> {
         let root_path = get_root_path()?;
         let gitignore_path = root_path.join(".rgitignore");
         let mut ignored_files = vec![];
@@ -162,18 +163,7 @@ impl WorkspaceTree {
         let mut files = WalkDir::new(path)
             .into_iter()
             .filter_map(|entry| entry.ok())
-            .filter(|entry| {
-                let path = entry.path();
-                let components = path.components().map(|c| c.as_os_str()).collect::<Vec<_>>();
-                let mut is_ignored = false;
-                for ignored in ignored_files.iter() {
-                    if components.contains(&ignored.as_ref()) {
-                        is_ignored = true;
-                        break;
-                    }
-                }
-                !is_ignored
-            })
+            .filter(|entry| !WorkspaceTree::is_ignored(entry.path(), &ignored_files))
             .filter_map(|entry| {
                 entry.file_type().is_file().then(|| File {
                     name: entry
@@ -196,6 +186,38 @@ impl WorkspaceTree {
         files.sort_by(|a, b| a.path.cmp(&b.path));
         files
     }
+
+    /// Whether any component of `path` is on the ignore list
+    pub fn is_ignored(path: &Path, ignored_files: &[String]) -> bool {
+        let components = path.components().map(|c| c.as_os_str()).collect::<Vec<_>>();
+        ignored_files
+            .iter()
+            .any(|ignored| components.contains(&ignored.as_ref()))
+    }
+
+    /// List the files under `path` that the ignore list leaves out, relative to the current
+    /// directory. Nothing inside `.rgit` is listed.
+    pub fn list_ignored_files(path: &PathBuf) -> Vec<PathBuf> {
+        let ignored_files = WorkspaceTree::ignored_files().expect("failed to get ignored files");
+        let current_dir = std::env::current_dir().unwrap();
+        let mut files = WalkDir::new(path)
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != ".rgit")
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(&current_dir)
+                    .expect("failed to strip prefix")
+                    .to_path_buf()
+            })
+            .filter(|path| WorkspaceTree::is_ignored(path, &ignored_files))
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
     pub fn new(root: Option<&PathBuf>) -> Self {
         match root {
             Some(root) => {
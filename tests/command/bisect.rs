@@ -0,0 +1,166 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{get_rgit_cmd, rgit, setup_history};
+
+/// Commits `c0` to `c9`, each writing its number to `f`
+fn history() -> Vec<(String, Vec<(&'static str, String)>)> {
+    (0..10)
+        .map(|n| (format!("c{}", n), vec![("f", format!("{}\n", n))]))
+        .collect()
+}
+
+fn checked_out(temp_dir: &TempDir) -> Result<String> {
+    Ok(fs::read_to_string(temp_dir.path().join("f"))?
+        .trim()
+        .to_owned())
+}
+
+#[test]
+fn test_bisect_marks_commits_until_the_first_bad_one() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+
+    assert_eq!(
+        rgit(&temp_dir, &["bisect", "start"])?,
+        "status: waiting for both good and bad commits\n"
+    );
+    rgit(&temp_dir, &["bisect", "bad"])?;
+    let output = rgit(&temp_dir, &["bisect", "good", "HEAD~9"])?;
+    assert!(
+        output.starts_with("Bisecting: 4 revisions left to test after this (roughly 2 steps)\n")
+    );
+    assert_eq!(checked_out(&temp_dir)?, "4");
+
+    rgit(&temp_dir, &["bisect", "good"])?;
+    assert_eq!(checked_out(&temp_dir)?, "6");
+    rgit(&temp_dir, &["bisect", "skip"])?;
+    assert_eq!(checked_out(&temp_dir)?, "7");
+    rgit(&temp_dir, &["bisect", "bad"])?;
+    assert_eq!(checked_out(&temp_dir)?, "5");
+    // with c5 untestable too, the first bad commit could be any of c5 to c7
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .args(["bisect", "skip"])
+        .assert()
+        .code(2);
+    let output = String::from_utf8(assert.get_output().stdout.clone())?;
+    assert!(output.starts_with("There are only 'skip'ped commits left to test.\n"));
+
+    let log = rgit(&temp_dir, &["bisect", "log"])?;
+    assert!(log.contains("git bisect start\n"));
+    assert_eq!(log.matches("git bisect skip ").count(), 2);
+
+    rgit(&temp_dir, &["bisect", "reset"])?;
+    assert_eq!(checked_out(&temp_dir)?, "9");
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join(".rgit/HEAD"))?,
+        "ref: refs/heads/master\n"
+    );
+    assert!(!temp_dir.path().join(".rgit/BISECT_START").exists());
+    assert!(!temp_dir.path().join(".rgit/BISECT_LOG").exists());
+    Ok(())
+}
+
+#[test]
+fn test_bisect_run_and_replay() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+
+    rgit(&temp_dir, &["bisect", "start", "HEAD", "HEAD~9"])?;
+    // c3 cannot be built: the script skips it
+    let output = rgit(
+        &temp_dir,
+        &[
+            "bisect",
+            "run",
+            "sh",
+            "-c",
+            "n=$(cat f); test $n -eq 3 && exit 125; test $n -lt 6",
+        ],
+    )?;
+    assert!(output.contains("is the first bad commit\n"));
+    assert!(output.ends_with("    c6\nbisect found first bad commit\n"));
+
+    let log = rgit(&temp_dir, &["bisect", "log"])?;
+    fs::write(temp_dir.path().join("bisect.log"), log)?;
+    rgit(&temp_dir, &["bisect", "reset"])?;
+
+    let output = rgit(&temp_dir, &["bisect", "replay", "bisect.log"])?;
+    assert!(output.contains("is the first bad commit\n"));
+    let bad = fs::read_to_string(temp_dir.path().join(".rgit/BISECT_BAD"))?;
+    assert!(output.starts_with(&format!("{} is the first bad commit", bad.trim())));
+    Ok(())
+}
+
+#[test]
+fn test_bisect_rejects_bad_revisions_and_commands_without_a_session() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+
+    for args in [vec!["bisect", "good"], vec!["bisect", "run", "true"]] {
+        let mut cmd = get_rgit_cmd();
+        cmd.current_dir(&temp_dir)
+            .args(args)
+            .assert()
+            .code(1)
+            .stderr("You need to start by \"git bisect start\"\n");
+    }
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["bisect", "log"])
+        .assert()
+        .code(1)
+        .stderr("error: We are not bisecting.\n");
+
+    // nothing is started when a revision cannot be resolved
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["bisect", "start", "HEAD", "nope"])
+        .assert()
+        .code(128)
+        .stderr("fatal: Bad rev input: nope\n");
+    assert!(!temp_dir.path().join(".rgit/BISECT_START").exists());
+
+    rgit(&temp_dir, &["bisect", "start"])?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["bisect", "run", "true"])
+        .assert()
+        .code(1)
+        .stderr(predicates::str::starts_with(
+            "You need to give me at least one good and one bad revision.\n",
+        ));
+    rgit(&temp_dir, &["bisect", "bad", "HEAD~9"])?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["bisect", "good", "HEAD"])
+        .assert()
+        .code(1)
+        .stderr(predicates::str::starts_with(
+            "Some good revs are not ancestors of the bad rev.\n",
+        ));
+    rgit(&temp_dir, &["bisect", "reset"])?;
+    assert_eq!(checked_out(&temp_dir)?, "9");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["bisect", "replay", "missing.log"])
+        .assert()
+        .code(128)
+        .stderr("fatal: cannot read file 'missing.log' for replaying\n");
+    fs::write(
+        temp_dir.path().join("bisect.log"),
+        "git bisect start\ngit bisect maybe HEAD\n",
+    )?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["bisect", "replay", "bisect.log"])
+        .assert()
+        .code(1)
+        .stderr("error: ?? what are you talking about? git bisect maybe HEAD\n");
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use tempdir::TempDir;
+
+use crate::setup::{get_rgit_cmd, rgit, setup_history};
+
+static MOVED: &str = "this line is long enough to be followed when it moves";
+
+/// Commit `a.txt` and `b.txt`, then a second commit that changes a line of `a.txt`, moves
+/// its last line to the top and copies the first line of `b.txt` into it, returning the
+/// oids of both
+fn setup_moves(temp_dir: &TempDir) -> Result<(String, String)> {
+    let oids = setup_history(
+        temp_dir,
+        &[
+            (
+                "First commit",
+                vec![
+                    ("a.txt", format!("one\ntwo\nthree\n{}\n", MOVED)),
+                    (
+                        "b.txt",
+                        "a line copied over from the other file\nb\n".to_owned(),
+                    ),
+                ],
+            ),
+            (
+                "Second commit",
+                vec![(
+                    "a.txt",
+                    format!(
+                        "{}\none\nTWO\nthree\na line copied over from the other file\n",
+                        MOVED
+                    ),
+                )],
+            ),
+        ],
+    )?;
+    Ok((oids[0].clone(), oids[1].clone()))
+}
+
+fn blamed_commits(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .map(|line| line.split(' ').next().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_blame_attributes_lines_to_commits() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    let (first, second) = setup_moves(&temp_dir)?;
+    let boundary = format!("^{}", &first[..7]);
+    let changed = second[..8].to_owned();
+
+    let output = rgit(&temp_dir, &["blame", "a.txt"])?;
+    assert_eq!(
+        blamed_commits(&output),
+        [&changed, &boundary, &changed, &boundary, &changed]
+    );
+    assert!(
+        output.lines().nth(2).unwrap().contains("(Test Author ")
+            && output.lines().nth(2).unwrap().ends_with(" 3) TWO")
+    );
+
+    let output = rgit(&temp_dir, &["blame", "-M", "a.txt"])?;
+    assert_eq!(
+        blamed_commits(&output),
+        [&boundary, &boundary, &changed, &boundary, &changed]
+    );
+
+    let output = rgit(&temp_dir, &["blame", "-C", "a.txt"])?;
+    assert_eq!(
+        blamed_commits(&output),
+        [&boundary, &boundary, &changed, &boundary, &boundary]
+    );
+    assert!(output
+        .lines()
+        .last()
+        .unwrap()
+        .starts_with(&format!("{} b.txt (", boundary)));
+    Ok(())
+}
+
+#[test]
+fn test_blame_porcelain_with_line_range() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    let (first, second) = setup_moves(&temp_dir)?;
+
+    let output = rgit(
+        &temp_dir,
+        &["blame", "--porcelain", "-L", "2,3", "HEAD", "a.txt"],
+    )?;
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], format!("{} 1 2 1", first));
+    assert!(lines.contains(&"author Test Author"));
+    assert!(lines.contains(&"summary First commit"));
+    assert!(lines.contains(&"boundary"));
+    assert!(lines.contains(&"filename a.txt"));
+    assert!(lines.contains(&"\tone"));
+    assert!(lines.contains(&format!("{} 3 3 1", second).as_str()));
+    assert!(lines.contains(&"summary Second commit"));
+    assert_eq!(lines.last(), Some(&"\tTWO"));
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["blame", "-L", "9,10", "a.txt"])
+        .assert()
+        .code(128);
+    Ok(())
+}
+
+#[test]
+fn test_blame_rejects_invalid_ranges_and_missing_paths() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_moves(&temp_dir)?;
+
+    for (range, error) in [
+        ("x,2", "fatal: invalid -L argument 'x,2'\n"),
+        ("2,+y", "fatal: invalid -L argument '2,+y'\n"),
+        ("9,10", "fatal: file a.txt has only 5 lines\n"),
+    ] {
+        let mut cmd = get_rgit_cmd();
+        cmd.current_dir(&temp_dir)
+            .args(["blame", "-L", range, "a.txt"])
+            .assert()
+            .code(128)
+            .stderr(error);
+    }
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["blame", "HEAD~1", "c.txt"])
+        .assert()
+        .code(128)
+        .stderr("fatal: no such path 'c.txt' in HEAD~1\n");
+    Ok(())
+}
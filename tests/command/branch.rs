@@ -315,10 +315,10 @@ fn test_revisions_in_branch_command() -> Result<()> {
     let refs = Refs::new(temp_dir.path().join(".rgit"));
     let all_commits = refs.get_all_commits()?;
 
-    let test_branch_1_content = refs.get_specific_ref_content("test-branch_1");
-    let test_branch_2_content = refs.get_specific_ref_content("test-branch_2");
-    let test_branch_3_content = refs.get_specific_ref_content("test-branch_3");
-    let test_branch_4_content = refs.get_specific_ref_content("test-branch_4");
+    let test_branch_1_content = refs.get_specific_ref_content("test-branch_1")?.unwrap();
+    let test_branch_2_content = refs.get_specific_ref_content("test-branch_2")?.unwrap();
+    let test_branch_3_content = refs.get_specific_ref_content("test-branch_3")?.unwrap();
+    let test_branch_4_content = refs.get_specific_ref_content("test-branch_4")?.unwrap();
 
     let head_oid = all_commits[0].oid.clone().unwrap();
     assert_eq!(test_branch_1_content, head_oid);
@@ -434,10 +434,10 @@ fn test_valid_plain_commit_based_branches() -> Result<()> {
         .assert()
         .success();
 
-    let test_branch_1_content = refs.get_specific_ref_content("test-branch_1");
-    let test_branch_2_content = refs.get_specific_ref_content("test-branch_2");
-    let test_branch_3_content = refs.get_specific_ref_content("test-branch_3");
-    let test_branch_4_content = refs.get_specific_ref_content("test-branch_4");
+    let test_branch_1_content = refs.get_specific_ref_content("test-branch_1")?.unwrap();
+    let test_branch_2_content = refs.get_specific_ref_content("test-branch_2")?.unwrap();
+    let test_branch_3_content = refs.get_specific_ref_content("test-branch_3")?.unwrap();
+    let test_branch_4_content = refs.get_specific_ref_content("test-branch_4")?.unwrap();
 
     let head_parent_oid = all_commits[1].oid.clone().unwrap();
     assert_eq!(test_branch_1_content, head_parent_oid);
@@ -557,10 +557,10 @@ fn test_valid_commit_based_reivsions() -> Result<()> {
         .assert()
         .success();
 
-    let test_branch_1_content = refs.get_specific_ref_content("test-branch_1");
-    let test_branch_2_content = refs.get_specific_ref_content("test-branch_2");
-    let test_branch_3_content = refs.get_specific_ref_content("test-branch_3");
-    let test_branch_4_content = refs.get_specific_ref_content("test-branch_4");
+    let test_branch_1_content = refs.get_specific_ref_content("test-branch_1")?.unwrap();
+    let test_branch_2_content = refs.get_specific_ref_content("test-branch_2")?.unwrap();
+    let test_branch_3_content = refs.get_specific_ref_content("test-branch_3")?.unwrap();
+    let test_branch_4_content = refs.get_specific_ref_content("test-branch_4")?.unwrap();
 
     let head_oid = all_commits[0].oid.clone().unwrap();
     assert_eq!(test_branch_1_content, head_oid);
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, setup_rgit};
+
+/// Create a repository with one commit holding `a.txt` and `d/b.txt`, returning the oid of
+/// the commit
+fn setup_commit(temp_dir: &TempDir) -> Result<String> {
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "hello\n")?;
+    fs::create_dir(temp_dir.path().join("d"))?;
+    fs::write(temp_dir.path().join("d/b.txt"), "b\n")?;
+    commit_all(temp_dir, "First commit");
+    let oid = fs::read_to_string(temp_dir.path().join(".rgit/refs/heads/master"))?;
+    Ok(oid.trim().to_owned())
+}
+
+fn cat_file(temp_dir: &TempDir, args: &[&str]) -> Result<String> {
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(temp_dir)
+        .arg("cat-file")
+        .args(args)
+        .assert()
+        .success();
+    Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+}
+
+fn cat_file_batch(temp_dir: &TempDir, mode: &str, input: &str) -> Result<String> {
+    let mut cmd = assert_cmd::Command::cargo_bin("rgit")?;
+    let assert = cmd
+        .current_dir(temp_dir)
+        .arg("cat-file")
+        .arg(mode)
+        .write_stdin(input)
+        .assert()
+        .success();
+    Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+}
+
+#[test]
+fn test_cat_file_type_size_and_content() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    let commit_oid = setup_commit(&temp_dir)?;
+
+    // hello\n is a well known blob
+    let blob_oid = "ce013625030ba8dba906f756967f9e9ca394464a";
+    assert_eq!(cat_file(&temp_dir, &["-t", blob_oid])?, "blob\n");
+    assert_eq!(cat_file(&temp_dir, &["-s", blob_oid])?, "6\n");
+    assert_eq!(cat_file(&temp_dir, &["-p", &blob_oid[..7]])?, "hello\n");
+
+    assert_eq!(cat_file(&temp_dir, &["-t", "HEAD"])?, "commit\n");
+    let commit = cat_file(&temp_dir, &["-p", &commit_oid])?;
+    assert!(commit.starts_with("tree "));
+    assert!(commit.ends_with("\n\nFirst commit\n"));
+
+    let tree_oid = &commit.lines().next().unwrap()[5..];
+    let tree = cat_file(&temp_dir, &["-p", tree_oid])?;
+    let lines = tree.lines().collect::<Vec<_>>();
+    // .rgitignore is committed along with the files
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with("\t.rgitignore"));
+    assert_eq!(lines[1], format!("100644 blob {}\ta.txt", blob_oid));
+    assert!(lines[2].starts_with("040000 tree "));
+    assert!(lines[2].ends_with("\td"));
+
+    Ok(())
+}
+
+#[test]
+fn test_cat_file_exists() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    let commit_oid = setup_commit(&temp_dir)?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("cat-file")
+        .arg("-e")
+        .arg(&commit_oid)
+        .assert()
+        .success()
+        .stdout("");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("cat-file")
+        .arg("-e")
+        .arg("0".repeat(40))
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("");
+
+    Ok(())
+}
+
+#[test]
+fn test_cat_file_batch_modes() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    let commit_oid = setup_commit(&temp_dir)?;
+    let blob_oid = "ce013625030ba8dba906f756967f9e9ca394464a";
+
+    let commit_size = cat_file(&temp_dir, &["-s", &commit_oid])?;
+    assert_eq!(
+        cat_file_batch(
+            &temp_dir,
+            "--batch-check",
+            &format!("master\nmissing\n{}\n", blob_oid)
+        )?,
+        format!(
+            "{} commit {}\nmissing missing\n{} blob 6\n",
+            commit_oid,
+            commit_size.trim(),
+            blob_oid
+        )
+    );
+
+    assert_eq!(
+        cat_file_batch(
+            &temp_dir,
+            "--batch",
+            &format!("{}\n{}\n", blob_oid, &blob_oid[..8])
+        )?,
+        format!("{0} blob 6\nhello\n\n{0} blob 6\nhello\n\n", blob_oid)
+    );
+
+    Ok(())
+}
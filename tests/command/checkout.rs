@@ -0,0 +1,284 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, rgit, setup_fs, setup_rgit};
+
+#[test]
+fn test_checkout_switches_branch_and_updates_workspace() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit").expect("Failed to create temp dir");
+    setup_fs(&temp_dir).expect("Failed to setup fs");
+    setup_rgit(&temp_dir.path().to_path_buf()).expect("Failed to setup rgit");
+    commit_all(&temp_dir, "Initial commit");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("branch")
+        .arg("topic")
+        .assert()
+        .success();
+
+    fs::write(temp_dir.path().join("a.txt"), "changed on master")?;
+    fs::remove_file(temp_dir.path().join("k/l/m/o.txt"))?;
+    fs::write(temp_dir.path().join("new.txt"), "new")?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("add")
+        .arg("k/l/m/o.txt")
+        .assert()
+        .success();
+    commit_all(&temp_dir, "Second commit");
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("checkout")
+        .arg("topic")
+        .assert()
+        .success();
+    let output = assert.get_output();
+    assert_eq!(
+        String::from_utf8(output.stderr.clone())?,
+        "Switched to branch 'topic'\n"
+    );
+
+    assert_eq!(fs::read_to_string(temp_dir.path().join("a.txt"))?, "a");
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("k/l/m/o.txt"))?,
+        "o"
+    );
+    assert!(!temp_dir.path().join("new.txt").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join(".rgit/HEAD"))?,
+        "ref: refs/heads/topic\n"
+    );
+
+    // the index follows the workspace, so nothing shows up as changed
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir).arg("status").assert().success();
+    let output = cmd.output().expect("Failed to run command");
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "Untracked files:\nChanges to be committed:\nChanged not staged for commit:\n"
+    );
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("switch")
+        .arg("master")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt"))?,
+        "changed on master"
+    );
+    assert!(!temp_dir.path().join("k/l/m/o.txt").exists());
+    assert_eq!(fs::read_to_string(temp_dir.path().join("new.txt"))?, "new");
+
+    Ok(())
+}
+
+#[test]
+fn test_checkout_with_new_branch() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit").expect("Failed to create temp dir");
+    setup_fs(&temp_dir).expect("Failed to setup fs");
+    setup_rgit(&temp_dir.path().to_path_buf()).expect("Failed to setup rgit");
+    commit_all(&temp_dir, "Initial commit");
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("checkout")
+        .arg("-b")
+        .arg("topic")
+        .assert()
+        .success();
+    let output = assert.get_output();
+    assert_eq!(
+        String::from_utf8(output.stderr.clone())?,
+        "Switched to a new branch 'topic'\n"
+    );
+
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join(".rgit/HEAD"))?,
+        "ref: refs/heads/topic\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join(".rgit/refs/heads/topic"))?,
+        fs::read_to_string(temp_dir.path().join(".rgit/refs/heads/master"))?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_checkout_refuses_to_overwrite_local_changes() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit").expect("Failed to create temp dir");
+    setup_fs(&temp_dir).expect("Failed to setup fs");
+    setup_rgit(&temp_dir.path().to_path_buf()).expect("Failed to setup rgit");
+    commit_all(&temp_dir, "Initial commit");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("checkout")
+        .arg("-b")
+        .arg("topic")
+        .assert()
+        .success();
+    fs::write(temp_dir.path().join("a.txt"), "changed on topic")?;
+    commit_all(&temp_dir, "Topic commit");
+
+    fs::write(temp_dir.path().join("a.txt"), "uncommitted")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("checkout")
+        .arg("master")
+        .assert()
+        .failure();
+    let output = cmd.output().expect("Failed to run command");
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        "error: Your local changes to the following files would be overwritten by checkout:\n\ta.txt\nPlease commit your changes or stash them before you switch branches.\nAborting\n"
+    );
+
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt"))?,
+        "uncommitted"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join(".rgit/HEAD"))?,
+        "ref: refs/heads/topic\n"
+    );
+    assert!(!temp_dir.path().join(".rgit/index.lock").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_checkout_new_branch_aborted_by_local_changes_is_not_created() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit").expect("Failed to create temp dir");
+    setup_fs(&temp_dir).expect("Failed to setup fs");
+    setup_rgit(&temp_dir.path().to_path_buf()).expect("Failed to setup rgit");
+    commit_all(&temp_dir, "Initial commit");
+    fs::write(temp_dir.path().join("a.txt"), "changed on master")?;
+    commit_all(&temp_dir, "Second commit");
+
+    fs::write(temp_dir.path().join("a.txt"), "uncommitted")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("checkout")
+        .arg("-b")
+        .arg("topic")
+        .arg("HEAD~1")
+        .assert()
+        .failure();
+    let output = cmd.output().expect("Failed to run command");
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        "error: Your local changes to the following files would be overwritten by checkout:\n\ta.txt\nPlease commit your changes or stash them before you switch branches.\nAborting\n"
+    );
+
+    assert!(!temp_dir.path().join(".rgit/refs/heads/topic").exists());
+    assert!(!temp_dir.path().join(".rgit/logs/refs/heads/topic").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join(".rgit/HEAD"))?,
+        "ref: refs/heads/master\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt"))?,
+        "uncommitted"
+    );
+
+    // so the same checkout can be run again once the changes are out of the way
+    fs::write(temp_dir.path().join("a.txt"), "changed on master")?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("checkout")
+        .arg("-b")
+        .arg("topic")
+        .arg("HEAD~1")
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join("a.txt"))?, "a");
+
+    Ok(())
+}
+
+#[test]
+fn test_checkout_commit_detaches_head() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit").expect("Failed to create temp dir");
+    setup_fs(&temp_dir).expect("Failed to setup fs");
+    setup_rgit(&temp_dir.path().to_path_buf()).expect("Failed to setup rgit");
+    commit_all(&temp_dir, "Initial commit");
+    let first_oid = fs::read_to_string(temp_dir.path().join(".rgit/refs/heads/master"))?;
+
+    fs::write(temp_dir.path().join("b.txt"), "second")?;
+    commit_all(&temp_dir, "Second commit");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("checkout")
+        .arg("HEAD^")
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join(".rgit/HEAD"))?,
+        first_oid
+    );
+    assert_eq!(fs::read_to_string(temp_dir.path().join("b.txt"))?, "b");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir).arg("branch").assert().success();
+    let output = cmd.output().expect("Failed to run command");
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        format!("* (HEAD detached at {})\nmaster\n", &first_oid[..7])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_checkout_replaces_a_directory_with_a_file_and_back() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::create_dir_all(temp_dir.path().join("d/e"))?;
+    fs::write(temp_dir.path().join("d/e/f.txt"), "f")?;
+    fs::write(temp_dir.path().join("d/g.txt"), "g")?;
+    commit_all(&temp_dir, "Directory d");
+    rgit(&temp_dir, &["checkout", "-b", "file"])?;
+    rgit(&temp_dir, &["rm", "-r", "d"])?;
+    fs::write(temp_dir.path().join("d"), "d")?;
+    commit_all(&temp_dir, "File d");
+
+    rgit(&temp_dir, &["checkout", "master"])?;
+    assert_eq!(fs::read_to_string(temp_dir.path().join("d/e/f.txt"))?, "f");
+    assert_eq!(fs::read_to_string(temp_dir.path().join("d/g.txt"))?, "g");
+    rgit(&temp_dir, &["checkout", "file"])?;
+    assert_eq!(fs::read_to_string(temp_dir.path().join("d"))?, "d");
+    assert_eq!(
+        rgit(&temp_dir, &["status"])?,
+        "Untracked files:\nChanges to be committed:\nChanged not staged for commit:\n"
+    );
+
+    // an untracked file in the directory is still in the way
+    rgit(&temp_dir, &["checkout", "master"])?;
+    fs::write(temp_dir.path().join("d/e/new.txt"), "new")?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["checkout", "file"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "error: The following untracked working tree files would be overwritten by checkout:\n\td\n",
+        ));
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("d/e/new.txt"))?,
+        "new"
+    );
+    Ok(())
+}
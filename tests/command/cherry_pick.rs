@@ -0,0 +1,197 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, read_ref, rgit, setup_rgit};
+
+use rgit::database::{Database, ParsedContent};
+
+/// Create `master` with a base commit and a commit changing `f.txt`, and `topic` branching
+/// off the base with commits adding `g.txt` and changing `f.txt`
+fn setup_branches(temp_dir: &TempDir) -> Result<()> {
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("f.txt"), "1\n2\n3\n")?;
+    commit_all(temp_dir, "Base commit");
+    rgit(temp_dir, &["checkout", "-b", "topic"])?;
+    fs::write(temp_dir.path().join("g.txt"), "g\n")?;
+    commit_all(temp_dir, "Add g");
+    fs::write(temp_dir.path().join("f.txt"), "1\ntopic\n3\n")?;
+    commit_all(temp_dir, "Change f on topic");
+    rgit(temp_dir, &["checkout", "master"])?;
+    fs::write(temp_dir.path().join("f.txt"), "1\nmaster\n3\n")?;
+    commit_all(temp_dir, "Change f on master");
+    Ok(())
+}
+
+#[test]
+fn test_cherry_pick_keeps_author_and_records_origin() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+    let head_oid = read_ref(&temp_dir, "refs/heads/master")?;
+    let picked_oid = rgit(&temp_dir, &["rev-parse", "topic~1"])?
+        .trim()
+        .to_owned();
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .env("RGIT_AUTHOR_NAME", "Someone Else")
+        .args(["cherry-pick", "topic~1"])
+        .assert()
+        .success();
+    let output = String::from_utf8(assert.get_output().stdout.clone())?;
+    assert!(output.starts_with("[master "));
+    assert!(output.ends_with("] Add g\n"));
+    assert_eq!(fs::read_to_string(temp_dir.path().join("g.txt"))?, "g\n");
+    assert!(!temp_dir.path().join(".rgit/sequencer").exists());
+
+    let database = Database::new(temp_dir.path().join(".rgit/objects"));
+    match database.read_object(&read_ref(&temp_dir, "refs/heads/master")?)? {
+        ParsedContent::CommitContent(commit) => {
+            assert_eq!(commit.parents, vec![head_oid]);
+            assert_eq!(commit.author.name, "Test Author");
+            assert_eq!(
+                commit.message,
+                format!("Add g\n\n(cherry picked from commit {})", picked_oid)
+            );
+        }
+        _ => panic!("expected a commit"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_cherry_pick_conflict_and_continue() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+    let picked_oid = rgit(&temp_dir, &["rev-parse", "topic"])?.trim().to_owned();
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .args(["cherry-pick", "topic", "topic~1"])
+        .assert()
+        .failure();
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone())?,
+        "Auto-merging f.txt\nCONFLICT (content): Merge conflict in f.txt\n"
+    );
+    assert!(String::from_utf8(assert.get_output().stderr.clone())?
+        .starts_with("error: could not apply "));
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("f.txt"))?,
+        format!(
+            "1\n<<<<<<< HEAD\nmaster\n=======\ntopic\n>>>>>>> {} (Change f on topic)\n3\n",
+            &picked_oid[..7]
+        )
+    );
+    assert_eq!(read_ref(&temp_dir, "CHERRY_PICK_HEAD")?, picked_oid);
+
+    // a new cherry-pick is refused until this one is finished
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["cherry-pick", "topic"])
+        .assert()
+        .code(128);
+
+    fs::write(temp_dir.path().join("f.txt"), "1\nresolved\n3\n")?;
+    rgit(&temp_dir, &["add", "f.txt"])?;
+    let output = rgit(&temp_dir, &["cherry-pick", "--continue"])?;
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with("] Change f on topic"));
+    assert!(lines[1].ends_with("] Add g"));
+    assert!(!temp_dir.path().join(".rgit/CHERRY_PICK_HEAD").exists());
+    assert!(!temp_dir.path().join(".rgit/sequencer").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("f.txt"))?,
+        "1\nresolved\n3\n"
+    );
+    assert_eq!(fs::read_to_string(temp_dir.path().join("g.txt"))?, "g\n");
+    Ok(())
+}
+
+#[test]
+fn test_cherry_pick_stops_on_applied_changes_and_operations_in_progress() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+    let picked_oid = rgit(&temp_dir, &["rev-parse", "topic~1"])?
+        .trim()
+        .to_owned();
+    rgit(&temp_dir, &["cherry-pick", "topic~1"])?;
+    let head_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    // the change is already there, which makes nothing to commit
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["cherry-pick", "topic~1"])
+        .assert()
+        .code(1)
+        .stdout(format!(
+            "On branch master\nYou are currently cherry-picking commit {}.\n\nnothing to commit, working tree clean\n",
+            &picked_oid[..7]
+        ));
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, head_oid);
+    assert_eq!(read_ref(&temp_dir, "CHERRY_PICK_HEAD")?, picked_oid);
+
+    // a new cherry-pick is refused while this one is going on, and continuing skips it
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["cherry-pick", "topic"])
+        .assert()
+        .code(128)
+        .stderr(
+            "error: a cherry-pick or revert is already in progress\nhint: try \"rgit cherry-pick (--continue | --abort)\"\nfatal: cherry-pick failed\n",
+        );
+    assert_eq!(rgit(&temp_dir, &["cherry-pick", "--continue"])?, "");
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, head_oid);
+    assert!(!temp_dir.path().join(".rgit/CHERRY_PICK_HEAD").exists());
+    assert!(!temp_dir.path().join(".rgit/sequencer").exists());
+
+    // a merge waiting for its conflicts to be resolved is named as the one in progress
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["merge", "topic"])
+        .assert()
+        .failure();
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["cherry-pick", "topic"])
+        .assert()
+        .code(128)
+        .stderr("fatal: You have not concluded your merge (MERGE_HEAD exists).\n");
+    assert!(!temp_dir.path().join(".rgit/sequencer").exists());
+    Ok(())
+}
+
+#[test]
+fn test_cherry_pick_refuses_staged_changes() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+    let head_oid = read_ref(&temp_dir, "refs/heads/master")?;
+    fs::write(temp_dir.path().join("h.txt"), "h\n")?;
+    rgit(&temp_dir, &["add", "h.txt"])?;
+
+    // the staged file has nothing to do with the picked commit, but would end up in it
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["cherry-pick", "topic~1"])
+        .assert()
+        .code(128)
+        .stderr("error: your local changes would be overwritten by cherry-pick.\nhint: commit your changes or stash them to proceed.\nfatal: cherry-pick failed\n");
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, head_oid);
+    assert!(!temp_dir.path().join("g.txt").exists());
+    assert!(!temp_dir.path().join(".rgit/sequencer").exists());
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["revert", "HEAD"])
+        .assert()
+        .code(128)
+        .stderr(predicates::str::starts_with(
+            "error: your local changes would be overwritten by revert.\n",
+        ));
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, head_oid);
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, rgit, setup_rgit};
+
+/// Commit `src/a.rs` with `build` on the ignore list, then add untracked files next to it,
+/// in an untracked directory and in the ignored directory
+fn setup_files(temp_dir: &TempDir) -> Result<()> {
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    let root = temp_dir.path();
+    fs::write(root.join(".rgitignore"), ".git/\nbuild\n")?;
+    fs::create_dir_all(root.join("src"))?;
+    fs::write(root.join("src/a.rs"), "a\n")?;
+    commit_all(temp_dir, "Initial commit");
+
+    fs::create_dir_all(root.join("build"))?;
+    fs::create_dir_all(root.join("new/inner"))?;
+    fs::write(root.join("src/u.rs"), "u\n")?;
+    fs::write(root.join("build/out"), "out\n")?;
+    fs::write(root.join("new/inner/n.txt"), "n\n")?;
+    fs::write(root.join("notes.tmp"), "tmp\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_clean_requires_force_and_lists_with_dry_run() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir).arg("clean").assert().code(128);
+
+    assert_eq!(
+        rgit(&temp_dir, &["clean", "-n"])?,
+        "Would remove notes.tmp\nWould remove src/u.rs\n"
+    );
+    assert_eq!(
+        rgit(&temp_dir, &["clean", "-n", "-d", "-e", "*.tmp"])?,
+        "Would remove new/\nWould remove src/u.rs\n"
+    );
+    assert_eq!(
+        rgit(&temp_dir, &["clean", "-n", "-d", "-x"])?,
+        "Would remove build/\nWould remove new/\nWould remove notes.tmp\nWould remove src/u.rs\n"
+    );
+    assert_eq!(
+        rgit(&temp_dir, &["clean", "-n", "-d", "-X"])?,
+        "Would remove build/\n"
+    );
+    assert_eq!(
+        rgit(&temp_dir, &["clean", "-n", "src"])?,
+        "Would remove src/u.rs\n"
+    );
+    assert!(temp_dir.path().join("src/u.rs").exists());
+    Ok(())
+}
+
+#[test]
+fn test_clean_force_removes_untracked_files() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+
+    assert_eq!(
+        rgit(&temp_dir, &["clean", "-f", "-d"])?,
+        "Removing new/\nRemoving notes.tmp\nRemoving src/u.rs\n"
+    );
+    let root = temp_dir.path();
+    assert!(!root.join("new").exists());
+    assert!(!root.join("notes.tmp").exists());
+    assert!(!root.join("src/u.rs").exists());
+    assert!(root.join("src/a.rs").exists());
+    assert!(root.join("build/out").exists());
+    assert!(root.join(".rgit/HEAD").exists());
+    Ok(())
+}
+
+#[test]
+fn test_clean_removes_symlinks_without_following_them() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+    let root = temp_dir.path();
+    let outside = TempDir::new("test_rgit_outside")?;
+    fs::write(outside.path().join("a"), "a\n")?;
+    std::os::unix::fs::symlink(outside.path(), root.join("link"))?;
+    std::os::unix::fs::symlink(".rgit", root.join("meta"))?;
+    // a nested repository is left alone
+    fs::create_dir_all(root.join("nested/.rgit"))?;
+    fs::write(root.join("nested/file"), "nested\n")?;
+
+    assert_eq!(
+        rgit(&temp_dir, &["clean", "-n", "-d", "-e", "HEAD"])?,
+        "Would remove link\nWould remove meta\nWould remove new/\nWould remove notes.tmp\nWould remove src/u.rs\n"
+    );
+    rgit(&temp_dir, &["clean", "-f", "-d", "-e", "b"])?;
+    assert!(fs::symlink_metadata(root.join("link")).is_err());
+    assert!(fs::symlink_metadata(root.join("meta")).is_err());
+    assert!(outside.path().join("a").exists());
+    assert!(root.join(".rgit/HEAD").exists());
+    assert!(root.join(".rgit/index").exists());
+    assert!(root.join("nested/file").exists());
+    assert_eq!(rgit(&temp_dir, &["ls-files"])?, ".rgitignore\nsrc/a.rs\n");
+    Ok(())
+}
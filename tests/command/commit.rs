@@ -1,4 +1,4 @@
-use crate::setup::{get_git_cmd, get_rgit_cmd, setup_git, setup_rgit};
+use crate::setup::{commit_all, get_git_cmd, get_rgit_cmd, setup_git, setup_rgit};
 use anyhow::Result;
 use assert_cmd::prelude::*;
 use std::{fs, process::Command};
@@ -163,3 +163,76 @@ fn test_commit_chaining() {
     let output = String::from_utf8_lossy(&output.stdout);
     assert_eq!(output.split("\n").count(), 12);
 }
+
+fn read_head_commit(temp_dir: &TempDir) -> Result<rgit::database::Commit> {
+    let refs = rgit::refs::Refs::new(temp_dir.path().join(".rgit"));
+    let database = rgit::database::Database::new(temp_dir.path().join(".rgit/objects"));
+    database.read_commit(&refs.read_head().expect("HEAD should point at a commit"))
+}
+
+#[test]
+fn test_commit_amend_keeps_parents_message_and_author() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "a")?;
+    commit_all(&temp_dir, "First commit");
+    fs::write(temp_dir.path().join("b.txt"), "b")?;
+    commit_all(&temp_dir, "Secnod commit");
+    let amended = read_head_commit(&temp_dir)?;
+
+    fs::write(temp_dir.path().join("c.txt"), "c")?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["add", "c.txt"])
+        .assert()
+        .success();
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .env("RGIT_AUTHOR_NAME", "Someone Else")
+        .env("GIT_EDITOR", "sed -i 's/Secnod/Second/'")
+        .args(["commit", "--amend"])
+        .assert()
+        .success();
+
+    let commit = read_head_commit(&temp_dir)?;
+    assert_eq!(commit.message, "Second commit");
+    assert_eq!(commit.parents, amended.parents);
+    assert_eq!(commit.author.name, "Test Author");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .env("RGIT_AUTHOR_NAME", "Someone Else")
+        .args(["commit", "--amend", "--no-edit", "--reset-author"])
+        .assert()
+        .success();
+    let commit = read_head_commit(&temp_dir)?;
+    assert_eq!(commit.message, "Second commit");
+    assert_eq!(commit.parents, amended.parents);
+    assert_eq!(commit.author.name, "Someone Else");
+    Ok(())
+}
+
+#[test]
+fn test_commit_amend_warns_about_other_branches() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "a")?;
+    commit_all(&temp_dir, "First commit");
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["branch", "keep"])
+        .assert()
+        .success();
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .args(["commit", "--amend", "-m", "Amended"])
+        .assert()
+        .success();
+    let stderr = String::from_utf8(assert.get_output().stderr.clone())?;
+    assert!(stderr.starts_with("warning: the amended commit "));
+    assert!(stderr.contains("branch 'keep'"));
+    assert_eq!(read_head_commit(&temp_dir)?.message, "Amended");
+    Ok(())
+}
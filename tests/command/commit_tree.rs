@@ -0,0 +1,73 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, setup_fs, setup_rgit};
+
+fn rgit_output(temp_dir: &TempDir, args: &[&str]) -> Result<String> {
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd.current_dir(temp_dir).args(args).assert().success();
+    Ok(String::from_utf8(assert.get_output().stdout.clone())?
+        .trim()
+        .to_owned())
+}
+
+#[test]
+fn test_commit_tree_creates_commit_with_parents() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    setup_fs(&temp_dir)?;
+    commit_all(&temp_dir, "First commit");
+
+    let master = fs::read_to_string(temp_dir.path().join(".rgit/refs/heads/master"))?;
+    let master = master.trim();
+    let head = rgit_output(&temp_dir, &["cat-file", "-p", "HEAD"])?;
+    let tree = head.lines().next().unwrap()[5..].to_owned();
+
+    let commit = rgit_output(
+        &temp_dir,
+        &[
+            "commit-tree",
+            &tree,
+            "-p",
+            "HEAD",
+            "-m",
+            "Subject",
+            "-m",
+            "Body",
+        ],
+    )?;
+    let content = rgit_output(&temp_dir, &["cat-file", "-p", &commit])?;
+    let lines = content.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], format!("tree {}", tree));
+    assert_eq!(lines[1], format!("parent {}", master));
+    assert!(lines[2].starts_with("author Test Author <test@example.com>"));
+    assert!(content.ends_with("\n\nSubject\n\nBody"));
+
+    // commit-tree never moves any ref
+    let after = fs::read_to_string(temp_dir.path().join(".rgit/refs/heads/master"))?;
+    assert_eq!(after.trim(), master);
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_tree_rejects_non_tree() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    setup_fs(&temp_dir)?;
+    commit_all(&temp_dir, "First commit");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("commit-tree")
+        .arg("HEAD")
+        .arg("-m")
+        .arg("message")
+        .assert()
+        .code(128)
+        .stderr("fatal: HEAD is not a valid 'tree' object\n");
+
+    Ok(())
+}
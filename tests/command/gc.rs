@@ -0,0 +1,162 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, git, rgit, setup_rgit, write_blob};
+
+fn pack_names(temp_dir: &TempDir) -> Result<Vec<String>> {
+    let mut names = fs::read_dir(temp_dir.path().join(".rgit/objects/pack"))?
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    names.sort();
+    Ok(names)
+}
+
+#[test]
+fn test_gc_consolidates_packs_and_loose_objects() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "a\n")?;
+    commit_all(&temp_dir, "first");
+    // a pack written by git, next to objects rgit keeps writing loose
+    git(&temp_dir, &["repack", "-a", "-d", "-q"])?;
+    fs::write(temp_dir.path().join("b.txt"), "b\n")?;
+    commit_all(&temp_dir, "second");
+    rgit(&temp_dir, &["repack", "-q"])?;
+    fs::write(temp_dir.path().join("c.txt"), "c\n")?;
+    commit_all(&temp_dir, "third");
+    assert_eq!(pack_names(&temp_dir)?.len(), 4);
+
+    rgit(&temp_dir, &["gc", "--quiet"])?;
+    let names = pack_names(&temp_dir)?;
+    assert_eq!(names.len(), 2);
+    assert!(names[0].starts_with("pack-") && names[0].ends_with(".idx"));
+    let objects = fs::read_dir(temp_dir.path().join(".rgit/objects"))?
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.len() == 2)
+        .collect::<Vec<_>>();
+    assert!(objects.is_empty());
+
+    assert_eq!(rgit(&temp_dir, &["show", "HEAD~2:a.txt"])?, "a\n");
+    assert_eq!(rgit(&temp_dir, &["show", "HEAD~1:b.txt"])?, "b\n");
+    assert_eq!(rgit(&temp_dir, &["show", "HEAD:c.txt"])?, "c\n");
+
+    // a second run has the same objects to write, so it ends up with the same pack
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("gc")
+        .assert()
+        .success()
+        .stderr(predicates::str::starts_with(
+            "Enumerating objects: 10, done.\n",
+        ));
+    assert_eq!(pack_names(&temp_dir)?, names);
+    Ok(())
+}
+
+#[test]
+fn test_gc_prunes_unreachable_loose_objects() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "a\n")?;
+    commit_all(&temp_dir, "first");
+    let unreachable = write_blob(&temp_dir, "unreachable\n")?;
+    let object_path = temp_dir
+        .path()
+        .join(".rgit/objects")
+        .join(&unreachable[0..2])
+        .join(&unreachable[2..]);
+
+    // too recent to go by default
+    rgit(&temp_dir, &["gc", "-q"])?;
+    assert!(object_path.exists());
+    rgit(&temp_dir, &["gc", "-q", "--prune=now"])?;
+    assert!(!object_path.exists());
+    assert_eq!(rgit(&temp_dir, &["show", "HEAD:a.txt"])?, "a\n");
+    Ok(())
+}
+
+#[test]
+fn test_gc_keeps_the_commits_of_operations_in_progress() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "a\n")?;
+    commit_all(&temp_dir, "first");
+    let tree = rgit(&temp_dir, &["write-tree"])?;
+    let commit = |message: &str| -> Result<String> {
+        let oid = rgit(&temp_dir, &["commit-tree", tree.trim(), "-m", message])?;
+        Ok(oid.trim().to_owned())
+    };
+    let (orig, merge, head, todo) = (
+        commit("orig")?,
+        commit("merge")?,
+        commit("head")?,
+        commit("todo")?,
+    );
+    let dropped = commit("dropped")?;
+
+    let git_path = temp_dir.path().join(".rgit");
+    fs::write(git_path.join("ORIG_HEAD"), format!("{}\n", orig))?;
+    fs::write(git_path.join("MERGE_HEAD"), format!("{}\n", merge))?;
+    fs::create_dir_all(git_path.join("sequencer"))?;
+    fs::write(git_path.join("sequencer/head"), format!("{}\n", head))?;
+    fs::write(git_path.join("sequencer/done"), "")?;
+    fs::write(
+        git_path.join("sequencer/todo"),
+        format!("pick {} todo\n", &todo[..7]),
+    )?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["gc", "--prune=soon"])
+        .assert()
+        .code(129)
+        .stderr("error: invalid timestamp 'soon' given to '--prune'\n");
+    assert_eq!(rgit(&temp_dir, &["cat-file", "-t", &dropped])?, "commit\n");
+
+    rgit(&temp_dir, &["gc", "-q", "--prune=now"])?;
+    for oid in [&orig, &merge, &head, &todo] {
+        assert_eq!(rgit(&temp_dir, &["cat-file", "-t", oid])?, "commit\n");
+    }
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["cat-file", "-e", &dropped])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+#[test]
+fn test_gc_gives_unreachable_packed_objects_the_prune_grace_period() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "a\n")?;
+    commit_all(&temp_dir, "first");
+    // packed while a tag pointed to it, then left unreachable in the pack
+    let unreachable = write_blob(&temp_dir, "unreachable\n")?;
+    fs::create_dir_all(temp_dir.path().join(".rgit/refs/tags"))?;
+    let tag_path = temp_dir.path().join(".rgit/refs/tags/blob");
+    fs::write(&tag_path, format!("{}\n", unreachable))?;
+    rgit(&temp_dir, &["repack", "-a", "-d", "-q"])?;
+    fs::remove_file(&tag_path)?;
+    let object_path = temp_dir
+        .path()
+        .join(".rgit/objects")
+        .join(&unreachable[0..2])
+        .join(&unreachable[2..]);
+    assert!(!object_path.exists());
+
+    rgit(&temp_dir, &["gc", "-q", "--prune=never"])?;
+    assert!(object_path.exists());
+    assert_eq!(
+        rgit(&temp_dir, &["cat-file", "-p", &unreachable])?,
+        "unreachable\n"
+    );
+    rgit(&temp_dir, &["gc", "-q"])?;
+    assert!(object_path.exists());
+    rgit(&temp_dir, &["gc", "-q", "--prune=now"])?;
+    assert!(!object_path.exists());
+    assert_eq!(rgit(&temp_dir, &["show", "HEAD:a.txt"])?, "a\n");
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, rgit, setup_rgit};
+
+/// Commit a source file, a text file and a binary file, then change the text file in the
+/// working tree and add an untracked file
+fn setup_files(temp_dir: &TempDir) -> Result<()> {
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::create_dir(temp_dir.path().join("src"))?;
+    fs::write(
+        temp_dir.path().join("src/main.rs"),
+        "fn main() {\n    println!(\"Hello\");\n}\nhello(world)\n",
+    )?;
+    fs::write(temp_dir.path().join("notes.txt"), "Hello there\nfoo+bar\n")?;
+    fs::write(temp_dir.path().join("data.bin"), b"Hello\0binary")?;
+    commit_all(temp_dir, "Initial commit");
+    fs::write(
+        temp_dir.path().join("notes.txt"),
+        "Hello there\nfoo+bar\nHello again\n",
+    )?;
+    fs::write(temp_dir.path().join("untracked.txt"), "Hello untracked\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_grep_searches_tracked_files_in_working_tree() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+
+    assert_eq!(
+        rgit(&temp_dir, &["grep", "Hello"])?,
+        "Binary file data.bin matches\nnotes.txt:Hello there\nnotes.txt:Hello again\nsrc/main.rs:    println!(\"Hello\");\n"
+    );
+    assert_eq!(
+        rgit(&temp_dir, &["grep", "-n", "-i", "-w", "hello", "--", "src"])?,
+        "src/main.rs:2:    println!(\"Hello\");\nsrc/main.rs:4:hello(world)\n"
+    );
+    assert_eq!(rgit(&temp_dir, &["grep", "-l", "foo+bar"])?, "notes.txt\n");
+    assert_eq!(
+        rgit(&temp_dir, &["grep", "-F", "hello(world)"])?,
+        "src/main.rs:hello(world)\n"
+    );
+
+    // in an extended expression `+` repeats, so `foo+bar` no longer matches itself
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["grep", "-E", "foo+bar"])
+        .assert()
+        .code(1);
+    Ok(())
+}
+
+#[test]
+fn test_grep_cached_and_revisions() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+
+    assert_eq!(
+        rgit(&temp_dir, &["grep", "--cached", "-c", "Hello"])?,
+        "data.bin:1\nnotes.txt:1\nsrc/main.rs:1\n"
+    );
+    assert_eq!(
+        rgit(&temp_dir, &["grep", "-n", "Hello", "HEAD", "notes.txt"])?,
+        "HEAD:notes.txt:1:Hello there\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_grep_word_boundaries_and_revisions_told_from_paths() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+
+    assert_eq!(
+        rgit(&temp_dir, &["grep", r"\<there\>"])?,
+        "notes.txt:Hello there\n"
+    );
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["grep", r"\<ello"])
+        .assert()
+        .code(1);
+
+    // a file named like a branch needs `--` to say which one is meant
+    rgit(&temp_dir, &["branch", "notes"])?;
+    fs::write(temp_dir.path().join("notes"), "Hello notes\n")?;
+    rgit(&temp_dir, &["add", "notes"])?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["grep", "Hello", "notes"])
+        .assert()
+        .code(128)
+        .stderr(predicates::str::starts_with(
+            "fatal: ambiguous argument 'notes': both revision and filename\nUse '--' to separate",
+        ));
+    assert_eq!(
+        rgit(&temp_dir, &["grep", "Hello", "--", "notes"])?,
+        "notes:Hello notes\n"
+    );
+    assert_eq!(
+        rgit(&temp_dir, &["grep", "Hello", "notes", "--", "notes.txt"])?,
+        "notes:notes.txt:Hello there\n"
+    );
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["grep", "Hello", "missing"])
+        .assert()
+        .code(128)
+        .stderr(predicates::str::starts_with(
+            "fatal: ambiguous argument 'missing': unknown revision or path not in the working tree.\n",
+        ));
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["grep", "Hello", "notes.txt", "--", "src"])
+        .assert()
+        .code(128);
+    Ok(())
+}
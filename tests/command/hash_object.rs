@@ -0,0 +1,81 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{get_rgit_cmd, setup_rgit};
+
+#[test]
+fn test_hash_object_computes_oid_without_writing() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "hello\n")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("hash-object")
+        .arg("a.txt")
+        .assert()
+        .success()
+        .stdout("ce013625030ba8dba906f756967f9e9ca394464a\n");
+    assert!(!temp_dir.path().join(".rgit/objects/ce").exists());
+
+    let mut cmd = assert_cmd::Command::cargo_bin("rgit")?;
+    cmd.current_dir(&temp_dir)
+        .arg("hash-object")
+        .arg("-t")
+        .arg("commit")
+        .arg("--stdin")
+        .write_stdin("hello\n")
+        .assert()
+        .success()
+        .stdout("656d88de433ec9f9c5d4ed9b2c643844127a0fb4\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_object_writes_blob() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+
+    let mut cmd = assert_cmd::Command::cargo_bin("rgit")?;
+    cmd.current_dir(&temp_dir)
+        .arg("hash-object")
+        .arg("-w")
+        .arg("--stdin")
+        .write_stdin("hello\n")
+        .assert()
+        .success()
+        .stdout("ce013625030ba8dba906f756967f9e9ca394464a\n");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("cat-file")
+        .arg("-p")
+        .arg("ce013625030ba8dba906f756967f9e9ca394464a")
+        .assert()
+        .success()
+        .stdout("hello\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_object_rejects_unknown_type() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "hello\n")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("hash-object")
+        .arg("-t")
+        .arg("thing")
+        .arg("a.txt")
+        .assert()
+        .code(128)
+        .stderr("fatal: invalid object type \"thing\"\n");
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, setup_rgit};
+
+fn ls_files(temp_dir: &TempDir, args: &[&str]) -> Result<String> {
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(temp_dir)
+        .arg("ls-files")
+        .args(args)
+        .assert()
+        .success();
+    Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+}
+
+/// Commit `a.txt` and the executable `d/run.sh`, with `build` on the ignore list
+fn setup_files(temp_dir: &TempDir) -> Result<()> {
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join(".rgitignore"), ".git/\nbuild\n")?;
+    fs::write(temp_dir.path().join("a.txt"), "hello\n")?;
+    fs::create_dir(temp_dir.path().join("d"))?;
+    fs::write(temp_dir.path().join("d/run.sh"), "run")?;
+    let mut cmd = std::process::Command::new("chmod");
+    cmd.arg("+x")
+        .arg(temp_dir.path().join("d/run.sh"))
+        .assert()
+        .success();
+    commit_all(temp_dir, "First commit");
+    Ok(())
+}
+
+#[test]
+fn test_ls_files_lists_index_entries() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+
+    assert_eq!(ls_files(&temp_dir, &[])?, ".rgitignore\na.txt\nd/run.sh\n");
+
+    let stage = ls_files(&temp_dir, &["--stage"])?;
+    let lines = stage.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[1],
+        "100644 ce013625030ba8dba906f756967f9e9ca394464a 0\ta.txt"
+    );
+    assert!(lines[2].starts_with("100755 "));
+    assert!(lines[2].ends_with(" 0\td/run.sh"));
+
+    Ok(())
+}
+
+#[test]
+fn test_ls_files_compares_with_working_tree() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+
+    fs::write(temp_dir.path().join("a.txt"), "changed\n")?;
+    fs::remove_file(temp_dir.path().join("d/run.sh"))?;
+    fs::write(temp_dir.path().join("new.txt"), "new")?;
+    fs::create_dir(temp_dir.path().join("build"))?;
+    fs::write(temp_dir.path().join("build/out"), "out")?;
+
+    assert_eq!(ls_files(&temp_dir, &["--deleted"])?, "d/run.sh\n");
+    // deleted files count as modified too
+    assert_eq!(ls_files(&temp_dir, &["--modified"])?, "a.txt\nd/run.sh\n");
+    assert_eq!(ls_files(&temp_dir, &["--others"])?, "new.txt\n");
+    assert_eq!(
+        ls_files(&temp_dir, &["--others", "--ignored"])?,
+        "build/out\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_ls_files_ignored_needs_cached_or_others() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("ls-files")
+        .arg("--ignored")
+        .assert()
+        .code(128)
+        .stderr("fatal: ls-files -i must be used with either -o or -c\n");
+
+    Ok(())
+}
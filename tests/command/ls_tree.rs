@@ -0,0 +1,79 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, setup_fs, setup_rgit};
+
+fn ls_tree(temp_dir: &TempDir, args: &[&str]) -> Result<String> {
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(temp_dir)
+        .arg("ls-tree")
+        .args(args)
+        .assert()
+        .success();
+    Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+}
+
+#[test]
+fn test_ls_tree_lists_top_level_entries() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    setup_fs(&temp_dir)?;
+    commit_all(&temp_dir, "First commit");
+
+    let output = ls_tree(&temp_dir, &["HEAD"])?;
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[1],
+        "100644 blob 2e65efe2a145dda7ee51d1741299f848e5bf752e\ta.txt"
+    );
+    assert!(lines
+        .iter()
+        .any(|line| line.starts_with("040000 tree ") && line.ends_with("\tk")));
+    assert!(!lines.iter().any(|line| line.ends_with("\tf/g.txt")));
+
+    assert_eq!(
+        ls_tree(&temp_dir, &["--name-only", "master", "a.txt", "f"])?,
+        "a.txt\nf\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_ls_tree_recurses_into_subtrees() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    setup_fs(&temp_dir)?;
+    commit_all(&temp_dir, "First commit");
+
+    assert_eq!(
+        ls_tree(&temp_dir, &["-r", "--name-only", "HEAD", "k"])?,
+        "k/l/m/o.txt\nk/l/m/q.txt\n"
+    );
+    assert_eq!(
+        ls_tree(&temp_dir, &["-r", "-t", "--name-only", "HEAD", "k"])?,
+        "k\nk/l\nk/l/m\nk/l/m/o.txt\nk/l/m/q.txt\n"
+    );
+    // a trailing slash lists what is inside the directory
+    assert_eq!(
+        ls_tree(&temp_dir, &["--name-only", "HEAD", "k/l/"])?,
+        "k/l/m\n"
+    );
+
+    // a tree oid works as well as a commit
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("write-tree")
+        .assert()
+        .success();
+    let tree = String::from_utf8(assert.get_output().stdout.clone())?;
+    assert_eq!(
+        ls_tree(&temp_dir, &["--name-only", tree.trim(), "f/"])?,
+        "f/g.txt\n"
+    );
+
+    Ok(())
+}
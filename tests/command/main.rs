@@ -1,7 +1,37 @@
 mod add;
+mod bisect;
+mod blame;
 mod branch;
+mod cat_file;
+mod checkout;
+mod cherry_pick;
+mod clean;
 mod commit;
+mod commit_tree;
 mod diff;
+mod gc;
+mod grep;
+mod hash_object;
 mod init;
+mod ls_files;
+mod ls_tree;
+mod merge;
+mod merge_file;
+mod mktree;
+mod mv;
+mod pack;
+mod prune;
+mod rebase;
+mod reflog;
+mod repack;
+mod reset;
+mod restore;
+mod rev_parse;
+mod revert;
+mod rm;
 mod setup;
+mod show;
+mod stash;
 mod status;
+mod tag;
+mod write_tree;
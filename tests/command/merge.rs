@@ -0,0 +1,235 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, read_ref, rgit, setup_rgit};
+
+use rgit::database::{Database, ParsedContent};
+
+fn checkout(temp_dir: &TempDir, args: &[&str]) {
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(temp_dir)
+        .arg("checkout")
+        .args(args)
+        .assert()
+        .success();
+}
+
+/// Create a repository with a base commit and the branches `master` and `topic` on it
+fn setup_branches(temp_dir: &TempDir) -> Result<()> {
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("f.txt"), "1\n2\n3\n")?;
+    fs::write(temp_dir.path().join("g.txt"), "g\n")?;
+    commit_all(temp_dir, "Base commit");
+    checkout(temp_dir, &["-b", "topic"]);
+    Ok(())
+}
+
+#[test]
+fn test_merge_fast_forward() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+
+    fs::write(temp_dir.path().join("h.txt"), "h\n")?;
+    commit_all(&temp_dir, "Topic commit");
+    checkout(&temp_dir, &["master"]);
+    let master_oid = read_ref(&temp_dir, "refs/heads/master")?;
+    let topic_oid = read_ref(&temp_dir, "refs/heads/topic")?;
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("merge")
+        .arg("topic")
+        .assert()
+        .success();
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone())?,
+        format!(
+            "Updating {}..{}\nFast-forward\n",
+            &master_oid[..7],
+            &topic_oid[..7]
+        )
+    );
+
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, topic_oid);
+    assert_eq!(fs::read_to_string(temp_dir.path().join("h.txt"))?, "h\n");
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("merge")
+        .arg("topic")
+        .assert()
+        .success();
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone())?,
+        "Already up to date.\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_combines_changes_into_commit_with_two_parents() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+
+    fs::write(temp_dir.path().join("f.txt"), "1\n2\n3\n4\n")?;
+    commit_all(&temp_dir, "Topic commit");
+    checkout(&temp_dir, &["master"]);
+    fs::write(temp_dir.path().join("f.txt"), "one\n2\n3\n")?;
+    fs::write(temp_dir.path().join("g.txt"), "changed on master\n")?;
+    commit_all(&temp_dir, "Master commit");
+
+    let master_oid = read_ref(&temp_dir, "refs/heads/master")?;
+    let topic_oid = read_ref(&temp_dir, "refs/heads/topic")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("merge")
+        .arg("topic")
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("f.txt"))?,
+        "one\n2\n3\n4\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("g.txt"))?,
+        "changed on master\n"
+    );
+
+    let merge_oid = read_ref(&temp_dir, "refs/heads/master")?;
+    let database = Database::new(temp_dir.path().join(".rgit/objects"));
+    match database.read_object(&merge_oid)? {
+        ParsedContent::CommitContent(commit) => {
+            assert_eq!(commit.parents, vec![master_oid, topic_oid]);
+            assert_eq!(commit.message, "Merge branch 'topic'");
+        }
+        _ => panic!("expected a commit"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_conflict_is_finished_by_commit() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+
+    fs::write(temp_dir.path().join("f.txt"), "topic\n")?;
+    commit_all(&temp_dir, "Topic commit");
+    checkout(&temp_dir, &["master"]);
+    fs::write(temp_dir.path().join("f.txt"), "master\n")?;
+    commit_all(&temp_dir, "Master commit");
+    let topic_oid = read_ref(&temp_dir, "refs/heads/topic")?;
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("merge")
+        .arg("topic")
+        .assert()
+        .failure();
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone())?,
+        "Auto-merging f.txt\nCONFLICT (content): Merge conflict in f.txt\nAutomatic merge failed; fix conflicts and then commit the result.\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("f.txt"))?,
+        "<<<<<<< HEAD\nmaster\n=======\ntopic\n>>>>>>> topic\n"
+    );
+    assert_eq!(read_ref(&temp_dir, "MERGE_HEAD")?, topic_oid);
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd.current_dir(&temp_dir).arg("status").assert().success();
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone())?,
+        "Unmerged paths:\nboth modified: f.txt\nUntracked files:\nChanges to be committed:\nChanged not staged for commit:\n"
+    );
+
+    // committing is refused while the conflict is unresolved
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir).arg("commit").assert().failure();
+
+    fs::write(temp_dir.path().join("f.txt"), "resolved\n")?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("add")
+        .arg("f.txt")
+        .assert()
+        .success();
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir).arg("commit").assert().success();
+
+    assert!(!temp_dir.path().join(".rgit/MERGE_HEAD").exists());
+    let merge_oid = read_ref(&temp_dir, "refs/heads/master")?;
+    let database = Database::new(temp_dir.path().join(".rgit/objects"));
+    match database.read_object(&merge_oid)? {
+        ParsedContent::CommitContent(commit) => {
+            assert_eq!(commit.parents.len(), 2);
+            assert_eq!(commit.parents[1], topic_oid);
+            assert_eq!(commit.message, "Merge branch 'topic'");
+        }
+        _ => panic!("expected a commit"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_refuses_staged_changes_and_unrelated_histories() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+    fs::write(temp_dir.path().join("f.txt"), "1\n2\n3\n4\n")?;
+    commit_all(&temp_dir, "Topic commit");
+    checkout(&temp_dir, &["master"]);
+    fs::write(temp_dir.path().join("g.txt"), "changed on master\n")?;
+    commit_all(&temp_dir, "Master commit");
+    let master_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    // a staged change the merge doesn't touch would be committed along with it
+    fs::write(temp_dir.path().join("h.txt"), "h\n")?;
+    rgit(&temp_dir, &["add", "h.txt"])?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["merge", "topic"])
+        .assert()
+        .code(1)
+        .stderr("error: Your local changes to the following files would be overwritten by merge:\n\th.txt\nPlease commit your changes or stash them before you merge.\nAborting\n");
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, master_oid);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("f.txt"))?,
+        "1\n2\n3\n"
+    );
+    rgit(&temp_dir, &["rm", "--cached", "h.txt"])?;
+    fs::remove_file(temp_dir.path().join("h.txt"))?;
+
+    // a root commit with the same files shares no history with master
+    let tree = rgit(&temp_dir, &["write-tree"])?;
+    let unrelated = rgit(&temp_dir, &["commit-tree", tree.trim(), "-m", "Unrelated"])?;
+    let unrelated = unrelated.trim();
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["merge", unrelated])
+        .assert()
+        .code(128)
+        .stderr("fatal: refusing to merge unrelated histories\n");
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, master_oid);
+
+    rgit(
+        &temp_dir,
+        &["merge", "--allow-unrelated-histories", unrelated],
+    )?;
+    let database = Database::new(temp_dir.path().join(".rgit/objects"));
+    match database.read_object(&read_ref(&temp_dir, "refs/heads/master")?)? {
+        ParsedContent::CommitContent(commit) => {
+            assert_eq!(commit.parents, vec![master_oid, unrelated.to_owned()]);
+        }
+        _ => panic!("expected a commit"),
+    }
+    Ok(())
+}
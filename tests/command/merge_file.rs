@@ -0,0 +1,99 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::get_rgit_cmd;
+
+fn write_versions(temp_dir: &TempDir, current: &str, base: &str, other: &str) -> Result<()> {
+    fs::write(temp_dir.path().join("current.txt"), current)?;
+    fs::write(temp_dir.path().join("base.txt"), base)?;
+    fs::write(temp_dir.path().join("other.txt"), other)?;
+    Ok(())
+}
+
+#[test]
+fn test_merge_file_combines_separate_changes() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    write_versions(
+        &temp_dir,
+        "one\n2\n3\n4\n5\n",
+        "1\n2\n3\n4\n5\n",
+        "1\n2\n3\n4\nfive\nsix\n",
+    )?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("merge-file")
+        .arg("current.txt")
+        .arg("base.txt")
+        .arg("other.txt")
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("current.txt"))?,
+        "one\n2\n3\n4\nfive\nsix\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_file_marks_overlapping_changes() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    write_versions(
+        &temp_dir,
+        "1\nours\n3\n4\n5\nsix\n",
+        "1\n2\n3\n4\n5\n",
+        "1\ntheirs\n3\n4\n5\n",
+    )?;
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("merge-file")
+        .arg("-p")
+        .arg("current.txt")
+        .arg("base.txt")
+        .arg("other.txt")
+        .assert()
+        .code(1);
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone())?,
+        "1\n<<<<<<< current.txt\nours\n=======\ntheirs\n>>>>>>> other.txt\n3\n4\n5\nsix\n"
+    );
+
+    // with --stdout the current file is left alone
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("current.txt"))?,
+        "1\nours\n3\n4\n5\nsix\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_file_labels_and_diff3_style() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    write_versions(&temp_dir, "a\nours\n", "a\nbase\n", "a\ntheirs\nb\n")?;
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("merge-file")
+        .arg("-p")
+        .arg("--diff3")
+        .args(["-L", "mine", "-L", "original", "-L", "yours"])
+        .arg("current.txt")
+        .arg("base.txt")
+        .arg("other.txt")
+        .assert()
+        .code(1);
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone())?,
+        "a\n<<<<<<< mine\nours\n||||||| original\nbase\n=======\ntheirs\nb\n>>>>>>> yours\n"
+    );
+
+    Ok(())
+}
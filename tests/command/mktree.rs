@@ -0,0 +1,98 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, git, setup_fs, setup_rgit, write_blob};
+
+fn mktree(temp_dir: &TempDir, input: &str) -> Result<assert_cmd::assert::Assert> {
+    let mut cmd = assert_cmd::Command::cargo_bin("rgit")?;
+    Ok(cmd
+        .current_dir(temp_dir)
+        .arg("mktree")
+        .write_stdin(input)
+        .assert())
+}
+
+#[test]
+fn test_mktree_round_trips_printed_tree() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    setup_fs(&temp_dir)?;
+    commit_all(&temp_dir, "First commit");
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("write-tree")
+        .assert()
+        .success();
+    let tree = String::from_utf8(assert.get_output().stdout.clone())?;
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("cat-file")
+        .arg("-p")
+        .arg(tree.trim())
+        .assert()
+        .success();
+    let listing = String::from_utf8(assert.get_output().stdout.clone())?;
+
+    mktree(&temp_dir, &listing)?.success().stdout(tree);
+
+    Ok(())
+}
+
+#[test]
+fn test_mktree_validates_entries() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+
+    mktree(&temp_dir, "not a tree entry\n")?
+        .code(128)
+        .stderr("fatal: input format error: not a tree entry\n");
+
+    let missing = "1".repeat(40);
+    mktree(&temp_dir, &format!("100644 blob {}\tx.txt\n", missing))?
+        .code(128)
+        .stderr(format!(
+            "fatal: entry 'x.txt' object {} is unavailable\n",
+            missing
+        ));
+    mktree(&temp_dir, &format!("040000 blob {}\tdir\n", missing))?
+        .code(128)
+        .stderr("fatal: entry 'dir' object type (blob) doesn't match mode type (tree)\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_mktree_sorts_a_tree_as_if_its_name_ended_with_a_slash() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    let blob = write_blob(&temp_dir, "a\n")?;
+    let subtree = mktree(&temp_dir, &format!("100644 blob {}\tb.txt\n", blob))?.success();
+    let subtree = String::from_utf8(subtree.get_output().stdout.clone())?;
+
+    // `a.txt` sorts before `a/`, though `a` sorts before `a.txt`
+    let input = format!(
+        "040000 tree {}\ta\n100644 blob {}\ta.txt\n",
+        subtree.trim(),
+        blob
+    );
+    let assert = mktree(&temp_dir, &input)?.success();
+    let tree = String::from_utf8(assert.get_output().stdout.clone())?;
+    let assert = assert_cmd::Command::new("git")
+        .current_dir(&temp_dir)
+        .args(["--git-dir=.rgit", "mktree"])
+        .write_stdin(input)
+        .assert()
+        .success();
+    assert_eq!(String::from_utf8(assert.get_output().stdout.clone())?, tree);
+    assert_eq!(
+        git(&temp_dir, &["ls-tree", "--name-only", tree.trim()])?,
+        "a.txt\na\n"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, rgit, setup_rgit};
+
+fn setup_files(temp_dir: &TempDir) -> Result<()> {
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::create_dir_all(temp_dir.path().join("dir/sub"))?;
+    fs::write(temp_dir.path().join("a.txt"), "a\n")?;
+    fs::write(temp_dir.path().join("b.txt"), "b\n")?;
+    fs::write(temp_dir.path().join("dir/c.txt"), "c\n")?;
+    fs::write(temp_dir.path().join("dir/sub/d.txt"), "d\n")?;
+    commit_all(temp_dir, "Initial commit");
+    Ok(())
+}
+
+#[test]
+fn test_mv_renames_files_and_directories() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+    fs::create_dir(temp_dir.path().join("other"))?;
+
+    rgit(&temp_dir, &["mv", "a.txt", "renamed.txt"])?;
+    rgit(&temp_dir, &["mv", "dir", "other"])?;
+
+    assert!(!temp_dir.path().join("a.txt").exists());
+    assert!(!temp_dir.path().join("dir").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("other/dir/sub/d.txt"))?,
+        "d\n"
+    );
+    assert_eq!(
+        rgit(&temp_dir, &["ls-files"])?,
+        ".rgitignore\nb.txt\nother/dir/c.txt\nother/dir/sub/d.txt\nrenamed.txt\n"
+    );
+
+    // the moved entries keep their stat data, so nothing looks modified
+    let status = rgit(&temp_dir, &["status"])?;
+    let unstaged = status
+        .split("Changed not staged for commit:")
+        .nth(1)
+        .unwrap();
+    assert_eq!(unstaged.trim(), "");
+    Ok(())
+}
+
+#[test]
+fn test_mv_refuses_existing_destination_unless_forced() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .args(["mv", "a.txt", "b.txt"])
+        .assert()
+        .code(128);
+    assert_eq!(
+        String::from_utf8(assert.get_output().stderr.clone())?,
+        "fatal: destination exists, source=a.txt, destination=b.txt\n"
+    );
+
+    // -k skips the sources that cannot be moved
+    rgit(&temp_dir, &["mv", "-k", "missing.txt", "a.txt", "dir"])?;
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("dir/a.txt"))?,
+        "a\n"
+    );
+
+    rgit(&temp_dir, &["mv", "-f", "dir/a.txt", "b.txt"])?;
+    assert_eq!(fs::read_to_string(temp_dir.path().join("b.txt"))?, "a\n");
+    assert_eq!(
+        rgit(&temp_dir, &["ls-files"])?,
+        ".rgitignore\nb.txt\ndir/c.txt\ndir/sub/d.txt\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_mv_refuses_missing_directories_and_conflicted_entries() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_files(&temp_dir)?;
+    let mv = |args: &[&str]| -> Result<String> {
+        let mut cmd = get_rgit_cmd();
+        let assert = cmd.current_dir(&temp_dir).args(args).assert().code(128);
+        Ok(String::from_utf8(assert.get_output().stderr.clone())?)
+    };
+
+    // a trailing slash names a directory, which is not created on the way
+    assert_eq!(
+        mv(&["mv", "a.txt", "missing/"])?,
+        "fatal: destination directory does not exist, source=a.txt, destination=missing/a.txt\n"
+    );
+    assert!(!temp_dir.path().join("missing").exists());
+
+    // a file deleted in the workspace keeps its entry where a directory now is
+    fs::remove_file(temp_dir.path().join("b.txt"))?;
+    fs::create_dir(temp_dir.path().join("b.txt"))?;
+    assert_eq!(
+        mv(&["mv", "a.txt", "b.txt/"])?,
+        "fatal: destination directory is a file in the index, source=a.txt, destination=b.txt/a.txt\n"
+    );
+    fs::remove_dir(temp_dir.path().join("b.txt"))?;
+    fs::write(temp_dir.path().join("b.txt"), "b\n")?;
+    assert!(temp_dir.path().join("a.txt").exists());
+
+    // the conflict stages of a file inside a directory do not move with it
+    rgit(&temp_dir, &["branch", "topic"])?;
+    fs::write(temp_dir.path().join("dir/sub/d.txt"), "master\n")?;
+    commit_all(&temp_dir, "Master commit");
+    rgit(&temp_dir, &["checkout", "topic"])?;
+    fs::write(temp_dir.path().join("dir/sub/d.txt"), "topic\n")?;
+    commit_all(&temp_dir, "Topic commit");
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["merge", "master"])
+        .assert()
+        .failure();
+    let staged = rgit(&temp_dir, &["ls-files", "-s"])?;
+
+    assert_eq!(
+        mv(&["mv", "dir", "moved"])?,
+        "fatal: conflicted, source=dir, destination=moved\n"
+    );
+    assert_eq!(
+        mv(&["mv", "dir/sub", "dir/other"])?,
+        "fatal: conflicted, source=dir/sub, destination=dir/other\n"
+    );
+    assert!(temp_dir.path().join("dir/sub/d.txt").exists());
+    assert_eq!(rgit(&temp_dir, &["ls-files", "-s"])?, staged);
+
+    // and moving a file next to them leaves their stages as they are
+    rgit(&temp_dir, &["mv", "dir/c.txt", "dir/sub/c.txt"])?;
+    assert_eq!(
+        rgit(&temp_dir, &["ls-files", "-s"])?,
+        staged.replace("dir/c.txt", "dir/sub/c.txt")
+    );
+    Ok(())
+}
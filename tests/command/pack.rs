@@ -0,0 +1,261 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use flate2::{write::ZlibEncoder, Compression};
+use predicates::prelude::*;
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::io::Write;
+use std::time::Duration;
+use tempdir::TempDir;
+
+use crate::setup::{
+    commit_all, delta_history, get_rgit_cmd, git, rgit, setup_history, setup_rgit, write_blob,
+};
+
+/// Whether any object is left outside of the packs
+fn has_loose_objects(temp_dir: &TempDir) -> Result<bool> {
+    Ok(fs::read_dir(temp_dir.path().join(".rgit/objects"))?
+        .filter_map(|entry| entry.ok())
+        .any(|entry| entry.file_name().len() == 2))
+}
+
+/// Check that rgit reads every object the same as git does
+fn assert_objects_match(temp_dir: &TempDir) -> Result<()> {
+    let objects = git(temp_dir, &["rev-list", "--objects", "--all"])?;
+    for line in objects.lines() {
+        let oid = &line[..40];
+        assert_eq!(
+            rgit(temp_dir, &["cat-file", "-p", oid])?,
+            git(temp_dir, &["cat-file", "-p", oid])?
+        );
+        assert_eq!(
+            rgit(temp_dir, &["cat-file", "-t", oid])?,
+            git(temp_dir, &["cat-file", "-t", oid])?
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_read_objects_from_a_pack_with_offset_deltas() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &delta_history(1, 20))?;
+    let head = rgit(&temp_dir, &["rev-parse", "HEAD"])?;
+
+    git(&temp_dir, &["repack", "-a", "-d", "-f", "--depth=50"])?;
+    assert!(!has_loose_objects(&temp_dir)?);
+    let pack = fs::read_dir(temp_dir.path().join(".rgit/objects/pack"))?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| entry.path().extension().is_some_and(|e| e == "idx"))
+        .unwrap();
+    let verify = git(
+        &temp_dir,
+        &["verify-pack", "-v", pack.path().to_str().unwrap()],
+    )?;
+    assert!(verify.contains("chain length = 10"));
+
+    assert_objects_match(&temp_dir)?;
+    assert_eq!(rgit(&temp_dir, &["rev-parse", &head[..8]])?, head);
+    assert!(rgit(&temp_dir, &["log"])?.contains("\nc1\n"));
+    assert_eq!(rgit(&temp_dir, &["diff"])?, "");
+    Ok(())
+}
+
+#[test]
+fn test_read_objects_from_a_pack_with_ref_deltas_and_loose_objects() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &delta_history(1, 20))?;
+    git(
+        &temp_dir,
+        &[
+            "-c",
+            "repack.useDeltaBaseOffset=false",
+            "repack",
+            "-a",
+            "-d",
+            "-f",
+        ],
+    )?;
+    assert!(!has_loose_objects(&temp_dir)?);
+
+    // new objects go next to the pack, and both kinds are read together
+    fs::write(temp_dir.path().join("g"), "21\n")?;
+    commit_all(&temp_dir, "c21");
+    assert!(has_loose_objects(&temp_dir)?);
+    assert_objects_match(&temp_dir)?;
+
+    rgit(&temp_dir, &["checkout", "HEAD~15"])?;
+    assert_eq!(fs::read_to_string(temp_dir.path().join("g"))?, "6\n");
+    assert!(fs::read_to_string(temp_dir.path().join("f"))?.contains("LINE 60\nline 61\n"));
+    Ok(())
+}
+
+fn blob_oid(content: &str) -> String {
+    hex::encode(Sha1::digest(format!("blob {}\0{}", content.len(), content)))
+}
+
+/// A delta rebuilding an object of `size` from the first `copy` bytes of its base and then
+/// `insert`, short enough for every size to fit in one byte
+fn delta(base_size: u8, size: u8, copy: u8, insert: &str) -> Vec<u8> {
+    let mut delta = vec![base_size, size, 0x90, copy, insert.len() as u8];
+    delta.extend_from_slice(insert.as_bytes());
+    delta
+}
+
+/// A REF_DELTA entry on top of `base`, with a delta of less than 16 bytes
+fn ref_delta_entry(base: &str, delta: &[u8]) -> Result<Vec<u8>> {
+    let mut entry = vec![0x70 | delta.len() as u8];
+    entry.extend(hex::decode(base)?);
+    let mut encoder = ZlibEncoder::new(entry, Compression::default());
+    encoder.write_all(delta)?;
+    Ok(encoder.finish()?)
+}
+
+/// Write a pack named `name` and its index, holding the entries for the given oids
+fn write_pack(temp_dir: &TempDir, name: &str, entries: &[(String, Vec<u8>)]) -> Result<()> {
+    let mut pack = b"PACK".to_vec();
+    pack.extend(2u32.to_be_bytes());
+    pack.extend((entries.len() as u32).to_be_bytes());
+    let mut offsets = vec![];
+    for (oid, entry) in entries {
+        offsets.push((hex::decode(oid)?, pack.len() as u32));
+        pack.extend(entry);
+    }
+    let checksum = Sha1::digest(&pack);
+    pack.extend(checksum);
+
+    offsets.sort();
+    let mut index = b"\xfftOc".to_vec();
+    index.extend(2u32.to_be_bytes());
+    for byte in 0..=255 {
+        let count = offsets.iter().filter(|(oid, _)| oid[0] <= byte).count() as u32;
+        index.extend(count.to_be_bytes());
+    }
+    for (oid, _) in &offsets {
+        index.extend(oid);
+    }
+    index.extend(vec![0; offsets.len() * 4]);
+    for (_, offset) in &offsets {
+        index.extend(offset.to_be_bytes());
+    }
+    index.extend(checksum);
+    let index_checksum = Sha1::digest(&index);
+    index.extend(index_checksum);
+
+    let pack_dir = temp_dir.path().join(".rgit/objects/pack");
+    fs::create_dir_all(&pack_dir)?;
+    fs::write(pack_dir.join(format!("{}.pack", name)), pack)?;
+    fs::write(pack_dir.join(format!("{}.idx", name)), index)?;
+    Ok(())
+}
+
+#[test]
+fn test_read_ref_deltas_against_bases_outside_their_pack() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    let loose = write_blob(&temp_dir, "hello world\n")?;
+    let first = blob_oid("hello world\nagain\n");
+    let second = blob_oid("hello world\nagain\nand again\n");
+
+    // the base of the first delta is loose, and the one of the second is in the other pack
+    let entry = ref_delta_entry(&loose, &delta(12, 18, 12, "again\n"))?;
+    write_pack(&temp_dir, "pack-first", &[(first.clone(), entry)])?;
+    let entry = ref_delta_entry(&first, &delta(18, 28, 18, "and again\n"))?;
+    write_pack(&temp_dir, "pack-second", &[(second.clone(), entry)])?;
+
+    assert_eq!(
+        rgit(&temp_dir, &["cat-file", "-p", &first])?,
+        "hello world\nagain\n"
+    );
+    assert_eq!(
+        rgit(&temp_dir, &["cat-file", "-p", &second])?,
+        "hello world\nagain\nand again\n"
+    );
+    assert_eq!(rgit(&temp_dir, &["cat-file", "-t", &second])?, "blob\n");
+    assert_eq!(rgit(&temp_dir, &["cat-file", "-s", &second])?, "28\n");
+    Ok(())
+}
+
+#[test]
+fn test_corrupt_packs_fail_instead_of_looping_or_panicking() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+
+    // two deltas each on top of the other
+    let (first, second) = (blob_oid("first\n"), blob_oid("second\n"));
+    let entries = [
+        (
+            first.clone(),
+            ref_delta_entry(&second, &delta(6, 6, 6, ""))?,
+        ),
+        (
+            second.clone(),
+            ref_delta_entry(&first, &delta(6, 6, 6, ""))?,
+        ),
+    ];
+    write_pack(&temp_dir, "pack-cycle", &entries)?;
+    for mode in ["-p", "-s"] {
+        let mut cmd = assert_cmd::Command::cargo_bin("rgit")?;
+        cmd.current_dir(&temp_dir)
+            .args(["cat-file", mode, &first])
+            .timeout(Duration::from_secs(30))
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("more than 4096 deltas deep"));
+    }
+
+    // the fanout says more oids start with the first byte than there are in the index
+    let broken = blob_oid("broken\n");
+    let entry = ref_delta_entry(&first, &delta(6, 6, 6, ""))?;
+    write_pack(&temp_dir, "pack-broken", &[(broken.clone(), entry)])?;
+    let index_path = temp_dir.path().join(".rgit/objects/pack/pack-broken.idx");
+    let mut index = fs::read(&index_path)?;
+    index[8..12].copy_from_slice(&5u32.to_be_bytes());
+    fs::write(&index_path, index)?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["cat-file", "-p", &broken])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("panicked").not());
+    Ok(())
+}
+
+#[test]
+fn test_overlong_sizes_fail_instead_of_overflowing() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    let base = write_blob(&temp_dir, "base\n")?;
+
+    // the base size of the delta goes on for more bytes than a usize holds
+    let mut overlong = vec![0xff; 12];
+    overlong.extend([0x00, 0x05, 0x90, 0x05, 0x00]);
+    let delta_oid = blob_oid("delta\n");
+    let entry = ref_delta_entry(&base, &overlong)?;
+    write_pack(&temp_dir, "pack-delta", &[(delta_oid.clone(), entry)])?;
+
+    // and so does the size in the header of a whole object
+    let mut entry = vec![0xb0];
+    entry.extend(vec![0x80; 11]);
+    entry.push(0x00);
+    let mut encoder = ZlibEncoder::new(entry, Compression::default());
+    encoder.write_all(b"object\n")?;
+    let object_oid = blob_oid("object\n");
+    write_pack(
+        &temp_dir,
+        "pack-object",
+        &[(object_oid.clone(), encoder.finish()?)],
+    )?;
+
+    for oid in [&delta_oid, &object_oid] {
+        for mode in ["-p", "-s"] {
+            let mut cmd = get_rgit_cmd();
+            cmd.current_dir(&temp_dir)
+                .args(["cat-file", mode, oid])
+                .assert()
+                .failure()
+                .stderr(predicate::str::contains("does not fit in 64 bits"));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, rgit, setup_rgit, write_blob};
+
+fn object_path(temp_dir: &TempDir, oid: &str) -> PathBuf {
+    temp_dir
+        .path()
+        .join(".rgit/objects")
+        .join(&oid[0..2])
+        .join(&oid[2..])
+}
+
+/// Pretend a file was last written `days` ago
+fn age(path: &PathBuf, days: u64) -> Result<()> {
+    let modified = SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60);
+    File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(modified)?;
+    Ok(())
+}
+
+#[test]
+fn test_prune_removes_old_unreachable_objects() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "first\n")?;
+    commit_all(&temp_dir, "first");
+    fs::write(temp_dir.path().join("a.txt"), "second\n")?;
+    commit_all(&temp_dir, "second");
+    // only the reflog still points to the second commit
+    rgit(&temp_dir, &["reset", "--hard", "HEAD~1"])?;
+    fs::write(temp_dir.path().join("a.txt"), "stashed\n")?;
+    rgit(&temp_dir, &["stash"])?;
+    fs::write(temp_dir.path().join("b.txt"), "staged\n")?;
+    rgit(&temp_dir, &["add", "b.txt"])?;
+
+    let old = write_blob(&temp_dir, "old\n")?;
+    let fresh = write_blob(&temp_dir, "fresh\n")?;
+    let mut objects = vec![];
+    for dir in fs::read_dir(temp_dir.path().join(".rgit/objects"))? {
+        for file in fs::read_dir(dir?.path())? {
+            let path = file?.path();
+            age(&path, 30)?;
+            objects.push(path);
+        }
+    }
+    age(&object_path(&temp_dir, &fresh), 1)?;
+
+    assert_eq!(
+        rgit(&temp_dir, &["prune", "-n"])?,
+        format!("{} blob\n", old)
+    );
+    assert!(object_path(&temp_dir, &old).exists());
+
+    rgit(&temp_dir, &["prune"])?;
+    assert!(!object_path(&temp_dir, &old).exists());
+    assert!(!object_path(&temp_dir, &old).parent().unwrap().exists());
+    assert!(object_path(&temp_dir, &fresh).exists());
+    rgit(&temp_dir, &["prune", "--expire=now"])?;
+    assert!(!object_path(&temp_dir, &fresh).exists());
+
+    // everything else is still reachable from somewhere
+    for path in &objects {
+        let removed = [&old, &fresh].map(|oid| object_path(&temp_dir, oid));
+        assert_eq!(path.exists(), !removed.contains(path));
+    }
+    assert_eq!(rgit(&temp_dir, &["show", "HEAD@{1}:a.txt"])?, "second\n");
+    assert!(!temp_dir.path().join(".rgit/index.lock").exists());
+    rgit(&temp_dir, &["stash", "pop"])?;
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt"))?,
+        "stashed\n"
+    );
+    let staged = rgit(&temp_dir, &["hash-object", "b.txt"])?;
+    assert_eq!(
+        rgit(&temp_dir, &["cat-file", "-p", staged.trim()])?,
+        "staged\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_prune_waits_for_the_index_lock_and_removes_stale_temporary_files() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "a\n")?;
+    commit_all(&temp_dir, "first");
+    let unreachable = write_blob(&temp_dir, "unreachable\n")?;
+
+    // an add writing its blobs holds the index lock
+    let lock_path = temp_dir.path().join(".rgit/index.lock");
+    fs::write(&lock_path, "")?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["prune", "--expire=now"])
+        .assert()
+        .failure();
+    assert!(object_path(&temp_dir, &unreachable).exists());
+    assert!(lock_path.exists());
+    fs::remove_file(&lock_path)?;
+
+    let pack_dir = temp_dir.path().join(".rgit/objects/pack");
+    fs::create_dir_all(&pack_dir)?;
+    let stale = pack_dir.join("tmp_pack_1234");
+    let recent = pack_dir.join("tmp_pack_5678");
+    fs::write(&stale, "PACK")?;
+    fs::write(&recent, "PACK")?;
+    age(&stale, 30)?;
+    age(&object_path(&temp_dir, &unreachable), 30)?;
+
+    let output = rgit(&temp_dir, &["prune", "-n"])?;
+    assert_eq!(
+        output,
+        format!(
+            "{} blob\nRemoving stale temporary file {}\n",
+            unreachable,
+            stale.display()
+        )
+    );
+    assert!(stale.exists());
+    rgit(&temp_dir, &["prune"])?;
+    assert!(!stale.exists());
+    assert!(recent.exists());
+    assert!(!object_path(&temp_dir, &unreachable).exists());
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["prune", "--expire=soon"])
+        .assert()
+        .code(129)
+        .stderr("error: invalid timestamp 'soon' given to '--expire'\n");
+    Ok(())
+}
+
+#[test]
+fn test_prune_spares_old_objects_written_again() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "a\n")?;
+    commit_all(&temp_dir, "first");
+    let reused = write_blob(&temp_dir, "reused\n")?;
+    let dropped = write_blob(&temp_dir, "dropped\n")?;
+    age(&object_path(&temp_dir, &reused), 30)?;
+    age(&object_path(&temp_dir, &dropped), 30)?;
+
+    // a commit about to record the old blob writes it again, which makes it new to prune
+    fs::write(temp_dir.path().join("b.txt"), "reused\n")?;
+    rgit(&temp_dir, &["add", "b.txt"])?;
+    rgit(&temp_dir, &["reset", "--", "b.txt"])?;
+    assert_eq!(
+        rgit(&temp_dir, &["prune", "-n"])?,
+        format!("{} blob\n", dropped)
+    );
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["reflog", "expire", "--expire-unreachable=soon", "--all"])
+        .assert()
+        .code(129)
+        .stderr("error: invalid timestamp 'soon' given to '--expire-unreachable'\n");
+    Ok(())
+}
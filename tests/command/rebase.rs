@@ -0,0 +1,207 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, read_ref, rgit, setup_rgit};
+
+use rgit::database::{Commit, Database, ParsedContent};
+
+fn read_commit(temp_dir: &TempDir, oid: &str) -> Result<Commit> {
+    let database = Database::new(temp_dir.path().join(".rgit/objects"));
+    match database.read_object(oid)? {
+        ParsedContent::CommitContent(commit) => Ok(commit),
+        _ => panic!("expected a commit"),
+    }
+}
+
+/// Create `master` with a base commit and a commit changing `f.txt`, and `topic` branching
+/// off the base with commits adding `g.txt` and `h.txt`, left checked out
+fn setup_branches(temp_dir: &TempDir) -> Result<()> {
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("f.txt"), "1\n2\n3\n")?;
+    commit_all(temp_dir, "Base commit");
+    rgit(temp_dir, &["branch", "topic"])?;
+    fs::write(temp_dir.path().join("f.txt"), "1\nmaster\n3\n")?;
+    commit_all(temp_dir, "Change f on master");
+    rgit(temp_dir, &["checkout", "topic"])?;
+    fs::write(temp_dir.path().join("g.txt"), "g\n")?;
+    commit_all(temp_dir, "Add g");
+    fs::write(temp_dir.path().join("h.txt"), "h\n")?;
+    commit_all(temp_dir, "Add h");
+    Ok(())
+}
+
+#[test]
+fn test_rebase_replays_commits_on_upstream() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+    let master_oid = read_ref(&temp_dir, "refs/heads/master")?;
+    let original_oid = read_ref(&temp_dir, "refs/heads/topic")?;
+
+    let output = rgit(&temp_dir, &["rebase", "master"])?;
+    assert_eq!(
+        output,
+        "Successfully rebased and updated refs/heads/topic.\n"
+    );
+    assert_eq!(read_ref(&temp_dir, "HEAD")?, "ref: refs/heads/topic");
+    assert_eq!(read_ref(&temp_dir, "ORIG_HEAD")?, original_oid);
+    assert!(!temp_dir.path().join(".rgit/rebase-merge").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("f.txt"))?,
+        "1\nmaster\n3\n"
+    );
+
+    let head = read_commit(&temp_dir, &read_ref(&temp_dir, "refs/heads/topic")?)?;
+    assert_eq!(head.message, "Add h");
+    let parent = read_commit(&temp_dir, &head.parents[0])?;
+    assert_eq!(parent.message, "Add g");
+    assert_eq!(parent.parents, vec![master_oid]);
+
+    let output = rgit(&temp_dir, &["rebase", "master"])?;
+    assert_eq!(output, "Current branch topic is up to date.\n");
+    Ok(())
+}
+
+#[test]
+fn test_rebase_conflict_continue_and_abort() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+    fs::write(temp_dir.path().join("f.txt"), "1\ntopic\n3\n")?;
+    commit_all(&temp_dir, "Change f on topic");
+    let original_oid = read_ref(&temp_dir, "refs/heads/topic")?;
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .args(["rebase", "master"])
+        .assert()
+        .code(1);
+    assert!(String::from_utf8(assert.get_output().stderr.clone())?
+        .starts_with("error: could not apply "));
+    // the branch only moves once the rebase is done
+    assert_eq!(read_ref(&temp_dir, "refs/heads/topic")?, original_oid);
+
+    rgit(&temp_dir, &["rebase", "--abort"])?;
+    assert_eq!(read_ref(&temp_dir, "HEAD")?, "ref: refs/heads/topic");
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("f.txt"))?,
+        "1\ntopic\n3\n"
+    );
+    assert!(!temp_dir.path().join(".rgit/rebase-merge").exists());
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["rebase", "master"])
+        .assert()
+        .code(1);
+    fs::write(temp_dir.path().join("f.txt"), "1\nresolved\n3\n")?;
+    rgit(&temp_dir, &["add", "f.txt"])?;
+    let output = rgit(&temp_dir, &["rebase", "--continue"])?;
+    assert_eq!(
+        output,
+        "Successfully rebased and updated refs/heads/topic.\n"
+    );
+
+    let head = read_commit(&temp_dir, &read_ref(&temp_dir, "refs/heads/topic")?)?;
+    assert_eq!(head.message, "Change f on topic");
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("f.txt"))?,
+        "1\nresolved\n3\n"
+    );
+    assert!(!temp_dir.path().join(".rgit/CHERRY_PICK_HEAD").exists());
+    Ok(())
+}
+
+#[test]
+fn test_rebase_interactive_follows_edited_todo() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+    fs::write(temp_dir.path().join("i.txt"), "i\n")?;
+    commit_all(&temp_dir, "Add i");
+    let base_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    // squash the second commit into the first, drop the third and run a command at the end
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .env(
+            "GIT_SEQUENCE_EDITOR",
+            "sed -i -e '2s/^pick/squash/' -e '3s/^pick/drop/' -e '3a exec echo done > exec.txt'",
+        )
+        .env("GIT_EDITOR", "true")
+        .args(["rebase", "-i", "master"])
+        .assert()
+        .success();
+    let output = String::from_utf8(assert.get_output().stdout.clone())?;
+    assert!(output.ends_with("Successfully rebased and updated refs/heads/topic.\n"));
+
+    let head = read_commit(&temp_dir, &read_ref(&temp_dir, "refs/heads/topic")?)?;
+    assert_eq!(head.message, "Add g\n\nAdd h");
+    assert_eq!(head.parents, vec![base_oid]);
+    assert!(temp_dir.path().join("h.txt").exists());
+    assert!(!temp_dir.path().join("i.txt").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("exec.txt"))?,
+        "done\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_rebase_refuses_unmerged_files_and_a_branch_moved_meanwhile() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+    fs::write(temp_dir.path().join("f.txt"), "1\ntopic\n3\n")?;
+    commit_all(&temp_dir, "Change f on topic");
+    let original_oid = read_ref(&temp_dir, "refs/heads/topic")?;
+    let master_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["rebase", "nope"])
+        .assert()
+        .code(128)
+        .stderr("fatal: invalid upstream 'nope'\n");
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["rebase", "--continue"])
+        .assert()
+        .code(128)
+        .stderr("fatal: No rebase in progress?\n");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["rebase", "master"])
+        .assert()
+        .code(1);
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["rebase", "--continue"])
+        .assert()
+        .code(1)
+        .stderr("error: Committing is not possible because you have unmerged files.\nhint: Fix them up in the work tree, and then use 'rgit add <file>'\nhint: as appropriate to mark resolution and make a commit.\n");
+    assert!(temp_dir.path().join(".rgit/rebase-merge").exists());
+
+    // something else moves the branch before the rebase is done, which it does not undo
+    fs::write(
+        temp_dir.path().join(".rgit/refs/heads/topic"),
+        format!("{}\n", master_oid),
+    )?;
+    fs::write(temp_dir.path().join("f.txt"), "1\nresolved\n3\n")?;
+    rgit(&temp_dir, &["add", "f.txt"])?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["rebase", "--continue"])
+        .assert()
+        .code(1)
+        .stderr(format!(
+            "error: update_ref failed for ref 'refs/heads/topic': is at {} but expected {}\nhint: the branch moved while it was being rebased, the rebased commits are\nhint: left on a detached HEAD\n",
+            master_oid, original_oid
+        ));
+    assert_eq!(read_ref(&temp_dir, "refs/heads/topic")?, master_oid);
+    let head = read_commit(&temp_dir, &read_ref(&temp_dir, "HEAD")?)?;
+    assert_eq!(head.message, "Change f on topic");
+    assert!(!temp_dir.path().join(".rgit/rebase-merge").exists());
+    Ok(())
+}
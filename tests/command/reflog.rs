@@ -0,0 +1,188 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, commit_history, get_rgit_cmd, rgit, setup_history};
+
+use rgit::reflog::Reflog;
+
+fn rev_parse(temp_dir: &TempDir, revision: &str) -> Result<String> {
+    Ok(rgit(temp_dir, &["rev-parse", revision])?.trim().to_owned())
+}
+
+/// Commit `one` and `two` on master, `three` on topic, then go back to master and reset
+/// it to `one`
+fn setup_branches(temp_dir: &TempDir) -> Result<()> {
+    setup_history(
+        temp_dir,
+        &[("one", vec![("f", "1\n")]), ("two", vec![("f", "2\n")])],
+    )?;
+    rgit(temp_dir, &["checkout", "-b", "topic"])?;
+    commit_history(temp_dir, &[("three", vec![("f", "3\n")])])?;
+    rgit(temp_dir, &["checkout", "master"])?;
+    rgit(temp_dir, &["reset", "--hard", "HEAD~1"])?;
+    Ok(())
+}
+
+/// The messages of a reflog listing, newest first
+fn messages(listing: &str) -> Vec<&str> {
+    listing
+        .lines()
+        .map(|line| line.split_once(": ").unwrap().1)
+        .collect()
+}
+
+#[test]
+fn test_reflog_records_head_and_branch_moves() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+
+    let head = rgit(&temp_dir, &["reflog"])?;
+    assert_eq!(
+        messages(&head),
+        [
+            "reset: moving to HEAD~1",
+            "checkout: moving from topic to master",
+            "commit: three",
+            "checkout: moving from master to topic",
+            "commit: two",
+            "commit (initial): one",
+        ]
+    );
+    assert!(head.starts_with(&format!(
+        "{} HEAD@{{0}}: ",
+        &rev_parse(&temp_dir, "HEAD")?[..7]
+    )));
+    assert_eq!(
+        messages(&rgit(&temp_dir, &["reflog", "show", "topic"])?),
+        ["commit: three", "branch: Created from HEAD"]
+    );
+    assert_eq!(
+        messages(&rgit(&temp_dir, &["reflog", "master"])?),
+        [
+            "reset: moving to HEAD~1",
+            "commit: two",
+            "commit (initial): one"
+        ]
+    );
+
+    // each line holds the old and new oids, who moved the ref and when
+    let log = fs::read_to_string(temp_dir.path().join(".rgit/logs/refs/heads/master"))?;
+    let first = log.lines().next().unwrap();
+    assert!(first.starts_with(&format!(
+        "{} {} Test Author <test@example.com> ",
+        "0".repeat(40),
+        rev_parse(&temp_dir, "HEAD")?
+    )));
+    assert!(first.ends_with("\tcommit (initial): one"));
+    Ok(())
+}
+
+#[test]
+fn test_reflog_selectors_delete_and_expire() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+    let one = rev_parse(&temp_dir, "master~0")?;
+    let two = rev_parse(&temp_dir, "topic~1")?;
+    let three = rev_parse(&temp_dir, "topic")?;
+
+    assert_eq!(rev_parse(&temp_dir, "HEAD@{2}")?, three);
+    assert_eq!(rev_parse(&temp_dir, "@{1}")?, two);
+    assert_eq!(rev_parse(&temp_dir, "master@{1}~1")?, one);
+    assert_eq!(rev_parse(&temp_dir, "topic@{1}")?, two);
+    assert_eq!(rev_parse(&temp_dir, "@{now}")?, one);
+    assert_eq!(rev_parse(&temp_dir, "master@{1.year.ago}")?, one);
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["rev-parse", "--verify", "master@{3}"])
+        .assert()
+        .code(128);
+
+    rgit(&temp_dir, &["reflog", "delete", "HEAD@{0}", "HEAD@{2}"])?;
+    assert_eq!(
+        messages(&rgit(&temp_dir, &["reflog"])?),
+        [
+            "checkout: moving from topic to master",
+            "checkout: moving from master to topic",
+            "commit: two",
+            "commit (initial): one",
+        ]
+    );
+
+    // `two` is no longer on master, so only the entry moving master to it goes
+    rgit(
+        &temp_dir,
+        &[
+            "reflog",
+            "expire",
+            "--expire=never",
+            "--expire-unreachable=now",
+            "master",
+        ],
+    )?;
+    assert_eq!(
+        messages(&rgit(&temp_dir, &["reflog", "master"])?),
+        ["reset: moving to HEAD~1", "commit (initial): one"]
+    );
+    rgit(&temp_dir, &["reflog", "expire", "--expire=now", "--all"])?;
+    assert_eq!(rgit(&temp_dir, &["reflog", "topic"])?, "");
+    Ok(())
+}
+
+#[test]
+fn test_reflog_expire_and_updates_take_turns_on_the_ref_lock() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_branches(&temp_dir)?;
+    let git_path = temp_dir.path().join(".rgit");
+
+    // a commit on master while its log is being expired has to wait for the lock instead
+    // of appending an entry the expire would then write over
+    fs::write(temp_dir.path().join("f"), "4\n")?;
+    Reflog::new(&git_path, "refs/heads/master").rewrite(|entries| {
+        let mut cmd = get_rgit_cmd();
+        cmd.current_dir(&temp_dir)
+            .args(["add", "f"])
+            .assert()
+            .success();
+        let mut cmd = get_rgit_cmd();
+        cmd.current_dir(&temp_dir)
+            .args(["commit", "-m", "four"])
+            .assert()
+            .failure();
+        Ok(Some(entries[entries.len() - 1..].to_vec()))
+    })?;
+    assert_eq!(
+        messages(&rgit(&temp_dir, &["reflog", "master"])?),
+        ["reset: moving to HEAD~1"]
+    );
+    commit_all(&temp_dir, "four");
+    assert_eq!(
+        messages(&rgit(&temp_dir, &["reflog", "master"])?),
+        ["commit: four", "reset: moving to HEAD~1"]
+    );
+
+    // and an expire waits for an update holding the lock, HEAD's log included as the
+    // update of the branch HEAD is on writes to it
+    for (name, log) in [("master", "refs/heads/master"), ("HEAD", "HEAD")] {
+        let lock = git_path.join(format!("{}.lock", log));
+        fs::write(&lock, "")?;
+        let before = fs::read_to_string(git_path.join("logs").join(log))?;
+        let mut cmd = get_rgit_cmd();
+        cmd.current_dir(&temp_dir)
+            .args(["reflog", "expire", "--expire=now", name])
+            .assert()
+            .failure();
+        let mut cmd = get_rgit_cmd();
+        cmd.current_dir(&temp_dir)
+            .args(["commit", "--amend", "-m", "four again"])
+            .assert()
+            .failure();
+        assert_eq!(fs::read_to_string(git_path.join("logs").join(log))?, before);
+        fs::remove_file(&lock)?;
+    }
+    rgit(&temp_dir, &["reflog", "expire", "--expire=now", "--all"])?;
+    assert!(!git_path.join("logs/refs/heads/master").exists());
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempdir::TempDir;
+
+use crate::setup::{
+    commit_all, commit_history, delta_history, get_rgit_cmd, git, rgit, setup_history, setup_rgit,
+    write_blob,
+};
+
+fn loose_objects(temp_dir: &TempDir) -> Result<Vec<String>> {
+    let object_store = temp_dir.path().join(".rgit/objects");
+    let mut oids = vec![];
+    for dir in fs::read_dir(&object_store)? {
+        let dir = dir?;
+        let prefix = dir.file_name().into_string().unwrap();
+        if prefix.len() != 2 {
+            continue;
+        }
+        for file in fs::read_dir(dir.path())? {
+            oids.push(format!("{}{}", prefix, file?.file_name().to_str().unwrap()));
+        }
+    }
+    oids.sort();
+    Ok(oids)
+}
+
+fn packs(temp_dir: &TempDir) -> Result<Vec<PathBuf>> {
+    let mut packs = fs::read_dir(temp_dir.path().join(".rgit/objects/pack"))?
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "pack"))
+        .collect::<Vec<_>>();
+    packs.sort();
+    Ok(packs)
+}
+
+/// Check a pack with git, returning what it lists of its objects
+fn verify_pack(temp_dir: &TempDir, pack: &Path) -> Result<String> {
+    let idx = pack.with_extension("idx");
+    git(temp_dir, &["verify-pack", "-v", idx.to_str().unwrap()])
+}
+
+#[test]
+fn test_repack_packs_the_loose_reachable_objects() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &delta_history(1, 5))?;
+    let unreachable = write_blob(&temp_dir, "unreachable\n")?;
+    fs::write(temp_dir.path().join("staged"), "staged\n")?;
+    rgit(&temp_dir, &["add", "staged"])?;
+    let staged = rgit(&temp_dir, &["hash-object", "staged"])?;
+
+    rgit(&temp_dir, &["repack", "-d", "-q"])?;
+    // everything in the history, and the staged blob
+    let first = packs(&temp_dir)?;
+    assert_eq!(first.len(), 1);
+    assert!(verify_pack(&temp_dir, &first[0])?.contains(": ok\n"));
+    let history = git(&temp_dir, &["rev-list", "--objects", "--all"])?;
+    assert_eq!(
+        git(&temp_dir, &["count-objects", "-v"])?.lines().nth(2),
+        Some(format!("in-pack: {}", history.lines().count() + 1).as_str())
+    );
+    // what nothing points to stays loose
+    assert_eq!(loose_objects(&temp_dir)?, [unreachable.as_str()]);
+    assert_eq!(
+        rgit(&temp_dir, &["cat-file", "-p", staged.trim()])?,
+        "staged\n"
+    );
+
+    // the next repack only takes the new objects
+    commit_history(&temp_dir, &delta_history(6, 7))?;
+    let output = rgit(&temp_dir, &["repack", "-d"])?;
+    assert!(output.is_empty());
+    let second = packs(&temp_dir)?;
+    assert_eq!(second.len(), 2);
+    assert!(second.contains(&first[0]));
+    assert_eq!(loose_objects(&temp_dir)?, [unreachable.as_str()]);
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["repack", "-d"])
+        .assert()
+        .success()
+        .stdout("Nothing new to pack.\n");
+    Ok(())
+}
+
+#[test]
+fn test_repack_all_deltifies_and_replaces_the_packs() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &delta_history(1, 10))?;
+    rgit(&temp_dir, &["repack", "-d", "-q"])?;
+    // c20 is only left in the reflog, which still keeps it
+    let dropped = commit_history(&temp_dir, &delta_history(11, 20))?
+        .pop()
+        .unwrap();
+    rgit(&temp_dir, &["reset", "--hard", "HEAD~1"])?;
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .args(["repack", "-a", "-d", "--threads=3"])
+        .assert()
+        .success();
+    let objects = git(&temp_dir, &["rev-list", "--objects", "--all", "--reflog"])?;
+    let progress = String::from_utf8(assert.get_output().stderr.clone())?;
+    assert!(progress.starts_with(&format!(
+        "Enumerating objects: {}, done.\n",
+        objects.lines().count()
+    )));
+    assert!(progress.contains("Delta compression using up to 3 threads\n"));
+
+    let packs = packs(&temp_dir)?;
+    assert_eq!(packs.len(), 1);
+    assert!(loose_objects(&temp_dir)?.is_empty());
+    let verify = verify_pack(&temp_dir, &packs[0])?;
+    assert!(verify.contains("chain length = 2"));
+
+    // git and rgit read the same objects out of it
+    for line in objects.lines() {
+        let oid = &line[..40];
+        assert_eq!(
+            rgit(&temp_dir, &["cat-file", "-p", oid])?,
+            git(&temp_dir, &["cat-file", "-p", oid])?
+        );
+    }
+    assert_eq!(rgit(&temp_dir, &["cat-file", "-t", &dropped])?, "commit\n");
+    Ok(())
+}
+
+#[test]
+fn test_repack_fails_on_a_missing_object_without_removing_anything() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "a\n")?;
+    fs::write(temp_dir.path().join("b.txt"), "b\n")?;
+    commit_all(&temp_dir, "first");
+
+    let missing = rgit(&temp_dir, &["hash-object", "b.txt"])?;
+    let missing = missing.trim();
+    let object_store = temp_dir.path().join(".rgit/objects");
+    fs::remove_file(object_store.join(&missing[0..2]).join(&missing[2..]))?;
+    let objects = loose_objects(&temp_dir)?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["repack", "-a", "-d", "-q"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(format!(
+            "fatal: missing blob {}",
+            missing
+        )));
+    assert_eq!(loose_objects(&temp_dir)?, objects);
+    assert!(!object_store.join("pack").exists() || packs(&temp_dir)?.is_empty());
+    Ok(())
+}
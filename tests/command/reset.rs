@@ -0,0 +1,179 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, read_ref, rgit, setup_history};
+
+use rgit::index::{FlatIndex, Index};
+
+/// Two commits; the second changes `a.txt` and adds `d/new.txt`
+fn history() -> Vec<(&'static str, Vec<(&'static str, &'static str)>)> {
+    vec![
+        ("First commit", vec![("a.txt", "one\n")]),
+        (
+            "Second commit",
+            vec![("a.txt", "two\n"), ("d/new.txt", "new\n")],
+        ),
+    ]
+}
+
+/// The size each index entry caches for its file, 0 when nothing is cached
+fn index_sizes(temp_dir: &TempDir) -> Result<BTreeMap<String, u32>> {
+    let mut index = Index::new(temp_dir.path().join(".rgit/index"));
+    index.load()?;
+    let mut flat_index = FlatIndex {
+        entries: Default::default(),
+    };
+    Index::flatten_entries(&index.entries, &mut flat_index);
+    Ok(flat_index
+        .entries
+        .into_iter()
+        .map(|(path, file)| (path, file.stat.size))
+        .collect())
+}
+
+fn status(temp_dir: &TempDir) -> Result<String> {
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd.current_dir(temp_dir).arg("status").assert().success();
+    Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+}
+
+#[test]
+fn test_reset_soft_and_mixed() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    let first_oid = setup_history(&temp_dir, &history())?[0].clone();
+    let second_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("reset")
+        .arg("--soft")
+        .arg("HEAD^")
+        .assert()
+        .success();
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, first_oid);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join(".rgit/ORIG_HEAD"))?,
+        format!("{}\n", second_oid)
+    );
+    assert_eq!(
+        status(&temp_dir)?,
+        "Untracked files:\nChanges to be committed:\nmodified: a.txt\nnew file: d/new.txt\nChanged not staged for commit:\n"
+    );
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir).arg("reset").assert().success();
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, first_oid);
+    assert_eq!(
+        status(&temp_dir)?,
+        "Untracked files:\nd/new.txt\nChanges to be committed:\nChanged not staged for commit:\nmodified: a.txt\n"
+    );
+    assert_eq!(fs::read_to_string(temp_dir.path().join("a.txt"))?, "two\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_reset_hard_restores_workspace() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    let first_oid = setup_history(&temp_dir, &history())?[0].clone();
+    fs::write(temp_dir.path().join("a.txt"), "local change\n")?;
+    fs::write(temp_dir.path().join("untracked.txt"), "keep me\n")?;
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("reset")
+        .arg("--hard")
+        .arg("master~1")
+        .assert()
+        .success();
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone())?,
+        format!("HEAD is now at {} First commit\n", &first_oid[..7])
+    );
+
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, first_oid);
+    assert_eq!(fs::read_to_string(temp_dir.path().join("a.txt"))?, "one\n");
+    assert!(!temp_dir.path().join("d").exists());
+    assert!(temp_dir.path().join("untracked.txt").exists());
+    assert_eq!(
+        status(&temp_dir)?,
+        "Untracked files:\nuntracked.txt\nChanges to be committed:\nChanged not staged for commit:\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_reset_paths_only_touches_index() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+    let head_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    fs::write(temp_dir.path().join("a.txt"), "staged\n")?;
+    fs::write(temp_dir.path().join("d/new.txt"), "staged\n")?;
+    commit_all(&temp_dir, "Third commit");
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("reset")
+        .arg("--soft")
+        .arg(&head_oid)
+        .assert()
+        .success();
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("reset")
+        .arg("--")
+        .arg("d")
+        .assert()
+        .success();
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, head_oid);
+    assert_eq!(
+        status(&temp_dir)?,
+        "Untracked files:\nChanges to be committed:\nmodified: a.txt\nChanged not staged for commit:\nmodified: d/new.txt\n"
+    );
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("reset")
+        .arg("--hard")
+        .arg("HEAD")
+        .arg("--")
+        .arg("a.txt")
+        .assert()
+        .code(128);
+    assert_eq!(
+        String::from_utf8(assert.get_output().stderr.clone())?,
+        "fatal: Cannot do hard reset with paths.\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_reset_keeps_the_stat_only_of_unchanged_files() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(
+        &temp_dir,
+        &[
+            ("First commit", vec![("a.txt", "one\n"), ("b.txt", "b\n")]),
+            ("Second commit", vec![("a.txt", "a longer two\n")]),
+        ],
+    )?;
+
+    // a.txt no longer holds the blob of its entry, so no stat is cached for it
+    rgit(&temp_dir, &["reset", "HEAD^"])?;
+    let sizes = index_sizes(&temp_dir)?;
+    assert_eq!((sizes["a.txt"], sizes["b.txt"]), (0, 2));
+
+    rgit(&temp_dir, &["add", "a.txt"])?;
+    assert_eq!(index_sizes(&temp_dir)?["a.txt"], 13);
+    rgit(&temp_dir, &["reset", "HEAD", "--", "a.txt"])?;
+    assert_eq!(index_sizes(&temp_dir)?, sizes);
+    Ok(())
+}
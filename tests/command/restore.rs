@@ -0,0 +1,155 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, rgit, setup_fs, setup_rgit};
+
+use rgit::index::{FlatIndex, Index};
+
+/// The size the index entry of `path` caches for its file, 0 when nothing is cached
+fn index_size(temp_dir: &TempDir, path: &str) -> Result<u32> {
+    let mut index = Index::new(temp_dir.path().join(".rgit/index"));
+    index.load()?;
+    let mut flat_index = FlatIndex {
+        entries: Default::default(),
+    };
+    Index::flatten_entries(&index.entries, &mut flat_index);
+    Ok(flat_index.entries[path].stat.size)
+}
+
+fn status(temp_dir: &TempDir) -> Result<String> {
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd.current_dir(temp_dir).arg("status").assert().success();
+    Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+}
+
+fn add(temp_dir: &TempDir, path: &str) {
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(temp_dir)
+        .arg("add")
+        .arg(path)
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_restore_discards_worktree_changes_from_index() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_fs(&temp_dir)?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    commit_all(&temp_dir, "Initial commit");
+
+    fs::write(temp_dir.path().join("a.txt"), "staged")?;
+    add(&temp_dir, "a.txt");
+    fs::write(temp_dir.path().join("a.txt"), "not staged")?;
+    fs::remove_file(temp_dir.path().join("k/l/m/o.txt"))?;
+    fs::write(temp_dir.path().join("b.txt"), "changed")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("restore")
+        .arg("a.txt")
+        .arg("k")
+        .assert()
+        .success();
+
+    // the staged content comes back, the other changes are left alone
+    assert_eq!(fs::read_to_string(temp_dir.path().join("a.txt"))?, "staged");
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("k/l/m/o.txt"))?,
+        "o"
+    );
+    assert_eq!(
+        status(&temp_dir)?,
+        "Untracked files:\nChanges to be committed:\nmodified: a.txt\nChanged not staged for commit:\nmodified: b.txt\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_restore_staged_unstages_without_touching_worktree() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_fs(&temp_dir)?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    commit_all(&temp_dir, "Initial commit");
+
+    fs::write(temp_dir.path().join("a.txt"), "changed")?;
+    fs::write(temp_dir.path().join("new.txt"), "new")?;
+    add(&temp_dir, "a.txt");
+    add(&temp_dir, "new.txt");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("restore")
+        .arg("--staged")
+        .arg("a.txt")
+        .arg("new.txt")
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt"))?,
+        "changed"
+    );
+    assert_eq!(
+        status(&temp_dir)?,
+        "Untracked files:\nnew.txt\nChanges to be committed:\nChanged not staged for commit:\nmodified: a.txt\n"
+    );
+    // the working copy is not the blob of the entry, so its stat is not cached
+    assert_eq!(index_size(&temp_dir, "a.txt")?, 0);
+
+    rgit(&temp_dir, &["restore", "--staged", "--worktree", "a.txt"])?;
+    assert_eq!(fs::read_to_string(temp_dir.path().join("a.txt"))?, "a");
+    assert_eq!(index_size(&temp_dir, "a.txt")?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_restore_from_source_keeps_file_mode() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_fs(&temp_dir)?;
+    let script = temp_dir.path().join("run.sh");
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    commit_all(&temp_dir, "Initial commit");
+
+    fs::write(&script, "changed")?;
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o644))?;
+    commit_all(&temp_dir, "Second commit");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("restore")
+        .arg("--source")
+        .arg("HEAD~1")
+        .arg("--staged")
+        .arg("--worktree")
+        .arg("run.sh")
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(&script)?, "run");
+    assert_eq!(fs::metadata(&script)?.permissions().mode() & 0o777, 0o755);
+    assert_eq!(
+        status(&temp_dir)?,
+        "Untracked files:\nChanges to be committed:\nmodified: run.sh\nChanged not staged for commit:\n"
+    );
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("restore")
+        .arg("missing.txt")
+        .assert()
+        .code(1);
+    assert_eq!(
+        String::from_utf8(assert.get_output().stderr.clone())?,
+        "error: pathspec 'missing.txt' did not match any file(s) known to git\n"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use tempdir::TempDir;
+
+use crate::setup::{get_rgit_cmd, setup_history, setup_rgit};
+
+fn rev_parse(temp_dir: &TempDir, args: &[&str]) -> Result<String> {
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(temp_dir)
+        .arg("rev-parse")
+        .args(args)
+        .assert()
+        .success();
+    Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+}
+
+/// Two commits on master, each writing `a.txt`
+fn history() -> Vec<(&'static str, Vec<(&'static str, &'static str)>)> {
+    vec![
+        ("First commit", vec![("a.txt", "one\n")]),
+        ("Second commit", vec![("a.txt", "two\n")]),
+    ]
+}
+
+#[test]
+fn test_rev_parse_resolves_revisions() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    let oids = setup_history(&temp_dir, &history())?;
+    let (first, second) = (&oids[0], &oids[1]);
+
+    assert_eq!(
+        rev_parse(&temp_dir, &["HEAD", "master^", "@~1", "refs/heads/master"])?,
+        format!("{0}\n{1}\n{1}\n{0}\n", second, first)
+    );
+    assert_eq!(
+        rev_parse(&temp_dir, &["--short", "HEAD"])?,
+        format!("{}\n", &second[..7])
+    );
+    assert_eq!(
+        rev_parse(&temp_dir, &["--short=10", &first[..8]])?,
+        format!("{}\n", &first[..10])
+    );
+    assert_eq!(
+        rev_parse(&temp_dir, &["--abbrev-ref", "HEAD", "refs/heads/master"])?,
+        "master\nmaster\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rev_parse_reports_invalid_revisions() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("rev-parse")
+        .arg("HEAD~2")
+        .assert()
+        .code(128)
+        .stdout("")
+        .stderr(
+            "fatal: ambiguous argument 'HEAD~2': unknown revision or path not in the working tree.\n\
+             Use '--' to separate paths from revisions, like this:\n\
+             'rgit <command> [<revision>...] -- [<file>...]'\n",
+        );
+
+    for args in [vec!["--verify", "nope"], vec!["--verify", "HEAD", "HEAD^"]] {
+        let mut cmd = get_rgit_cmd();
+        cmd.current_dir(&temp_dir)
+            .arg("rev-parse")
+            .args(args)
+            .assert()
+            .code(128)
+            .stderr("fatal: Needed a single revision\n");
+    }
+
+    // a file name that is not a revision is echoed back
+    assert_eq!(rev_parse(&temp_dir, &["a.txt"])?, "a.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_commands_exit_with_128_on_invalid_revisions() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+
+    for args in [
+        vec!["branch", "topic", "nope"],
+        vec!["cat-file", "-p", "nope"],
+        vec!["checkout", "nope"],
+        vec!["commit-tree", "nope", "-m", "message"],
+        vec!["ls-tree", "nope"],
+        vec!["merge", "nope"],
+        vec!["reset", "nope"],
+        vec!["restore", "--source", "nope", "a.txt"],
+        vec!["show", "nope"],
+        vec!["tag", "v1", "nope"],
+    ] {
+        let mut cmd = get_rgit_cmd();
+        cmd.current_dir(&temp_dir)
+            .args(&args)
+            .assert()
+            .code(128)
+            .stderr("fatal: Not a valid object name: 'nope'\n");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rev_parse_shows_repository_paths() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+
+    assert_eq!(
+        rev_parse(
+            &temp_dir,
+            &["--git-dir", "--show-toplevel", "--is-inside-work-tree"]
+        )?,
+        format!(".rgit\n{}\ntrue\n", temp_dir.path().display())
+    );
+
+    let outside = TempDir::new("test_rgit")?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&outside)
+        .arg("rev-parse")
+        .arg("--is-inside-work-tree")
+        .assert()
+        .code(128)
+        .stderr("fatal: not a git repository (or any of the parent directories): .rgit\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_rev_parse_reports_ambiguous_abbreviated_oids() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("write-tree")
+        .assert()
+        .success();
+    let tree = String::from_utf8(assert.get_output().stdout.clone())?;
+
+    // write commits until two of them start alike, at the latest after 256 of them
+    let mut prefixes = std::collections::HashMap::new();
+    let (prefix, oids) = loop {
+        let mut cmd = get_rgit_cmd();
+        let assert = cmd
+            .current_dir(&temp_dir)
+            .args(["commit-tree", tree.trim(), "-m"])
+            .arg(format!("commit {}", prefixes.len()))
+            .assert()
+            .success();
+        let oid = String::from_utf8(assert.get_output().stdout.clone())?
+            .trim()
+            .to_owned();
+        let prefix = oid[..2].to_owned();
+        if let Some(other) = prefixes.insert(prefix.clone(), oid.clone()) {
+            break (prefix, [other, oid]);
+        }
+    };
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["cat-file", "-t", &prefix])
+        .assert()
+        .code(128)
+        .stderr(format!(
+            "error: short object ID {} is ambiguous\nfatal: Not a valid object name: '{}'\n",
+            prefix, prefix
+        ));
+
+    // a longer abbreviation tells them apart again
+    for oid in oids {
+        assert_eq!(rev_parse(&temp_dir, &[&oid[..12]])?, format!("{}\n", oid));
+    }
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, read_ref, rgit, setup_history};
+
+use rgit::database::{Database, ParsedContent};
+
+/// Three commits: the first adds `f.txt`, the second changes it and the third adds `g.txt`
+fn history() -> Vec<(&'static str, Vec<(&'static str, &'static str)>)> {
+    vec![
+        ("Add f", vec![("f.txt", "1\n2\n3\n")]),
+        ("Change f", vec![("f.txt", "1\ntwo\n3\n")]),
+        ("Add g", vec![("g.txt", "g\n")]),
+    ]
+}
+
+#[test]
+fn test_revert_commit() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+    let head_oid = read_ref(&temp_dir, "refs/heads/master")?;
+    let reverted_oid = rgit(&temp_dir, &["rev-parse", "HEAD~1"])?.trim().to_owned();
+
+    let output = rgit(&temp_dir, &["revert", "HEAD~1"])?;
+    assert!(output.ends_with("] Revert \"Change f\"\n"));
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("f.txt"))?,
+        "1\n2\n3\n"
+    );
+    assert_eq!(fs::read_to_string(temp_dir.path().join("g.txt"))?, "g\n");
+
+    let database = Database::new(temp_dir.path().join(".rgit/objects"));
+    match database.read_object(&read_ref(&temp_dir, "refs/heads/master")?)? {
+        ParsedContent::CommitContent(commit) => {
+            assert_eq!(commit.parents, vec![head_oid]);
+            assert_eq!(
+                commit.message,
+                format!(
+                    "Revert \"Change f\"\n\nThis reverts commit {}.",
+                    reverted_oid
+                )
+            );
+        }
+        _ => panic!("expected a commit"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_revert_conflict_and_abort() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+    fs::write(temp_dir.path().join("f.txt"), "1\nthree\n3\n")?;
+    commit_all(&temp_dir, "Change f again");
+    let head_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["revert", "HEAD~1", "HEAD~2"])
+        .assert()
+        .code(1);
+    // reverting "Add g" went through, reverting "Change f" conflicts with the later change
+    assert!(!temp_dir.path().join("g.txt").exists());
+    assert!(fs::read_to_string(temp_dir.path().join("f.txt"))?
+        .starts_with("1\n<<<<<<< HEAD\nthree\n=======\n2\n>>>>>>> parent of "));
+    assert!(temp_dir.path().join(".rgit/REVERT_HEAD").exists());
+    assert!(temp_dir.path().join(".rgit/sequencer").exists());
+    assert_ne!(read_ref(&temp_dir, "refs/heads/master")?, head_oid);
+
+    rgit(&temp_dir, &["revert", "--abort"])?;
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, head_oid);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("f.txt"))?,
+        "1\nthree\n3\n"
+    );
+    assert_eq!(fs::read_to_string(temp_dir.path().join("g.txt"))?, "g\n");
+    assert!(!temp_dir.path().join(".rgit/REVERT_HEAD").exists());
+    assert!(!temp_dir.path().join(".rgit/sequencer").exists());
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["revert", "--continue"])
+        .assert()
+        .code(128)
+        .stderr("error: no cherry-pick or revert in progress\nfatal: revert failed\n");
+    Ok(())
+}
+
+#[test]
+fn test_revert_refuses_the_root_commit_and_an_already_reverted_change() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+    let root_oid = rgit(&temp_dir, &["rev-parse", "HEAD~2"])?.trim().to_owned();
+    let head_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["revert", "HEAD~2"])
+        .assert()
+        .code(128)
+        .stderr(format!(
+            "error: cannot revert root commit {}\nfatal: revert failed\n",
+            root_oid
+        ));
+    assert_eq!(read_ref(&temp_dir, "refs/heads/master")?, head_oid);
+    assert!(!temp_dir.path().join(".rgit/sequencer").exists());
+
+    rgit(&temp_dir, &["revert", "HEAD"])?;
+    let reverted_oid = rgit(&temp_dir, &["rev-parse", "HEAD~1"])?.trim().to_owned();
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["revert", "HEAD~1"])
+        .assert()
+        .code(1)
+        .stdout(format!(
+            "On branch master\nYou are currently reverting commit {}.\n\nnothing to commit, working tree clean\n",
+            &reverted_oid[..7]
+        ));
+    assert_eq!(read_ref(&temp_dir, "REVERT_HEAD")?, reverted_oid);
+    rgit(&temp_dir, &["revert", "--abort"])?;
+    assert!(!temp_dir.path().join(".rgit/REVERT_HEAD").exists());
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, setup_fs, setup_rgit};
+
+use rgit::index::{FlatIndex, Index};
+
+fn index_paths(temp_dir: &TempDir) -> Result<Vec<String>> {
+    let mut index = Index::new(temp_dir.path().join(".rgit/index"));
+    index.load()?;
+    let mut flat_index = FlatIndex {
+        entries: Default::default(),
+    };
+    Index::flatten_entries(&index.entries, &mut flat_index);
+    Ok(flat_index.entries.into_keys().collect())
+}
+
+#[test]
+fn test_rm_removes_file_from_index_and_workspace() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_fs(&temp_dir)?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    commit_all(&temp_dir, "Initial commit");
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("rm")
+        .arg("a.txt")
+        .arg("f/g.txt")
+        .assert()
+        .success();
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone())?,
+        "rm 'a.txt'\nrm 'f/g.txt'\n"
+    );
+
+    assert!(!temp_dir.path().join("a.txt").exists());
+    assert!(!temp_dir.path().join("f").exists());
+    let paths = index_paths(&temp_dir)?;
+    assert!(!paths.contains(&"a.txt".to_owned()));
+    assert!(!paths.contains(&"f/g.txt".to_owned()));
+    assert!(paths.contains(&"b.txt".to_owned()));
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("rm")
+        .arg("missing.txt")
+        .assert()
+        .code(128);
+    assert_eq!(
+        String::from_utf8(assert.get_output().stderr.clone())?,
+        "fatal: pathspec 'missing.txt' did not match any files\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rm_directory_needs_recursive_flag() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_fs(&temp_dir)?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    commit_all(&temp_dir, "Initial commit");
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("rm")
+        .arg("k")
+        .assert()
+        .code(128);
+    assert_eq!(
+        String::from_utf8(assert.get_output().stderr.clone())?,
+        "fatal: not removing 'k' recursively without -r\n"
+    );
+    assert!(temp_dir.path().join("k/l/m/o.txt").exists());
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("rm")
+        .arg("-r")
+        .arg("k/")
+        .assert()
+        .success();
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone())?,
+        "rm 'k/l/m/o.txt'\nrm 'k/l/m/q.txt'\n"
+    );
+    assert!(!temp_dir.path().join("k").exists());
+    assert!(index_paths(&temp_dir)?
+        .iter()
+        .all(|path| !path.starts_with("k/")));
+
+    Ok(())
+}
+
+#[test]
+fn test_rm_refuses_local_modifications_unless_forced() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_fs(&temp_dir)?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    commit_all(&temp_dir, "Initial commit");
+    fs::write(temp_dir.path().join("a.txt"), "changed")?;
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("rm")
+        .arg("a.txt")
+        .assert()
+        .code(1);
+    assert_eq!(
+        String::from_utf8(assert.get_output().stderr.clone())?,
+        "error: the following file has local modifications:\n    a.txt\n(use --cached to keep the file, or -f to force removal)\n"
+    );
+    assert!(index_paths(&temp_dir)?.contains(&"a.txt".to_owned()));
+
+    // --cached keeps the modified file in the workspace
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("rm")
+        .arg("--cached")
+        .arg("a.txt")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt"))?,
+        "changed"
+    );
+    assert!(!index_paths(&temp_dir)?.contains(&"a.txt".to_owned()));
+
+    fs::write(temp_dir.path().join("b.txt"), "changed")?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("rm")
+        .arg("-f")
+        .arg("b.txt")
+        .assert()
+        .success();
+    assert!(!temp_dir.path().join("b.txt").exists());
+
+    Ok(())
+}
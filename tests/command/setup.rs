@@ -66,3 +66,101 @@ pub fn setup_fs(tempdir: &TempDir) -> Result<()> {
 
     Ok(())
 }
+
+/// Stage everything in the workspace and commit it
+pub fn commit_all(temp_dir: &TempDir, message: &str) {
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(temp_dir)
+        .arg("add")
+        .arg(".")
+        .assert()
+        .success();
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(temp_dir)
+        .arg("commit")
+        .arg("-m")
+        .arg(message)
+        .assert()
+        .success();
+}
+
+/// Run rgit in `temp_dir`, expecting it to succeed, and return what it printed
+pub fn rgit(temp_dir: &TempDir, args: &[&str]) -> Result<String> {
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd.current_dir(temp_dir).args(args).assert().success();
+    Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+}
+
+/// Run git on the `.rgit` directory, which shares its layout, expecting it to succeed
+pub fn git(temp_dir: &TempDir, args: &[&str]) -> Result<String> {
+    let mut cmd = get_git_cmd();
+    let assert = cmd
+        .current_dir(temp_dir)
+        .arg("--git-dir=.rgit")
+        .args(args)
+        .assert()
+        .success();
+    Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+}
+
+/// Write `content` to the object store as a blob, returning its oid
+pub fn write_blob(temp_dir: &TempDir, content: &str) -> Result<String> {
+    let mut cmd = assert_cmd::Command::cargo_bin("rgit")?;
+    let assert = cmd
+        .current_dir(temp_dir)
+        .args(["hash-object", "-w", "--stdin"])
+        .write_stdin(content)
+        .assert()
+        .success();
+    let oid = String::from_utf8(assert.get_output().stdout.clone())?;
+    Ok(oid.trim().to_owned())
+}
+
+/// The content of a file under `.rgit`, such as a ref, without its trailing newline
+pub fn read_ref(temp_dir: &TempDir, name: &str) -> Result<String> {
+    let content = fs::read_to_string(temp_dir.path().join(".rgit").join(name))?;
+    Ok(content.trim().to_owned())
+}
+
+/// Make a commit for each of `commits`, given as its message and the files it writes, and
+/// return their oids in order
+pub fn commit_history<M: AsRef<str>, C: AsRef<[u8]>>(
+    temp_dir: &TempDir,
+    commits: &[(M, Vec<(&str, C)>)],
+) -> Result<Vec<String>> {
+    let mut oids = vec![];
+    for (message, files) in commits {
+        for (path, content) in files {
+            let path = temp_dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap())?;
+            write(path, content)?;
+        }
+        commit_all(temp_dir, message.as_ref());
+        oids.push(rgit(temp_dir, &["rev-parse", "HEAD"])?.trim().to_owned());
+    }
+    Ok(oids)
+}
+
+/// Initialise rgit in `temp_dir` and commit `commits` as `commit_history` does
+pub fn setup_history<M: AsRef<str>, C: AsRef<[u8]>>(
+    temp_dir: &TempDir,
+    commits: &[(M, Vec<(&str, C)>)],
+) -> Result<Vec<String>> {
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    commit_history(temp_dir, commits)
+}
+
+/// Commits `c<from>` to `c<to>`, each capitalising 10 more lines of `f` and writing its
+/// number to `g`, so that the versions of `f` make good deltas of each other
+pub fn delta_history(from: usize, to: usize) -> Vec<(String, Vec<(&'static str, String)>)> {
+    (from..=to)
+        .map(|n| {
+            let lines = (1..=400).map(|line| match line <= n * 10 {
+                true => format!("LINE {}\n", line),
+                false => format!("line {}\n", line),
+            });
+            let files = vec![("f", lines.collect()), ("g", format!("{}\n", n))];
+            (format!("c{}", n), files)
+        })
+        .collect()
+}
@@ -0,0 +1,98 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use tempdir::TempDir;
+
+use crate::setup::{get_rgit_cmd, setup_history};
+
+fn show(temp_dir: &TempDir, args: &[&str]) -> Result<String> {
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(temp_dir)
+        .arg("show")
+        .args(args)
+        .assert()
+        .success();
+    Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+}
+
+/// Two commits, the second changing `a.txt` and adding `d/new.txt`
+fn history() -> Vec<(&'static str, Vec<(&'static str, &'static str)>)> {
+    vec![
+        ("First commit", vec![("a.txt", "one\ntwo\nthree\n")]),
+        (
+            "Second commit",
+            vec![("a.txt", "one\n2\nthree\n"), ("d/new.txt", "new\n")],
+        ),
+    ]
+}
+
+#[test]
+fn test_show_commit_with_patch() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    let oid = setup_history(&temp_dir, &history())?[1].clone();
+
+    let output = show(&temp_dir, &[])?;
+    let (header, patch) = output.split_once("\n\n    Second commit\n\n").unwrap();
+    let header = header.lines().collect::<Vec<_>>();
+    assert_eq!(header[0], format!("commit {}", oid));
+    assert_eq!(header[1], "Author: Test Author <test@example.com>");
+    assert!(header[2].starts_with("Date:   "));
+
+    assert!(patch.starts_with("diff --git a/a.txt b/a.txt\nindex "));
+    assert!(patch.contains("--- a/a.txt\n+++ b/a.txt\n"));
+    assert!(patch.contains(" one\n-two\n+2\n three\n"));
+    assert!(patch.contains(
+        "diff --git a/d/new.txt b/d/new.txt\nnew file mode 100644\nindex 0000000..3e75765\n\
+         --- /dev/null\n+++ b/d/new.txt\n"
+    ));
+    assert!(patch.ends_with("+new\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_show_blob_and_tree_by_path() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+
+    assert_eq!(show(&temp_dir, &["HEAD~1:a.txt"])?, "one\ntwo\nthree\n");
+    assert_eq!(show(&temp_dir, &["HEAD:d/new.txt"])?, "new\n");
+    assert_eq!(
+        show(&temp_dir, &["HEAD:"])?,
+        "tree HEAD:\n\n.rgitignore\na.txt\nd/\n"
+    );
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("show")
+        .arg("HEAD:missing.txt")
+        .assert()
+        .code(128)
+        .stderr("fatal: path 'missing.txt' does not exist in 'HEAD'\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_show_annotated_tag() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    let oid = setup_history(&temp_dir, &history())?[1].clone();
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("tag")
+        .arg("-m")
+        .arg("Release one")
+        .arg("v1")
+        .assert()
+        .success();
+
+    let output = show(&temp_dir, &["v1"])?;
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "tag v1");
+    assert_eq!(lines[1], "Tagger: Test Author <test@example.com>");
+    assert_eq!(lines[3..6], ["", "Release one", ""]);
+    assert_eq!(lines[6], format!("commit {}", oid));
+
+    Ok(())
+}
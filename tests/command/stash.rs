@@ -0,0 +1,208 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, read_ref, rgit, setup_rgit};
+
+/// Create a repository with `a.txt` and `b.txt` committed
+fn setup_repo(temp_dir: &TempDir) -> Result<()> {
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    fs::write(temp_dir.path().join("a.txt"), "one\ntwo\n")?;
+    fs::write(temp_dir.path().join("b.txt"), "b\n")?;
+    commit_all(temp_dir, "First commit");
+    Ok(())
+}
+
+#[test]
+fn test_stash_push_and_pop_restore_index_and_workspace() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_repo(&temp_dir)?;
+
+    fs::write(temp_dir.path().join("a.txt"), "one\ntwo\nthree\n")?;
+    fs::write(temp_dir.path().join("b.txt"), "staged\n")?;
+    fs::write(temp_dir.path().join("n.txt"), "new\n")?;
+    rgit(&temp_dir, &["add", "b.txt", "n.txt"])?;
+    fs::write(temp_dir.path().join("b.txt"), "unstaged\n")?;
+    let before = rgit(&temp_dir, &["status"])?;
+
+    let output = rgit(&temp_dir, &["stash"])?;
+    assert!(output.starts_with("Saved working directory and index state WIP on master: "));
+    assert!(output.ends_with(" First commit\n"));
+    assert_eq!(
+        rgit(&temp_dir, &["status"])?,
+        "Untracked files:\nChanges to be committed:\nChanged not staged for commit:\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt"))?,
+        "one\ntwo\n"
+    );
+    assert!(!temp_dir.path().join("n.txt").exists());
+
+    // the stash commit has HEAD and the index commit as parents
+    let stash_oid = rgit(&temp_dir, &["rev-parse", "refs/stash"])?;
+    let stash = rgit(&temp_dir, &["cat-file", "-p", stash_oid.trim()])?;
+    let head_oid = rgit(&temp_dir, &["rev-parse", "HEAD"])?;
+    assert!(stash.contains(&format!("parent {}", head_oid)));
+    assert_eq!(stash.matches("parent ").count(), 2);
+
+    let output = rgit(&temp_dir, &["stash", "pop"])?;
+    assert_eq!(
+        output,
+        format!("Dropped refs/stash@{{0}} ({})\n", stash_oid.trim())
+    );
+    assert_eq!(rgit(&temp_dir, &["status"])?, before);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("b.txt"))?,
+        "unstaged\n"
+    );
+    // b.txt is staged again with the content it had in the index
+    let staged = rgit(&temp_dir, &["ls-files", "-s"])?;
+    assert!(staged.contains("100644 19d9cc8584ac2c7dcf57d2680375e80f099dc481 0\tb.txt\n"));
+    assert!(!temp_dir.path().join(".rgit/refs/stash").exists());
+    Ok(())
+}
+
+#[test]
+fn test_stash_untracked_list_and_drop() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_repo(&temp_dir)?;
+
+    assert_eq!(rgit(&temp_dir, &["stash"])?, "No local changes to save\n");
+
+    fs::write(temp_dir.path().join("a.txt"), "first\n")?;
+    rgit(&temp_dir, &["stash", "push", "-m", "first change"])?;
+    fs::create_dir(temp_dir.path().join("d"))?;
+    fs::write(temp_dir.path().join("d/u.txt"), "untracked\n")?;
+    rgit(&temp_dir, &["stash", "-u"])?;
+    assert!(!temp_dir.path().join("d").exists());
+
+    let list = rgit(&temp_dir, &["stash", "list"])?;
+    let lines = list.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("stash@{0}: WIP on master: "));
+    assert_eq!(lines[1], "stash@{1}: On master: first change");
+
+    rgit(&temp_dir, &["stash", "apply"])?;
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("d/u.txt"))?,
+        "untracked\n"
+    );
+
+    let first_oid = fs::read_to_string(temp_dir.path().join(".rgit/logs/refs/stash"))?
+        .split(' ')
+        .nth(1)
+        .unwrap()
+        .to_owned();
+    let output = rgit(&temp_dir, &["stash", "drop", "stash@{1}"])?;
+    assert_eq!(
+        output,
+        format!("Dropped refs/stash@{{1}} ({})\n", first_oid)
+    );
+    assert_eq!(rgit(&temp_dir, &["stash", "list"])?.lines().count(), 1);
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["stash", "drop", "stash@{3}"])
+        .assert()
+        .failure()
+        .stderr("error: stash@{3} is not a valid reference\n");
+    Ok(())
+}
+
+#[test]
+fn test_stash_show() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_repo(&temp_dir)?;
+
+    fs::write(temp_dir.path().join("a.txt"), "one\n2\n")?;
+    fs::write(temp_dir.path().join("c.txt"), "c\n")?;
+    rgit(&temp_dir, &["add", "c.txt"])?;
+    rgit(&temp_dir, &["stash"])?;
+
+    assert_eq!(
+        rgit(&temp_dir, &["stash", "show"])?,
+        " a.txt | 2 +-\n c.txt | 1 +\n 2 files changed, 2 insertions(+), 1 deletion(-)\n"
+    );
+    let patch = rgit(&temp_dir, &["stash", "show", "-p"])?;
+    assert!(patch.starts_with("diff --git a/a.txt b/a.txt\nindex "));
+    assert!(patch.contains(" one\n-two\n+2\n"));
+    assert!(patch.contains("diff --git a/c.txt b/c.txt\nnew file mode 100644\n"));
+    Ok(())
+}
+
+#[test]
+fn test_stash_pop_conflicts_keep_the_entry() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_repo(&temp_dir)?;
+    fs::write(temp_dir.path().join("a.txt"), "one\nstashed\n")?;
+    rgit(&temp_dir, &["stash"])?;
+    fs::write(temp_dir.path().join("a.txt"), "one\ncommitted\n")?;
+    commit_all(&temp_dir, "Second commit");
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["stash", "pop"])
+        .assert()
+        .code(1)
+        .stdout(
+            "Auto-merging a.txt\nCONFLICT (content): Merge conflict in a.txt\nThe stash entry is kept in case you need it again.\n",
+        );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt"))?,
+        "one\n<<<<<<< Updated upstream\ncommitted\n=======\nstashed\n>>>>>>> Stashed changes\n"
+    );
+    assert_eq!(rgit(&temp_dir, &["stash", "list"])?.lines().count(), 1);
+
+    // the conflict has to be resolved before anything else is applied
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["stash", "apply"])
+        .assert()
+        .code(1)
+        .stderr("a.txt: needs merge\n");
+
+    rgit(&temp_dir, &["reset", "--hard"])?;
+    rgit(&temp_dir, &["stash", "drop"])?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["stash", "pop"])
+        .assert()
+        .code(1)
+        .stderr("No stash entries found.\n");
+    Ok(())
+}
+
+#[test]
+fn test_stash_drop_moves_the_ref_along_with_its_log() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_repo(&temp_dir)?;
+    fs::write(temp_dir.path().join("a.txt"), "first\n")?;
+    rgit(&temp_dir, &["stash"])?;
+    let first_oid = read_ref(&temp_dir, "refs/stash")?;
+    fs::write(temp_dir.path().join("a.txt"), "second\n")?;
+    rgit(&temp_dir, &["stash"])?;
+    let log = fs::read_to_string(temp_dir.path().join(".rgit/logs/refs/stash"))?;
+    assert_eq!(log.lines().count(), 2);
+
+    // the log is only written under the lock of the ref
+    let lock_path = temp_dir.path().join(".rgit/refs/stash.lock");
+    fs::write(&lock_path, "")?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .args(["stash", "drop"])
+        .assert()
+        .failure();
+    fs::remove_file(&lock_path)?;
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join(".rgit/logs/refs/stash"))?,
+        log
+    );
+
+    rgit(&temp_dir, &["stash", "drop"])?;
+    assert_eq!(read_ref(&temp_dir, "refs/stash")?, first_oid);
+    rgit(&temp_dir, &["stash", "drop"])?;
+    assert!(!temp_dir.path().join(".rgit/refs/stash").exists());
+    assert!(!temp_dir.path().join(".rgit/logs/refs/stash").exists());
+    Ok(())
+}
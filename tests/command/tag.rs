@@ -0,0 +1,233 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{get_rgit_cmd, read_ref, rgit, setup_history};
+
+use rgit::database::{Database, ParsedContent};
+
+/// Two commits on master, each writing `a.txt`
+fn history() -> Vec<(&'static str, Vec<(&'static str, &'static str)>)> {
+    vec![
+        ("First commit", vec![("a.txt", "one\n")]),
+        ("Second commit", vec![("a.txt", "two\n")]),
+    ]
+}
+
+#[test]
+fn test_tag_creates_and_lists_lightweight_tags() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+    let head_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("tag")
+        .arg("v2.0")
+        .assert()
+        .success();
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("tag")
+        .arg("v1.0")
+        .arg("master^")
+        .assert()
+        .success();
+
+    assert_eq!(read_ref(&temp_dir, "refs/tags/v2.0")?, head_oid);
+    let database = Database::new(temp_dir.path().join(".rgit/objects"));
+    let head_commit = database.read_commit(&head_oid)?;
+    assert_eq!(
+        read_ref(&temp_dir, "refs/tags/v1.0")?,
+        head_commit.parents[0]
+    );
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd.current_dir(&temp_dir).arg("tag").assert().success();
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone())?,
+        "v1.0\nv2.0\n"
+    );
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("tag")
+        .arg("v1.0")
+        .assert()
+        .code(128);
+    assert_eq!(
+        String::from_utf8(assert.get_output().stderr.clone())?,
+        "fatal: tag 'v1.0' already exists\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_tag_annotated_object_is_peeled() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+    let head_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("tag")
+        .arg("-a")
+        .arg("v1")
+        .arg("-m")
+        .arg("First release")
+        .assert()
+        .success();
+
+    let tag_oid = read_ref(&temp_dir, "refs/tags/v1")?;
+    assert_ne!(tag_oid, head_oid);
+    let database = Database::new(temp_dir.path().join(".rgit/objects"));
+    match database.read_object(&tag_oid)? {
+        ParsedContent::TagContent(tag) => {
+            assert_eq!(tag.object, head_oid);
+            assert_eq!(tag.object_type, "commit");
+            assert_eq!(tag.name, "v1");
+            assert_eq!(tag.tagger.name, "Test Author");
+            assert_eq!(tag.message, "First release");
+        }
+        _ => panic!("expected a tag"),
+    }
+
+    // `^{}` peels the tag down to the commit, and so do commands that need a commit
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("branch")
+        .arg("peeled")
+        .arg("v1^{}")
+        .assert()
+        .success();
+    assert_eq!(read_ref(&temp_dir, "refs/heads/peeled")?, head_oid);
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("reset")
+        .arg("--hard")
+        .arg("v1^")
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join("a.txt"))?, "one\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_tag_delete() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+    let head_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("tag")
+        .arg("v1")
+        .assert()
+        .success();
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("tag")
+        .arg("-d")
+        .arg("v1")
+        .arg("missing")
+        .assert()
+        .code(1);
+    let output = assert.get_output();
+    assert_eq!(
+        String::from_utf8(output.stdout.clone())?,
+        format!("Deleted tag 'v1' (was {})\n", &head_oid[..7])
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr.clone())?,
+        "error: tag 'missing' not found.\n"
+    );
+    assert!(!temp_dir.path().join(".rgit/refs/tags/v1").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_tag_nested_names() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+    let head_oid = read_ref(&temp_dir, "refs/heads/master")?;
+
+    rgit(&temp_dir, &["tag", "v1/rc1", "HEAD~1"])?;
+    rgit(&temp_dir, &["tag", "-m", "Second candidate", "v1/rc2"])?;
+    rgit(&temp_dir, &["tag", "v2"])?;
+    assert_eq!(rgit(&temp_dir, &["tag"])?, "v1/rc1\nv1/rc2\nv2\n");
+    assert_eq!(
+        rgit(&temp_dir, &["rev-parse", "v1/rc1"])?,
+        rgit(&temp_dir, &["rev-parse", "HEAD~1"])?
+    );
+    assert_eq!(read_ref(&temp_dir, "refs/tags/v2")?, head_oid);
+
+    for (name, taken) in [("v1", "v1/rc1"), ("v2/rc1", "v2")] {
+        let mut cmd = get_rgit_cmd();
+        cmd.current_dir(&temp_dir)
+            .args(["tag", name])
+            .assert()
+            .code(128)
+            .stderr(format!(
+                "fatal: cannot lock ref 'refs/tags/{}': 'refs/tags/{}' exists; cannot create 'refs/tags/{}'\n",
+                name, taken, name
+            ));
+    }
+
+    rgit(&temp_dir, &["tag", "-d", "v1/rc1", "v1/rc2"])?;
+    assert!(!temp_dir.path().join(".rgit/refs/tags/v1").exists());
+    rgit(&temp_dir, &["tag", "v1"])?;
+    assert_eq!(rgit(&temp_dir, &["tag"])?, "v1\nv2\n");
+    Ok(())
+}
+
+#[test]
+fn test_tag_wins_over_a_branch_of_the_same_name() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+    let first_oid = rgit(&temp_dir, &["rev-parse", "HEAD~1"])?;
+    let head_oid = rgit(&temp_dir, &["rev-parse", "HEAD"])?;
+
+    rgit(&temp_dir, &["branch", "X"])?;
+    rgit(&temp_dir, &["tag", "X", "HEAD~1"])?;
+    assert_eq!(rgit(&temp_dir, &["rev-parse", "X"])?, first_oid);
+    assert_eq!(rgit(&temp_dir, &["rev-parse", "refs/heads/X"])?, head_oid);
+    assert_eq!(rgit(&temp_dir, &["cat-file", "-t", "X"])?, "commit\n");
+    Ok(())
+}
+
+#[test]
+fn test_tag_annotate_without_message_opens_the_editor() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_history(&temp_dir, &history())?;
+
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .env("GIT_EDITOR", "sed -i '1s/^$/Edited release/'")
+        .args(["tag", "-a", "v1"])
+        .assert()
+        .success();
+    let database = Database::new(temp_dir.path().join(".rgit/objects"));
+    match database.read_object(&read_ref(&temp_dir, "refs/tags/v1")?)? {
+        ParsedContent::TagContent(tag) => assert_eq!(tag.message, "Edited release"),
+        _ => panic!("expected a tag"),
+    }
+
+    // leaving the message empty creates no tag
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .env("GIT_EDITOR", "true")
+        .args(["tag", "-a", "v2"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("fatal: no tag message?"));
+    assert!(!temp_dir.path().join(".rgit/refs/tags/v2").exists());
+    Ok(())
+}
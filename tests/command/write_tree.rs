@@ -0,0 +1,54 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use std::fs;
+use tempdir::TempDir;
+
+use crate::setup::{commit_all, get_rgit_cmd, setup_fs, setup_rgit};
+
+#[test]
+fn test_write_tree_matches_committed_tree() -> Result<()> {
+    let temp_dir = TempDir::new("test_rgit")?;
+    setup_rgit(&temp_dir.path().to_path_buf())?;
+    setup_fs(&temp_dir)?;
+    commit_all(&temp_dir, "First commit");
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("write-tree")
+        .assert()
+        .success();
+    let tree = String::from_utf8(assert.get_output().stdout.clone())?;
+
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("cat-file")
+        .arg("-p")
+        .arg("HEAD")
+        .assert()
+        .success();
+    let commit = String::from_utf8(assert.get_output().stdout.clone())?;
+    assert_eq!(
+        commit.lines().next(),
+        Some(format!("tree {}", tree.trim()).as_str())
+    );
+
+    // staging a change gives a different tree without touching HEAD
+    fs::write(temp_dir.path().join("a.txt"), "changed")?;
+    let mut cmd = get_rgit_cmd();
+    cmd.current_dir(&temp_dir)
+        .arg("add")
+        .arg("a.txt")
+        .assert()
+        .success();
+    let mut cmd = get_rgit_cmd();
+    let assert = cmd
+        .current_dir(&temp_dir)
+        .arg("write-tree")
+        .assert()
+        .success();
+    assert_ne!(String::from_utf8(assert.get_output().stdout.clone())?, tree);
+
+    Ok(())
+}